@@ -0,0 +1,31 @@
+#ifndef WAIIR_H
+#define WAIIR_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque interpreter handle; variables persist between evaluations. */
+typedef struct WaiirInterpreter WaiirInterpreter;
+
+/* Creates an interpreter, to be released with waiir_free. */
+WaiirInterpreter *waiir_new(void);
+
+/*
+ * Evaluates a NUL-terminated UTF-8 program.
+ * Returns the printed result, or NULL on error (see waiir_last_error).
+ * The string is owned by the interpreter and valid until the next call.
+ */
+const char *waiir_eval(WaiirInterpreter *interpreter, const char *source);
+
+/* Message of the last failed evaluation, or NULL. Valid until the next call. */
+const char *waiir_last_error(const WaiirInterpreter *interpreter);
+
+/* Releases the interpreter. Passing NULL is a no-op. */
+void waiir_free(WaiirInterpreter *interpreter);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* WAIIR_H */
@@ -0,0 +1,126 @@
+//! C ABI for embedding the interpreter, declared in `include/waiir.h`.
+
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+use std::rc::Rc;
+use waiir::eval::{Environment, eval_with_env};
+
+pub struct WaiirInterpreter {
+    env: Rc<Environment>,
+    result: Option<CString>,
+    last_error: Option<CString>,
+}
+
+/// Creates an interpreter, to be released with [`waiir_free`].
+#[unsafe(no_mangle)]
+pub extern "C" fn waiir_new() -> *mut WaiirInterpreter {
+    Box::into_raw(Box::new(WaiirInterpreter {
+        env: Rc::new(Environment::default()),
+        result: None,
+        last_error: None,
+    }))
+}
+
+/// Evaluates `source` and returns the printed result, or null on error.
+/// The returned string is owned by the interpreter and valid until the next call.
+///
+/// # Safety
+/// `interpreter` must come from [`waiir_new`] and `source` must be a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn waiir_eval(
+    interpreter: *mut WaiirInterpreter,
+    source: *const c_char,
+) -> *const c_char {
+    let Some(interpreter) = (unsafe { interpreter.as_mut() }) else {
+        return ptr::null();
+    };
+    interpreter.result = None;
+    interpreter.last_error = None;
+    if source.is_null() {
+        interpreter.last_error = c_string("Source is null");
+        return ptr::null();
+    }
+    let source = match unsafe { CStr::from_ptr(source) }.to_str() {
+        Ok(source) => source,
+        Err(_) => {
+            interpreter.last_error = c_string("Source is not valid UTF-8");
+            return ptr::null();
+        }
+    };
+    match eval_with_env(source, Rc::clone(&interpreter.env)) {
+        Ok(obj) => {
+            interpreter.result = c_string(&obj.to_string());
+            interpreter
+                .result
+                .as_ref()
+                .map_or(ptr::null(), |s| s.as_ptr())
+        }
+        Err(err) => {
+            interpreter.last_error = c_string(&err.to_string());
+            ptr::null()
+        }
+    }
+}
+
+/// Message of the last failed evaluation, or null.
+///
+/// # Safety
+/// `interpreter` must come from [`waiir_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn waiir_last_error(interpreter: *const WaiirInterpreter) -> *const c_char {
+    unsafe { interpreter.as_ref() }
+        .and_then(|i| i.last_error.as_ref())
+        .map_or(ptr::null(), |s| s.as_ptr())
+}
+
+/// # Safety
+/// `interpreter` must come from [`waiir_new`] and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn waiir_free(interpreter: *mut WaiirInterpreter) {
+    if !interpreter.is_null() {
+        drop(unsafe { Box::from_raw(interpreter) });
+    }
+}
+
+/// Interior NULs can't cross the boundary, so they are replaced.
+fn c_string(string: &str) -> Option<CString> {
+    CString::new(string.replace('\0', "\u{FFFD}")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_str(ptr: *const c_char) -> Option<String> {
+        (!ptr.is_null()).then(|| {
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    #[test]
+    fn embedding() {
+        let interpreter = waiir_new();
+        unsafe {
+            let source = CString::new("let x = 2; x * 21").unwrap();
+            assert_eq!(
+                to_str(waiir_eval(interpreter, source.as_ptr())),
+                Some("42".to_owned())
+            );
+            assert_eq!(to_str(waiir_last_error(interpreter)), None);
+
+            let source = CString::new("x + true").unwrap();
+            assert_eq!(to_str(waiir_eval(interpreter, source.as_ptr())), None);
+            assert!(to_str(waiir_last_error(interpreter)).is_some());
+
+            assert_eq!(to_str(waiir_eval(interpreter, ptr::null())), None);
+            assert_eq!(
+                to_str(waiir_last_error(interpreter)),
+                Some("Source is null".to_owned())
+            );
+            waiir_free(interpreter);
+            waiir_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::analysis::{SymbolTable, Type};
+use crate::cst::{SyntaxKind, SyntaxNode};
+use crate::{InfixOperator, Token};
+use std::ops::Range;
+
+/// Abstract interpreter computing the static type of CST expressions where it can be
+/// known without running the program.
+pub struct Inferrer<'a> {
+    root: &'a SyntaxNode,
+    table: SymbolTable,
+    visiting: Vec<Range<usize>>,
+}
+
+impl<'a> Inferrer<'a> {
+    pub fn new(root: &'a SyntaxNode) -> Self {
+        Self {
+            root,
+            table: SymbolTable::build(root),
+            visiting: vec![],
+        }
+    }
+
+    pub fn infer(&mut self, node: &SyntaxNode) -> Type {
+        match node.kind {
+            SyntaxKind::Literal => match node.child_tokens().next().map(|t| &t.token) {
+                Some(Token::Int(_)) => Type::Int,
+                Some(Token::Float(_)) => Type::Float,
+                Some(Token::String(_)) => Type::String,
+                Some(Token::True | Token::False) => Type::Bool,
+                _ => Type::Unknown,
+            },
+            SyntaxKind::Ident => self.infer_ident(node),
+            // Errors aren't typed, so a typed operand is never returned by `?`.
+            SyntaxKind::Paren | SyntaxKind::Try => self.infer_nth(node, 0),
+            SyntaxKind::Prefix => match node.child_tokens().next().map(|t| &t.token) {
+                Some(Token::Bang) => Type::Bool,
+                Some(Token::Minus | Token::Plus) => match self.infer_nth(node, 0) {
+                    ty @ (Type::Int | Type::Float) => ty,
+                    _ => Type::Unknown,
+                },
+                _ => Type::Unknown,
+            },
+            SyntaxKind::Infix => {
+                let left = self.infer_nth(node, 0);
+                let right = self.infer_nth(node, 1);
+                match node.child_tokens().next() {
+                    Some(op) => infix_type(&op.token, left, right).unwrap_or(Type::Unknown),
+                    None => Type::Unknown,
+                }
+            }
+            SyntaxKind::Call => self.infer_call(node),
+            SyntaxKind::Array => Type::Array,
+            SyntaxKind::Tuple => Type::Tuple,
+            SyntaxKind::Hash => Type::Hash,
+            SyntaxKind::Func => Type::Function,
+            SyntaxKind::For | SyntaxKind::While => Type::Null,
+            SyntaxKind::If => {
+                let blocks: Vec<&SyntaxNode> = node
+                    .child_nodes()
+                    .filter(|n| n.kind == SyntaxKind::Block)
+                    .chain(
+                        node.child_nodes()
+                            .filter(|n| n.kind == SyntaxKind::Else)
+                            .flat_map(|n| n.child_nodes()),
+                    )
+                    .collect();
+                match blocks.as_slice() {
+                    [then_, else_] => self.infer_block(then_).join(self.infer_block(else_)),
+                    _ => Type::Unknown,
+                }
+            }
+            _ => Type::Unknown,
+        }
+    }
+
+    /// Operators applied to values of types the evaluator rejects.
+    pub fn mismatches(&mut self, node: &SyntaxNode) -> Vec<(Range<usize>, String)> {
+        let mut output = vec![];
+        if node.kind == SyntaxKind::Infix
+            && let Some(op) = node.child_tokens().next()
+        {
+            let left = self.infer_nth(node, 0);
+            let right = self.infer_nth(node, 1);
+            if left != Type::Unknown
+                && right != Type::Unknown
+                && infix_type(&op.token, left, right).is_none()
+            {
+                output.push((
+                    node.span().unwrap_or_default(),
+                    format!(
+                        "Invalid operation ({}) between {left} and {right}",
+                        op.token
+                    ),
+                ));
+            }
+        }
+        for child in node.child_nodes() {
+            output.extend(self.mismatches(child));
+        }
+        output
+    }
+
+    fn infer_nth(&mut self, node: &SyntaxNode, n: usize) -> Type {
+        match node.child_nodes().nth(n) {
+            Some(child) => self.infer(child),
+            None => Type::Unknown,
+        }
+    }
+
+    fn infer_block(&mut self, block: &SyntaxNode) -> Type {
+        match block.child_nodes().last() {
+            Some(stmt) if stmt.kind == SyntaxKind::ExprStmt => self.infer_nth(stmt, 0),
+            Some(_) => Type::Unknown,
+            None => Type::Null,
+        }
+    }
+
+    fn infer_ident(&mut self, node: &SyntaxNode) -> Type {
+        let Some(span) = node.span() else {
+            return Type::Unknown;
+        };
+        if node.source() == "null" {
+            return Type::Null;
+        }
+        let Some(symbol) = self.table.symbol_at(span.start) else {
+            return if crate::eval::is_builtin(&node.source()) {
+                Type::Function
+            } else {
+                Type::Unknown
+            };
+        };
+        let definitions = symbol.definitions.clone();
+        // The values assigned to the variable later on are among those it can hold too.
+        let assignments: Vec<Range<usize>> = symbol
+            .references
+            .iter()
+            .filter(|r| find_binding(self.root, SyntaxKind::AssignStmt, r).is_some())
+            .cloned()
+            .collect();
+        if definitions
+            .iter()
+            .chain(&assignments)
+            .any(|d| self.visiting.contains(d))
+        {
+            return Type::Unknown;
+        }
+
+        let mut output: Option<Type> = None;
+        let bindings = definitions
+            .into_iter()
+            .map(|d| (SyntaxKind::LetStmt, d))
+            .chain(assignments.into_iter().map(|a| (SyntaxKind::AssignStmt, a)));
+        for (kind, span) in bindings {
+            let ty = match find_binding(self.root, kind, &span) {
+                Some(stmt) => {
+                    self.visiting.push(span);
+                    let ty = match stmt.child_nodes().last() {
+                        Some(value) => self.infer(value),
+                        None => Type::Unknown,
+                    };
+                    self.visiting.pop();
+                    ty
+                }
+                None => Type::Unknown,
+            };
+            output = Some(output.map_or(ty, |t| t.join(ty)));
+        }
+        output.unwrap_or(Type::Unknown)
+    }
+
+    fn infer_call(&mut self, node: &SyntaxNode) -> Type {
+        let Some(callee) = node.child_nodes().next() else {
+            return Type::Unknown;
+        };
+        let first_arg = node
+            .child_nodes()
+            .nth(1)
+            .and_then(|args| args.child_nodes().next())
+            .map(|arg| self.infer(arg));
+        if callee.kind != SyntaxKind::Ident
+            || callee
+                .span()
+                .is_some_and(|s| self.table.symbol_at(s.start).is_some())
+        {
+            return Type::Unknown;
+        }
+        match callee.source().as_str() {
+            "len" => Type::Int,
+            "rest" | "push" => match first_arg {
+                Some(ty @ (Type::Array | Type::String | Type::Hash)) => ty,
+                _ => Type::Unknown,
+            },
+            _ => Type::Unknown,
+        }
+    }
+}
+
+/// Result type of an infix operation, `None` when the evaluator would reject it.
+fn infix_type(token: &Token, left: Type, right: Type) -> Option<Type> {
+    match InfixOperator::try_from(token) {
+        Ok(operator) => Type::infix(&operator, left, right),
+        Err(_) => Some(Type::Unknown),
+    }
+}
+
+/// The `let` or assignment statement of `kind` whose name is at `span`.
+fn find_binding<'a>(
+    node: &'a SyntaxNode,
+    kind: SyntaxKind,
+    span: &Range<usize>,
+) -> Option<&'a SyntaxNode> {
+    if node.kind == kind
+        && node
+            .child_nodes()
+            .next()
+            .and_then(SyntaxNode::span)
+            .as_ref()
+            == Some(span)
+    {
+        return Some(node);
+    }
+    node.child_nodes().find_map(|n| find_binding(n, kind, span))
+}
@@ -0,0 +1,310 @@
+use crate::Token;
+use crate::cst::{SyntaxKind, SyntaxNode};
+use crate::error::{Error, ErrorCode, Result, did_you_mean};
+use crate::eval::{HashMapKey, builtin_names, is_builtin};
+use std::ops::Range;
+
+mod infer;
+mod resolver;
+mod types;
+
+pub use infer::Inferrer;
+pub use resolver::{Shadowing, Symbol, SymbolTable};
+pub use types::Type;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct HoverInfo {
+    pub range: Range<usize>,
+    pub source: String,
+    pub ty: Type,
+}
+
+/// Span of the definition the identifier at `offset` refers to: the closest `let`
+/// before it when the name was bound several times, the first one otherwise.
+pub fn definition(program: &SyntaxNode, offset: usize) -> Option<Range<usize>> {
+    let table = SymbolTable::build(program);
+    let symbol = table.symbol_at(offset)?;
+    symbol
+        .definitions
+        .iter()
+        .rev()
+        .find(|span| span.start <= offset)
+        .or(symbol.definitions.first())
+        .cloned()
+}
+
+/// Spans of every use of the binding the identifier at `offset` refers to,
+/// excluding its definitions.
+pub fn references(program: &SyntaxNode, offset: usize) -> Vec<Range<usize>> {
+    let table = SymbolTable::build(program);
+    match table.symbol_at(offset) {
+        Some(symbol) => symbol.references.clone(),
+        None => vec![],
+    }
+}
+
+/// Static type of the innermost expression at `offset`.
+pub fn hover(program: &SyntaxNode, offset: usize) -> Option<HoverInfo> {
+    let mut path = vec![program];
+    while let Some(child) = path
+        .last()?
+        .child_nodes()
+        .find(|n| n.span().is_some_and(|s| s.contains(&offset)))
+    {
+        path.push(child);
+    }
+    // Hovering the name of a `let` shows its value, punctuation shows the enclosing expression.
+    let node = match path.last()?.kind {
+        SyntaxKind::LetStmt => path.last()?.child_nodes().last()?,
+        _ => *path.iter().rev().find(|n| is_expression(n.kind))?,
+    };
+    Some(HoverInfo {
+        range: node.span()?,
+        source: node.source(),
+        ty: Inferrer::new(program).infer(node),
+    })
+}
+
+fn is_expression(kind: SyntaxKind) -> bool {
+    !matches!(
+        kind,
+        SyntaxKind::Program
+            | SyntaxKind::LetStmt
+            | SyntaxKind::AssignStmt
+            | SyntaxKind::StructStmt
+            | SyntaxKind::FieldList
+            | SyntaxKind::ReturnStmt
+            | SyntaxKind::YieldStmt
+            | SyntaxKind::BreakStmt
+            | SyntaxKind::ContinueStmt
+            | SyntaxKind::ExprStmt
+            | SyntaxKind::Block
+            | SyntaxKind::Else
+            | SyntaxKind::ArgList
+            | SyntaxKind::KeywordArg
+            | SyntaxKind::ParamList
+            | SyntaxKind::HashPair
+            | SyntaxKind::MatchArm
+            | SyntaxKind::ArrayPattern
+            | SyntaxKind::TuplePattern
+            | SyntaxKind::HashPattern
+            | SyntaxKind::StructPattern
+            | SyntaxKind::RestPattern
+            | SyntaxKind::Wildcard
+            | SyntaxKind::Error
+    )
+}
+
+/// Operations whose operand types are statically known to be invalid, like `"a" - 1`.
+pub fn type_warnings(program: &SyntaxNode) -> Vec<(Range<usize>, String)> {
+    Inferrer::new(program).mismatches(program)
+}
+
+/// Identifiers that aren't bound anywhere in scope, with a suggestion when one is close enough.
+pub fn unknown_identifiers(program: &SyntaxNode) -> Vec<(Range<usize>, String)> {
+    let table = SymbolTable::build(program);
+    let mut names: Vec<&str> = builtin_names().collect();
+    names.extend(table.symbols.iter().map(|s| s.name.as_str()));
+    table
+        .unresolved
+        .iter()
+        .filter(|(name, _)| !is_builtin(name))
+        .map(|(name, range)| {
+            let message = match did_you_mean(name, names.iter().copied()) {
+                Some(suggestion) => {
+                    format!("Unknown identifier `{name}`, did you mean `{suggestion}`?")
+                }
+                None => format!("Unknown identifier `{name}`"),
+            };
+            (range.clone(), message)
+        })
+        .collect()
+}
+
+/// Fails at the first assignment to a variable bound without `let mut`, before the
+/// program runs. Variables defined outside of `program` are checked when assigned.
+pub fn check_assignments(program: &SyntaxNode) -> Result<()> {
+    let table = SymbolTable::build(program);
+    match table.immutable_assignments.into_iter().next() {
+        Some((name, span)) => Err(Error::new(
+            ErrorCode::ImmutableAssignment,
+            format!("Cannot assign to `{name}`, it isn't mutable: use `let mut` to define it"),
+        )
+        .with_span(span)),
+        None => Ok(()),
+    }
+}
+
+/// `let`s shadowing another binding, which is allowed but easy to do by mistake.
+pub fn shadowed_bindings(program: &SyntaxNode) -> Vec<Shadowing> {
+    SymbolTable::build(program).shadowings
+}
+
+/// Key that appears more than once in the same hash literal.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct DuplicateKey {
+    pub key: String,
+    pub first: Range<usize>,
+    pub duplicate: Range<usize>,
+}
+
+impl DuplicateKey {
+    /// The warning as an error, for strict checking.
+    pub fn to_error(&self) -> Error {
+        Error::new(
+            ErrorCode::DuplicateHashKey,
+            format!("Duplicate key {} in hash literal", self.key),
+        )
+        .with_span(self.duplicate.clone())
+    }
+}
+
+/// Keys of hash literals that are overwritten by a later key with the same value.
+/// Only literal keys are compared, the others can't be known before evaluation.
+pub fn duplicate_hash_keys(program: &SyntaxNode) -> Vec<DuplicateKey> {
+    let mut output = vec![];
+    if program.kind == SyntaxKind::Hash {
+        let mut seen: Vec<(HashMapKey, Range<usize>)> = vec![];
+        for pair in program.child_nodes() {
+            let Some(key) = pair.child_nodes().next() else {
+                continue;
+            };
+            let (Some(value), Some(span)) = (constant_key(key), key.span()) else {
+                continue;
+            };
+            match seen.iter().find(|(k, _)| *k == value) {
+                Some((_, first)) => output.push(DuplicateKey {
+                    key: key.source(),
+                    first: first.clone(),
+                    duplicate: span,
+                }),
+                None => seen.push((value, span)),
+            }
+        }
+    }
+    for child in program.child_nodes() {
+        output.extend(duplicate_hash_keys(child));
+    }
+    output
+}
+
+fn constant_key(node: &SyntaxNode) -> Option<HashMapKey> {
+    match node.kind {
+        SyntaxKind::Literal => match &node.child_tokens().next()?.token {
+            Token::Int(value) => Some(HashMapKey::Int(*value)),
+            Token::String(value) => Some(HashMapKey::String(value.clone())),
+            Token::True => Some(HashMapKey::Bool(true)),
+            Token::False => Some(HashMapKey::Bool(false)),
+            _ => None,
+        },
+        SyntaxKind::Prefix if node.child_tokens().next()?.token == Token::Minus => {
+            match constant_key(node.child_nodes().next()?)? {
+                HashMapKey::Int(value) => Some(HashMapKey::Int(value.checked_neg()?)),
+                _ => None,
+            }
+        }
+        SyntaxKind::Paren => constant_key(node.child_nodes().next()?),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cst;
+
+    #[test]
+    fn definition_and_references() {
+        let input = "let a = 1; let f = fn() { a }; let a = 2; f() + a";
+        let root = cst::parse(input);
+
+        assert_eq!(definition(&root, 26), Some(4..5));
+        assert_eq!(definition(&root, 48), Some(35..36));
+        assert_eq!(definition(&root, 4), Some(4..5));
+        assert_eq!(definition(&root, 42), Some(15..16));
+        assert_eq!(definition(&root, 7), None);
+
+        assert_eq!(references(&root, 35), vec![26..27, 48..49]);
+        assert_eq!(references(&root, 8), vec![]);
+    }
+
+    #[test]
+    fn hover_types() {
+        let input = "let s = \"a\" + \"b\"; let n = len(s) * 2; let f = fn(x) { x }; [s, n == 1, f(1), if (n > 1) { 1 } else { 2 }]";
+        let root = cst::parse(input);
+        let ty_at = |needle: &str| hover(&root, input.rfind(needle).unwrap()).unwrap().ty;
+
+        assert_eq!(ty_at("s,"), Type::String);
+        assert_eq!(ty_at("n"), Type::Int);
+        assert_eq!(ty_at("=="), Type::Bool);
+        assert_eq!(ty_at("f("), Type::Function);
+        assert_eq!(ty_at("(1)"), Type::Unknown);
+        assert_eq!(ty_at("if"), Type::Int);
+        assert_eq!(ty_at("["), Type::Array);
+        assert_eq!(ty_at("let n").to_string(), "int");
+        assert_eq!(
+            hover(&root, input.find('*').unwrap()).unwrap().source,
+            "len(s) * 2"
+        );
+        assert_eq!(hover(&cst::parse("1 + 0.5"), 2).unwrap().ty, Type::Float);
+        let input = "let v = 1; v = v * 2.5; v";
+        let ty = hover(&cst::parse(input), input.len() - 1).unwrap().ty;
+        assert_eq!(ty, Type::Unknown);
+    }
+
+    #[test]
+    fn type_mismatches() {
+        let root = cst::parse(
+            "let a = \"a\" - 1; let b = fn(x) { x - 1 }; true + false; {\"__add\": b} + 1",
+        );
+        let warnings = type_warnings(&root);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].0, 8..15);
+        assert_eq!(warnings[0].1, "Invalid operation (`-`) between str and int");
+    }
+
+    #[test]
+    fn unknown() {
+        let root = cst::parse("let counter = 1; let f = fn(x) { countr + x + y }; lenn(f)");
+        assert_eq!(
+            unknown_identifiers(&root),
+            vec![
+                (
+                    33..39,
+                    "Unknown identifier `countr`, did you mean `counter`?".to_owned()
+                ),
+                (46..47, "Unknown identifier `y`".to_owned()),
+                (
+                    51..55,
+                    "Unknown identifier `lenn`, did you mean `len`?".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let root =
+            cst::parse("{\"a\": 1, 1: 2, \"1\": 3, (\"a\"): 4, [{-1: 1, -1: 2}]: x, k: 1, k: 2}");
+        let duplicates = duplicate_hash_keys(&root);
+        assert_eq!(
+            duplicates,
+            vec![
+                DuplicateKey {
+                    key: "(\"a\")".to_owned(),
+                    first: 1..4,
+                    duplicate: 23..28,
+                },
+                DuplicateKey {
+                    key: "-1".to_owned(),
+                    first: 35..37,
+                    duplicate: 42..44,
+                },
+            ]
+        );
+        let err = duplicates[1].to_error();
+        assert_eq!(err.code, ErrorCode::DuplicateHashKey);
+        assert_eq!(err.to_string(), "Duplicate key -1 in hash literal");
+        assert_eq!(err.span, Some(42..44));
+    }
+}
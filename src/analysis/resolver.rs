@@ -0,0 +1,465 @@
+use crate::Token;
+use crate::cst::{SyntaxKind, SyntaxNode};
+use crate::error::{Error, ErrorCode};
+use crate::lexer::Position;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A variable as seen by the evaluator: every `let` of the same name in the same
+/// function body writes the same slot, so they are all definitions of one symbol.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    /// Whether one of the definitions is a `let mut`, which allows assigning the variable.
+    pub mutable: bool,
+    pub definitions: Vec<Range<usize>>,
+    pub references: Vec<Range<usize>>,
+}
+
+impl Symbol {
+    /// Spans of the definitions and references, in source order.
+    pub fn occurrences(&self) -> Vec<Range<usize>> {
+        let mut output: Vec<Range<usize>> = self
+            .definitions
+            .iter()
+            .chain(self.references.iter())
+            .cloned()
+            .collect();
+        output.sort_by_key(|span| span.start);
+        output
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Default)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+    /// Identifiers not bound by any enclosing scope (builtins, typos, ...).
+    pub unresolved: Vec<(String, Range<usize>)>,
+    /// Assignments to variables that no `let mut` defines, spanning the assigned name.
+    pub immutable_assignments: Vec<(String, Range<usize>)>,
+    pub shadowings: Vec<Shadowing>,
+    /// Names of keyword arguments whose parameter isn't known statically, e.g. those of
+    /// calls of parameters or of methods, spanning the name.
+    pub keyword_args: Vec<(String, Range<usize>)>,
+    /// Parameters of the function literals bound by a `let`, by the index of the symbol
+    /// bound to them.
+    parameters: HashMap<usize, HashMap<String, usize>>,
+}
+
+/// A `let` of a name already bound before it in the same scope, or in an enclosing one.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Shadowing {
+    pub name: String,
+    /// The binding that is shadowed: the closest one before the `let` in the same scope,
+    /// else the closest one before it in the innermost enclosing scope binding the name
+    /// (or its first one when all come after, e.g. in the body of a function).
+    pub shadowed: Range<usize>,
+    pub span: Range<usize>,
+}
+
+impl Shadowing {
+    /// The warning for `source`, the program the spans are in.
+    pub fn message(&self, source: &str) -> String {
+        format!(
+            "`{}` at {} shadows the binding at {}",
+            self.name,
+            Position::at(source, self.span.start),
+            Position::at(source, self.shadowed.start)
+        )
+    }
+
+    /// The warning as an error, for strict checking.
+    pub fn to_error(&self, source: &str) -> Error {
+        Error::new(ErrorCode::ShadowedBinding, self.message(source)).with_span(self.span.clone())
+    }
+}
+
+impl SymbolTable {
+    pub fn build(root: &SyntaxNode) -> Self {
+        let mut table = Self::default();
+        let mut scopes = vec![table.declare(root, &[])];
+        table.resolve(root, &mut scopes);
+        for symbol in &mut table.symbols {
+            symbol.definitions.sort_by_key(|span| span.start);
+        }
+        table
+    }
+
+    pub fn symbol_at(&self, offset: usize) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| {
+            s.definitions
+                .iter()
+                .chain(s.references.iter())
+                .any(|span| span.contains(&offset))
+        })
+    }
+
+    /// Creates the symbols of a new scope: `params` plus every `let`, struct, loop variable
+    /// and name bound by a `match` arm in `node` outside of nested function literals.
+    fn declare(&mut self, node: &SyntaxNode, params: &[&SyntaxNode]) -> HashMap<String, usize> {
+        let mut scope = HashMap::new();
+        for param in params {
+            self.define(&mut scope, param, false);
+        }
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if matches!(
+                node.kind,
+                SyntaxKind::LetStmt
+                    | SyntaxKind::StructStmt
+                    | SyntaxKind::For
+                    | SyntaxKind::MatchArm
+            ) && let Some(target) = node.child_nodes().next()
+            {
+                let mutable = node.child_tokens().any(|t| t.token == Token::Mut);
+                for name in bound_names(target) {
+                    self.define(&mut scope, name, mutable);
+                }
+            }
+            stack.extend(node.child_nodes().filter(|n| n.kind != SyntaxKind::Func));
+        }
+        scope
+    }
+
+    fn define(&mut self, scope: &mut HashMap<String, usize>, ident: &SyntaxNode, mutable: bool) {
+        let Some((name, span)) = ident_of(ident) else {
+            return;
+        };
+        let index = *scope.entry(name.clone()).or_insert_with(|| {
+            self.symbols.push(Symbol {
+                name,
+                mutable: false,
+                definitions: vec![],
+                references: vec![],
+            });
+            self.symbols.len() - 1
+        });
+        self.symbols[index].mutable |= mutable;
+        self.symbols[index].definitions.push(span);
+    }
+
+    fn resolve(&mut self, node: &SyntaxNode, scopes: &mut Vec<HashMap<String, usize>>) {
+        match node.kind {
+            SyntaxKind::Func => {
+                let params: Vec<&SyntaxNode> = node
+                    .child_nodes()
+                    .filter(|n| n.kind == SyntaxKind::ParamList)
+                    .flat_map(|n| n.child_nodes().flat_map(bound_names))
+                    .collect();
+                let scope = match node.child_nodes().find(|n| n.kind == SyntaxKind::Block) {
+                    Some(body) => self.declare(body, &params),
+                    None => self.declare(&SyntaxNode::new(SyntaxKind::Block), &params),
+                };
+                scopes.push(scope);
+                for child in node
+                    .child_nodes()
+                    .filter(|n| n.kind != SyntaxKind::ParamList)
+                {
+                    self.resolve(child, scopes);
+                }
+                scopes.pop();
+            }
+            SyntaxKind::LetStmt
+            | SyntaxKind::StructStmt
+            | SyntaxKind::For
+            | SyntaxKind::MatchArm => {
+                if node.kind == SyntaxKind::LetStmt
+                    && let Some(target) = node.child_nodes().next()
+                {
+                    for ident in bound_names(target) {
+                        self.check_shadowing(ident, scopes);
+                    }
+                }
+                for child in node.child_nodes().skip(1) {
+                    self.resolve(child, scopes);
+                }
+                if node.kind == SyntaxKind::LetStmt {
+                    self.record_parameters(node, scopes);
+                }
+            }
+            SyntaxKind::Call | SyntaxKind::MethodCall => {
+                let parameters = self.callee_parameters(node, scopes);
+                let keywords = node
+                    .child_nodes()
+                    .filter(|n| n.kind == SyntaxKind::ArgList)
+                    .flat_map(|n| n.child_nodes())
+                    .filter(|n| n.kind == SyntaxKind::KeywordArg);
+                for keyword in keywords {
+                    let Some((name, span)) = ident_of(keyword) else {
+                        continue;
+                    };
+                    match parameters.as_ref().and_then(|p| p.get(&name)) {
+                        Some(&index) => self.symbols[index].references.push(span),
+                        None => self.keyword_args.push((name, span)),
+                    }
+                }
+                for child in node.child_nodes() {
+                    self.resolve(child, scopes);
+                }
+            }
+            SyntaxKind::AssignStmt => {
+                if let Some((name, span)) = node.child_nodes().next().and_then(ident_of)
+                    && let Some(&index) = scopes.iter().rev().find_map(|scope| scope.get(&name))
+                    && !self.symbols[index].mutable
+                {
+                    self.immutable_assignments.push((name, span));
+                }
+                for child in node.child_nodes() {
+                    self.resolve(child, scopes);
+                }
+            }
+            SyntaxKind::Ident => {
+                let Some((name, span)) = ident_of(node) else {
+                    return;
+                };
+                match scopes.iter().rev().find_map(|scope| scope.get(&name)) {
+                    Some(&index) => self.symbols[index].references.push(span),
+                    None => self.unresolved.push((name, span)),
+                }
+            }
+            _ => {
+                for child in node.child_nodes() {
+                    self.resolve(child, scopes);
+                }
+            }
+        }
+    }
+
+    /// Remembers the parameters of the function literal bound by `let`, once resolved.
+    fn record_parameters(&mut self, node: &SyntaxNode, scopes: &[HashMap<String, usize>]) {
+        let mut children = node.child_nodes();
+        let (Some((name, _)), Some(func)) = (
+            children.next().and_then(ident_of),
+            children.find(|n| n.kind == SyntaxKind::Func),
+        ) else {
+            return;
+        };
+        let Some(&index) = scopes.last().and_then(|scope| scope.get(&name)) else {
+            return;
+        };
+        let parameters = func
+            .child_nodes()
+            .filter(|n| n.kind == SyntaxKind::ParamList)
+            .flat_map(|n| n.child_nodes())
+            .filter_map(ident_of)
+            .filter_map(|(name, span)| {
+                let param = self
+                    .symbols
+                    .iter()
+                    .position(|s| s.definitions.contains(&span))?;
+                Some((name, param))
+            })
+            .collect();
+        self.parameters.insert(index, parameters);
+    }
+
+    /// Parameters of the function called by `node`, when it is a variable bound once, and
+    /// never assigned, to a function literal resolved before the call.
+    fn callee_parameters(
+        &self,
+        node: &SyntaxNode,
+        scopes: &[HashMap<String, usize>],
+    ) -> Option<HashMap<String, usize>> {
+        if node.kind != SyntaxKind::Call {
+            return None;
+        }
+        let callee = node.child_nodes().next()?;
+        if callee.kind != SyntaxKind::Ident {
+            return None;
+        }
+        let (name, _) = ident_of(callee)?;
+        let &index = scopes.iter().rev().find_map(|scope| scope.get(&name))?;
+        let symbol = &self.symbols[index];
+        if symbol.mutable || symbol.definitions.len() != 1 {
+            return None;
+        }
+        self.parameters.get(&index).cloned()
+    }
+
+    fn check_shadowing(&mut self, ident: &SyntaxNode, scopes: &[HashMap<String, usize>]) {
+        let Some((name, span)) = ident_of(ident) else {
+            return;
+        };
+        let Some((scope, outer)) = scopes.split_last() else {
+            return;
+        };
+        let before = |index: usize| {
+            self.symbols[index]
+                .definitions
+                .iter()
+                .filter(|definition| definition.start < span.start)
+                .max_by_key(|definition| definition.start)
+        };
+        let shadowed = scope
+            .get(&name)
+            .and_then(|&index| before(index))
+            .or_else(|| {
+                let &index = outer.iter().rev().find_map(|scope| scope.get(&name))?;
+                let first = self.symbols[index]
+                    .definitions
+                    .iter()
+                    .min_by_key(|d| d.start);
+                before(index).or(first)
+            });
+        if let Some(shadowed) = shadowed.cloned() {
+            self.shadowings.push(Shadowing {
+                name,
+                shadowed,
+                span,
+            });
+        }
+    }
+}
+
+/// The names bound by the target of a `let`, a `for`, a `match` arm or a parameter: the
+/// target itself or, for patterns, the identifiers in them.
+fn bound_names(target: &SyntaxNode) -> Vec<&SyntaxNode> {
+    if target.kind == SyntaxKind::Ident {
+        return vec![target];
+    }
+    target.child_nodes().flat_map(bound_names).collect()
+}
+
+fn ident_of(node: &SyntaxNode) -> Option<(String, Range<usize>)> {
+    let token = node.child_tokens().next()?;
+    match &token.token {
+        Token::Ident(name) => Some((name.clone(), token.offset..token.offset + token.text.len())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cst;
+
+    fn spans_of(input: &str, name: &str) -> Vec<Range<usize>> {
+        input
+            .match_indices(name)
+            .map(|(i, _)| i..i + name.len())
+            .collect()
+    }
+
+    #[test]
+    fn scopes_and_shadowing() {
+        let input = "let x = 1; let f = fn(x) { x + y }; let y = x; f(y);";
+        let table = SymbolTable::build(&cst::parse(input));
+        let xs = spans_of(input, "x");
+
+        let outer_x = table.symbol_at(xs[0].start).unwrap();
+        assert_eq!(outer_x.definitions, vec![xs[0].clone()]);
+        assert_eq!(outer_x.references, vec![xs[3].clone()]);
+
+        let param_x = table.symbol_at(xs[1].start).unwrap();
+        assert_eq!(param_x.occurrences(), vec![xs[1].clone(), xs[2].clone()]);
+
+        let y = table.symbol_at(spans_of(input, "y")[0].start).unwrap();
+        assert_eq!(y.occurrences(), spans_of(input, "y"));
+    }
+
+    #[test]
+    fn loop_variables() {
+        let input = "let v = 0; for (v in [1]) { v }; v";
+        let table = SymbolTable::build(&cst::parse(input));
+        let v = table.symbol_at(4).unwrap();
+        assert_eq!(v.definitions.len(), 2);
+        assert_eq!(v.references.len(), 2);
+        assert!(table.unresolved.is_empty());
+    }
+
+    #[test]
+    fn pattern_bindings() {
+        let input = "let [h, ...r] = xs; match (r) { {\"k\": k} if k => h, _ => k }";
+        let table = SymbolTable::build(&cst::parse(input));
+        let k = table.symbol_at(spans_of(input, "k")[1].start).unwrap();
+        assert_eq!(k.definitions, vec![spans_of(input, "k")[1].clone()]);
+        assert_eq!(k.references.len(), 2);
+        let r = table.symbol_at(spans_of(input, "r")[0].start).unwrap();
+        assert_eq!(r.references.len(), 1);
+        let names: Vec<&str> = table.unresolved.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["xs"]);
+
+        let input = "let (p, q) = t; fn((a, [b]), c) { a + b + c + p + q }";
+        let table = SymbolTable::build(&cst::parse(input));
+        let b = table.symbol_at(spans_of(input, "b")[0].start).unwrap();
+        assert_eq!(b.occurrences(), spans_of(input, "b"));
+        let names: Vec<&str> = table.unresolved.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["t"]);
+    }
+
+    #[test]
+    fn mutability() {
+        let input = "let mut a = 1; let b = 2; let f = fn(a) { a = b; b = a }; a = 3; c = 4";
+        let table = SymbolTable::build(&cst::parse(input));
+        assert!(table.symbol_at(8).unwrap().mutable);
+        assert!(!table.symbol_at(19).unwrap().mutable);
+        let names: Vec<&str> = table
+            .immutable_assignments
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(table.immutable_assignments[0].1, spans_of(input, "a")[2]);
+    }
+
+    #[test]
+    fn shadowing() {
+        let input = "let a = 1; let f = fn(b) { let a = b; let b = 2; for (c in [a]) { let c = 3; } }; \
+                     let a = f; let g = fn() { a }; let h = fn() { let g = 1; g };";
+        let table = SymbolTable::build(&cst::parse(input));
+        let (a, b, c) = (
+            spans_of(input, "a"),
+            spans_of(input, "b"),
+            spans_of(input, "c"),
+        );
+        let g = spans_of(input, "g");
+        let shadowed: Vec<(&str, Range<usize>, Range<usize>)> = table
+            .shadowings
+            .iter()
+            .map(|s| (s.name.as_str(), s.shadowed.clone(), s.span.clone()))
+            .collect();
+        assert_eq!(
+            shadowed,
+            vec![
+                ("a", a[0].clone(), a[1].clone()),
+                ("b", b[0].clone(), b[2].clone()),
+                ("c", c[0].clone(), c[1].clone()),
+                ("a", a[0].clone(), a[3].clone()),
+                ("g", g[0].clone(), g[1].clone()),
+            ]
+        );
+        let error = table.shadowings[0].to_error(input);
+        assert_eq!(
+            (error.code, error.message, error.span),
+            (
+                ErrorCode::ShadowedBinding,
+                "`a` at line 1, column 32 shadows the binding at line 1, column 5".to_owned(),
+                Some(a[1].clone())
+            )
+        );
+        assert!(
+            SymbolTable::build(&cst::parse("let f = fn(x) { x }; let g = fn(x) { x };"))
+                .shadowings
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn keyword_arguments() {
+        let input = "let f = fn(a, b) { a }; f(b: 1, a: 2); let g = fn(h) { h(a: 3) }; f(c: 4)";
+        let table = SymbolTable::build(&cst::parse(input));
+        let a = table.symbol_at(11).unwrap();
+        assert_eq!(a.occurrences(), spans_of(input, "a")[..3].to_vec());
+        let keywords: Vec<&str> = table.keyword_args.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(keywords, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn unresolved() {
+        let table = SymbolTable::build(&cst::parse("let a = len(b); a"));
+        let names: Vec<&str> = table.unresolved.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["len", "b"]);
+        assert_eq!(table.symbols.len(), 1);
+        assert_eq!(table.symbols[0].references.len(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{InfixOperator, TypeAnnotation};
+use std::fmt;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Type {
+    Null,
+    Int,
+    Float,
+    Bool,
+    String,
+    Array,
+    Tuple,
+    Hash,
+    Function,
+    Unknown,
+}
+
+impl Type {
+    /// Common type of two values that can flow into the same place.
+    pub fn join(self, other: Type) -> Type {
+        if self == other { self } else { Type::Unknown }
+    }
+
+    /// Whether a value of type `actual` can be stored where `self` is expected.
+    pub fn accepts(self, actual: Type) -> bool {
+        self == Type::Unknown || actual == Type::Unknown || self == actual
+    }
+
+    /// Result type of an infix operation, `None` when the evaluator would reject it.
+    pub fn infix(operator: &InfixOperator, left: Type, right: Type) -> Option<Type> {
+        if let InfixOperator::And | InfixOperator::Or = operator {
+            return Some(Type::Bool);
+        }
+        if left == Type::Unknown || right == Type::Unknown {
+            return Some(Type::Unknown);
+        }
+        Some(match (left, right, operator) {
+            (Type::Null, Type::Null, _) => Type::Null,
+            (
+                Type::Int,
+                Type::Int,
+                InfixOperator::Add
+                | InfixOperator::Sub
+                | InfixOperator::Mul
+                | InfixOperator::Div
+                | InfixOperator::Mod
+                | InfixOperator::Pow,
+            ) => Type::Int,
+            (
+                Type::Int | Type::Float,
+                Type::Int | Type::Float,
+                InfixOperator::Add
+                | InfixOperator::Sub
+                | InfixOperator::Mul
+                | InfixOperator::Div
+                | InfixOperator::Mod,
+            ) => Type::Float,
+            (
+                Type::Int | Type::Float,
+                Type::Int | Type::Float,
+                InfixOperator::Eq
+                | InfixOperator::NotEq
+                | InfixOperator::Lt
+                | InfixOperator::Gt
+                | InfixOperator::LtEq
+                | InfixOperator::GtEq,
+            ) => Type::Bool,
+            (
+                Type::Bool | Type::Tuple,
+                Type::Bool | Type::Tuple,
+                InfixOperator::Eq | InfixOperator::NotEq,
+            ) if left == right => Type::Bool,
+            (Type::String, Type::String, InfixOperator::Add) => Type::String,
+            (
+                Type::String,
+                Type::String,
+                InfixOperator::Eq
+                | InfixOperator::NotEq
+                | InfixOperator::Lt
+                | InfixOperator::Gt
+                | InfixOperator::LtEq
+                | InfixOperator::GtEq,
+            ) => Type::Bool,
+            (Type::Array | Type::Tuple, Type::Int, InfixOperator::Index) => Type::Unknown,
+            (Type::Hash, Type::Int | Type::String | Type::Bool, InfixOperator::Index) => {
+                Type::Unknown
+            }
+            // Hashes may overload the other operators with hooks like `__add`.
+            (Type::Hash, _, operator) if *operator != InfixOperator::Index => Type::Unknown,
+            _ => return None,
+        })
+    }
+}
+
+impl From<TypeAnnotation> for Type {
+    fn from(annotation: TypeAnnotation) -> Self {
+        match annotation {
+            TypeAnnotation::Int => Type::Int,
+            TypeAnnotation::Float => Type::Float,
+            TypeAnnotation::Bool => Type::Bool,
+            TypeAnnotation::Str => Type::String,
+            TypeAnnotation::Array => Type::Array,
+            TypeAnnotation::Tuple => Type::Tuple,
+            TypeAnnotation::Hash => Type::Hash,
+            TypeAnnotation::Fn => Type::Function,
+            TypeAnnotation::Null => Type::Null,
+            TypeAnnotation::Any => Type::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Null => write!(f, "null"),
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "str"),
+            Type::Array => write!(f, "array"),
+            Type::Tuple => write!(f, "tuple"),
+            Type::Hash => write!(f, "hash"),
+            Type::Function => write!(f, "fn"),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
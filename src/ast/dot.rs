@@ -0,0 +1,275 @@
+use crate::ast::printer::pattern_to_source;
+use crate::ast::{Expression, InfixOperator, PrefixOperator, Program, Statement};
+use crate::prelude::*;
+
+/// Renders the AST as a Graphviz graph, each node labelled with its kind and the
+/// edges with the role of the child when it isn't obvious.
+pub fn to_dot(program: &Program) -> String {
+    let mut graph = Graph {
+        output: String::from("digraph ast {\n    node [shape=box];\n"),
+        nodes: 0,
+    };
+    let root = graph.node("program");
+    for statement in &program.statements {
+        let child = graph.statement(statement);
+        graph.edge(root, child, "");
+    }
+    graph.output.push_str("}\n");
+    graph.output
+}
+
+/// Escapes `text` for a double-quoted DOT string.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+struct Graph {
+    output: String,
+    nodes: usize,
+}
+
+impl Graph {
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.nodes;
+        self.nodes += 1;
+        self.output
+            .push_str(&format!("    n{id} [label=\"{}\"];\n", escape(label)));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        match label {
+            "" => self.output.push_str(&format!("    n{from} -> n{to};\n")),
+            label => self
+                .output
+                .push_str(&format!("    n{from} -> n{to} [label=\"{label}\"];\n")),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        match statement {
+            Statement::Let {
+                name,
+                mutable,
+                annotation,
+                value,
+            } => {
+                let keyword = if *mutable { "let mut" } else { "let" };
+                let id = match annotation {
+                    Some(annotation) => self.node(&format!("{keyword} {name}: {annotation}")),
+                    None => self.node(&format!("{keyword} {name}")),
+                };
+                let child = self.expression(value);
+                self.edge(id, child, "");
+                id
+            }
+            Statement::Assign { name, value } => self.wrap(&format!("{name} ="), value),
+            Statement::Destructure { pattern, value } => {
+                self.wrap(&format!("let {}", pattern_to_source(pattern)), value)
+            }
+            Statement::Return { value } => self.wrap("return", value),
+            Statement::Yield { value } => self.wrap("yield", value),
+            Statement::Break => self.node("break"),
+            Statement::Continue => self.node("continue"),
+            Statement::Struct { name, fields } => {
+                self.node(&format!("struct {name} {{ {} }}", fields.join(", ")))
+            }
+            Statement::Expr(expr) => self.expression(expr),
+            Statement::Block(stmts) => self.block(stmts),
+        }
+    }
+
+    fn block(&mut self, stmts: &[Statement]) -> usize {
+        let id = self.node("block");
+        for statement in stmts {
+            let child = self.statement(statement);
+            self.edge(id, child, "");
+        }
+        id
+    }
+
+    fn wrap(&mut self, label: &str, expr: &Expression) -> usize {
+        let id = self.node(label);
+        let child = self.expression(expr);
+        self.edge(id, child, "");
+        id
+    }
+
+    fn expression(&mut self, expr: &Expression) -> usize {
+        match expr {
+            Expression::Bool(value) => self.node(&value.to_string()),
+            Expression::Int(value) => self.node(&value.to_string()),
+            Expression::Float(value) => self.node(&format!("{value:?}")),
+            Expression::Ident(ident) => self.node(ident),
+            Expression::String(string) => self.node(&format!("\"{string}\"")),
+            Expression::Prefix { operator, right } => self.wrap(
+                match operator {
+                    PrefixOperator::Not => "!",
+                    PrefixOperator::Neg => "-",
+                    PrefixOperator::Plus => "+",
+                },
+                right,
+            ),
+            Expression::Field { object, name } => self.wrap(&format!(".{name}"), object),
+            Expression::Infix {
+                operator,
+                left,
+                right,
+            } => {
+                let id = match operator {
+                    InfixOperator::Index => self.node("index"),
+                    operator => self.node(operator.to_string().trim_matches('`')),
+                };
+                let (left, right) = (self.expression(left), self.expression(right));
+                self.edge(id, left, "");
+                self.edge(id, right, "");
+                id
+            }
+            Expression::Func {
+                args,
+                arg_types,
+                return_type,
+                body,
+            } => {
+                let params: Vec<String> = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, arg)| match arg_types.get(i) {
+                        Some(Some(annotation)) => format!("{arg}: {annotation}"),
+                        _ => arg.clone(),
+                    })
+                    .collect();
+                let mut label = format!("fn({})", params.join(", "));
+                if let Some(annotation) = return_type {
+                    label.push_str(&format!(" -> {annotation}"));
+                }
+                let id = self.node(&label);
+                let body = self.block(body);
+                self.edge(id, body, "");
+                id
+            }
+            Expression::Call {
+                func, args, kwargs, ..
+            } => {
+                let id = self.node("call");
+                let func = self.expression(func);
+                self.edge(id, func, "callee");
+                for arg in args {
+                    let arg = self.expression(arg);
+                    self.edge(id, arg, "");
+                }
+                for (name, value) in kwargs {
+                    let value = self.expression(value);
+                    self.edge(id, value, name);
+                }
+                id
+            }
+            Expression::For {
+                name,
+                iterable,
+                body,
+            } => {
+                let id = self.node(&format!("for {name}"));
+                let (iterable, body) = (self.expression(iterable), self.block(body));
+                self.edge(id, iterable, "in");
+                self.edge(id, body, "");
+                id
+            }
+            Expression::While { cond, body } => {
+                let id = self.node("while");
+                let (cond, body) = (self.expression(cond), self.block(body));
+                self.edge(id, cond, "cond");
+                self.edge(id, body, "");
+                id
+            }
+            Expression::Cond { cond, then_, else_ } => {
+                let id = self.node("if");
+                let (cond, then_) = (self.expression(cond), self.block(then_));
+                self.edge(id, cond, "cond");
+                self.edge(id, then_, "then");
+                if let Some(else_) = else_ {
+                    let else_ = self.block(else_);
+                    self.edge(id, else_, "else");
+                }
+                id
+            }
+            Expression::Match { subject, arms } => {
+                let id = self.node("match");
+                let subject = self.expression(subject);
+                self.edge(id, subject, "subject");
+                for arm in arms {
+                    let child = self.node(&pattern_to_source(&arm.pattern));
+                    self.edge(id, child, "");
+                    if let Some(guard) = &arm.guard {
+                        let guard = self.expression(guard);
+                        self.edge(child, guard, "if");
+                    }
+                    let value = self.expression(&arm.value);
+                    self.edge(child, value, "=>");
+                }
+                id
+            }
+            Expression::Array(content) | Expression::Tuple(content) => {
+                let label = match expr {
+                    Expression::Array(_) => "array",
+                    _ => "tuple",
+                };
+                let id = self.node(label);
+                for item in content {
+                    let item = self.expression(item);
+                    self.edge(id, item, "");
+                }
+                id
+            }
+            Expression::Hash { pairs, .. } => {
+                let id = self.node("hash");
+                for (key, value) in pairs {
+                    let (key, value) = (self.expression(key), self.expression(value));
+                    self.edge(id, key, "key");
+                    self.edge(id, value, "value");
+                }
+                id
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn graph() {
+        let program = Parser::init("let s = \"a\\b\"; if (s) { f(1 + 2) }")
+            .parse_program()
+            .unwrap();
+        assert_eq!(
+            to_dot(&program),
+            "digraph ast {\n    node [shape=box];\n    \
+             n0 [label=\"program\"];\n    \
+             n1 [label=\"let s\"];\n    \
+             n2 [label=\"\\\"a\\\\b\\\"\"];\n    \
+             n1 -> n2;\n    \
+             n0 -> n1;\n    \
+             n3 [label=\"if\"];\n    \
+             n4 [label=\"s\"];\n    \
+             n5 [label=\"block\"];\n    \
+             n6 [label=\"call\"];\n    \
+             n7 [label=\"f\"];\n    \
+             n6 -> n7 [label=\"callee\"];\n    \
+             n8 [label=\"+\"];\n    \
+             n9 [label=\"1\"];\n    \
+             n10 [label=\"2\"];\n    \
+             n8 -> n9;\n    \
+             n8 -> n10;\n    \
+             n6 -> n8;\n    \
+             n5 -> n6;\n    \
+             n3 -> n4 [label=\"cond\"];\n    \
+             n3 -> n5 [label=\"then\"];\n    \
+             n0 -> n3;\n}\n"
+        );
+    }
+}
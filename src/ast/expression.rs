@@ -1,12 +1,18 @@
 use crate::ast::{
     operators::{InfixOperator, PrefixOperator},
+    pattern::{MatchArm, Pattern},
     statement::Statement,
+    types::TypeAnnotation,
 };
+use crate::prelude::*;
+use core::ops::Range;
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expression {
     Bool(bool),
     Int(i64),
+    Float(f64),
     Ident(String),
     String(String),
     Infix {
@@ -20,11 +26,18 @@ pub enum Expression {
     },
     Func {
         args: Vec<String>,
+        arg_types: Vec<Option<TypeAnnotation>>,
+        return_type: Option<TypeAnnotation>,
         body: Vec<Statement>,
     },
     Call {
         func: Box<Expression>,
         args: Vec<Expression>,
+        /// `name: value` arguments, following the positional ones and bound to the
+        /// parameters of the same names.
+        kwargs: Vec<(String, Expression)>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        span: CallSpan,
     },
     Cond {
         cond: Box<Expression>,
@@ -32,7 +45,341 @@ pub enum Expression {
         else_: Option<Vec<Statement>>,
     },
     Array(Vec<Expression>),
-    Hash(Vec<(Expression, Expression)>),
+    /// `(a, b)`, or `(a,)` with a single value and `()` with none.
+    Tuple(Vec<Expression>),
+    Hash {
+        pairs: Vec<(Expression, Expression)>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        span: HashSpan,
+    },
+    /// `for (name in iterable) { body }`, binding `name` in the enclosing scope.
+    For {
+        name: String,
+        iterable: Box<Expression>,
+        body: Vec<Statement>,
+    },
+    /// `while (cond) { body }`, evaluating `body` as long as `cond` is truthy.
+    While {
+        cond: Box<Expression>,
+        body: Vec<Statement>,
+    },
+    /// `object.name`, the field of a struct, or the value at the key `"name"` of a hash.
+    Field {
+        object: Box<Expression>,
+        name: String,
+    },
+    /// `match (subject) { pattern [if guard] => value, ... }`, evaluating to the value
+    /// of the first arm matching, or to null when none does.
+    Match {
+        subject: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+/// The binding holding the operand of a `?`, which scripts can't name.
+const TRY_BINDING: &str = "$try";
+
+/// The binding holding the object of a method call.
+const SELF_BINDING: &str = "$self";
+
+/// Prefix of the parameters standing for those given as patterns, followed by their
+/// position.
+const PARAM_BINDING: &str = "$param";
+
+impl Expression {
+    /// `value?`, desugared to `if (true) { let $try = value; if (is_error($try)) { return
+    /// $try; } else { $try } }` so that errors are returned from the enclosing function.
+    pub fn propagating(value: Expression) -> Self {
+        Expression::Cond {
+            cond: Box::new(Expression::Bool(true)),
+            then_: vec![
+                Statement::Let {
+                    name: TRY_BINDING.to_owned(),
+                    mutable: false,
+                    annotation: None,
+                    value,
+                },
+                Statement::Expr(Self::error_check()),
+            ],
+            else_: None,
+        }
+    }
+
+    /// The operand of a `?` desugared by [`Expression::propagating`], if it's one.
+    pub fn propagated(&self) -> Option<&Expression> {
+        let Expression::Cond {
+            cond,
+            then_,
+            else_: None,
+        } = self
+        else {
+            return None;
+        };
+        match (&**cond, &then_[..]) {
+            (
+                Expression::Bool(true),
+                [
+                    Statement::Let {
+                        name,
+                        mutable: false,
+                        annotation: None,
+                        value,
+                    },
+                    Statement::Expr(check),
+                ],
+            ) if name == TRY_BINDING && *check == Self::error_check() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// `object.name(args)`, desugared to `if (true) { let $self = object;
+    /// $self["name"]($self, args) }` so that `object` is evaluated once and passed to the
+    /// function it holds as its first argument, by convention named `self`.
+    ///
+    /// The method is looked up like any other key, only in `object` itself: there's no
+    /// chain of prototypes, shared methods are copied in, e.g. with `merge`. Calling a
+    /// missing key fails since `null` isn't a function.
+    pub fn method_call(
+        object: Expression,
+        name: &str,
+        args: Vec<Expression>,
+        kwargs: Vec<(String, Expression)>,
+        span: CallSpan,
+    ) -> Self {
+        let binding = || Expression::from(SELF_BINDING);
+        let call = Expression::Call {
+            func: Box::new(Expression::Infix {
+                operator: InfixOperator::Index,
+                left: Box::new(binding()),
+                right: Box::new(Expression::String(name.to_owned())),
+            }),
+            args: [binding()].into_iter().chain(args).collect(),
+            kwargs,
+            span,
+        };
+        Expression::Cond {
+            cond: Box::new(Expression::Bool(true)),
+            then_: vec![
+                Statement::Let {
+                    name: SELF_BINDING.to_owned(),
+                    mutable: false,
+                    annotation: None,
+                    value: object,
+                },
+                Statement::Expr(call),
+            ],
+            else_: None,
+        }
+    }
+
+    /// The object, the method name and the other arguments of a call desugared by
+    /// [`Expression::method_call`], if it's one.
+    pub fn method_called(&self) -> Option<MethodCall<'_>> {
+        let Expression::Cond {
+            cond,
+            then_,
+            else_: None,
+        } = self
+        else {
+            return None;
+        };
+        let (
+            Expression::Bool(true),
+            [
+                Statement::Let {
+                    name: binding,
+                    mutable: false,
+                    annotation: None,
+                    value: object,
+                },
+                Statement::Expr(Expression::Call {
+                    func, args, kwargs, ..
+                }),
+            ],
+        ) = (&**cond, &then_[..])
+        else {
+            return None;
+        };
+        let Expression::Infix {
+            operator: InfixOperator::Index,
+            left,
+            right,
+        } = &**func
+        else {
+            return None;
+        };
+        let self_binding = Expression::from(SELF_BINDING);
+        match (&**right, &args[..]) {
+            (Expression::String(name), [first, args @ ..])
+                if binding == SELF_BINDING && **left == self_binding && *first == self_binding =>
+            {
+                Some(MethodCall {
+                    object,
+                    name,
+                    args,
+                    kwargs,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The parameter standing for the pattern at `index` in `fn(pattern) { body }`, and
+    /// the statement destructuring it: the function is desugared to `fn($param0) { let
+    /// pattern = $param0; body }`.
+    pub fn pattern_parameter(index: usize, pattern: Pattern) -> (String, Statement) {
+        let name = format!("{PARAM_BINDING}{index}");
+        let destructure = Statement::Destructure {
+            pattern,
+            value: Expression::from(name.as_str()),
+        };
+        (name, destructure)
+    }
+
+    /// The parameters of a function as patterns, taking those desugared by
+    /// [`Expression::pattern_parameter`] back from the start of `body`, with the rest of it.
+    pub fn parameter_patterns<'a>(
+        args: &[String],
+        body: &'a [Statement],
+    ) -> (Vec<Pattern>, &'a [Statement]) {
+        let mut rest = body;
+        let patterns = args
+            .iter()
+            .map(|arg| {
+                if arg.starts_with(PARAM_BINDING)
+                    && let [
+                        Statement::Destructure {
+                            pattern,
+                            value: Expression::Ident(name),
+                        },
+                        others @ ..,
+                    ] = rest
+                    && name == arg
+                {
+                    rest = others;
+                    return pattern.clone();
+                }
+                Pattern::Ident(arg.clone())
+            })
+            .collect();
+        (patterns, rest)
+    }
+
+    fn error_check() -> Self {
+        let binding = || Expression::from(TRY_BINDING);
+        Expression::Cond {
+            cond: Box::new(Expression::Call {
+                func: Box::new(Expression::from("is_error")),
+                args: vec![binding()],
+                kwargs: vec![],
+                span: CallSpan::default(),
+            }),
+            then_: vec![Statement::Return { value: binding() }],
+            else_: Some(vec![Statement::Expr(binding())]),
+        }
+    }
+
+    /// See [`Statement::yields`].
+    pub fn yields(&self) -> bool {
+        match self {
+            Expression::Bool(_)
+            | Expression::Int(_)
+            | Expression::Float(_)
+            | Expression::Ident(_)
+            | Expression::String(_)
+            | Expression::Func { .. } => false,
+            Expression::Infix { left, right, .. } => left.yields() || right.yields(),
+            Expression::Prefix { right, .. } => right.yields(),
+            Expression::Field { object, .. } => object.yields(),
+            Expression::Call {
+                func, args, kwargs, ..
+            } => {
+                func.yields()
+                    || args.iter().any(Expression::yields)
+                    || kwargs.iter().any(|(_, value)| value.yields())
+            }
+            Expression::Cond { cond, then_, else_ } => {
+                cond.yields()
+                    || then_
+                        .iter()
+                        .chain(else_.iter().flatten())
+                        .any(Statement::yields)
+            }
+            Expression::Array(content) | Expression::Tuple(content) => {
+                content.iter().any(Expression::yields)
+            }
+            Expression::Hash { pairs, .. } => pairs.iter().any(|(k, v)| k.yields() || v.yields()),
+            Expression::For { iterable, body, .. } => {
+                iterable.yields() || body.iter().any(Statement::yields)
+            }
+            Expression::While { cond, body } => cond.yields() || body.iter().any(Statement::yields),
+            Expression::Match { subject, arms } => {
+                subject.yields()
+                    || arms.iter().any(|arm| {
+                        arm.guard.as_ref().is_some_and(Expression::yields) || arm.value.yields()
+                    })
+            }
+        }
+    }
+}
+
+/// The parts of `object.name(args, kwargs)`, see [`Expression::method_called`].
+#[derive(Debug, PartialEq)]
+pub struct MethodCall<'a> {
+    pub object: &'a Expression,
+    pub name: &'a str,
+    pub args: &'a [Expression],
+    pub kwargs: &'a [(String, Expression)],
+}
+
+/// Where a call is in the source it was parsed from: the whole call, and the expression
+/// called. Calls made up by desugaring, like the check of `?`, have none.
+///
+/// Spans take no part in comparisons, so that the same program parsed from differently
+/// laid out sources is equal.
+#[derive(Debug, Clone, Default)]
+pub struct CallSpan(Option<(Range<usize>, Range<usize>)>);
+
+impl CallSpan {
+    pub fn new(call: Range<usize>, callee: Range<usize>) -> Self {
+        Self(Some((call, callee)))
+    }
+
+    pub fn call(&self) -> Option<Range<usize>> {
+        self.0.as_ref().map(|(call, _)| call.clone())
+    }
+
+    pub fn callee(&self) -> Option<Range<usize>> {
+        self.0.as_ref().map(|(_, callee)| callee.clone())
+    }
+}
+
+impl PartialEq for CallSpan {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// Where the keys of a hash literal are in the source it was parsed from, one per pair.
+/// Like [`CallSpan`], spans take no part in comparisons.
+#[derive(Debug, Clone, Default)]
+pub struct HashSpan(Vec<Range<usize>>);
+
+impl HashSpan {
+    pub fn new(keys: Vec<Range<usize>>) -> Self {
+        Self(keys)
+    }
+
+    /// The span of the key of the pair at `index`, if known.
+    pub fn key(&self, index: usize) -> Option<Range<usize>> {
+        self.0.get(index).cloned()
+    }
+}
+
+impl PartialEq for HashSpan {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
 }
 
 impl From<i64> for Expression {
@@ -1,8 +1,21 @@
 pub mod expression;
-pub use expression::Expression;
+pub use expression::{CallSpan, Expression, HashSpan, MethodCall};
+
+pub mod dot;
+pub use dot::to_dot;
+
+pub mod pattern;
+pub use pattern::{MatchArm, Pattern};
+
+pub mod printer;
+
+pub mod sexp;
 
 pub mod operators;
 pub use operators::{InfixOperator, PrefixOperator};
 
 pub mod statement;
 pub use statement::{Program, Statement};
+
+pub mod types;
+pub use types::TypeAnnotation;
@@ -1,22 +1,29 @@
-use anyhow;
-
 use crate::Token;
-use std::fmt;
+use crate::error::{Error, ErrorCode, Result, fail};
+use crate::prelude::*;
+use core::fmt;
 
 #[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum PrefixOperator {
     Not,
     Neg,
+    /// `+x`, giving back a number unchanged.
+    Plus,
 }
 
 impl TryFrom<&Token> for PrefixOperator {
-    type Error = anyhow::Error;
+    type Error = Error;
 
-    fn try_from(token: &Token) -> anyhow::Result<Self> {
+    fn try_from(token: &Token) -> Result<Self> {
         Ok(match token {
             Token::Bang => Self::Not,
             Token::Minus => Self::Neg,
-            _ => anyhow::bail!("Invalid token {token} as a prefix operator"),
+            Token::Plus => Self::Plus,
+            _ => fail!(
+                ErrorCode::UnexpectedToken,
+                "Invalid token {token} as a prefix operator"
+            ),
         })
     }
 }
@@ -26,38 +33,59 @@ impl fmt::Display for PrefixOperator {
         match self {
             PrefixOperator::Not => write!(f, "`!`"),
             PrefixOperator::Neg => write!(f, "`-`"),
+            PrefixOperator::Plus => write!(f, "`+`"),
         }
     }
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum InfixOperator {
     Add,
     Sub,
     Mul,
     Div,
+    /// `%`, the remainder having the sign of the left operand.
+    Mod,
+    /// `**`, right-associative.
+    Pow,
     Eq,
     NotEq,
     Gt,
     Lt,
+    GtEq,
+    LtEq,
+    /// `&&` and `||` only evaluate their right operand when the left one doesn't decide,
+    /// and give a bool.
+    And,
+    Or,
     Index,
 }
 
 impl TryFrom<&Token> for InfixOperator {
-    type Error = anyhow::Error;
+    type Error = Error;
 
-    fn try_from(token: &Token) -> anyhow::Result<Self> {
+    fn try_from(token: &Token) -> Result<Self> {
         Ok(match token {
             Token::Plus => Self::Add,
             Token::Minus => Self::Sub,
             Token::Asterisk => Self::Mul,
             Token::Slash => Self::Div,
+            Token::Percent => Self::Mod,
+            Token::Power => Self::Pow,
             Token::Eq => Self::Eq,
             Token::NotEq => Self::NotEq,
             Token::Gt => Self::Gt,
             Token::Lt => Self::Lt,
+            Token::GtEq => Self::GtEq,
+            Token::LtEq => Self::LtEq,
+            Token::And => Self::And,
+            Token::Or => Self::Or,
             Token::LBracket => Self::Index,
-            _ => anyhow::bail!("Invalid token {token} as a infix operator"),
+            _ => fail!(
+                ErrorCode::UnexpectedToken,
+                "Invalid token {token} as a infix operator"
+            ),
         })
     }
 }
@@ -69,10 +97,16 @@ impl fmt::Display for InfixOperator {
             InfixOperator::Sub => write!(f, "`-`"),
             InfixOperator::Mul => write!(f, "`*`"),
             InfixOperator::Div => write!(f, "`/`"),
+            InfixOperator::Mod => write!(f, "`%`"),
+            InfixOperator::Pow => write!(f, "`**`"),
             InfixOperator::Eq => write!(f, "`==`"),
             InfixOperator::NotEq => write!(f, "`!=`"),
             InfixOperator::Gt => write!(f, "`>`"),
             InfixOperator::Lt => write!(f, "`<`"),
+            InfixOperator::GtEq => write!(f, "`>=`"),
+            InfixOperator::LtEq => write!(f, "`<=`"),
+            InfixOperator::And => write!(f, "`&&`"),
+            InfixOperator::Or => write!(f, "`||`"),
             InfixOperator::Index => write!(f, "`[...]`"),
         }
     }
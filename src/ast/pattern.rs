@@ -0,0 +1,109 @@
+use crate::ast::expression::Expression;
+use crate::ast::printer::pattern_to_source;
+use crate::prelude::*;
+use core::fmt;
+
+/// The shape a value is matched against in `match` arms and `let` destructuring.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Pattern {
+    /// `_`, matching anything without binding it.
+    Wildcard,
+    /// Matches anything, binding it to the name.
+    Ident(String),
+    /// An int, string or bool literal, or `null`, matching values equal to it.
+    Literal(Expression),
+    /// `[first, second, ...rest]`, matching arrays with exactly as many elements as
+    /// `items`, or at least as many when there is a `rest`, which gets the others.
+    Array {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+    },
+    /// `(first, second)`, matching tuples with exactly as many values as `items`.
+    Tuple(Vec<Pattern>),
+    /// `{"key": pattern}`, matching hashes holding every key, whatever the others.
+    Hash(Vec<(Expression, Pattern)>),
+    /// `Name(patterns)`, matching instances of the struct called `name` whose fields
+    /// match the patterns, in the order they're declared.
+    Struct { name: String, fields: Vec<Pattern> },
+}
+
+impl Pattern {
+    /// The names the pattern binds, in order.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.visit(&mut |pattern| {
+            if let Pattern::Ident(name) = pattern {
+                names.push(name.as_str());
+            }
+        });
+        names
+    }
+
+    pub fn names_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Pattern::Wildcard | Pattern::Literal(_) => vec![],
+            Pattern::Ident(name) => vec![name],
+            Pattern::Array { items, rest } => items
+                .iter_mut()
+                .chain(rest.as_deref_mut())
+                .flat_map(Pattern::names_mut)
+                .collect(),
+            Pattern::Tuple(items) => items.iter_mut().flat_map(Pattern::names_mut).collect(),
+            Pattern::Hash(pairs) => pairs
+                .iter_mut()
+                .flat_map(|(_, pattern)| pattern.names_mut())
+                .collect(),
+            Pattern::Struct { fields, .. } => {
+                fields.iter_mut().flat_map(Pattern::names_mut).collect()
+            }
+        }
+    }
+
+    /// Whether every value matches the pattern.
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Pattern::Wildcard | Pattern::Ident(_))
+    }
+
+    fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Pattern)) {
+        f(self);
+        match self {
+            Pattern::Wildcard | Pattern::Ident(_) | Pattern::Literal(_) => {}
+            Pattern::Array { items, rest } => {
+                for item in items.iter().chain(rest.as_deref()) {
+                    item.visit(f);
+                }
+            }
+            Pattern::Tuple(items) => {
+                for item in items {
+                    item.visit(f);
+                }
+            }
+            Pattern::Hash(pairs) => {
+                for (_, pattern) in pairs {
+                    pattern.visit(f);
+                }
+            }
+            Pattern::Struct { fields, .. } => {
+                for field in fields {
+                    field.visit(f);
+                }
+            }
+        }
+    }
+}
+
+/// `pattern [if guard] => value`, an arm of a `match` expression.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub value: Expression,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", pattern_to_source(self))
+    }
+}
@@ -0,0 +1,580 @@
+use crate::ast::{Expression, InfixOperator, Pattern, PrefixOperator, Program, Statement};
+use crate::parser::Precedence;
+use crate::prelude::*;
+
+/// Renders the AST back to Monkey source that parses to the same tree.
+/// The pretty layout puts each statement on its own line, the compact one drops
+/// every space and separator that isn't needed.
+pub struct Printer {
+    compact: bool,
+    depth: usize,
+    output: String,
+}
+
+pub fn to_source(program: &Program) -> String {
+    let mut printer = Printer::new(false);
+    printer.statements(&program.statements, true);
+    printer.output
+}
+
+pub fn to_compact_source(program: &Program) -> String {
+    let mut printer = Printer::new(true);
+    printer.statements(&program.statements, true);
+    printer.output
+}
+
+pub fn expression_to_source(expr: &Expression) -> String {
+    let mut printer = Printer::new(false);
+    printer.expression(expr, Precedence::Lowest);
+    printer.output
+}
+
+pub fn pattern_to_source(pattern: &Pattern) -> String {
+    let mut printer = Printer::new(false);
+    printer.pattern(pattern);
+    printer.output
+}
+
+pub fn statement_to_source(statement: &Statement) -> String {
+    let mut printer = Printer::new(false);
+    printer.statement(statement, true);
+    printer.output
+}
+
+impl Printer {
+    fn new(compact: bool) -> Self {
+        Self {
+            compact,
+            depth: 0,
+            output: String::new(),
+        }
+    }
+
+    fn statements(&mut self, stmts: &[Statement], top_level: bool) {
+        for (i, statement) in stmts.iter().enumerate() {
+            let last = i + 1 == stmts.len();
+            if !self.compact {
+                if !top_level || i > 0 {
+                    self.output.push('\n');
+                }
+                self.output.push_str(&"    ".repeat(self.depth));
+            }
+            self.statement(statement, last);
+        }
+        if !self.compact && top_level && !stmts.is_empty() {
+            self.output.push('\n');
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, last: bool) {
+        match statement {
+            Statement::Let {
+                name,
+                mutable,
+                annotation,
+                value,
+            } => {
+                self.output
+                    .push_str(if *mutable { "let mut " } else { "let " });
+                self.output.push_str(name);
+                if let Some(annotation) = annotation {
+                    self.output.push(':');
+                    self.space();
+                    self.output.push_str(&annotation.to_string());
+                }
+                self.space();
+                self.output.push('=');
+                self.space();
+                self.expression(value, Precedence::Lowest);
+                self.output.push(';');
+            }
+            Statement::Assign { name, value } => {
+                self.output.push_str(name);
+                self.space();
+                self.output.push('=');
+                self.space();
+                self.expression(value, Precedence::Lowest);
+                self.output.push(';');
+            }
+            Statement::Destructure { pattern, value } => {
+                self.output.push_str("let ");
+                self.pattern(pattern);
+                self.space();
+                self.output.push('=');
+                self.space();
+                self.expression(value, Precedence::Lowest);
+                self.output.push(';');
+            }
+            Statement::Return { value } => {
+                self.output.push_str("return ");
+                self.expression(value, Precedence::Lowest);
+                self.output.push(';');
+            }
+            Statement::Yield { value } => {
+                self.output.push_str("yield ");
+                self.expression(value, Precedence::Lowest);
+                self.output.push(';');
+            }
+            Statement::Break => self.output.push_str("break;"),
+            Statement::Continue => self.output.push_str("continue;"),
+            Statement::Struct { name, fields } => {
+                self.output.push_str("struct ");
+                self.output.push_str(name);
+                self.space();
+                self.output.push('{');
+                self.space();
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.output.push_str(field);
+                }
+                if !fields.is_empty() {
+                    self.space();
+                }
+                self.output.push('}');
+            }
+            Statement::Expr(expr) => {
+                self.expression(expr, Precedence::Lowest);
+                if !self.compact || !last {
+                    self.output.push(';');
+                }
+            }
+            Statement::Block(stmts) => self.block(stmts),
+        }
+    }
+
+    fn block(&mut self, stmts: &[Statement]) {
+        self.output.push('{');
+        if !stmts.is_empty() {
+            self.depth += 1;
+            self.statements(stmts, false);
+            self.depth -= 1;
+            if !self.compact {
+                self.output.push('\n');
+                self.output.push_str(&"    ".repeat(self.depth));
+            }
+        }
+        self.output.push('}');
+    }
+
+    fn expression(&mut self, expr: &Expression, context: Precedence) {
+        let precedence = precedence_of(expr);
+        let parenthesized = precedence < context;
+        if parenthesized {
+            self.output.push('(');
+        }
+
+        match expr {
+            Expression::Bool(value) => self.output.push_str(&value.to_string()),
+            Expression::Int(value) => self.output.push_str(&value.to_string()),
+            Expression::Float(value) => self.output.push_str(&format!("{value:?}")),
+            Expression::Ident(ident) => self.output.push_str(ident),
+            Expression::String(string) => {
+                self.output.push('"');
+                self.output.push_str(string);
+                self.output.push('"');
+            }
+            Expression::Prefix { operator, right } => {
+                self.output.push(match operator {
+                    PrefixOperator::Not => '!',
+                    PrefixOperator::Neg => '-',
+                    PrefixOperator::Plus => '+',
+                });
+                self.expression(right, Precedence::Prefix);
+            }
+            Expression::Infix {
+                operator: InfixOperator::Index,
+                left,
+                right,
+            } => {
+                self.expression(left, Precedence::Index);
+                self.output.push('[');
+                self.expression(right, Precedence::Lowest);
+                self.output.push(']');
+            }
+            Expression::Infix {
+                operator: InfixOperator::Pow,
+                left,
+                right,
+            } => {
+                self.expression(left, next(precedence));
+                self.space();
+                self.output.push_str("**");
+                self.space();
+                self.expression(right, precedence);
+            }
+            Expression::Infix {
+                operator,
+                left,
+                right,
+            } => {
+                self.expression(left, precedence);
+                self.space();
+                self.output.push_str(operator_source(operator));
+                self.space();
+                self.expression(right, next(precedence));
+            }
+            Expression::Func {
+                args,
+                arg_types,
+                return_type,
+                body,
+            } => {
+                let (params, body) = Expression::parameter_patterns(args, body);
+                self.output.push_str("fn(");
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.pattern(param);
+                    if let Some(Some(annotation)) = arg_types.get(i) {
+                        self.output.push(':');
+                        self.space();
+                        self.output.push_str(&annotation.to_string());
+                    }
+                }
+                self.output.push(')');
+                if let Some(annotation) = return_type {
+                    self.space();
+                    self.output.push_str("->");
+                    self.space();
+                    self.output.push_str(&annotation.to_string());
+                }
+                self.space();
+                self.block(body);
+            }
+            Expression::Field { object, name } => {
+                self.expression(object, Precedence::Index);
+                self.output.push('.');
+                self.output.push_str(name);
+            }
+            Expression::Call {
+                func, args, kwargs, ..
+            } => {
+                self.expression(func, Precedence::Call);
+                self.arguments(args, kwargs);
+            }
+            Expression::For {
+                name,
+                iterable,
+                body,
+            } => {
+                self.output.push_str("for");
+                self.space();
+                self.output.push('(');
+                self.output.push_str(name);
+                self.output.push_str(" in ");
+                self.expression(iterable, Precedence::Lowest);
+                self.output.push(')');
+                self.space();
+                self.block(body);
+            }
+            Expression::While { cond, body } => {
+                self.output.push_str("while");
+                self.space();
+                self.output.push('(');
+                self.expression(cond, Precedence::Lowest);
+                self.output.push(')');
+                self.space();
+                self.block(body);
+            }
+            Expression::Cond { .. } if let Some(call) = expr.method_called() => {
+                self.expression(call.object, Precedence::Index);
+                self.output.push('.');
+                self.output.push_str(call.name);
+                self.arguments(call.args, call.kwargs);
+            }
+            Expression::Cond { .. } if let Some(value) = expr.propagated() => {
+                self.expression(value, Precedence::Index);
+                self.output.push('?');
+            }
+            Expression::Cond { cond, then_, else_ } => {
+                self.output.push_str("if");
+                self.space();
+                self.output.push('(');
+                self.expression(cond, Precedence::Lowest);
+                self.output.push(')');
+                self.space();
+                self.block(then_);
+                if let Some(else_) = else_ {
+                    self.space();
+                    self.output.push_str("else");
+                    self.space();
+                    self.block(else_);
+                }
+            }
+            Expression::Match { subject, arms } => {
+                self.output.push_str("match");
+                self.space();
+                self.output.push('(');
+                self.expression(subject, Precedence::Lowest);
+                self.output.push(')');
+                self.space();
+                self.output.push('{');
+                self.depth += 1;
+                for (i, arm) in arms.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push(',');
+                    }
+                    if !self.compact {
+                        self.output.push('\n');
+                        self.output.push_str(&"    ".repeat(self.depth));
+                    }
+                    self.pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.output.push_str(" if ");
+                        self.expression(guard, Precedence::Lowest);
+                    }
+                    self.space();
+                    self.output.push_str("=>");
+                    self.space();
+                    self.expression(&arm.value, Precedence::Lowest);
+                }
+                self.depth -= 1;
+                if !self.compact && !arms.is_empty() {
+                    self.output.push_str(",\n");
+                    self.output.push_str(&"    ".repeat(self.depth));
+                }
+                self.output.push('}');
+            }
+            Expression::Array(content) => {
+                self.output.push('[');
+                for (i, e) in content.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.expression(e, Precedence::Lowest);
+                }
+                self.output.push(']');
+            }
+            Expression::Tuple(content) => {
+                self.output.push('(');
+                for (i, e) in content.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.expression(e, Precedence::Lowest);
+                }
+                if content.len() == 1 {
+                    self.output.push(',');
+                }
+                self.output.push(')');
+            }
+            Expression::Hash { pairs, .. } => {
+                self.output.push('{');
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.expression(k, Precedence::Lowest);
+                    self.output.push(':');
+                    self.space();
+                    self.expression(v, Precedence::Lowest);
+                }
+                self.output.push('}');
+            }
+        }
+
+        if parenthesized {
+            self.output.push(')');
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard => self.output.push('_'),
+            Pattern::Ident(name) => self.output.push_str(name),
+            Pattern::Literal(literal) => self.expression(literal, Precedence::Lowest),
+            Pattern::Array { items, rest } => {
+                self.output.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.pattern(item);
+                }
+                if let Some(rest) = rest {
+                    if !items.is_empty() {
+                        self.separator();
+                    }
+                    self.output.push_str("...");
+                    self.pattern(rest);
+                }
+                self.output.push(']');
+            }
+            Pattern::Tuple(items) => {
+                self.output.push('(');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.pattern(item);
+                }
+                if items.len() == 1 {
+                    self.output.push(',');
+                }
+                self.output.push(')');
+            }
+            Pattern::Hash(pairs) => {
+                self.output.push('{');
+                for (i, (key, pattern)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.expression(key, Precedence::Lowest);
+                    self.output.push(':');
+                    self.space();
+                    self.pattern(pattern);
+                }
+                self.output.push('}');
+            }
+            Pattern::Struct { name, fields } => {
+                self.output.push_str(name);
+                self.output.push('(');
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.separator();
+                    }
+                    self.pattern(field);
+                }
+                self.output.push(')');
+            }
+        }
+    }
+
+    fn space(&mut self) {
+        if !self.compact {
+            self.output.push(' ');
+        }
+    }
+
+    fn separator(&mut self) {
+        self.output.push(',');
+        self.space();
+    }
+
+    fn arguments(&mut self, args: &[Expression], kwargs: &[(String, Expression)]) {
+        self.output.push('(');
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.separator();
+            }
+            self.expression(arg, Precedence::Lowest);
+        }
+        for (i, (name, value)) in kwargs.iter().enumerate() {
+            if i > 0 || !args.is_empty() {
+                self.separator();
+            }
+            self.output.push_str(name);
+            self.output.push(':');
+            self.space();
+            self.expression(value, Precedence::Lowest);
+        }
+        self.output.push(')');
+    }
+}
+
+fn precedence_of(expr: &Expression) -> Precedence {
+    match expr {
+        Expression::Infix { operator, .. } => Precedence::get_from_operator(operator),
+        Expression::Prefix { .. } => Precedence::Prefix,
+        _ => Precedence::Index,
+    }
+}
+
+/// Precedence a right operand must exceed so that left-associativity is preserved.
+fn next(precedence: Precedence) -> Precedence {
+    match precedence {
+        Precedence::Lowest => Precedence::Or,
+        Precedence::Or => Precedence::And,
+        Precedence::And => Precedence::Equals,
+        Precedence::Equals => Precedence::LessGreater,
+        Precedence::LessGreater => Precedence::Sum,
+        Precedence::Sum => Precedence::Product,
+        Precedence::Product => Precedence::Power,
+        Precedence::Power => Precedence::Prefix,
+        Precedence::Prefix => Precedence::Call,
+        Precedence::Call | Precedence::Index => Precedence::Index,
+    }
+}
+
+fn operator_source(operator: &InfixOperator) -> &'static str {
+    match operator {
+        InfixOperator::Add => "+",
+        InfixOperator::Sub => "-",
+        InfixOperator::Mul => "*",
+        InfixOperator::Div => "/",
+        InfixOperator::Mod => "%",
+        InfixOperator::Pow => "**",
+        InfixOperator::Eq => "==",
+        InfixOperator::NotEq => "!=",
+        InfixOperator::Gt => ">",
+        InfixOperator::Lt => "<",
+        InfixOperator::GtEq => ">=",
+        InfixOperator::LtEq => "<=",
+        InfixOperator::And => "&&",
+        InfixOperator::Or => "||",
+        InfixOperator::Index => "[]",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn assert_reparses(input: &str) {
+        let program = Parser::init(input).parse_program().unwrap();
+        for source in [to_source(&program), to_compact_source(&program)] {
+            assert_eq!(Parser::init(&source).parse_program().unwrap(), program);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_reparses("let a = 1 - (2 - 3) * -(4 + 5); a / 2 / 3 < 5 == true;");
+        assert_reparses("let mut n: int = 0; n = n + 1;");
+        assert_reparses(
+            "let f: fn = fn(x: int, y) -> int { if (x > y) { return x; } else { y } };",
+        );
+        assert_reparses("f(1, g(2))[0] + [1, 2][1] * {\"a\": 1, 2: true}[\"a\"]; fn(x) { x }(5)");
+        assert_reparses("!-a; -(!b); (a + b)(c)");
+        assert_reparses("a <= b == (c >= d); (a <= b) <= c");
+        assert_reparses("a || b && !c; (a || b) && c == d; a && (b && c)");
+        assert_reparses("(a, (b,), ()); let (x, [y]) = t; fn((a, b), _, c: int) { \"doc\"; a }");
+        assert_reparses("a ** b ** c; (a ** b) ** c; -a ** (b % c) * d; (a * b) ** c");
+        assert_reparses("for (x in range(3)) { let y = x * 2; }; null");
+        assert_reparses(
+            "let [a, {\"r\": r, 1: _}, ...rest] = x; match (f(x)) { [] => 0, [-1, ...t] if t == 2 => t, n => null }",
+        );
+        assert_reparses("let g = fn(n) { for (x in range(n)) { yield x * 2; }; yield -1; };");
+        assert_reparses("let f = fn(x) { -g(x?)?[0] + (a + b)?; };");
+        assert_reparses("f(1, by: g(x: 2), then: [3])");
+        assert_reparses("-(a + b).f(1)[0].g(by: 2); [1].h()");
+        assert_reparses(
+            "struct P { x, y } let f = fn(p) { match (p) { P(0, [y]) => -p.x.y, _ => p.f().x } };",
+        );
+    }
+
+    #[test]
+    fn layouts() {
+        let program = Parser::init("let add = fn(x, y) { x + y; }; add(1, 2)")
+            .parse_program()
+            .unwrap();
+        assert_eq!(
+            to_source(&program),
+            "let add = fn(x, y) {\n    x + y;\n};\nadd(1, 2);\n"
+        );
+        assert_eq!(to_compact_source(&program), "let add=fn(x,y){x+y};add(1,2)");
+
+        let program = Parser::init("struct P { x, y }; P(1, 2).x")
+            .parse_program()
+            .unwrap();
+        assert_eq!(to_source(&program), "struct P { x, y }\nP(1, 2).x;\n");
+        assert_eq!(to_compact_source(&program), "struct P{x,y}P(1,2).x");
+
+        let program = Parser::init("(-f(x)?)?").parse_program().unwrap();
+        assert_eq!(to_compact_source(&program), "(-f(x)?)?");
+    }
+}
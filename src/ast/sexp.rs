@@ -0,0 +1,248 @@
+use crate::ast::{Expression, InfixOperator, Pattern, PrefixOperator, Program, Statement};
+use crate::prelude::*;
+
+/// Renders the AST as S-expressions, one top-level statement per line, e.g.
+/// `(let x (+ 1 (* 2 3)))`. Meant for reading, not for parsing back.
+pub fn to_sexp(program: &Program) -> String {
+    program
+        .statements
+        .iter()
+        .map(|statement| statement_sexp(statement) + "\n")
+        .collect()
+}
+
+fn statement_sexp(statement: &Statement) -> String {
+    match statement {
+        Statement::Let {
+            name,
+            mutable,
+            annotation,
+            value,
+        } => {
+            let keyword = if *mutable { "let mut" } else { "let" };
+            match annotation {
+                Some(annotation) => {
+                    format!(
+                        "({keyword} ({name} {annotation}) {})",
+                        expression_sexp(value)
+                    )
+                }
+                None => format!("({keyword} {name} {})", expression_sexp(value)),
+            }
+        }
+        Statement::Assign { name, value } => format!("(set {name} {})", expression_sexp(value)),
+        Statement::Destructure { pattern, value } => {
+            format!("(let {} {})", pattern_sexp(pattern), expression_sexp(value))
+        }
+        Statement::Return { value } => format!("(return {})", expression_sexp(value)),
+        Statement::Yield { value } => format!("(yield {})", expression_sexp(value)),
+        Statement::Break => "(break)".to_owned(),
+        Statement::Continue => "(continue)".to_owned(),
+        Statement::Struct { name, fields } => list(
+            "struct",
+            [name.clone(), list("fields", fields.iter().cloned())].into_iter(),
+        ),
+        Statement::Expr(expr) => expression_sexp(expr),
+        Statement::Block(stmts) => block_sexp(stmts),
+    }
+}
+
+fn block_sexp(stmts: &[Statement]) -> String {
+    list("block", stmts.iter().map(statement_sexp))
+}
+
+fn expression_sexp(expr: &Expression) -> String {
+    match expr {
+        Expression::Bool(value) => value.to_string(),
+        Expression::Int(value) => value.to_string(),
+        Expression::Float(value) => format!("{value:?}"),
+        Expression::Ident(ident) => ident.clone(),
+        Expression::String(string) => format!("{string:?}"),
+        Expression::Prefix { operator, right } => {
+            let operator = match operator {
+                PrefixOperator::Not => "!",
+                PrefixOperator::Neg => "-",
+                PrefixOperator::Plus => "+",
+            };
+            format!("({operator} {})", expression_sexp(right))
+        }
+        Expression::Field { object, name } => format!("(. {} {name})", expression_sexp(object)),
+        Expression::Infix {
+            operator,
+            left,
+            right,
+        } => {
+            let operator = match operator {
+                InfixOperator::Add => "+",
+                InfixOperator::Sub => "-",
+                InfixOperator::Mul => "*",
+                InfixOperator::Div => "/",
+                InfixOperator::Mod => "%",
+                InfixOperator::Pow => "**",
+                InfixOperator::Eq => "==",
+                InfixOperator::NotEq => "!=",
+                InfixOperator::Gt => ">",
+                InfixOperator::Lt => "<",
+                InfixOperator::GtEq => ">=",
+                InfixOperator::LtEq => "<=",
+                InfixOperator::And => "&&",
+                InfixOperator::Or => "||",
+                InfixOperator::Index => "index",
+            };
+            format!(
+                "({operator} {} {})",
+                expression_sexp(left),
+                expression_sexp(right)
+            )
+        }
+        Expression::Func {
+            args,
+            arg_types,
+            return_type,
+            body,
+        } => {
+            let params = args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| match arg_types.get(i) {
+                    Some(Some(annotation)) => format!("({arg} {annotation})"),
+                    _ => arg.clone(),
+                });
+            let params = format!("({})", params.collect::<Vec<String>>().join(" "));
+            match return_type {
+                Some(annotation) => {
+                    format!("(fn {params} (-> {annotation}) {})", block_sexp(body))
+                }
+                None => format!("(fn {params} {})", block_sexp(body)),
+            }
+        }
+        Expression::Call {
+            func, args, kwargs, ..
+        } => list(
+            "call",
+            [expression_sexp(func)]
+                .into_iter()
+                .chain(args.iter().map(expression_sexp))
+                .chain(kwargs.iter().map(|(name, value)| {
+                    list("kw", [name.clone(), expression_sexp(value)].into_iter())
+                })),
+        ),
+        Expression::For {
+            name,
+            iterable,
+            body,
+        } => format!(
+            "(for {name} {} {})",
+            expression_sexp(iterable),
+            block_sexp(body)
+        ),
+        Expression::While { cond, body } => {
+            format!("(while {} {})", expression_sexp(cond), block_sexp(body))
+        }
+        Expression::Cond { cond, then_, else_ } => {
+            let mut sexp = format!("(if {} {}", expression_sexp(cond), block_sexp(then_));
+            if let Some(else_) = else_ {
+                sexp.push(' ');
+                sexp.push_str(&block_sexp(else_));
+            }
+            sexp + ")"
+        }
+        Expression::Match { subject, arms } => list(
+            "match",
+            [expression_sexp(subject)]
+                .into_iter()
+                .chain(arms.iter().map(|arm| {
+                    let guard = arm
+                        .guard
+                        .as_ref()
+                        .map(|guard| format!("(if {})", expression_sexp(guard)));
+                    list(
+                        "arm",
+                        [pattern_sexp(&arm.pattern)]
+                            .into_iter()
+                            .chain(guard)
+                            .chain([expression_sexp(&arm.value)]),
+                    )
+                })),
+        ),
+        Expression::Array(content) => list("array", content.iter().map(expression_sexp)),
+        Expression::Tuple(content) => list("tuple", content.iter().map(expression_sexp)),
+        Expression::Hash { pairs, .. } => list(
+            "hash",
+            pairs
+                .iter()
+                .map(|(k, v)| format!("({} {})", expression_sexp(k), expression_sexp(v))),
+        ),
+    }
+}
+
+fn pattern_sexp(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard => "_".to_owned(),
+        Pattern::Ident(name) => name.clone(),
+        Pattern::Literal(literal) => expression_sexp(literal),
+        Pattern::Array { items, rest } => list(
+            "array",
+            items.iter().map(pattern_sexp).chain(
+                rest.iter()
+                    .map(|rest| format!("(... {})", pattern_sexp(rest))),
+            ),
+        ),
+        Pattern::Tuple(items) => list("tuple", items.iter().map(pattern_sexp)),
+        Pattern::Hash(pairs) => list(
+            "hash",
+            pairs
+                .iter()
+                .map(|(k, p)| format!("({} {})", expression_sexp(k), pattern_sexp(p))),
+        ),
+        Pattern::Struct { name, fields } => list(name, fields.iter().map(pattern_sexp)),
+    }
+}
+
+fn list(head: &str, items: impl Iterator<Item = String>) -> String {
+    let mut sexp = format!("({head}");
+    for item in items {
+        sexp.push(' ');
+        sexp.push_str(&item);
+    }
+    sexp + ")"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn s_expressions() {
+        let program = Parser::init(
+            "let x: int = -1 + 2 * 3; let f = fn(a, b: str) -> str { if (a) { b } }; f(x, [\"s\", {1: true}][0]);",
+        )
+        .parse_program()
+        .unwrap();
+        assert_eq!(
+            to_sexp(&program),
+            "(let (x int) (+ (- 1) (* 2 3)))\n\
+             (let f (fn (a (b str)) (-> str) (block (if a (block b)))))\n\
+             (call f x (index (array \"s\" (hash (1 true))) 0))\n"
+        );
+
+        let program =
+            Parser::init("let [a, ...r] = x; match (x) { {\"k\": -1} if a => r, _ => 0 }")
+                .parse_program()
+                .unwrap();
+        assert_eq!(
+            to_sexp(&program),
+            "(let (array a (... r)) x)\n\
+             (match x (arm (hash (\"k\" -1)) (if a) r) (arm _ 0))\n"
+        );
+
+        let program = Parser::init("a || !b && c == d || e")
+            .parse_program()
+            .unwrap();
+        assert_eq!(to_sexp(&program), "(|| (|| a (&& (! b) (== c d))) e)\n");
+
+        let program = Parser::init("a % b * c ** d ** e").parse_program().unwrap();
+        assert_eq!(to_sexp(&program), "(* (% a b) (** c (** d e)))\n");
+    }
+}
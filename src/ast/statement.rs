@@ -1,14 +1,85 @@
-use crate::ast::expression::Expression;
+use crate::ast::{expression::Expression, pattern::Pattern, types::TypeAnnotation};
+use crate::prelude::*;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Statement {
-    Let { name: String, value: Expression },
-    Return { value: Expression },
+    /// `let name = value;`, or `let mut name = value;` for a variable that can be assigned.
+    Let {
+        name: String,
+        mutable: bool,
+        annotation: Option<TypeAnnotation>,
+        value: Expression,
+    },
+    /// `name = value;`, updating the variable in the environment where it's defined.
+    Assign {
+        name: String,
+        value: Expression,
+    },
+    /// `let [first, ...rest] = value;`, failing when the value doesn't match.
+    Destructure {
+        pattern: Pattern,
+        value: Expression,
+    },
+    Return {
+        value: Expression,
+    },
+    /// Suspends the generator the statement belongs to, producing `value`.
+    Yield {
+        value: Expression,
+    },
+    /// Leaves the innermost loop the statement belongs to.
+    Break,
+    /// Skips to the next iteration of the innermost loop the statement belongs to.
+    Continue,
+    /// `struct Name { fields }`, binding `Name` to the constructor of a new type, taking
+    /// the values of the fields in order.
+    Struct {
+        name: String,
+        fields: Vec<String>,
+    },
     Expr(Expression),
     Block(Vec<Statement>),
 }
 
+impl Statement {
+    /// Whether a `yield` is reachable from the statement without entering a function
+    /// literal, which makes the enclosing function a generator.
+    pub fn yields(&self) -> bool {
+        match self {
+            Statement::Yield { .. } => true,
+            Statement::Break | Statement::Continue | Statement::Struct { .. } => false,
+            Statement::Let { value, .. }
+            | Statement::Assign { value, .. }
+            | Statement::Destructure { value, .. }
+            | Statement::Return { value }
+            | Statement::Expr(value) => value.yields(),
+            Statement::Block(stmts) => stmts.iter().any(Statement::yields),
+        }
+    }
+
+    /// The string literal a function body starts with, when other statements follow it.
+    pub fn docstring(body: &[Statement]) -> Option<&str> {
+        // Parameters given as patterns are destructured first, see
+        // [`Expression::pattern_parameter`].
+        let desugared = body
+            .iter()
+            .take_while(|statement| {
+                matches!(statement, Statement::Destructure {
+                    value: Expression::Ident(name),
+                    ..
+                } if name.starts_with('$'))
+            })
+            .count();
+        match &body[desugared..] {
+            [Statement::Expr(Expression::String(doc)), _, ..] => Some(doc),
+            _ => None,
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
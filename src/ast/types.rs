@@ -0,0 +1,57 @@
+use crate::error::{Error, ErrorCode, Result, fail};
+use crate::prelude::*;
+use core::fmt;
+
+/// Optional type annotation on `let` bindings, parameters and function results.
+/// Annotations are only used by the type checker, evaluation ignores them.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TypeAnnotation {
+    Int,
+    Float,
+    Bool,
+    Str,
+    Array,
+    Tuple,
+    Hash,
+    Fn,
+    Null,
+    Any,
+}
+
+impl TryFrom<&str> for TypeAnnotation {
+    type Error = Error;
+
+    fn try_from(name: &str) -> Result<Self> {
+        Ok(match name {
+            "int" => Self::Int,
+            "float" => Self::Float,
+            "bool" => Self::Bool,
+            "str" => Self::Str,
+            "array" => Self::Array,
+            "tuple" => Self::Tuple,
+            "hash" => Self::Hash,
+            "fn" => Self::Fn,
+            "null" => Self::Null,
+            "any" => Self::Any,
+            _ => fail!(ErrorCode::UnknownType, "Unknown type `{name}`"),
+        })
+    }
+}
+
+impl fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeAnnotation::Int => write!(f, "int"),
+            TypeAnnotation::Float => write!(f, "float"),
+            TypeAnnotation::Bool => write!(f, "bool"),
+            TypeAnnotation::Str => write!(f, "str"),
+            TypeAnnotation::Array => write!(f, "array"),
+            TypeAnnotation::Tuple => write!(f, "tuple"),
+            TypeAnnotation::Hash => write!(f, "hash"),
+            TypeAnnotation::Fn => write!(f, "fn"),
+            TypeAnnotation::Null => write!(f, "null"),
+            TypeAnnotation::Any => write!(f, "any"),
+        }
+    }
+}
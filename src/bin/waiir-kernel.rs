@@ -0,0 +1,7 @@
+use std::io::{stdin, stdout};
+
+use waiir::kernel::Kernel;
+
+fn main() -> std::io::Result<()> {
+    Kernel::default().serve(stdin().lock(), stdout().lock())
+}
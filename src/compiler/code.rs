@@ -0,0 +1,313 @@
+//! Instructions of the VM: an opcode byte followed by its operands, big-endian.
+
+use crate::prelude::*;
+use core::fmt;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    /// Pushes the constant at the operand.
+    Constant,
+    Pop,
+    Null,
+    True,
+    False,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    GreaterEqual,
+    LessEqual,
+    Minus,
+    Bang,
+    Plus,
+    Index,
+    Jump,
+    /// Pops the condition and jumps if it's falsy.
+    JumpNotTruthy,
+    /// Global variables live in the environment the VM runs in, by the name at the
+    /// operand in the constants.
+    GetGlobal,
+    /// Binds the top of the stack, leaving it there like every `Set` opcode.
+    SetGlobal,
+    /// Like [`Opcode::SetGlobal`], binding a variable that can be assigned, for `let mut`.
+    SetMutableGlobal,
+    /// Like [`Opcode::SetGlobal`], failing if the variable isn't defined or isn't mutable.
+    AssignGlobal,
+    GetLocal,
+    SetLocal,
+    /// Variables of enclosing functions, captured by the closure.
+    GetFree,
+    SetFree,
+    /// Pushes the builtin in the constants at the operand, if the policy allows it.
+    GetBuiltin,
+    /// Collects the number of items at the operand into an array.
+    Array,
+    /// Collects the number of values at the operand into a tuple.
+    Tuple,
+    /// Collects the number of key-value pairs at the operand into a hash.
+    Hash,
+    /// Calls the function below the number of arguments at the operand.
+    Call,
+    /// Like [`Opcode::Call`] with keyword arguments after the positional ones, named by
+    /// the array of strings in the constants at the second operand.
+    CallKeywords,
+    ReturnValue,
+    /// Creates a closure of the function at the operand, capturing its free variables.
+    Closure,
+    /// Marks the height of the stack that `break` and `continue` come back to.
+    Loop,
+    EndLoop,
+    /// Restores the height of the stack marked by the innermost loop and jumps, for
+    /// `break` and `continue`.
+    Unwind,
+    /// Replaces the top of the stack with an iterator over it.
+    Iter,
+    /// Pushes the next item of the iterator on the top of the stack, or jumps once
+    /// it's exhausted.
+    IterNext,
+    /// Matches the top of the stack against the pattern at the operand, leaving it
+    /// there, and pushes the values of the names bound followed by whether it matched.
+    Match,
+    /// Like [`Opcode::Match`] without the final bool, failing on a mismatch.
+    Destructure,
+    /// Replaces the top of the stack with its field named by the string in the
+    /// constants at the operand.
+    Field,
+    /// Suspends the generator running, handing over the top of the stack, which is
+    /// `null` once it resumes.
+    Yield,
+}
+
+/// How an opcode is printed and how wide its operands are.
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+/// Every opcode, in the order of their bytes.
+const OPCODES: [Opcode; 48] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Null,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::Pow,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::GreaterEqual,
+    Opcode::LessEqual,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::Plus,
+    Opcode::Index,
+    Opcode::Jump,
+    Opcode::JumpNotTruthy,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::SetMutableGlobal,
+    Opcode::AssignGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetFree,
+    Opcode::SetFree,
+    Opcode::GetBuiltin,
+    Opcode::Array,
+    Opcode::Tuple,
+    Opcode::Hash,
+    Opcode::Call,
+    Opcode::CallKeywords,
+    Opcode::ReturnValue,
+    Opcode::Closure,
+    Opcode::Loop,
+    Opcode::EndLoop,
+    Opcode::Unwind,
+    Opcode::Iter,
+    Opcode::IterNext,
+    Opcode::Match,
+    Opcode::Destructure,
+    Opcode::Field,
+    Opcode::Yield,
+];
+
+impl Opcode {
+    pub fn lookup(byte: u8) -> Option<Opcode> {
+        OPCODES
+            .get(usize::from(byte))
+            .copied()
+            .filter(|op| *op as u8 == byte)
+    }
+
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&str, &[usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::Mod => ("OpMod", &[]),
+            Opcode::Pow => ("OpPow", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::GreaterEqual => ("OpGreaterEqual", &[]),
+            Opcode::LessEqual => ("OpLessEqual", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::Plus => ("OpPlus", &[]),
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::SetMutableGlobal => ("OpSetMutableGlobal", &[2]),
+            Opcode::AssignGlobal => ("OpAssignGlobal", &[2]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            Opcode::SetFree => ("OpSetFree", &[1]),
+            Opcode::GetBuiltin => ("OpGetBuiltin", &[2]),
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Tuple => ("OpTuple", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::CallKeywords => ("OpCallKeywords", &[1, 2]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Closure => ("OpClosure", &[2]),
+            Opcode::Loop => ("OpLoop", &[]),
+            Opcode::EndLoop => ("OpEndLoop", &[]),
+            Opcode::Unwind => ("OpUnwind", &[2]),
+            Opcode::Iter => ("OpIter", &[]),
+            Opcode::IterNext => ("OpIterNext", &[2]),
+            Opcode::Match => ("OpMatch", &[2]),
+            Opcode::Destructure => ("OpDestructure", &[2]),
+            Opcode::Field => ("OpField", &[2]),
+            Opcode::Yield => ("OpYield", &[]),
+        };
+        Definition {
+            name,
+            operand_widths,
+        }
+    }
+}
+
+/// Encodes an instruction; operands too large for their width are truncated, so the
+/// compiler checks them first.
+pub fn make(op: Opcode, operands: &[usize]) -> Vec<u8> {
+    let mut instruction = vec![op as u8];
+    for (operand, width) in operands.iter().zip(op.definition().operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            _ => instruction.push(*operand as u8),
+        }
+    }
+    instruction
+}
+
+/// Decodes the operands of `op` at the start of `bytes`, with the number of bytes read.
+pub fn read_operands(op: Opcode, bytes: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = vec![];
+    let mut offset = 0;
+    for width in op.definition().operand_widths {
+        operands.push(match width {
+            2 => read_u16(&bytes[offset..]),
+            _ => usize::from(bytes[offset]),
+        });
+        offset += width;
+    }
+    (operands, offset)
+}
+
+pub fn read_u16(bytes: &[u8]) -> usize {
+    usize::from(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Instructions(pub Vec<u8>);
+
+/// Disassembles the instructions, one per line with its offset.
+impl fmt::Display for Instructions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ip = 0;
+        while ip < self.0.len() {
+            let Some(op) = Opcode::lookup(self.0[ip]) else {
+                writeln!(f, "{ip:04} <unknown opcode {}>", self.0[ip])?;
+                ip += 1;
+                continue;
+            };
+            let (operands, read) = read_operands(op, &self.0[ip + 1..]);
+            write!(f, "{ip:04} {}", op.definition().name)?;
+            for operand in operands {
+                write!(f, " {operand}")?;
+            }
+            writeln!(f)?;
+            ip += 1 + read;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instructions() {
+        assert_eq!(make(Opcode::Constant, &[65534]), vec![0, 255, 254]);
+        assert_eq!(
+            make(Opcode::GetLocal, &[255]),
+            vec![Opcode::GetLocal as u8, 255]
+        );
+        assert_eq!(make(Opcode::Add, &[]), vec![Opcode::Add as u8]);
+        assert_eq!(
+            read_operands(Opcode::Constant, &make(Opcode::Constant, &[65535])[1..]),
+            (vec![65535], 2)
+        );
+
+        let instructions = Instructions(
+            [
+                make(Opcode::Add, &[]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Closure, &[65535]),
+            ]
+            .concat(),
+        );
+        assert_eq!(
+            instructions.to_string(),
+            "0000 OpAdd\n0001 OpGetLocal 1\n0003 OpConstant 2\n0006 OpClosure 65535\n"
+        );
+    }
+
+    #[test]
+    fn lookups() {
+        for byte in 0..=u8::MAX {
+            if let Some(op) = Opcode::lookup(byte) {
+                assert_eq!(op as u8, byte);
+            }
+        }
+        assert_eq!(
+            Opcode::lookup(Opcode::Destructure as u8),
+            Some(Opcode::Destructure)
+        );
+        assert_eq!(Opcode::lookup(u8::MAX), None);
+    }
+}
@@ -0,0 +1,708 @@
+//! Lowers a [`Program`] to the bytecode run by the [`vm`](crate::vm), like the compiler
+//! of the book's sequel: every expression and statement leaves its value on the stack,
+//! the variables of functions live in numbered slots and the others in the environment.
+
+mod code;
+mod symbol_table;
+
+use crate::ast::printer::expression_to_source;
+use crate::ast::{CallSpan, HashSpan, Pattern};
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{BuiltinFunction, Object, StructType};
+use crate::prelude::*;
+use crate::{Expression, InfixOperator, PrefixOperator, Program, Statement};
+use alloc::rc::Rc;
+pub use code::{Definition, Instructions, Opcode, make, read_operands, read_u16};
+use symbol_table::{Symbol, SymbolTable, is_immutable, resolve};
+
+/// Where a closure captures a free variable from, in the function creating it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    Local(usize),
+    Free(usize),
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    /// The name the function literal is bound to by `let`, if any, for messages.
+    pub name: Option<String>,
+    pub parameters: Vec<String>,
+    /// The statements of the function literal, for its source and docstring.
+    pub body: Vec<Statement>,
+    /// Whether the function yields: calling it makes an iterator over what it yields.
+    pub generator: bool,
+    /// Slots of the parameters and of the variables bound in the body.
+    pub num_locals: usize,
+    pub captures: Vec<Capture>,
+    /// The calls the function makes, in the order of their instructions.
+    pub calls: Vec<CallSite>,
+    /// The hash literals the function builds, in the order of their instructions.
+    pub hashes: Vec<HashSite>,
+}
+
+impl CompiledFunction {
+    pub fn doc(&self) -> Option<&str> {
+        Statement::docstring(&self.body)
+    }
+
+    /// The call whose instruction ends at `next`, where the function resumes after it.
+    pub fn call_site(&self, next: usize) -> Option<&CallSite> {
+        let index = self
+            .calls
+            .binary_search_by_key(&next, |call| call.next)
+            .ok()?;
+        Some(&self.calls[index])
+    }
+
+    /// The hash literal whose instruction ends at `next`.
+    pub fn hash_site(&self, next: usize) -> Option<&HashSite> {
+        let index = self
+            .hashes
+            .binary_search_by_key(&next, |hash| hash.next)
+            .ok()?;
+        Some(&self.hashes[index])
+    }
+}
+
+/// A call in the source, for the errors raised by it or by the function called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSite {
+    /// Position of the instruction following the call.
+    pub next: usize,
+    /// Source of the expression called, like `expression_to_source` prints it.
+    pub callee: String,
+    pub span: CallSpan,
+}
+
+/// A hash literal in the source, for the duplicate keys it reports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashSite {
+    /// Position of the instruction following the one building the hash.
+    pub next: usize,
+    pub span: HashSpan,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Bytecode {
+    pub constants: Vec<Object>,
+    /// Function literals by the operand of [`Opcode::Closure`], the program itself first.
+    pub functions: Vec<CompiledFunction>,
+    /// Patterns of `match` arms and destructuring by the operand of [`Opcode::Match`].
+    pub patterns: Vec<Pattern>,
+}
+
+/// Compiles `program`. Fails on what the VM doesn't run, i.e. `yield` outside of a
+/// generator and `break` or `continue` outside of a loop, on assignments to variables of functions bound without
+/// `let mut`, and when the program is too large for the operands.
+pub fn compile(program: &Program) -> Result<Bytecode> {
+    let mut compiler = Compiler::default();
+    compiler.functions.push(CompiledFunction::default());
+    compiler.scopes.push(Scope::default());
+    compiler.block(&program.statements)?;
+    compiler.emit(Opcode::ReturnValue, &[])?;
+    let scope = compiler.scopes.pop().unwrap_or_default();
+    compiler.functions[0].instructions = Instructions(scope.instructions);
+    compiler.functions[0].calls = scope.calls;
+    compiler.functions[0].hashes = scope.hashes;
+    Ok(Bytecode {
+        constants: compiler.constants,
+        functions: compiler.functions,
+        patterns: compiler.patterns,
+    })
+}
+
+#[derive(Default)]
+struct Compiler {
+    constants: Vec<Object>,
+    functions: Vec<CompiledFunction>,
+    patterns: Vec<Pattern>,
+    /// The function being compiled last, the program first.
+    scopes: Vec<Scope>,
+    /// The tables of the functions being compiled, the program having none.
+    tables: Vec<SymbolTable>,
+}
+
+#[derive(Default)]
+struct Scope {
+    instructions: Vec<u8>,
+    loops: Vec<Loop>,
+    calls: Vec<CallSite>,
+    hashes: Vec<HashSite>,
+    /// Whether the function is a generator, which may `yield`.
+    generator: bool,
+}
+
+/// Where `continue` jumps to, and the `break`s to patch with the end of the loop.
+struct Loop {
+    start: usize,
+    breaks: Vec<usize>,
+}
+
+/// Placeholder of jump targets patched once known.
+const UNKNOWN: usize = 0xFFFF;
+
+impl Compiler {
+    fn scope(&mut self) -> &mut Scope {
+        let last = self.scopes.len() - 1;
+        &mut self.scopes[last]
+    }
+
+    fn position(&mut self) -> usize {
+        self.scope().instructions.len()
+    }
+
+    /// Appends an instruction, returning its position.
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> Result<usize> {
+        for (operand, width) in operands.iter().zip(op.definition().operand_widths) {
+            if *operand >= 1 << (8 * width) {
+                fail!(
+                    ErrorCode::LimitExceeded,
+                    "The program is too large to compile: {} can't refer to {operand}",
+                    op.definition().name
+                );
+            }
+        }
+        let position = self.position();
+        self.scope().instructions.extend(make(op, operands));
+        Ok(position)
+    }
+
+    /// Sets the target of the jump at `position`.
+    fn patch(&mut self, position: usize, target: usize) -> Result<()> {
+        let Ok(target) = u16::try_from(target) else {
+            fail!(
+                ErrorCode::LimitExceeded,
+                "The program is too large to compile: a jump can't reach {target}"
+            );
+        };
+        self.scope().instructions[position + 1..position + 3]
+            .copy_from_slice(&target.to_be_bytes());
+        Ok(())
+    }
+
+    fn constant(&mut self, obj: Object) -> usize {
+        let shared = matches!(obj, Object::Int(_) | Object::String(_) | Object::Builtin(_));
+        if shared && let Some(index) = self.constants.iter().position(|c| *c == obj) {
+            return index;
+        }
+        self.constants.push(obj);
+        self.constants.len() - 1
+    }
+
+    /// Leaves the value of the last statement, `null` if there are none.
+    fn block(&mut self, stmts: &[Statement]) -> Result<()> {
+        if stmts.is_empty() {
+            self.emit(Opcode::Null, &[])?;
+        }
+        for (i, statement) in stmts.iter().enumerate() {
+            if i > 0 {
+                self.emit(Opcode::Pop, &[])?;
+            }
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<()> {
+        match statement {
+            Statement::Expr(expr) => self.expression(expr, None)?,
+            Statement::Block(stmts) => self.block(stmts)?,
+            Statement::Let {
+                name,
+                mutable,
+                value,
+                ..
+            } => {
+                self.expression(value, Some(name))?;
+                if let Some(table) = self.tables.last_mut() {
+                    table.bind(name, *mutable);
+                }
+                if *mutable {
+                    self.store(name, Opcode::SetMutableGlobal)?;
+                } else {
+                    self.set(name)?;
+                }
+            }
+            Statement::Struct { name, fields } => {
+                let ty = Rc::new(StructType {
+                    name: name.clone(),
+                    fields: fields.clone(),
+                });
+                let constant = self.constant(Object::StructType(ty));
+                self.emit(Opcode::Constant, &[constant])?;
+                self.set(name)?;
+            }
+            Statement::Assign { name, value } => {
+                if is_immutable(&self.tables, name) {
+                    fail!(
+                        ErrorCode::ImmutableAssignment,
+                        "Cannot assign to `{name}`, it isn't mutable: use `let mut` to define it"
+                    );
+                }
+                self.expression(value, None)?;
+                self.store(name, Opcode::AssignGlobal)?;
+            }
+            Statement::Destructure { pattern, value } => {
+                self.expression(value, None)?;
+                self.patterns.push(pattern.clone());
+                self.emit(Opcode::Destructure, &[self.patterns.len() - 1])?;
+                self.bind(pattern)?;
+            }
+            Statement::Return { value } => {
+                self.expression(value, None)?;
+                self.emit(Opcode::ReturnValue, &[])?;
+            }
+            Statement::Break => {
+                let position = self.emit(Opcode::Unwind, &[UNKNOWN])?;
+                match self.scope().loops.last_mut() {
+                    Some(innermost) => innermost.breaks.push(position),
+                    None => fail!(ErrorCode::InvalidInput, "`break` outside of a loop"),
+                }
+            }
+            Statement::Continue => match self.scope().loops.last() {
+                Some(innermost) => {
+                    let start = innermost.start;
+                    self.emit(Opcode::Unwind, &[start])?;
+                }
+                None => fail!(ErrorCode::InvalidInput, "`continue` outside of a loop"),
+            },
+            Statement::Yield { value } => {
+                if !self.scope().generator {
+                    fail!(
+                        ErrorCode::InvalidYield,
+                        "`yield` can only be used as a statement of a generator"
+                    );
+                }
+                self.expression(value, None)?;
+                self.emit(Opcode::Yield, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Binds the top of the stack to `name`, leaving it there.
+    fn set(&mut self, name: &str) -> Result<()> {
+        self.store(name, Opcode::SetGlobal)
+    }
+
+    /// Stores the top of the stack in the variable `name`, with `global` if it's a global.
+    fn store(&mut self, name: &str, global: Opcode) -> Result<()> {
+        match resolve(&mut self.tables, name) {
+            Symbol::Global => {
+                let name = self.constant(Object::String(name.to_owned()));
+                self.emit(global, &[name])?
+            }
+            Symbol::Local(index) => self.emit(Opcode::SetLocal, &[index])?,
+            Symbol::Free(index) => self.emit(Opcode::SetFree, &[index])?,
+        };
+        Ok(())
+    }
+
+    /// Compiles `&&` and `||` to jumps over the right operand when the left one decides,
+    /// leaving a bool.
+    fn logical(
+        &mut self,
+        operator: &InfixOperator,
+        left: &Expression,
+        right: &Expression,
+    ) -> Result<()> {
+        self.expression(left, None)?;
+        let left_falsy = self.emit(Opcode::JumpNotTruthy, &[UNKNOWN])?;
+        let (mut to_false, mut to_end) = (vec![], vec![]);
+        if *operator == InfixOperator::Or {
+            self.emit(Opcode::True, &[])?;
+            to_end.push(self.emit(Opcode::Jump, &[UNKNOWN])?);
+            let right_start = self.position();
+            self.patch(left_falsy, right_start)?;
+        } else {
+            to_false.push(left_falsy);
+        }
+        self.expression(right, None)?;
+        to_false.push(self.emit(Opcode::JumpNotTruthy, &[UNKNOWN])?);
+        self.emit(Opcode::True, &[])?;
+        to_end.push(self.emit(Opcode::Jump, &[UNKNOWN])?);
+        let false_start = self.position();
+        for jump in to_false {
+            self.patch(jump, false_start)?;
+        }
+        self.emit(Opcode::False, &[])?;
+        let end = self.position();
+        for jump in to_end {
+            self.patch(jump, end)?;
+        }
+        Ok(())
+    }
+
+    /// Binds the values pushed by [`Opcode::Match`] or [`Opcode::Destructure`] to the
+    /// names of `pattern`.
+    fn bind(&mut self, pattern: &Pattern) -> Result<()> {
+        for name in pattern.names().into_iter().rev() {
+            self.set(name)?;
+            self.emit(Opcode::Pop, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Compiles `expr`, `name` being the variable it's bound to, if any.
+    fn expression(&mut self, expr: &Expression, name: Option<&str>) -> Result<()> {
+        match expr {
+            Expression::Bool(true) => {
+                self.emit(Opcode::True, &[])?;
+            }
+            Expression::Bool(false) => {
+                self.emit(Opcode::False, &[])?;
+            }
+            Expression::Int(value) => {
+                let constant = self.constant(Object::Int(*value));
+                self.emit(Opcode::Constant, &[constant])?;
+            }
+            Expression::Float(value) => {
+                let constant = self.constant(Object::Float(*value));
+                self.emit(Opcode::Constant, &[constant])?;
+            }
+            Expression::String(string) => {
+                let constant = self.constant(Object::String(string.clone()));
+                self.emit(Opcode::Constant, &[constant])?;
+            }
+            Expression::Ident(ident) if ident == "null" => {
+                self.emit(Opcode::Null, &[])?;
+            }
+            Expression::Ident(ident) => {
+                match resolve(&mut self.tables, ident) {
+                    // Still looked up among the globals first, see `Opcode::GetBuiltin`.
+                    Symbol::Global => match BuiltinFunction::from_name(ident) {
+                        Some(builtin_fn) => {
+                            let constant = self.constant(Object::Builtin(builtin_fn));
+                            self.emit(Opcode::GetBuiltin, &[constant])?
+                        }
+                        None => {
+                            let name = self.constant(Object::String(ident.clone()));
+                            self.emit(Opcode::GetGlobal, &[name])?
+                        }
+                    },
+                    Symbol::Local(index) => self.emit(Opcode::GetLocal, &[index])?,
+                    Symbol::Free(index) => self.emit(Opcode::GetFree, &[index])?,
+                };
+            }
+            Expression::Array(content) => {
+                for item in content {
+                    self.expression(item, None)?;
+                }
+                self.emit(Opcode::Array, &[content.len()])?;
+            }
+            Expression::Tuple(content) => {
+                for item in content {
+                    self.expression(item, None)?;
+                }
+                self.emit(Opcode::Tuple, &[content.len()])?;
+            }
+            Expression::Hash { pairs, span } => {
+                for (key, value) in pairs {
+                    self.expression(key, None)?;
+                    self.expression(value, None)?;
+                }
+                self.emit(Opcode::Hash, &[pairs.len()])?;
+                let hash = HashSite {
+                    next: self.position(),
+                    span: span.clone(),
+                };
+                self.scope().hashes.push(hash);
+            }
+            Expression::Prefix { operator, right } => {
+                self.expression(right, None)?;
+                self.emit(
+                    match operator {
+                        PrefixOperator::Neg => Opcode::Minus,
+                        PrefixOperator::Not => Opcode::Bang,
+                        PrefixOperator::Plus => Opcode::Plus,
+                    },
+                    &[],
+                )?;
+            }
+            Expression::Field { object, name } => {
+                self.expression(object, None)?;
+                let constant = self.constant(Object::String(name.clone()));
+                self.emit(Opcode::Field, &[constant])?;
+            }
+            Expression::Infix {
+                operator: operator @ (InfixOperator::And | InfixOperator::Or),
+                left,
+                right,
+            } => self.logical(operator, left, right)?,
+            Expression::Infix {
+                operator,
+                left,
+                right,
+            } => {
+                self.expression(left, None)?;
+                self.expression(right, None)?;
+                self.emit(infix_opcode(operator), &[])?;
+            }
+            Expression::Cond { cond, then_, else_ } => {
+                self.expression(cond, None)?;
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[UNKNOWN])?;
+                self.block(then_)?;
+                let jump = self.emit(Opcode::Jump, &[UNKNOWN])?;
+                let else_start = self.position();
+                self.patch(jump_not_truthy, else_start)?;
+                self.block(else_.as_deref().unwrap_or_default())?;
+                let end = self.position();
+                self.patch(jump, end)?;
+            }
+            Expression::Func { args, body, .. } => {
+                let generator = body.iter().any(Statement::yields);
+                self.scopes.push(Scope {
+                    generator,
+                    ..Scope::default()
+                });
+                self.tables.push(SymbolTable::new(args, body));
+                let compiled = self.block(body).and_then(|()| {
+                    self.emit(Opcode::ReturnValue, &[])?;
+                    Ok(())
+                });
+                let (scope, table) = (self.scopes.pop(), self.tables.pop());
+                compiled?;
+                let (scope, table) = (scope.unwrap_or_default(), table.unwrap_or_default());
+                self.functions.push(CompiledFunction {
+                    instructions: Instructions(scope.instructions),
+                    name: name.map(str::to_owned),
+                    parameters: args.clone(),
+                    body: body.clone(),
+                    generator,
+                    num_locals: table.num_locals(),
+                    captures: table.captures(),
+                    calls: scope.calls,
+                    hashes: scope.hashes,
+                });
+                self.emit(Opcode::Closure, &[self.functions.len() - 1])?;
+            }
+            Expression::Call {
+                func,
+                args,
+                kwargs,
+                span,
+            } => {
+                self.expression(func, None)?;
+                for arg in args {
+                    self.expression(arg, None)?;
+                }
+                if kwargs.is_empty() {
+                    self.emit(Opcode::Call, &[args.len()])?;
+                } else {
+                    for (_, value) in kwargs {
+                        self.expression(value, None)?;
+                    }
+                    let names = kwargs
+                        .iter()
+                        .map(|(name, _)| Object::String(name.clone()))
+                        .collect();
+                    let names = self.constant(Object::Array(Rc::new(names)));
+                    self.emit(Opcode::CallKeywords, &[args.len(), names])?;
+                }
+                let call = CallSite {
+                    next: self.position(),
+                    callee: expression_to_source(func),
+                    span: span.clone(),
+                };
+                self.scope().calls.push(call);
+            }
+            Expression::For {
+                name,
+                iterable,
+                body,
+            } => {
+                self.expression(iterable, None)?;
+                self.emit(Opcode::Iter, &[])?;
+                self.loop_body(|compiler, start| {
+                    let exit = compiler.emit(Opcode::IterNext, &[UNKNOWN])?;
+                    compiler.set(name)?;
+                    compiler.emit(Opcode::Pop, &[])?;
+                    compiler.block(body)?;
+                    compiler.emit(Opcode::Pop, &[])?;
+                    compiler.emit(Opcode::Jump, &[start])?;
+                    Ok(exit)
+                })?;
+                self.emit(Opcode::Pop, &[])?;
+                self.emit(Opcode::Null, &[])?;
+            }
+            Expression::While { cond, body } => {
+                self.loop_body(|compiler, start| {
+                    compiler.expression(cond, None)?;
+                    let exit = compiler.emit(Opcode::JumpNotTruthy, &[UNKNOWN])?;
+                    compiler.block(body)?;
+                    compiler.emit(Opcode::Pop, &[])?;
+                    compiler.emit(Opcode::Jump, &[start])?;
+                    Ok(exit)
+                })?;
+                self.emit(Opcode::Null, &[])?;
+            }
+            Expression::Match { subject, arms } => {
+                self.expression(subject, None)?;
+                let mut ends = vec![];
+                for arm in arms {
+                    self.patterns.push(arm.pattern.clone());
+                    self.emit(Opcode::Match, &[self.patterns.len() - 1])?;
+                    let mut mismatches = vec![self.emit(Opcode::JumpNotTruthy, &[UNKNOWN])?];
+                    self.bind(&arm.pattern)?;
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard, None)?;
+                        mismatches.push(self.emit(Opcode::JumpNotTruthy, &[UNKNOWN])?);
+                    }
+                    self.emit(Opcode::Pop, &[])?;
+                    self.expression(&arm.value, None)?;
+                    ends.push(self.emit(Opcode::Jump, &[UNKNOWN])?);
+                    let next = self.position();
+                    for mismatch in mismatches {
+                        self.patch(mismatch, next)?;
+                    }
+                }
+                self.emit(Opcode::Pop, &[])?;
+                self.emit(Opcode::Null, &[])?;
+                let end = self.position();
+                for jump in ends {
+                    self.patch(jump, end)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles a loop between [`Opcode::Loop`] and [`Opcode::EndLoop`]. `body` gets the
+    /// start of the loop and returns the jump leaving it, patched like the `break`s.
+    fn loop_body(&mut self, body: impl FnOnce(&mut Self, usize) -> Result<usize>) -> Result<()> {
+        self.emit(Opcode::Loop, &[])?;
+        let start = self.position();
+        self.scope().loops.push(Loop {
+            start,
+            breaks: vec![],
+        });
+        let exit = body(self, start);
+        let innermost = self.scope().loops.pop();
+        let exit = exit?;
+        let end = self.position();
+        for jump in innermost
+            .into_iter()
+            .flat_map(|innermost| innermost.breaks)
+            .chain([exit])
+        {
+            self.patch(jump, end)?;
+        }
+        self.emit(Opcode::EndLoop, &[])?;
+        Ok(())
+    }
+}
+
+fn infix_opcode(operator: &InfixOperator) -> Opcode {
+    match operator {
+        InfixOperator::Add => Opcode::Add,
+        InfixOperator::Sub => Opcode::Sub,
+        InfixOperator::Mul => Opcode::Mul,
+        InfixOperator::Div => Opcode::Div,
+        InfixOperator::Mod => Opcode::Mod,
+        InfixOperator::Pow => Opcode::Pow,
+        InfixOperator::Eq => Opcode::Equal,
+        InfixOperator::NotEq => Opcode::NotEqual,
+        InfixOperator::Gt => Opcode::GreaterThan,
+        InfixOperator::Lt => Opcode::LessThan,
+        InfixOperator::GtEq => Opcode::GreaterEqual,
+        InfixOperator::LtEq => Opcode::LessEqual,
+        InfixOperator::Index => Opcode::Index,
+        InfixOperator::And | InfixOperator::Or => {
+            unreachable!("`&&` and `||` are compiled to jumps")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compiled(input: &str) -> Bytecode {
+        compile(&crate::parse(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn instructions() {
+        let bytecode = compiled("let x = 1; if (x < 2) { x + 2 } else { \"a\" }");
+        assert_eq!(
+            bytecode.constants,
+            vec![
+                Object::Int(1),
+                Object::String("x".to_owned()),
+                Object::Int(2),
+                Object::String("a".to_owned())
+            ]
+        );
+        assert_eq!(
+            bytecode.functions[0].instructions.to_string(),
+            "0000 OpConstant 0\n0003 OpSetGlobal 1\n0006 OpPop\n0007 OpGetGlobal 1\n\
+             0010 OpConstant 2\n0013 OpLessThan\n0014 OpJumpNotTruthy 27\n\
+             0017 OpGetGlobal 1\n0020 OpConstant 2\n0023 OpAdd\n0024 OpJump 30\n\
+             0027 OpConstant 3\n0030 OpReturnValue\n"
+        );
+    }
+
+    #[test]
+    fn closures() {
+        let bytecode = compiled("let f = fn(b) { let mut a = b; let g = fn() { a = a + 1 }; g }");
+        let [_, g, f] = &bytecode.functions[..] else {
+            panic!("Expected 3 functions, found {}", bytecode.functions.len());
+        };
+        assert_eq!((f.name.as_deref(), f.num_locals), (Some("f"), 3));
+        assert_eq!(
+            f.instructions.to_string(),
+            "0000 OpGetLocal 0\n0002 OpSetLocal 1\n0004 OpPop\n0005 OpClosure 1\n\
+             0008 OpSetLocal 2\n0010 OpPop\n0011 OpGetLocal 2\n0013 OpReturnValue\n"
+        );
+        assert_eq!(
+            (g.name.as_deref(), &g.captures[..]),
+            (Some("g"), &[Capture::Local(1)][..])
+        );
+        assert_eq!(
+            g.instructions.to_string(),
+            "0000 OpGetFree 0\n0002 OpConstant 0\n0005 OpAdd\n0006 OpSetFree 0\n\
+             0008 OpReturnValue\n"
+        );
+    }
+
+    #[test]
+    fn loops() {
+        let bytecode = compiled("while (true) { if (false) { break; }; continue; }");
+        assert_eq!(
+            bytecode.functions[0].instructions.to_string(),
+            "0000 OpLoop\n0001 OpTrue\n0002 OpJumpNotTruthy 24\n0005 OpFalse\n\
+             0006 OpJumpNotTruthy 15\n0009 OpUnwind 24\n0012 OpJump 16\n0015 OpNull\n\
+             0016 OpPop\n0017 OpUnwind 1\n0020 OpPop\n0021 OpJump 1\n0024 OpEndLoop\n\
+             0025 OpNull\n0026 OpReturnValue\n"
+        );
+    }
+
+    #[test]
+    fn failures() {
+        let failure = |input: &str| compile(&crate::parse(input).unwrap()).unwrap_err().code;
+        let outside_loop = Program {
+            statements: vec![Statement::Break],
+        };
+        assert_eq!(
+            compile(&outside_loop).unwrap_err().code,
+            ErrorCode::InvalidInput
+        );
+        assert_eq!(failure("yield 1;"), ErrorCode::InvalidYield);
+        for input in [
+            "fn(a) { a = 1 }",
+            "fn() { let mut a = 1; fn() { let a = 2; a = 3 } }",
+            "fn() { let mut a = 1; let a = a; a = 2 }",
+        ] {
+            assert_eq!(failure(input), ErrorCode::ImmutableAssignment, "{input}");
+        }
+        assert!(compile(&crate::parse("fn() { let mut a = 1; fn() { a = 2 } }").unwrap()).is_ok());
+        let many = (0..300)
+            .map(|i| format!("let x{i} = 0;"))
+            .collect::<String>();
+        assert_eq!(
+            failure(&format!("fn() {{ {many} }}")),
+            ErrorCode::LimitExceeded
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Where the variables of a function live: in its own slots, in the closure as captured
+//! variables of an enclosing function, or in the environment as globals.
+
+use super::Capture;
+use crate::prelude::*;
+use crate::{Expression, Statement};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Symbol {
+    Global,
+    Local(usize),
+    Free(usize),
+}
+
+/// The variables of a function: its parameters and every name bound in its body, which
+/// is a single scope like an environment, and those it captures.
+#[derive(Debug, Default)]
+pub(crate) struct SymbolTable {
+    locals: Vec<String>,
+    free: Vec<(String, Capture)>,
+    /// The locals last bound with `let mut`, as compiled so far.
+    mutable: Vec<String>,
+}
+
+impl SymbolTable {
+    pub(crate) fn new(parameters: &[String], body: &[Statement]) -> Self {
+        let mut table = Self::default();
+        for name in parameters {
+            table.define(name);
+        }
+        declare_block(body, &mut |name| table.define(name));
+        table
+    }
+
+    fn define(&mut self, name: &str) {
+        if !self.locals.iter().any(|local| local == name) {
+            self.locals.push(name.to_owned());
+        }
+    }
+
+    /// Records whether the last `let` of the local `name` was a `let mut`.
+    pub(crate) fn bind(&mut self, name: &str, mutable: bool) {
+        self.mutable.retain(|local| local != name);
+        if mutable {
+            self.mutable.push(name.to_owned());
+        }
+    }
+
+    pub(crate) fn num_locals(&self) -> usize {
+        self.locals.len()
+    }
+
+    pub(crate) fn captures(&self) -> Vec<Capture> {
+        self.free.iter().map(|(_, capture)| *capture).collect()
+    }
+}
+
+/// Resolves `name` in the innermost of `tables`, capturing it from the enclosing ones
+/// when needed. Names bound in no function are globals.
+pub(crate) fn resolve(tables: &mut [SymbolTable], name: &str) -> Symbol {
+    let Some((table, outer)) = tables.split_last_mut() else {
+        return Symbol::Global;
+    };
+    if let Some(index) = table.locals.iter().position(|local| local == name) {
+        return Symbol::Local(index);
+    }
+    if let Some(index) = table.free.iter().position(|(free, _)| free == name) {
+        return Symbol::Free(index);
+    }
+    let capture = match resolve(outer, name) {
+        Symbol::Global => return Symbol::Global,
+        Symbol::Local(index) => Capture::Local(index),
+        Symbol::Free(index) => Capture::Free(index),
+    };
+    table.free.push((name.to_owned(), capture));
+    Symbol::Free(table.free.len() - 1)
+}
+
+/// Whether `name` is a variable of one of `tables` that can't be assigned, the innermost
+/// function binding it deciding. Globals are checked by the environment instead.
+pub(crate) fn is_immutable(tables: &[SymbolTable], name: &str) -> bool {
+    tables
+        .iter()
+        .rev()
+        .find(|table| table.locals.iter().any(|local| local == name))
+        .is_some_and(|table| !table.mutable.iter().any(|local| local == name))
+}
+
+/// Calls `define` on the names bound by `let`, `struct`, `for` and `match` arms in the statement,
+/// outside of nested function literals.
+fn declare_statement(statement: &Statement, define: &mut impl FnMut(&str)) {
+    match statement {
+        Statement::Let { name, value, .. } => {
+            define(name);
+            declare_expression(value, define);
+        }
+        Statement::Destructure { pattern, value } => {
+            pattern.names().into_iter().for_each(&mut *define);
+            declare_expression(value, define);
+        }
+        Statement::Struct { name, .. } => define(name),
+        Statement::Assign { value, .. }
+        | Statement::Return { value }
+        | Statement::Yield { value }
+        | Statement::Expr(value) => declare_expression(value, define),
+        Statement::Block(stmts) => declare_block(stmts, define),
+        Statement::Break | Statement::Continue => {}
+    }
+}
+
+fn declare_block(stmts: &[Statement], define: &mut impl FnMut(&str)) {
+    for statement in stmts {
+        declare_statement(statement, define);
+    }
+}
+
+fn declare_expression(expr: &Expression, define: &mut impl FnMut(&str)) {
+    match expr {
+        Expression::Bool(_)
+        | Expression::Int(_)
+        | Expression::Float(_)
+        | Expression::Ident(_)
+        | Expression::String(_)
+        | Expression::Func { .. } => {}
+        Expression::Infix { left, right, .. } => {
+            declare_expression(left, define);
+            declare_expression(right, define);
+        }
+        Expression::Prefix { right, .. } | Expression::Field { object: right, .. } => {
+            declare_expression(right, define)
+        }
+        Expression::Call {
+            func, args, kwargs, ..
+        } => {
+            declare_expression(func, define);
+            for arg in args.iter().chain(kwargs.iter().map(|(_, value)| value)) {
+                declare_expression(arg, define);
+            }
+        }
+        Expression::Cond { cond, then_, else_ } => {
+            declare_expression(cond, define);
+            declare_block(then_, define);
+            declare_block(else_.as_deref().unwrap_or_default(), define);
+        }
+        Expression::Array(content) | Expression::Tuple(content) => {
+            for item in content {
+                declare_expression(item, define);
+            }
+        }
+        Expression::Hash { pairs, .. } => {
+            for (key, value) in pairs {
+                declare_expression(key, define);
+                declare_expression(value, define);
+            }
+        }
+        Expression::For {
+            name,
+            iterable,
+            body,
+        } => {
+            define(name);
+            declare_expression(iterable, define);
+            declare_block(body, define);
+        }
+        Expression::While { cond, body } => {
+            declare_expression(cond, define);
+            declare_block(body, define);
+        }
+        Expression::Match { subject, arms } => {
+            declare_expression(subject, define);
+            for arm in arms {
+                arm.pattern.names().into_iter().for_each(&mut *define);
+                if let Some(guard) = &arm.guard {
+                    declare_expression(guard, define);
+                }
+                declare_expression(&arm.value, define);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn body(input: &str) -> Vec<Statement> {
+        Parser::init(input).parse_program().unwrap().statements
+    }
+
+    #[test]
+    fn resolution() {
+        let outer = SymbolTable::new(
+            &["a".to_owned()],
+            &body("let b = 1; if (a) { for (c in b) { let [d, _] = c; } }; fn() { let e = 1; }"),
+        );
+        assert_eq!(outer.locals, vec!["a", "b", "c", "d"]);
+
+        let inner = SymbolTable::new(&["b".to_owned()], &body("let f = 2;"));
+        let mut tables = vec![outer, inner];
+        assert_eq!(resolve(&mut tables, "b"), Symbol::Local(0));
+        assert_eq!(resolve(&mut tables, "f"), Symbol::Local(1));
+        assert_eq!(resolve(&mut tables, "d"), Symbol::Free(0));
+        assert_eq!(resolve(&mut tables, "a"), Symbol::Free(1));
+        assert_eq!(resolve(&mut tables, "d"), Symbol::Free(0));
+        assert_eq!(resolve(&mut tables, "g"), Symbol::Global);
+        assert_eq!(
+            tables[1].captures(),
+            vec![Capture::Local(3), Capture::Local(0)]
+        );
+        assert_eq!(resolve(&mut [], "a"), Symbol::Global);
+    }
+}
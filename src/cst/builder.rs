@@ -0,0 +1,453 @@
+use crate::cst::{SyntaxElement, SyntaxKind, SyntaxNode};
+use crate::lexer::TokenWithTrivia;
+use crate::parser::Precedence;
+use crate::{Lexer, Token};
+use std::mem::discriminant;
+
+/// Builds the lossless syntax tree of `input`.
+/// The builder never fails: unexpected tokens end up in `SyntaxKind::Error` nodes.
+pub fn parse(input: &str) -> SyntaxNode {
+    let tokens = Lexer::with_trivia(input).get_all_tokens_with_trivia();
+    let mut builder = Builder {
+        tokens,
+        position: 0,
+    };
+    builder.program()
+}
+
+struct Builder {
+    tokens: Vec<TokenWithTrivia>,
+    position: usize,
+}
+
+impl Builder {
+    fn program(&mut self) -> SyntaxNode {
+        let mut node = SyntaxNode::new(SyntaxKind::Program);
+        while self.curr() != &Token::Eof {
+            node.children.push(SyntaxElement::Node(self.statement()));
+        }
+        self.bump(&mut node);
+        node
+    }
+
+    fn statement(&mut self) -> SyntaxNode {
+        let mut node = match self.curr() {
+            Token::Let => {
+                let mut node = SyntaxNode::new(SyntaxKind::LetStmt);
+                self.bump(&mut node);
+                if self.curr() == &Token::Mut {
+                    self.bump(&mut node);
+                }
+                match self.curr() {
+                    Token::Ident(_) => self.wrap(&mut node, SyntaxKind::Ident),
+                    Token::LBracket | Token::LBrace | Token::LParen | Token::Underscore => {
+                        self.pattern(&mut node)
+                    }
+                    _ => self.error(&mut node),
+                }
+                self.type_annotation(&mut node, &Token::Colon);
+                self.expect(&mut node, &Token::Assign);
+                self.expression(&mut node, Precedence::Lowest);
+                node
+            }
+            Token::Ident(_) if self.peek() == &Token::Assign => {
+                let mut node = SyntaxNode::new(SyntaxKind::AssignStmt);
+                self.wrap(&mut node, SyntaxKind::Ident);
+                self.bump(&mut node);
+                self.expression(&mut node, Precedence::Lowest);
+                node
+            }
+            Token::Struct => {
+                let mut node = SyntaxNode::new(SyntaxKind::StructStmt);
+                self.bump(&mut node);
+                if matches!(self.curr(), Token::Ident(_)) {
+                    self.wrap(&mut node, SyntaxKind::Ident);
+                } else {
+                    self.error(&mut node);
+                }
+                if self.curr() == &Token::LBrace {
+                    let mut fields = SyntaxNode::new(SyntaxKind::FieldList);
+                    self.list(&mut fields, &Token::RBrace, |b, n| {
+                        if matches!(b.curr(), Token::Ident(_)) {
+                            b.bump(n);
+                        } else {
+                            b.error(n);
+                        }
+                    });
+                    node.children.push(SyntaxElement::Node(fields));
+                } else {
+                    self.error(&mut node);
+                }
+                node
+            }
+            Token::Return | Token::Yield => {
+                let kind = match self.curr() {
+                    Token::Return => SyntaxKind::ReturnStmt,
+                    _ => SyntaxKind::YieldStmt,
+                };
+                let mut node = SyntaxNode::new(kind);
+                self.bump(&mut node);
+                self.expression(&mut node, Precedence::Lowest);
+                node
+            }
+            Token::Break | Token::Continue => {
+                let kind = match self.curr() {
+                    Token::Break => SyntaxKind::BreakStmt,
+                    _ => SyntaxKind::ContinueStmt,
+                };
+                let mut node = SyntaxNode::new(kind);
+                self.bump(&mut node);
+                node
+            }
+            _ => {
+                let mut node = SyntaxNode::new(SyntaxKind::ExprStmt);
+                self.expression(&mut node, Precedence::Lowest);
+                node
+            }
+        };
+        if self.curr() == &Token::Semicolon {
+            self.bump(&mut node);
+        }
+        node
+    }
+
+    fn block(&mut self, parent: &mut SyntaxNode) {
+        let mut node = SyntaxNode::new(SyntaxKind::Block);
+        self.expect(&mut node, &Token::LBrace);
+        while !matches!(self.curr(), Token::RBrace | Token::Eof) {
+            node.children.push(SyntaxElement::Node(self.statement()));
+        }
+        self.expect(&mut node, &Token::RBrace);
+        parent.children.push(SyntaxElement::Node(node));
+    }
+
+    fn expression(&mut self, parent: &mut SyntaxNode, precedence: Precedence) {
+        let mut left = self.prefix();
+
+        while precedence < Precedence::get_from_token(self.curr()) {
+            let kind = match self.curr() {
+                Token::LParen => SyntaxKind::Call,
+                Token::LBracket => SyntaxKind::Index,
+                Token::Question => SyntaxKind::Try,
+                Token::Dot => SyntaxKind::MethodCall,
+                _ => SyntaxKind::Infix,
+            };
+            let mut node = SyntaxNode::new(kind);
+            node.children.push(SyntaxElement::Node(left));
+            match kind {
+                SyntaxKind::Call => self.arguments(&mut node),
+                SyntaxKind::MethodCall => {
+                    self.bump(&mut node);
+                    self.expect(&mut node, &Token::Ident(String::new()));
+                    if self.curr() == &Token::LParen {
+                        self.arguments(&mut node);
+                    } else {
+                        node.kind = SyntaxKind::Field;
+                    }
+                }
+                SyntaxKind::Index => {
+                    self.bump(&mut node);
+                    self.expression(&mut node, Precedence::Lowest);
+                    self.expect(&mut node, &Token::RBracket);
+                }
+                SyntaxKind::Try => self.bump(&mut node),
+                _ => {
+                    let precedence = Precedence::of_right_operand(self.curr());
+                    self.bump(&mut node);
+                    self.expression(&mut node, precedence);
+                }
+            }
+            left = node;
+        }
+
+        parent.children.push(SyntaxElement::Node(left));
+    }
+
+    fn prefix(&mut self) -> SyntaxNode {
+        let mut node = match self.curr() {
+            Token::Bang | Token::Minus | Token::Plus => {
+                let mut node = SyntaxNode::new(SyntaxKind::Prefix);
+                self.bump(&mut node);
+                self.expression(&mut node, Precedence::Prefix);
+                return node;
+            }
+            Token::Ident(_) => SyntaxNode::new(SyntaxKind::Ident),
+            Token::Int(_) | Token::Float(_) | Token::String(_) | Token::True | Token::False => {
+                SyntaxNode::new(SyntaxKind::Literal)
+            }
+            Token::LParen => {
+                let mut node = SyntaxNode::new(SyntaxKind::Paren);
+                self.list(&mut node, &Token::RParen, |b, n| {
+                    b.expression(n, Precedence::Lowest)
+                });
+                if node.child_nodes().count() != 1
+                    || node.child_tokens().any(|t| t.token == Token::Comma)
+                {
+                    node.kind = SyntaxKind::Tuple;
+                }
+                return node;
+            }
+            Token::If => return self.if_expression(),
+            Token::For => {
+                let mut node = SyntaxNode::new(SyntaxKind::For);
+                self.bump(&mut node);
+                self.expect(&mut node, &Token::LParen);
+                if matches!(self.curr(), Token::Ident(_)) {
+                    self.wrap(&mut node, SyntaxKind::Ident);
+                } else {
+                    self.error(&mut node);
+                }
+                self.expect(&mut node, &Token::In);
+                self.expression(&mut node, Precedence::Lowest);
+                self.expect(&mut node, &Token::RParen);
+                self.block(&mut node);
+                return node;
+            }
+            Token::While => {
+                let mut node = SyntaxNode::new(SyntaxKind::While);
+                self.bump(&mut node);
+                self.expect(&mut node, &Token::LParen);
+                self.expression(&mut node, Precedence::Lowest);
+                self.expect(&mut node, &Token::RParen);
+                self.block(&mut node);
+                return node;
+            }
+            Token::Match => {
+                let mut node = SyntaxNode::new(SyntaxKind::Match);
+                self.bump(&mut node);
+                self.expect(&mut node, &Token::LParen);
+                self.expression(&mut node, Precedence::Lowest);
+                self.expect(&mut node, &Token::RParen);
+                if self.curr() != &Token::LBrace {
+                    self.error(&mut node);
+                    return node;
+                }
+                self.list(&mut node, &Token::RBrace, |b, n| {
+                    let mut arm = SyntaxNode::new(SyntaxKind::MatchArm);
+                    b.pattern(&mut arm);
+                    if b.curr() == &Token::If {
+                        b.bump(&mut arm);
+                        b.expression(&mut arm, Precedence::Lowest);
+                    }
+                    b.expect(&mut arm, &Token::FatArrow);
+                    b.expression(&mut arm, Precedence::Lowest);
+                    n.children.push(SyntaxElement::Node(arm));
+                });
+                return node;
+            }
+            Token::Function => {
+                let mut node = SyntaxNode::new(SyntaxKind::Func);
+                self.bump(&mut node);
+                let mut params = SyntaxNode::new(SyntaxKind::ParamList);
+                self.list(&mut params, &Token::RParen, |b, n| {
+                    match b.curr() {
+                        Token::Ident(_) => b.wrap(n, SyntaxKind::Ident),
+                        Token::LParen | Token::LBracket | Token::LBrace | Token::Underscore => {
+                            b.pattern(n)
+                        }
+                        _ => return b.error(n),
+                    }
+                    b.type_annotation(n, &Token::Colon);
+                });
+                node.children.push(SyntaxElement::Node(params));
+                self.type_annotation(&mut node, &Token::Arrow);
+                self.block(&mut node);
+                return node;
+            }
+            Token::LBracket => {
+                let mut node = SyntaxNode::new(SyntaxKind::Array);
+                self.list(&mut node, &Token::RBracket, |b, n| {
+                    b.expression(n, Precedence::Lowest)
+                });
+                return node;
+            }
+            Token::LBrace => {
+                let mut node = SyntaxNode::new(SyntaxKind::Hash);
+                self.list(&mut node, &Token::RBrace, |b, n| {
+                    let mut pair = SyntaxNode::new(SyntaxKind::HashPair);
+                    b.expression(&mut pair, Precedence::Lowest);
+                    b.expect(&mut pair, &Token::Colon);
+                    b.expression(&mut pair, Precedence::Lowest);
+                    n.children.push(SyntaxElement::Node(pair));
+                });
+                return node;
+            }
+            _ => {
+                let mut node = SyntaxNode::new(SyntaxKind::Error);
+                if self.curr() != &Token::Eof {
+                    self.bump(&mut node);
+                }
+                return node;
+            }
+        };
+        self.bump(&mut node);
+        node
+    }
+
+    fn if_expression(&mut self) -> SyntaxNode {
+        let mut node = SyntaxNode::new(SyntaxKind::If);
+        self.bump(&mut node);
+        self.expect(&mut node, &Token::LParen);
+        self.expression(&mut node, Precedence::Lowest);
+        self.expect(&mut node, &Token::RParen);
+        self.block(&mut node);
+        if self.curr() == &Token::Else {
+            let mut else_ = SyntaxNode::new(SyntaxKind::Else);
+            self.bump(&mut else_);
+            self.block(&mut else_);
+            node.children.push(SyntaxElement::Node(else_));
+        }
+        node
+    }
+
+    /// Patterns reuse the expression kinds for names and literals, `null` being a literal.
+    fn pattern(&mut self, parent: &mut SyntaxNode) {
+        match self.curr() {
+            Token::Underscore => self.wrap(parent, SyntaxKind::Wildcard),
+            Token::Ident(name) if name == "null" => self.wrap(parent, SyntaxKind::Literal),
+            Token::Ident(_) if self.peek() == &Token::LParen => {
+                let mut node = SyntaxNode::new(SyntaxKind::StructPattern);
+                self.bump(&mut node);
+                self.list(&mut node, &Token::RParen, |b, n| b.pattern(n));
+                parent.children.push(SyntaxElement::Node(node));
+            }
+            Token::Ident(_) => self.wrap(parent, SyntaxKind::Ident),
+            Token::Int(_) | Token::Float(_) | Token::String(_) | Token::True | Token::False => {
+                self.wrap(parent, SyntaxKind::Literal)
+            }
+            Token::Minus => {
+                let mut node = SyntaxNode::new(SyntaxKind::Prefix);
+                self.bump(&mut node);
+                if matches!(self.curr(), Token::Int(_) | Token::Float(_)) {
+                    self.wrap(&mut node, SyntaxKind::Literal);
+                } else {
+                    self.error(&mut node);
+                }
+                parent.children.push(SyntaxElement::Node(node));
+            }
+            Token::LBracket => {
+                let mut node = SyntaxNode::new(SyntaxKind::ArrayPattern);
+                self.list(&mut node, &Token::RBracket, |b, n| {
+                    if b.curr() == &Token::Ellipsis {
+                        let mut rest = SyntaxNode::new(SyntaxKind::RestPattern);
+                        b.bump(&mut rest);
+                        b.pattern(&mut rest);
+                        n.children.push(SyntaxElement::Node(rest));
+                    } else {
+                        b.pattern(n);
+                    }
+                });
+                parent.children.push(SyntaxElement::Node(node));
+            }
+            Token::LParen => {
+                let mut node = SyntaxNode::new(SyntaxKind::TuplePattern);
+                self.list(&mut node, &Token::RParen, |b, n| b.pattern(n));
+                parent.children.push(SyntaxElement::Node(node));
+            }
+            Token::LBrace => {
+                let mut node = SyntaxNode::new(SyntaxKind::HashPattern);
+                self.list(&mut node, &Token::RBrace, |b, n| {
+                    let mut pair = SyntaxNode::new(SyntaxKind::HashPair);
+                    b.pattern(&mut pair);
+                    b.expect(&mut pair, &Token::Colon);
+                    b.pattern(&mut pair);
+                    n.children.push(SyntaxElement::Node(pair));
+                });
+                parent.children.push(SyntaxElement::Node(node));
+            }
+            _ => self.error(parent),
+        }
+    }
+
+    /// Parses `marker type` if the current token is `marker`.
+    fn type_annotation(&mut self, parent: &mut SyntaxNode, marker: &Token) {
+        if self.curr() != marker {
+            return;
+        }
+        let mut node = SyntaxNode::new(SyntaxKind::TypeAnnotation);
+        self.bump(&mut node);
+        if matches!(self.curr(), Token::Ident(_) | Token::Function) {
+            self.bump(&mut node);
+        } else {
+            self.error(&mut node);
+        }
+        parent.children.push(SyntaxElement::Node(node));
+    }
+
+    /// Parses `open item, item, ... close`, where `open` is the current token.
+    fn arguments(&mut self, parent: &mut SyntaxNode) {
+        let mut args = SyntaxNode::new(SyntaxKind::ArgList);
+        self.list(&mut args, &Token::RParen, |b, n| {
+            if matches!(b.curr(), Token::Ident(_)) && b.peek() == &Token::Colon {
+                let mut keyword = SyntaxNode::new(SyntaxKind::KeywordArg);
+                b.bump(&mut keyword);
+                b.bump(&mut keyword);
+                b.expression(&mut keyword, Precedence::Lowest);
+                n.children.push(SyntaxElement::Node(keyword));
+            } else {
+                b.expression(n, Precedence::Lowest);
+            }
+        });
+        parent.children.push(SyntaxElement::Node(args));
+    }
+
+    fn list(
+        &mut self,
+        node: &mut SyntaxNode,
+        close: &Token,
+        mut item: impl FnMut(&mut Self, &mut SyntaxNode),
+    ) {
+        self.bump(node);
+        while self.curr() != close && self.curr() != &Token::Eof {
+            let position = self.position;
+            item(self, node);
+            if self.curr() == &Token::Comma {
+                self.bump(node);
+            } else if self.position == position || self.curr() != close {
+                self.error(node);
+            }
+        }
+        self.expect(node, close);
+    }
+
+    fn curr(&self) -> &Token {
+        &self.tokens[self.position].token
+    }
+
+    /// The token after the current one, `Eof` at the end.
+    fn peek(&self) -> &Token {
+        let next = (self.position + 1).min(self.tokens.len() - 1);
+        &self.tokens[next].token
+    }
+
+    fn bump(&mut self, node: &mut SyntaxNode) {
+        let token = self.tokens[self.position].clone();
+        if token.token != Token::Eof {
+            self.position += 1;
+        }
+        node.children.push(SyntaxElement::Token(token));
+    }
+
+    fn wrap(&mut self, parent: &mut SyntaxNode, kind: SyntaxKind) {
+        let mut node = SyntaxNode::new(kind);
+        self.bump(&mut node);
+        parent.children.push(SyntaxElement::Node(node));
+    }
+
+    fn expect(&mut self, node: &mut SyntaxNode, token: &Token) {
+        if discriminant(self.curr()) == discriminant(token) {
+            self.bump(node);
+        } else {
+            node.children
+                .push(SyntaxElement::Node(SyntaxNode::new(SyntaxKind::Error)));
+        }
+    }
+
+    fn error(&mut self, parent: &mut SyntaxNode) {
+        let mut node = SyntaxNode::new(SyntaxKind::Error);
+        if self.curr() != &Token::Eof {
+            self.bump(&mut node);
+        }
+        parent.children.push(SyntaxElement::Node(node));
+    }
+}
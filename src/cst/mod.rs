@@ -0,0 +1,264 @@
+use crate::error::{ErrorCode, Result, fail};
+use crate::lexer::TokenWithTrivia;
+use crate::{Parser, Program, Token};
+use std::fmt;
+use std::ops::Range;
+
+mod builder;
+
+pub use builder::parse;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SyntaxKind {
+    Program,
+    LetStmt,
+    /// `name = value;`, `name` being a reference to an existing binding.
+    AssignStmt,
+    /// `struct Name { fields }`, the fields being the tokens of a `FieldList`.
+    StructStmt,
+    FieldList,
+    ReturnStmt,
+    YieldStmt,
+    BreakStmt,
+    ContinueStmt,
+    ExprStmt,
+    Block,
+    Ident,
+    Literal,
+    Prefix,
+    Infix,
+    Paren,
+    Call,
+    Index,
+    /// `value?`, returning `value` from the enclosing function when it's an error.
+    Try,
+    /// `object.name(args)`, `name` being the token of the key of the method in `object`.
+    MethodCall,
+    /// `object.name`, `name` being the token of the field.
+    Field,
+    ArgList,
+    /// `name: value` in an argument list, `name` being the token of a parameter.
+    KeywordArg,
+    Array,
+    /// `(a, b)`, `(a,)` or `()`, while `(a)` is a `Paren`.
+    Tuple,
+    Hash,
+    HashPair,
+    Func,
+    ParamList,
+    TypeAnnotation,
+    If,
+    Else,
+    For,
+    While,
+    Match,
+    MatchArm,
+    ArrayPattern,
+    TuplePattern,
+    HashPattern,
+    /// `Name(patterns)`, `Name` being the token of the struct.
+    StructPattern,
+    RestPattern,
+    Wildcard,
+    Error,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(TokenWithTrivia),
+}
+
+impl fmt::Display for SyntaxElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxElement::Node(node) => write!(f, "{node}"),
+            SyntaxElement::Token(token) => write!(f, "{token}"),
+        }
+    }
+}
+
+/// Node of the lossless syntax tree: every byte of the source, trivia included,
+/// belongs to exactly one token in the tree, so printing it gives back the source.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    pub fn new(kind: SyntaxKind) -> Self {
+        Self {
+            kind,
+            children: vec![],
+        }
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    pub fn child_tokens(&self) -> impl Iterator<Item = &TokenWithTrivia> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+    }
+
+    /// All the tokens below this node, in source order.
+    pub fn tokens(&self) -> Vec<&TokenWithTrivia> {
+        let mut output = vec![];
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => output.extend(node.tokens()),
+                SyntaxElement::Token(token) => output.push(token),
+            }
+        }
+        output
+    }
+
+    /// Byte range covered by the node, excluding the trivia in front of its first token.
+    pub fn span(&self) -> Option<Range<usize>> {
+        let tokens = self.tokens();
+        let first = tokens.first()?;
+        let last = tokens.last()?;
+        Some(first.offset..last.offset + last.text.len())
+    }
+
+    /// Source text of the node without surrounding trivia.
+    pub fn source(&self) -> String {
+        let tokens = self.tokens();
+        let mut output = String::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if i > 0 {
+                for trivia in &token.leading {
+                    output.push_str(trivia.text());
+                }
+            }
+            output.push_str(&token.text);
+        }
+        output
+    }
+
+    /// Innermost node whose span contains `offset`.
+    pub fn node_at(&self, offset: usize) -> Option<&SyntaxNode> {
+        let span = self.span()?;
+        if !span.contains(&offset) {
+            return None;
+        }
+        Some(
+            self.child_nodes()
+                .find_map(|node| node.node_at(offset))
+                .unwrap_or(self),
+        )
+    }
+
+    pub fn token_at(&self, offset: usize) -> Option<&TokenWithTrivia> {
+        self.tokens().into_iter().find(|t| {
+            t.token != Token::Eof && (t.offset..t.offset + t.text.len()).contains(&offset)
+        })
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.kind == SyntaxKind::Error || self.child_nodes().any(SyntaxNode::has_errors)
+    }
+
+    /// Derives the AST from the tree.
+    pub fn to_ast(&self) -> Result<Program> {
+        if self.kind != SyntaxKind::Program {
+            fail!(
+                ErrorCode::InvalidInput,
+                "Only a {:?} node can be converted to an AST",
+                SyntaxKind::Program
+            );
+        }
+        Parser::init(&self.to_string()).parse_program()
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for child in &self.children {
+            write!(f, "{child}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trip(input: &str) {
+        assert_eq!(parse(input).to_string(), input);
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_round_trip("");
+        assert_round_trip("  \n");
+        assert_round_trip("let five = 5;\n// comment\nlet ten = 10;\n");
+        assert_round_trip("let add = fn(x, y) {\n    x + y;\n};\n\nadd(1, 2 * 3)[0]");
+        assert_round_trip("if (a < b) { a } else { b }  // trailing\n");
+        assert_round_trip("for (x in range(3)) {\n    push(a, x)\n}");
+        assert_round_trip("for (in x) {}");
+        assert_round_trip("{\"one\": 1, \"two\": [2, 2]}[\"two\"]");
+        assert_round_trip("let = ; ) fn ( { ");
+        assert_round_trip("\"unterminated");
+        assert_round_trip("let f: fn = fn(x: int, y) -> int { x };");
+        assert_round_trip("let [a, ...rest] = xs; let {\"r\": r, 1: _} = h;");
+        assert_round_trip("greet(\"Ada\", excited :true , times: 2)");
+        assert_round_trip("counter . add(1).get( )\n  .done");
+        assert_round_trip("match (x) {\n    [-1, _] if a => 0,\n    null => 1,\n}");
+        assert_round_trip("match x { [... => }");
+        assert_round_trip("let y = -f(x?) ? [0];");
+        assert_round_trip("x = x + 1; y =");
+        assert_round_trip("struct P { x , y, }\nmatch (P(1, 2)) { P(a, _) => a.x }");
+        assert_round_trip("struct { x y");
+    }
+
+    #[test]
+    fn structure() {
+        let root = parse("let x = 1 + 2;\nx");
+        let kinds: Vec<SyntaxKind> = root.child_nodes().map(|n| n.kind).collect();
+        assert_eq!(kinds, vec![SyntaxKind::LetStmt, SyntaxKind::ExprStmt]);
+
+        assert_eq!(root.node_at(10).unwrap().kind, SyntaxKind::Infix);
+        assert_eq!(root.node_at(12).unwrap().kind, SyntaxKind::Literal);
+        assert_eq!(parse("f(x)?").node_at(4).unwrap().kind, SyntaxKind::Try);
+        assert_eq!(parse("p.x").node_at(2).unwrap().kind, SyntaxKind::Field);
+        assert_eq!(root.child_nodes().next().unwrap().span(), Some(0..14));
+        assert_eq!(
+            root.child_nodes()
+                .next()
+                .unwrap()
+                .child_nodes()
+                .nth(1)
+                .unwrap()
+                .source(),
+            "1 + 2"
+        );
+        assert!(!root.has_errors());
+        assert!(parse("let = 5;").has_errors());
+        let input = "match (x) { 0.5 => 1, -1.5 => 2 }";
+        assert!(!parse(input).has_errors());
+        assert_eq!(
+            parse(input).to_ast().unwrap(),
+            Parser::init(input).parse_program().unwrap()
+        );
+    }
+
+    #[test]
+    fn to_ast() {
+        let input = "// doubles\nlet double = fn(x) { x * 2 };\n";
+        assert_eq!(
+            parse(input).to_ast().unwrap(),
+            Parser::init("let double = fn(x) { x * 2 };")
+                .parse_program()
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Examples embedded in comments:
+//!
+//! ```text
+//! // >> double(2)
+//! // => 4
+//! ```
+//!
+//! Each example runs in a fresh child of the environment the whole file was evaluated in.
+
+use crate::cst;
+use crate::error::{Result, catch_unwind};
+use crate::eval::{Environment, Eval};
+use crate::lexer::{Lexer, Trivia};
+use std::rc::Rc;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Example {
+    /// 1-based line of the `// >>` comment.
+    pub line: usize,
+    pub source: String,
+    pub expected: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Outcome {
+    pub example: Example,
+    /// Printed result, or `Error: <message>` when evaluation fails.
+    pub actual: String,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.actual == self.example.expected
+    }
+}
+
+/// Collects the `// >>` comments that are directly followed by a `// =>` comment.
+pub fn examples(source: &str) -> Vec<Example> {
+    let mut comments = vec![];
+    let mut offset = 0;
+    for token in Lexer::with_trivia(source).get_all_tokens_with_trivia() {
+        for trivia in &token.leading {
+            let end = offset + trivia.text().len();
+            if let Trivia::Comment(_) = trivia {
+                let line = source[..offset].matches('\n').count() + 1;
+                comments.push((line, source[offset..end].trim_start_matches("//").trim()));
+            }
+            offset = end;
+        }
+        offset += token.text.len();
+    }
+
+    comments
+        .windows(2)
+        .filter_map(|pair| {
+            let (line, source) = pair[0];
+            let source = source.strip_prefix(">>")?.trim();
+            let expected = pair[1].1.strip_prefix("=>")?.trim();
+            Some(Example {
+                line,
+                source: source.to_owned(),
+                expected: expected.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Evaluates the file, then every example in it.
+/// Fails only if the file itself can't be parsed or evaluated.
+pub fn run(source: &str) -> Result<Vec<Outcome>> {
+    run_in(source, Rc::new(Environment::default()))
+}
+
+/// Like [`run`], evaluating the file in `env`, e.g. after the dependencies of its project.
+pub fn run_in(source: &str, env: Rc<Environment>) -> Result<Vec<Outcome>> {
+    catch_unwind(|| cst::parse(source).to_ast()?.eval(Rc::clone(&env)))?;
+
+    Ok(examples(source)
+        .into_iter()
+        .map(|example| {
+            let child = Rc::new(Environment::init_with_outer(Rc::clone(&env)));
+            let actual = match cst::parse(&example.source)
+                .to_ast()
+                .and_then(|program| catch_unwind(|| program.eval(child)))
+            {
+                Ok(obj) => obj.to_string(),
+                Err(err) => format!("Error: {err}"),
+            };
+            Outcome { example, actual }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "let double = fn(x) { x * 2 };\n\
+        // >> double(2)\n\
+        // => 4\n\
+        // not an example\n\
+        // >> let double = 1; double\n\
+        // => 1\n\
+        // >> double(\"a\")\n\
+        // => aa\n\
+        // >> double\n\
+        let y = 1;\n";
+
+    #[test]
+    fn collect() {
+        let examples = examples(SOURCE);
+        assert_eq!(examples.len(), 3);
+        assert_eq!(
+            examples[0],
+            Example {
+                line: 2,
+                source: "double(2)".to_owned(),
+                expected: "4".to_owned()
+            }
+        );
+        assert_eq!(examples[2].line, 7);
+    }
+
+    #[test]
+    fn outcomes() {
+        let outcomes = run(SOURCE).unwrap();
+        let passed: Vec<bool> = outcomes.iter().map(Outcome::passed).collect();
+        assert_eq!(passed, vec![true, true, false]);
+        assert_eq!(
+            outcomes[2].actual,
+            "Error: Invalid operation (`*`) between a and 2!"
+        );
+        // Examples don't leak into each other or into the file's environment.
+        assert!(run(&format!("{SOURCE}// >> double(1)\n// => 2\n")).unwrap()[3].passed());
+    }
+}
@@ -0,0 +1,556 @@
+//! Diagnostics carry a stable [`ErrorCode`], so callers can match on it instead of on messages.
+
+use crate::lexer::Position;
+use crate::prelude::*;
+use core::fmt;
+use core::ops::Range;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum ErrorCode {
+    UnexpectedToken,
+    NoPrefixParse,
+    UnknownType,
+    KeywordAsIdentifier,
+    UnexpectedEof,
+    AnnotationMismatch,
+    ArityMismatch,
+    TypeMismatch,
+    NotCallable,
+    ReturnTypeMismatch,
+    DuplicateHashKey,
+    ShadowedBinding,
+    InvalidOperation,
+    InvalidNegation,
+    NotAFunction,
+    ArgumentCount,
+    InvalidArgument,
+    InvalidHashKey,
+    InvalidYield,
+    Interrupted,
+    LimitExceeded,
+    SandboxViolation,
+    PatternMismatch,
+    UnknownIdentifier,
+    ImmutableAssignment,
+    DivisionByZero,
+    IntegerOverflow,
+    InvalidRename,
+    InvalidInput,
+    Internal,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 30] = [
+        ErrorCode::UnexpectedToken,
+        ErrorCode::NoPrefixParse,
+        ErrorCode::UnknownType,
+        ErrorCode::KeywordAsIdentifier,
+        ErrorCode::UnexpectedEof,
+        ErrorCode::AnnotationMismatch,
+        ErrorCode::ArityMismatch,
+        ErrorCode::TypeMismatch,
+        ErrorCode::NotCallable,
+        ErrorCode::ReturnTypeMismatch,
+        ErrorCode::DuplicateHashKey,
+        ErrorCode::ShadowedBinding,
+        ErrorCode::InvalidOperation,
+        ErrorCode::InvalidNegation,
+        ErrorCode::NotAFunction,
+        ErrorCode::ArgumentCount,
+        ErrorCode::InvalidArgument,
+        ErrorCode::InvalidHashKey,
+        ErrorCode::InvalidYield,
+        ErrorCode::Interrupted,
+        ErrorCode::LimitExceeded,
+        ErrorCode::SandboxViolation,
+        ErrorCode::PatternMismatch,
+        ErrorCode::UnknownIdentifier,
+        ErrorCode::ImmutableAssignment,
+        ErrorCode::DivisionByZero,
+        ErrorCode::IntegerOverflow,
+        ErrorCode::InvalidRename,
+        ErrorCode::InvalidInput,
+        ErrorCode::Internal,
+    ];
+
+    /// `E0xxx` are syntax errors, `E1xxx` type checker errors, `E2xxx` runtime errors,
+    /// `E3xxx` tooling errors and `E9xxx` bugs in the interpreter.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => "E0001",
+            ErrorCode::NoPrefixParse => "E0002",
+            ErrorCode::UnknownType => "E0003",
+            ErrorCode::KeywordAsIdentifier => "E0004",
+            ErrorCode::UnexpectedEof => "E0005",
+            ErrorCode::AnnotationMismatch => "E1001",
+            ErrorCode::ArityMismatch => "E1002",
+            ErrorCode::TypeMismatch => "E1003",
+            ErrorCode::NotCallable => "E1004",
+            ErrorCode::ReturnTypeMismatch => "E1005",
+            ErrorCode::DuplicateHashKey => "E1006",
+            ErrorCode::ShadowedBinding => "E1007",
+            ErrorCode::InvalidOperation => "E2001",
+            ErrorCode::InvalidNegation => "E2002",
+            ErrorCode::NotAFunction => "E2003",
+            ErrorCode::ArgumentCount => "E2004",
+            ErrorCode::InvalidArgument => "E2005",
+            ErrorCode::InvalidHashKey => "E2006",
+            ErrorCode::InvalidYield => "E2007",
+            ErrorCode::Interrupted => "E2008",
+            ErrorCode::LimitExceeded => "E2009",
+            ErrorCode::SandboxViolation => "E2010",
+            ErrorCode::PatternMismatch => "E2011",
+            ErrorCode::UnknownIdentifier => "E2012",
+            ErrorCode::ImmutableAssignment => "E2013",
+            ErrorCode::DivisionByZero => "E2014",
+            ErrorCode::IntegerOverflow => "E2015",
+            ErrorCode::InvalidRename => "E3001",
+            ErrorCode::InvalidInput => "E3002",
+            ErrorCode::Internal => "E9001",
+        }
+    }
+
+    /// Which stage raised the error, from the family of its code.
+    pub fn kind(&self) -> ErrorKind {
+        match &self.code()[..2] {
+            "E0" => ErrorKind::Syntax,
+            "E1" => ErrorKind::Type,
+            "E2" => ErrorKind::Runtime,
+            "E3" => ErrorKind::Tooling,
+            _ => ErrorKind::Internal,
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.code().eq_ignore_ascii_case(code))
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => "unexpected token",
+            ErrorCode::NoPrefixParse => "token cannot start an expression",
+            ErrorCode::UnknownType => "unknown type name",
+            ErrorCode::KeywordAsIdentifier => "keyword used as an identifier",
+            ErrorCode::UnexpectedEof => "unexpected end of input",
+            ErrorCode::AnnotationMismatch => "value does not match the annotation",
+            ErrorCode::ArityMismatch => "wrong number of arguments",
+            ErrorCode::TypeMismatch => "type mismatch",
+            ErrorCode::NotCallable => "value is not callable",
+            ErrorCode::ReturnTypeMismatch => "return type mismatch",
+            ErrorCode::DuplicateHashKey => "duplicate hash key",
+            ErrorCode::ShadowedBinding => "binding shadows another one",
+            ErrorCode::InvalidOperation => "invalid operation",
+            ErrorCode::InvalidNegation => "value cannot be negated",
+            ErrorCode::NotAFunction => "called value is not a function",
+            ErrorCode::ArgumentCount => "wrong number of arguments",
+            ErrorCode::InvalidArgument => "invalid builtin argument",
+            ErrorCode::InvalidHashKey => "invalid hash key",
+            ErrorCode::InvalidYield => "invalid yield",
+            ErrorCode::Interrupted => "interrupted",
+            ErrorCode::LimitExceeded => "resource limit exceeded",
+            ErrorCode::SandboxViolation => "denied by the sandbox policy",
+            ErrorCode::PatternMismatch => "value does not match the pattern",
+            ErrorCode::UnknownIdentifier => "unknown identifier",
+            ErrorCode::ImmutableAssignment => "assignment to an immutable variable",
+            ErrorCode::DivisionByZero => "division by zero",
+            ErrorCode::IntegerOverflow => "integer overflow",
+            ErrorCode::InvalidRename => "invalid rename",
+            ErrorCode::InvalidInput => "invalid input",
+            ErrorCode::Internal => "internal error",
+        }
+    }
+
+    /// Longer description with an example, printed by `waiir explain`.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => {
+                "The parser found a token that can't appear at this point.\n\n\
+                 \tlet = 5;\n\n\
+                 `let` must be followed by the name of the binding: `let x = 5;`."
+            }
+            ErrorCode::NoPrefixParse => {
+                "An expression was expected, but the token can't start one.\n\n\
+                 \tlet x = * 2;\n\n\
+                 Binary operators need a left operand: `let x = 1 * 2;`."
+            }
+            ErrorCode::UnknownType => {
+                "A type annotation names a type that doesn't exist.\n\n\
+                 \tlet x: integer = 5;\n\n\
+                 The available types are int, bool, str, array, hash, fn, null and any."
+            }
+            ErrorCode::KeywordAsIdentifier => {
+                "Keywords are reserved and can't name bindings or parameters.\n\n\
+                 \tlet if = 3;\n\n\
+                 The keywords are fn, let, mut, if, else, return, for, in, match, yield, struct, true and false."
+            }
+            ErrorCode::UnexpectedEof => {
+                "The input ended inside a string, or before a block, array or hash was closed.\n\n\
+                 \tlet f = fn(x) { [x, \"y\"];\n\n\
+                 The error tells what was still expected: here the `}` closing the body."
+            }
+            ErrorCode::AnnotationMismatch => {
+                "A binding is assigned a value of a type different from its annotation.\n\n\
+                 \tlet x: int = \"five\";\n\n\
+                 Once annotated, later `let`s of the same name in the scope must keep the type."
+            }
+            ErrorCode::ArityMismatch => {
+                "A function is called with a different number of arguments than it declares.\n\n\
+                 \tlet f = fn(a: int, b: int) { a + b };\n\tf(1);"
+            }
+            ErrorCode::TypeMismatch => {
+                "A value has a type that the operation or the parameter doesn't accept.\n\n\
+                 \tlet f = fn(a: int) { a };\n\tf(true);\n\n\
+                 Unannotated values are only checked when their type can be inferred."
+            }
+            ErrorCode::NotCallable => {
+                "A value that is statically known not to be a function is called.\n\n\
+                 \tlet x = 5;\n\tx(1);"
+            }
+            ErrorCode::ReturnTypeMismatch => {
+                "A function returns a value of a type different from its return annotation.\n\n\
+                 \tlet f = fn() -> int { \"one\" };"
+            }
+            ErrorCode::DuplicateHashKey => {
+                "A hash literal contains the same key twice, so the first value is silently \
+                 overwritten.\n\n\
+                 \t{\"a\": 1, \"a\": 2}\n\n\
+                 Keys are compared by value: `1` and `\"1\"` are different keys."
+            }
+            ErrorCode::ShadowedBinding => {
+                "A `let` binds a name that is already bound in the same scope or in an \
+                 enclosing one, hiding the previous binding from the code after it.\n\n\
+                 \tlet total = 0;\n\tlet add = fn(x) { let total = total + x; total };\n\n\
+                 Shadowing is allowed: it is only reported with `--warn-shadow`, and rejected \
+                 with `--deny-shadow`. Use `let mut` and an assignment to change a variable."
+            }
+            ErrorCode::InvalidOperation => {
+                "An infix operator was applied to values it isn't defined for.\n\n\
+                 \t1 + true\n\n\
+                 Arithmetic and `<`/`>` work on two ints, `+` also on two strings, `==` and `!=` \
+                 on two ints or two bools, and indexing on arrays and hashes."
+            }
+            ErrorCode::InvalidNegation => {
+                "Only integers can be negated with `-`.\n\n\
+                 \t-true\n\n\
+                 Use `!` for booleans."
+            }
+            ErrorCode::NotAFunction => {
+                "The called value is neither a function nor a builtin.\n\n\
+                 \tlet x = 5;\n\tx();"
+            }
+            ErrorCode::ArgumentCount => {
+                "A function or builtin was called with the wrong number of arguments.\n\n\
+                 \tlen(\"a\", \"b\")"
+            }
+            ErrorCode::InvalidArgument => {
+                "A builtin was called with an argument of a type it doesn't support.\n\n\
+                 \tlen(5)\n\n\
+                 `len`, `first`, `last` and `rest` take a string or an array."
+            }
+            ErrorCode::InvalidHashKey => {
+                "Only ints, strings and bools can be used as hash keys.\n\n\
+                 \t{[1]: 2}"
+            }
+            ErrorCode::InvalidYield => {
+                "`yield` was reached outside of a generator or somewhere a generator can't \
+                 be suspended, or a generator was resumed from its own body.\n\n\
+                 \tlet x = yield 1;\n\n\
+                 A function containing `yield` is a generator: calling it returns an iterator. \
+                 `yield` must be a statement of its body, possibly nested in `if` and `for` \
+                 statements, but not inside an expression."
+            }
+            ErrorCode::Interrupted => {
+                "The evaluation was stopped from outside, for instance with Ctrl-C while \
+                 `waiir` runs a script. The interrupt is checked at every function call and \
+                 loop iteration."
+            }
+            ErrorCode::LimitExceeded => {
+                "The program needed more resources than the host allows, for instance more \
+                 statements or a longer source than the limits of a session in a \
+                 `SessionPool`, or more function calls nested than the `Budget` of its \
+                 environment."
+            }
+            ErrorCode::SandboxViolation => {
+                "The program used a builtin, or a file, that the `Policy` of its environment \
+                 doesn't allow, for instance `read_stdin` in an environment created with \
+                 `Policy::locked_down()`. Unlike the other runtime errors this isn't a bug in \
+                 the program but a decision of the host."
+            }
+            ErrorCode::PatternMismatch => {
+                "A `let` destructured a value that doesn't have the shape of its pattern.\n\n\
+                 \tlet [a, b] = [1];\n\n\
+                 Use `match` to handle values of different shapes."
+            }
+            ErrorCode::UnknownIdentifier => {
+                "An identifier is neither bound nor a builtin, in an interpreter that rejects \
+                 them instead of evaluating them to `null`, like `Interpreter::expression_sandbox()`.\n\n\
+                 \tprice * qantity\n\n\
+                 The message suggests the closest known name, if any."
+            }
+            ErrorCode::ImmutableAssignment => {
+                "A variable bound with a plain `let` is assigned a new value.\n\n\
+                 \tlet count = 0;\n\tcount = count + 1;\n\n\
+                 Only variables defined with `let mut count = 0;` can be assigned. The \
+                 assignment is rejected before the program runs when the variable is bound \
+                 in the same source, and when it runs otherwise."
+            }
+            ErrorCode::DivisionByZero => {
+                "An integer was divided by zero with `/`, or its remainder taken with `%`.\n\n\
+                 \tlet buckets = 0;\n\t17 % buckets\n\n\
+                 Floats don't fail: `1.0 / 0` is infinite and `1.0 % 0` is NaN."
+            }
+            ErrorCode::IntegerOverflow => {
+                "The result of `+`, `-`, `*`, `/` or of a negation doesn't fit in a 64-bit \
+                 integer.\n\n\
+                 \tlet max = 9223372036854775807;\n\tmax + 1\n\n\
+                 Use floats for larger values: `9223372036854775807.0 + 1` doesn't fail. \
+                 `**` wraps around instead: `2 ** 64` is 0."
+            }
+            ErrorCode::InvalidRename => {
+                "A rename refactoring was rejected: the new name isn't a valid identifier, \
+                 is the name of a builtin, or would make an identifier refer to a different \
+                 binding."
+            }
+            ErrorCode::InvalidInput => {
+                "A tool was given input it can't work with, such as a malformed session \
+                 file for `waiir replay`."
+            }
+            ErrorCode::Internal => {
+                "The interpreter panicked while running the program. This is a bug: please \
+                 report it together with the program and the message of the error."
+            }
+        }
+    }
+}
+
+/// Families of [`ErrorCode`]s, telling e.g. a program that doesn't parse from one that
+/// failed while running without listing every code.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum ErrorKind {
+    /// Raised by the lexer or the parser.
+    Syntax,
+    /// Raised by the type checker.
+    Type,
+    /// Raised while evaluating.
+    Runtime,
+    /// Raised by tools like the refactorings or the session replay.
+    Tooling,
+    Internal,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// Error raised by the lexer, parser, type checker or evaluator.
+/// Displays as its message only, the code is available through the field.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Byte range of the source the error refers to, when known.
+    pub span: Option<Range<usize>>,
+    /// Calls a runtime error went through, innermost first, such as `fn(x)` or
+    /// `builtin len`.
+    pub stack: Vec<String>,
+}
+
+impl Error {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            span: None,
+            stack: vec![],
+        }
+    }
+
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Sets the span if the error has none yet, so that the innermost location known wins,
+    /// e.g. the call the error was raised in rather than the calls leading to it.
+    pub fn or_span(mut self, span: Option<Range<usize>>) -> Self {
+        if self.span.is_none() {
+            self.span = span;
+        }
+        self
+    }
+
+    /// Appends the line and column where the span starts in `source`, the program the
+    /// error was raised for, to the message of an error raised while evaluating, like a
+    /// runtime error or a duplicate hash key. Syntax errors say it already.
+    pub fn located_in(mut self, source: &str) -> Self {
+        if self.code.kind() != ErrorKind::Syntax
+            && let Some(span) = &self.span
+            && source.is_char_boundary(span.start)
+        {
+            let position = Position::at(source, span.start);
+            let message = self.message.strip_suffix('.').unwrap_or(&self.message);
+            self.message = format!("{message} at {position}");
+        }
+        self
+    }
+
+    /// Records that the error left the call `frame`.
+    pub fn in_frame(mut self, frame: impl Into<String>) -> Self {
+        self.stack.push(frame.into());
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl core::error::Error for Error {}
+
+pub type Result<T, E = Error> = core::result::Result<T, E>;
+
+/// Runs `f`, turning a panic into an [`ErrorCode::Internal`] error carrying the panic message,
+/// so that a bug in the interpreter doesn't take down the embedding application.
+#[cfg(feature = "std")]
+pub(crate) fn catch_unwind<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let payload = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_owned());
+            crate::trace::event!(%payload, "internal error");
+            Err(Error::new(
+                ErrorCode::Internal,
+                format!("Internal error: {payload}"),
+            ))
+        }
+    }
+}
+
+/// Without `std` panics can't be caught, `f` is just called.
+#[cfg(not(feature = "std"))]
+pub(crate) fn catch_unwind<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    f()
+}
+
+/// Closest candidate to a misspelled `name`, for "did you mean" hints.
+/// Only candidates within an edit distance of a third of the name's length are considered,
+/// so single-character names never get a suggestion.
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let len = name.chars().count();
+    let max_distance = (len / 3).max(1).min(len.saturating_sub(1));
+    candidates
+        .into_iter()
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance between `a` and `b`, counted in chars: swapping two adjacent chars is a
+/// single edit, like inserting, deleting or replacing one.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // The distances from the prefixes of `a` one and two chars shorter.
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = prev[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            row[j] = substitution.min(prev[j] + 1).min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = core::mem::replace(&mut prev, row);
+    }
+    prev[b.len()]
+}
+
+/// Returns early with an [`Error`].
+macro_rules! fail {
+    ($code:expr, $($arg:tt)*) => {
+        return Err($crate::error::Error::new($code, format!($($arg)*)))
+    };
+}
+
+pub(crate) use fail;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, eval_with_env};
+    use alloc::rc::Rc;
+
+    fn code(input: &str) -> Option<ErrorCode> {
+        eval_with_env(input, Rc::new(Environment::default()))
+            .err()
+            .map(|e| e.code)
+    }
+
+    #[test]
+    fn codes() {
+        assert_eq!(code("let = 5;"), Some(ErrorCode::UnexpectedToken));
+        assert_eq!(code("let x = * 2;"), Some(ErrorCode::NoPrefixParse));
+        assert_eq!(code("let x: integer = 5;"), Some(ErrorCode::UnknownType));
+        assert_eq!(code("1 + true"), Some(ErrorCode::InvalidOperation));
+        assert_eq!(code("-true"), Some(ErrorCode::InvalidNegation));
+        assert_eq!(code("5()"), Some(ErrorCode::NotAFunction));
+        assert_eq!(code("fn(x) { x }()"), Some(ErrorCode::ArgumentCount));
+        assert_eq!(code("len(5)"), Some(ErrorCode::InvalidArgument));
+        assert_eq!(code("{[1]: 2}"), Some(ErrorCode::InvalidHashKey));
+        assert_eq!(code("1 % 0"), Some(ErrorCode::DivisionByZero));
+        assert_eq!(
+            code("(-9223372036854775807 - 1) / -1"),
+            Some(ErrorCode::IntegerOverflow)
+        );
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(ErrorCode::NoPrefixParse.kind(), ErrorKind::Syntax);
+        assert_eq!(ErrorCode::TypeMismatch.kind(), ErrorKind::Type);
+        assert_eq!(ErrorCode::UnknownIdentifier.kind(), ErrorKind::Runtime);
+        assert_eq!(ErrorCode::InvalidRename.kind(), ErrorKind::Tooling);
+        assert_eq!(ErrorCode::Internal.kind(), ErrorKind::Internal);
+        let kind = |input| code(input).map(|code| code.kind());
+        assert_eq!(kind("let x = * 2;"), Some(ErrorKind::Syntax));
+        assert_eq!(kind("1 + true"), Some(ErrorKind::Runtime));
+    }
+
+    #[test]
+    fn suggestions() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(
+            did_you_mean("lenght", ["len", "length", "last"]),
+            Some("length")
+        );
+        assert_eq!(did_you_mean("fist", ["first", "last"]), Some("first"));
+        assert_eq!(did_you_mean("coutn", ["count", "cut"]), Some("count"));
+        assert_eq!(did_you_mean("x", ["x"]), None);
+        assert_eq!(did_you_mean("counter", ["push", "rest"]), None);
+    }
+
+    #[test]
+    fn lookup() {
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_code(code.code()), Some(code));
+        }
+        assert_eq!(ErrorCode::from_code("e1003"), Some(ErrorCode::TypeMismatch));
+        assert_eq!(ErrorCode::from_code("E9999"), None);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{HashMapKey, HashObject, Object};
+use crate::prelude::*;
+use std::sync::Mutex;
+
+static SCRIPT_ARGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Sets what the `args` builtin returns: the command-line arguments following the
+/// script, empty by default.
+pub fn set_script_args(args: Vec<String>) {
+    *SCRIPT_ARGS.lock().unwrap_or_else(|err| err.into_inner()) = args;
+}
+
+pub(crate) fn script_args() -> Vec<String> {
+    SCRIPT_ARGS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+}
+
+/// Parses `argv` following `spec`, a hash from option names to their defaults: `--name`
+/// sets a bool option to `true`, the other options take a value, `--name value` or
+/// `--name=value`, parsed as an int when the default is one. The result maps every
+/// option to its value and `_` to the positional arguments, those after `--` included.
+pub(crate) fn parse_args(spec: &HashObject, argv: Vec<String>) -> Result<Object> {
+    let mut options = HashObject::default();
+    for (key, default) in spec {
+        match (key, default) {
+            (
+                HashMapKey::String(_),
+                Object::Bool(_) | Object::Int(_) | Object::String(_) | Object::Null,
+            ) => {
+                options.insert(key.clone(), default.clone());
+            }
+            _ => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid option `{key}: {default}` for builtin function `parse_args`, expected a string name and a bool, int, string or null default"
+            ),
+        }
+    }
+
+    let mut positional = vec![];
+    let mut argv = argv.into_iter();
+    while let Some(arg) = argv.next() {
+        if arg == "--" {
+            positional.extend(argv.by_ref().map(Object::String));
+            break;
+        }
+        let Some(option) = arg.strip_prefix("--") else {
+            positional.push(Object::String(arg));
+            continue;
+        };
+        let (name, inline) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_owned())),
+            None => (option, None),
+        };
+        let key = HashMapKey::String(name.to_owned());
+        let value = match (spec.get(&key), inline) {
+            (None, _) => fail!(ErrorCode::InvalidArgument, "Unknown option `--{name}`"),
+            (Some(Object::Bool(_)), None) => Object::Bool(true),
+            (Some(Object::Bool(_)), Some(_)) => fail!(
+                ErrorCode::InvalidArgument,
+                "Option `--{name}` doesn't take a value"
+            ),
+            (Some(default), inline) => {
+                let Some(value) = inline.or_else(|| argv.next()) else {
+                    fail!(
+                        ErrorCode::InvalidArgument,
+                        "Option `--{name}` expects a value"
+                    );
+                };
+                match default {
+                    Object::Int(_) => match value.parse() {
+                        Ok(value) => Object::Int(value),
+                        Err(_) => fail!(
+                            ErrorCode::InvalidArgument,
+                            "Option `--{name}` expects an int, found `{value}`"
+                        ),
+                    },
+                    _ => Object::String(value),
+                }
+            }
+        };
+        options.insert(key, value);
+    }
+    options.insert(
+        HashMapKey::String("_".to_owned()),
+        Object::Array(positional.into()),
+    );
+    Ok(Object::Hash(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, PrettyOptions, eval_with_env};
+    use alloc::rc::Rc;
+
+    #[test]
+    fn script_arguments() {
+        set_script_args(vec!["in.txt".into(), "--lines".into(), "5".into()]);
+        let input = "dump(parse_args({\"lines\": 10, \"quiet\": false}))";
+        let result = eval_with_env(input, Rc::new(Environment::default()));
+        set_script_args(vec![]);
+        assert_eq!(
+            result.unwrap().pretty(&PrettyOptions::default()),
+            "{\n    _: [in.txt],\n    lines: 5,\n    quiet: false,\n}"
+        );
+    }
+}
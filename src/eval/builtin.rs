@@ -1,6 +1,18 @@
-use crate::eval::{HashMapKey, Object};
-use anyhow::{Result, bail};
-use std::fmt;
+use crate::ast::{CallSpan, Expression, Statement};
+use crate::error::{ErrorCode, Result, fail};
+#[cfg(feature = "std")]
+use crate::eval::args::{parse_args, script_args};
+#[cfg(feature = "sqlite")]
+use crate::eval::sqlite;
+#[cfg(feature = "sync")]
+use crate::eval::workers;
+use crate::eval::{Capability, Environment, HashMapKey, Iter, Object, PrettyOptions};
+use crate::eval::{csv, encoding, query};
+#[cfg(feature = "std")]
+use crate::eval::{host, output, policy, table};
+use crate::prelude::*;
+use alloc::rc::Rc;
+use core::fmt;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BuiltinFunction {
@@ -9,63 +21,1547 @@ pub enum BuiltinFunction {
     Last,
     Rest,
     Push,
+    Insert,
+    Merge,
+    Slice,
+    Range,
+    Iter,
+    Next,
+    Map,
+    Filter,
+    Collect,
+    Lines,
+    Words,
+    Fields,
+    Bytes,
+    Decode,
+    Hex,
+    FromHex,
+    Base64,
+    FromBase64,
+    #[cfg(feature = "crypto")]
+    HashSha256,
+    #[cfg(feature = "crypto")]
+    HmacSha256,
+    CsvParse,
+    #[cfg(feature = "std")]
+    CsvRead,
+    #[cfg(feature = "std")]
+    CsvWrite,
+    Where,
+    Select,
+    GroupBy,
+    OrderBy,
+    #[cfg(feature = "std")]
+    Puts,
+    #[cfg(feature = "std")]
+    Table,
+    Dump,
+    Copy,
+    DeepCopy,
+    Partial,
+    Compose,
+    Help,
+    Error,
+    IsError,
+    #[cfg(feature = "std")]
+    Args,
+    #[cfg(feature = "std")]
+    ParseArgs,
+    #[cfg(feature = "std")]
+    SendHost,
+    #[cfg(feature = "std")]
+    RecvHost,
+    #[cfg(feature = "sync")]
+    Spawn,
+    #[cfg(feature = "sync")]
+    Send,
+    #[cfg(feature = "sync")]
+    Recv,
+    #[cfg(feature = "sqlite")]
+    SqlOpen,
+    #[cfg(feature = "sqlite")]
+    SqlQuery,
+    #[cfg(feature = "sqlite")]
+    SqlExec,
+    #[cfg(feature = "stdin")]
+    ReadStdin,
+    /// The builtin with the given index among those registered at runtime.
+    #[cfg(feature = "std")]
+    Plugin(usize),
 }
 
-impl fmt::Display for BuiltinFunction {
+/// How many arguments a builtin takes, `max` being `None` when there's no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub const fn exactly(n: usize) -> Self {
+        Self {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    pub const fn between(min: usize, max: usize) -> Self {
+        Self {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub const fn at_least(min: usize) -> Self {
+        Self { min, max: None }
+    }
+}
+
+impl fmt::Display for Arity {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            BuiltinFunction::Len => write!(f, "len"),
-            BuiltinFunction::First => write!(f, "first"),
-            BuiltinFunction::Last => write!(f, "last"),
-            BuiltinFunction::Rest => write!(f, "rest"),
-            BuiltinFunction::Push => write!(f, "push"),
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{max}"),
+            Some(max) => write!(f, "{} to {max}", self.min),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
+}
+
+/// Entry of the [`BUILTINS`] registry.
+#[derive(Debug)]
+pub struct BuiltinInfo {
+    pub name: &'static str,
+    pub function: BuiltinFunction,
+    pub arity: Arity,
+    /// How the builtin is called, e.g. `range([start,] end[, step])`.
+    pub usage: &'static str,
+    pub help: &'static str,
+    /// What it needs to be allowed by the [`Policy`](crate::eval::Policy).
+    pub capability: Option<Capability>,
+    handler: fn(&BuiltinFunction, Vec<Object>) -> Result<Object>,
+}
+
+impl BuiltinInfo {
+    pub(crate) const fn new(
+        name: &'static str,
+        function: BuiltinFunction,
+        arity: Arity,
+        usage: &'static str,
+        help: &'static str,
+        handler: fn(&BuiltinFunction, Vec<Object>) -> Result<Object>,
+    ) -> Self {
+        Self {
+            name,
+            function,
+            arity,
+            usage,
+            help,
+            capability: None,
+            handler,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    const fn needs(self, capability: Capability) -> Self {
+        Self {
+            capability: Some(capability),
+            ..self
         }
     }
 }
 
+/// Every builtin, in the order they're documented. Identifiers are resolved, and the
+/// builtins called, through this table.
+pub static BUILTINS: &[BuiltinInfo] = &[
+    BuiltinInfo::new(
+        "len",
+        BuiltinFunction::Len,
+        Arity::exactly(1),
+        "len(value)",
+        "Number of bytes of a string, elements of an array or entries of a hash.",
+        BuiltinFunction::call_len,
+    ),
+    BuiltinInfo::new(
+        "first",
+        BuiltinFunction::First,
+        Arity::exactly(1),
+        "first(value)",
+        "First character of a string or element of an array, `null` when empty.",
+        BuiltinFunction::call_first,
+    ),
+    BuiltinInfo::new(
+        "last",
+        BuiltinFunction::Last,
+        Arity::exactly(1),
+        "last(value)",
+        "Last character of a string or element of an array, `null` when empty.",
+        BuiltinFunction::call_last,
+    ),
+    BuiltinInfo::new(
+        "rest",
+        BuiltinFunction::Rest,
+        Arity::exactly(1),
+        "rest(value)",
+        "Copy of a string or array without its first item, `null` when empty.",
+        BuiltinFunction::call_rest,
+    ),
+    BuiltinInfo::new(
+        "push",
+        BuiltinFunction::Push,
+        Arity::at_least(2),
+        "push(collection, items...)",
+        "Copy of a string with others appended or of an array with elements added. \
+         Pushing to hashes is deprecated in favor of `insert` and `merge`.",
+        BuiltinFunction::call_push,
+    ),
+    BuiltinInfo::new(
+        "insert",
+        BuiltinFunction::Insert,
+        Arity::exactly(3),
+        "insert(hash, key, value)",
+        "Copy of a hash with `key` mapped to `value`.",
+        BuiltinFunction::call_insert,
+    ),
+    BuiltinInfo::new(
+        "merge",
+        BuiltinFunction::Merge,
+        Arity::exactly(2),
+        "merge(hash, other)",
+        "Copy of a hash with the pairs of another added, replacing those with the same keys.",
+        BuiltinFunction::call_merge,
+    ),
+    BuiltinInfo::new(
+        "slice",
+        BuiltinFunction::Slice,
+        Arity::between(2, 3),
+        "slice(collection, start[, end])",
+        "Elements of an array, or bytes, from `start` up to `end` excluded, by default \
+         the end. Indexes out of bounds are clamped.",
+        BuiltinFunction::call_slice,
+    ),
+    BuiltinInfo::new(
+        "range",
+        BuiltinFunction::Range,
+        Arity::between(1, 3),
+        "range([start,] end[, step])",
+        "Iterator over the integers from `start`, 0 by default, up to `end` excluded.",
+        BuiltinFunction::call_range,
+    ),
+    BuiltinInfo::new(
+        "iter",
+        BuiltinFunction::Iter,
+        Arity::exactly(1),
+        "iter(iterable)",
+        "Iterator over a string, array, hash or iterator.",
+        BuiltinFunction::call_iter,
+    ),
+    BuiltinInfo::new(
+        "next",
+        BuiltinFunction::Next,
+        Arity::exactly(1),
+        "next(iterator)",
+        "Advances an iterator, returning the item or `null` once exhausted.",
+        BuiltinFunction::call_next,
+    ),
+    BuiltinInfo::new(
+        "map",
+        BuiltinFunction::Map,
+        Arity::exactly(2),
+        "map(iterable, f)",
+        "Lazy iterator over the results of `f` on each item.",
+        BuiltinFunction::call_adapter,
+    ),
+    BuiltinInfo::new(
+        "filter",
+        BuiltinFunction::Filter,
+        Arity::exactly(2),
+        "filter(iterable, f)",
+        "Lazy iterator over the items for which `f` is truthy.",
+        BuiltinFunction::call_adapter,
+    ),
+    BuiltinInfo::new(
+        "collect",
+        BuiltinFunction::Collect,
+        Arity::exactly(1),
+        "collect(iterable)",
+        "Array of the remaining items of an iterable.",
+        BuiltinFunction::call_collect,
+    ),
+    BuiltinInfo::new(
+        "lines",
+        BuiltinFunction::Lines,
+        Arity::exactly(1),
+        "lines(string)",
+        "Lines of a string, without their `\\n` or `\\r\\n` endings.",
+        BuiltinFunction::call_split,
+    ),
+    BuiltinInfo::new(
+        "words",
+        BuiltinFunction::Words,
+        Arity::exactly(1),
+        "words(string)",
+        "Parts of a string separated by whitespace.",
+        BuiltinFunction::call_split,
+    ),
+    BuiltinInfo::new(
+        "fields",
+        BuiltinFunction::Fields,
+        Arity::exactly(2),
+        "fields(string, separator)",
+        "Parts of a string separated by a non-empty separator.",
+        BuiltinFunction::call_fields,
+    ),
+    BuiltinInfo::new(
+        "bytes",
+        BuiltinFunction::Bytes,
+        Arity::between(1, 2),
+        "bytes(value[, encoding])",
+        "Bytes of a string in the given encoding, `\"utf8\"` by default or `\"latin1\"`, \
+         or of an array of ints from 0 to 255.",
+        BuiltinFunction::call_bytes,
+    ),
+    BuiltinInfo::new(
+        "decode",
+        BuiltinFunction::Decode,
+        Arity::between(1, 2),
+        "decode(bytes[, encoding])",
+        "String encoded by bytes in the given encoding, `\"utf8\"` by default or `\"latin1\"`.",
+        BuiltinFunction::call_decode,
+    ),
+    BuiltinInfo::new(
+        "hex",
+        BuiltinFunction::Hex,
+        Arity::exactly(1),
+        "hex(data)",
+        "Lowercase hexadecimal digits of bytes, or of a string encoded in UTF-8.",
+        BuiltinFunction::call_encode,
+    ),
+    BuiltinInfo::new(
+        "from_hex",
+        BuiltinFunction::FromHex,
+        Arity::exactly(1),
+        "from_hex(string)",
+        "Bytes written in hexadecimal digits by a string.",
+        BuiltinFunction::call_from_encoded,
+    ),
+    BuiltinInfo::new(
+        "base64",
+        BuiltinFunction::Base64,
+        Arity::exactly(1),
+        "base64(data)",
+        "Standard base64 encoding of bytes, or of a string encoded in UTF-8.",
+        BuiltinFunction::call_encode,
+    ),
+    BuiltinInfo::new(
+        "from_base64",
+        BuiltinFunction::FromBase64,
+        Arity::exactly(1),
+        "from_base64(string)",
+        "Bytes encoded by a standard base64 string, padded or not.",
+        BuiltinFunction::call_from_encoded,
+    ),
+    #[cfg(feature = "crypto")]
+    BuiltinInfo::new(
+        "hash_sha256",
+        BuiltinFunction::HashSha256,
+        Arity::exactly(1),
+        "hash_sha256(data)",
+        "SHA-256 digest of bytes, or of a string encoded in UTF-8.",
+        BuiltinFunction::call_encode,
+    ),
+    #[cfg(feature = "crypto")]
+    BuiltinInfo::new(
+        "hmac_sha256",
+        BuiltinFunction::HmacSha256,
+        Arity::exactly(2),
+        "hmac_sha256(key, message)",
+        "HMAC-SHA-256 of a message with a key, both bytes or strings encoded in UTF-8.",
+        BuiltinFunction::call_hmac_sha256,
+    ),
+    BuiltinInfo::new(
+        "csv_parse",
+        BuiltinFunction::CsvParse,
+        Arity::exactly(1),
+        "csv_parse(string)",
+        "Records of a CSV string as hashes from the column names of its header to fields, \
+         which are ints when they hold one.",
+        BuiltinFunction::call_csv_parse,
+    ),
+    #[cfg(feature = "std")]
+    BuiltinInfo::new(
+        "csv_read",
+        BuiltinFunction::CsvRead,
+        Arity::exactly(1),
+        "csv_read(path)",
+        "Records of a CSV file, like `csv_parse`.",
+        BuiltinFunction::call_csv_read,
+    )
+    .needs(Capability::FsRead),
+    #[cfg(feature = "std")]
+    BuiltinInfo::new(
+        "csv_write",
+        BuiltinFunction::CsvWrite,
+        Arity::between(2, 3),
+        "csv_write(path, rows[, columns])",
+        "Writes rows, hashes or arrays of fields, to a CSV file, returning how many were \
+         written. Hashes are written under a header of `columns`, by default their sorted keys.",
+        BuiltinFunction::call_csv_write,
+    )
+    .needs(Capability::FsWrite),
+    BuiltinInfo::new(
+        "where",
+        BuiltinFunction::Where,
+        Arity::exactly(2),
+        "where(rows, f)",
+        "Array of the rows for which `f` returns a truthy value.",
+        BuiltinFunction::call_query,
+    ),
+    BuiltinInfo::new(
+        "select",
+        BuiltinFunction::Select,
+        Arity::exactly(2),
+        "select(rows, columns)",
+        "Rows as hashes of the given columns, `null` when missing, or mapped by a function.",
+        BuiltinFunction::call_query,
+    ),
+    BuiltinInfo::new(
+        "group_by",
+        BuiltinFunction::GroupBy,
+        Arity::exactly(2),
+        "group_by(rows, key)",
+        "Hash from the values of a column, or of a function of the rows, to the rows having \
+         them, in order.",
+        BuiltinFunction::call_query,
+    ),
+    BuiltinInfo::new(
+        "order_by",
+        BuiltinFunction::OrderBy,
+        Arity::between(2, 3),
+        "order_by(rows, key[, descending])",
+        "Rows stably sorted by a column or a function of them, `null` first unless descending. \
+         The keys must otherwise be all numbers, strings or bools.",
+        BuiltinFunction::call_query,
+    ),
+    #[cfg(feature = "std")]
+    BuiltinInfo::new(
+        "puts",
+        BuiltinFunction::Puts,
+        Arity::at_least(0),
+        "puts(values...)",
+        "Prints each value on its own line, returning `null`.",
+        BuiltinFunction::call_puts,
+    ),
+    #[cfg(feature = "std")]
+    BuiltinInfo::new(
+        "table",
+        BuiltinFunction::Table,
+        Arity::exactly(1),
+        "table(rows)",
+        "Prints an array of hashes as a table, with a column per key, returning `null`.",
+        BuiltinFunction::call_table,
+    ),
+    BuiltinInfo::new(
+        "dump",
+        BuiltinFunction::Dump,
+        Arity::between(1, 3),
+        "dump(value[, max_depth, max_items])",
+        "Value rendered over several indented lines, optionally truncated.",
+        BuiltinFunction::call_dump,
+    ),
+    BuiltinInfo::new(
+        "copy",
+        BuiltinFunction::Copy,
+        Arity::exactly(1),
+        "copy(value)",
+        "Copy of an array, hash, tuple or struct holding the same items, or of an iterator \
+         advancing on its own. Functions and handles are shared, not copied.",
+        BuiltinFunction::call_copy,
+    ),
+    BuiltinInfo::new(
+        "deepcopy",
+        BuiltinFunction::DeepCopy,
+        Arity::exactly(1),
+        "deepcopy(value)",
+        "Like `copy`, also copying the items, recursively. What's reached several times \
+         is copied once.",
+        BuiltinFunction::call_deepcopy,
+    ),
+    BuiltinInfo::new(
+        "partial",
+        BuiltinFunction::Partial,
+        Arity::at_least(1),
+        "partial(f, args...)",
+        "Function taking the parameters of `f` left after binding the first ones to `args`.",
+        BuiltinFunction::call_partial,
+    ),
+    BuiltinInfo::new(
+        "compose",
+        BuiltinFunction::Compose,
+        Arity::exactly(2),
+        "compose(f, g)",
+        "Function calling `f` on the result of `g`.",
+        BuiltinFunction::call_compose,
+    ),
+    BuiltinInfo::new(
+        "help",
+        BuiltinFunction::Help,
+        Arity::exactly(1),
+        "help(value)",
+        "Usage and description of a builtin, given by name or value, or signature and \
+         docstring of a function: the string literal its body starts with.",
+        BuiltinFunction::call_help,
+    ),
+    BuiltinInfo::new(
+        "error",
+        BuiltinFunction::Error,
+        Arity::between(1, 2),
+        "error(message[, data])",
+        "Error with a string message and any data, `null` by default, which are read back \
+         with `e[\"message\"]` and `e[\"data\"]`.",
+        BuiltinFunction::call_error,
+    ),
+    BuiltinInfo::new(
+        "is_error",
+        BuiltinFunction::IsError,
+        Arity::exactly(1),
+        "is_error(value)",
+        "Whether a value is an error, which `value?` returns from the enclosing function.",
+        BuiltinFunction::call_is_error,
+    ),
+    #[cfg(feature = "std")]
+    BuiltinInfo::new(
+        "args",
+        BuiltinFunction::Args,
+        Arity::exactly(0),
+        "args()",
+        "Command-line arguments following the script, as strings.",
+        BuiltinFunction::call_args,
+    )
+    .needs(Capability::Env),
+    #[cfg(feature = "std")]
+    BuiltinInfo::new(
+        "parse_args",
+        BuiltinFunction::ParseArgs,
+        Arity::between(1, 2),
+        "parse_args(spec[, argv])",
+        "Hash of the options in `argv`, `args()` by default, following `spec`, a hash from \
+         option names to defaults: `--name` sets a bool option, the others take a value, \
+         `--name value` or `--name=value`. The positional arguments are under `_`.",
+        BuiltinFunction::call_parse_args,
+    ),
+    #[cfg(feature = "std")]
+    BuiltinInfo::new(
+        "send_host",
+        BuiltinFunction::SendHost,
+        Arity::exactly(1),
+        "send_host(value)",
+        "Sends a copy of `value` to the host embedding the interpreter, returning whether \
+         it's still listening. Functions and iterators can't be sent.",
+        BuiltinFunction::call_send_host,
+    )
+    .needs(Capability::Host),
+    #[cfg(feature = "std")]
+    BuiltinInfo::new(
+        "recv_host",
+        BuiltinFunction::RecvHost,
+        Arity::exactly(0),
+        "recv_host()",
+        "Waits for the next value sent by the host, `null` once it stopped sending.",
+        BuiltinFunction::call_recv_host,
+    )
+    .needs(Capability::Host),
+    #[cfg(feature = "sync")]
+    BuiltinInfo::new(
+        "spawn",
+        BuiltinFunction::Spawn,
+        Arity::at_least(1),
+        "spawn(f, args...)",
+        "Runs `f(args...)` on a new thread, returning the id of the worker. `f` only sees \
+         its parameters and the builtins, and values are copied between workers.",
+        BuiltinFunction::call_spawn,
+    )
+    .needs(Capability::Threads),
+    #[cfg(feature = "sync")]
+    BuiltinInfo::new(
+        "send",
+        BuiltinFunction::Send,
+        Arity::exactly(2),
+        "send(worker, value)",
+        "Sends a copy of `value` to a worker spawned by the caller, or to the one that \
+         spawned the caller with id 0. Functions and iterators can't be sent.",
+        BuiltinFunction::call_send,
+    ),
+    #[cfg(feature = "sync")]
+    BuiltinInfo::new(
+        "recv",
+        BuiltinFunction::Recv,
+        Arity::exactly(0),
+        "recv()",
+        "Waits for the next value sent to the caller. Outside workers, returns `null` \
+         once every worker has finished, and fails if one of them failed.",
+        BuiltinFunction::call_recv,
+    ),
+    #[cfg(feature = "sqlite")]
+    BuiltinInfo::new(
+        "sql_open",
+        BuiltinFunction::SqlOpen,
+        Arity::exactly(1),
+        "sql_open(path)",
+        "Opens the SQLite database at `path`, creating it if needed, or a new in-memory \
+         one for `\":memory:\"`.",
+        BuiltinFunction::call_sql_open,
+    )
+    .needs(Capability::FsWrite),
+    #[cfg(feature = "sqlite")]
+    BuiltinInfo::new(
+        "sql_query",
+        BuiltinFunction::SqlQuery,
+        Arity::between(2, 3),
+        "sql_query(db, sql[, params])",
+        "Runs a query, returning an array with a hash per row. `params` are bound to the \
+         `?` placeholders in order.",
+        BuiltinFunction::call_sql_query,
+    ),
+    #[cfg(feature = "sqlite")]
+    BuiltinInfo::new(
+        "sql_exec",
+        BuiltinFunction::SqlExec,
+        Arity::between(2, 3),
+        "sql_exec(db, sql[, params])",
+        "Runs a statement, returning how many rows it changed.",
+        BuiltinFunction::call_sql_exec,
+    ),
+    #[cfg(feature = "stdin")]
+    BuiltinInfo::new(
+        "read_stdin",
+        BuiltinFunction::ReadStdin,
+        Arity::exactly(0),
+        "read_stdin()",
+        "Whole standard input, as a string.",
+        BuiltinFunction::call_read_stdin,
+    )
+    .needs(Capability::Stdin),
+];
+
+/// The builtins of [`BUILTINS`] and, with `std`, those registered at runtime.
+pub fn all_builtins() -> Vec<&'static BuiltinInfo> {
+    #[allow(unused_mut)]
+    let mut builtins: Vec<&'static BuiltinInfo> = BUILTINS.iter().collect();
+    #[cfg(feature = "std")]
+    builtins.extend(crate::eval::plugin::plugin_builtins());
+    builtins
+}
+
+/// Markdown reference of the builtins, generated from [`all_builtins`].
+pub fn builtins_reference() -> String {
+    let mut output = String::from("# Builtin functions\n");
+    for builtin in all_builtins() {
+        output.push_str(&format!("\n## `{}`\n\n{}\n", builtin.usage, builtin.help));
+    }
+    output
+}
+
+impl fmt::Display for BuiltinFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.info().name)
+    }
+}
+
 impl BuiltinFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match BUILTINS.iter().find(|builtin| builtin.name == name) {
+            Some(builtin) => Some(builtin.function.clone()),
+            #[cfg(feature = "std")]
+            None => crate::eval::plugin::find_plugin(name),
+            #[cfg(not(feature = "std"))]
+            None => None,
+        }
+    }
+
+    pub fn info(&self) -> &'static BuiltinInfo {
+        #[cfg(feature = "std")]
+        if let BuiltinFunction::Plugin(index) = self {
+            return crate::eval::plugin::plugin_info(*index);
+        }
+        BUILTINS
+            .iter()
+            .find(|builtin| builtin.function == *self)
+            .expect("every builtin is registered")
+    }
+
+    /// What the builtin needs to be allowed by the [`Policy`](crate::eval::Policy).
+    pub fn capability(&self) -> Option<Capability> {
+        self.info().capability
+    }
+
+    /// Why calling the builtin with `args` is deprecated, if it is.
+    pub(crate) fn deprecation(&self, args: &[Object]) -> Option<&'static str> {
+        match (self, args) {
+            (BuiltinFunction::Push, [Object::Hash(_), _]) => Some(
+                "`push` on hashes is deprecated, use `insert(hash, key, value)` or `merge(hash, other)`",
+            ),
+            _ => None,
+        }
+    }
+
     pub fn call(&self, args: Vec<Object>) -> Result<Object> {
-        match &self {
-            BuiltinFunction::Len => self.call_len(args),
-            BuiltinFunction::First => self.call_first(args),
-            BuiltinFunction::Last => self.call_last(args),
-            BuiltinFunction::Rest => self.call_rest(args),
-            BuiltinFunction::Push => self.call_push(args),
+        (self.info().handler)(self, args)
+    }
+
+    fn call_range(&self, args: Vec<Object>) -> Result<Object> {
+        let bounds = args
+            .iter()
+            .map(|arg| match arg {
+                Object::Int(value) => Ok(*value),
+                o => fail!(
+                    ErrorCode::InvalidArgument,
+                    "Invalid argument for builtin function `range`, expected int, found {o}"
+                ),
+            })
+            .collect::<Result<Vec<i64>>>()?;
+        let (start, end, step) = match bounds[..] {
+            [end] => (0, end, 1),
+            [start, end] => (start, end, 1),
+            [start, end, step] => (start, end, step),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `range` expects 1 to 3 args, found {}.",
+                args.len()
+            ),
+        };
+        Ok(Object::Iterator(Iter::range(start, end, step)?))
+    }
+
+    /// Single argument of `iter`, `next` and `collect`, as an iterator.
+    fn iterator_arg(&self, args: Vec<Object>) -> Result<Iter> {
+        let [arg] = match <[Object; 1]>::try_from(args) {
+            Ok(args) => args,
+            Err(args) => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `{self}` expects 1 arg, found {}.",
+                args.len()
+            ),
+        };
+        Iter::from_object(arg)
+    }
+
+    fn call_iter(&self, args: Vec<Object>) -> Result<Object> {
+        Ok(Object::Iterator(self.iterator_arg(args)?))
+    }
+
+    fn call_next(&self, args: Vec<Object>) -> Result<Object> {
+        Ok(self.iterator_arg(args)?.next()?.unwrap_or(Object::Null))
+    }
+
+    fn call_collect(&self, args: Vec<Object>) -> Result<Object> {
+        let iter = self.iterator_arg(args)?;
+        let mut items = vec![];
+        while let Some(item) = iter.next()? {
+            items.push(item);
         }
+        Ok(Object::Array(items.into()))
     }
 
-    fn call_len(&self, args: Vec<Object>) -> Result<Object> {
-        if args.len() != 1 {
-            bail!(
-                "Builtin function `len` expects 1 arg, found {}.",
+    fn call_adapter(&self, args: Vec<Object>) -> Result<Object> {
+        let [iterable, func] = match <[Object; 2]>::try_from(args) {
+            Ok(args) => args,
+            Err(args) => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `{self}` expects 2 args, found {}.",
                 args.len()
+            ),
+        };
+        if !matches!(
+            func,
+            Object::Function { .. }
+                | Object::Builtin(_)
+                | Object::Closure(_)
+                | Object::StructType(_)
+        ) {
+            fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `{self}`, expected function, found {func}"
             );
         }
-        Ok(match args.first() {
-            Some(Object::String(string)) => Object::Int(string.len().try_into().unwrap()),
-            Some(Object::Array(content)) => Object::Int(content.len().try_into().unwrap()),
-            Some(Object::Hash(hashmap)) => Object::Int(hashmap.len().try_into().unwrap()),
-            Some(o) => bail!(
-                "Invalid argument for builtin function `len`, expected string or array, found {o}"
+        let iter = Iter::from_object(iterable)?;
+        Ok(Object::Iterator(match self {
+            BuiltinFunction::Map => iter.map(func),
+            _ => iter.filter(func),
+        }))
+    }
+
+    /// `lines` splits on line endings, `\n` or `\r\n`, `words` on runs of whitespace.
+    fn call_split(&self, args: Vec<Object>) -> Result<Object> {
+        let string = match &args[..] {
+            [Object::String(string)] => string,
+            [o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `{self}`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `{self}` expects 1 arg, found {}.",
+                args.len()
+            ),
+        };
+        let parts: Vec<&str> = match self {
+            BuiltinFunction::Lines => string.lines().collect(),
+            _ => string.split_whitespace().collect(),
+        };
+        Ok(Object::Array(
+            parts
+                .into_iter()
+                .map(|s| Object::String(s.to_owned()))
+                .collect::<Vec<_>>()
+                .into(),
+        ))
+    }
+
+    fn call_fields(&self, args: Vec<Object>) -> Result<Object> {
+        let (string, separator) = match &args[..] {
+            [Object::String(string), Object::String(separator)] if !separator.is_empty() => {
+                (string, separator)
+            }
+            [Object::String(_), o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `fields`, expected non-empty string, found {o}"
+            ),
+            [o, _] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `fields`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `fields` expects 2 args, found {}.",
+                args.len()
+            ),
+        };
+        Ok(Object::Array(
+            string
+                .split(separator.as_str())
+                .map(|s| Object::String(s.to_owned()))
+                .collect::<Vec<_>>()
+                .into(),
+        ))
+    }
+
+    fn call_bytes(&self, args: Vec<Object>) -> Result<Object> {
+        let (value, encoding) = match &args[..] {
+            [value] => (value, "utf8"),
+            [value, Object::String(encoding)] => (value, encoding.as_str()),
+            [_, o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `bytes`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `bytes` expects 1 or 2 args, found {}.",
+                args.len()
+            ),
+        };
+        let bytes = match (value, encoding) {
+            (Object::String(string), "utf8") => string.as_bytes().to_vec(),
+            (Object::String(string), "latin1") => string
+                .chars()
+                .map(|c| match u8::try_from(c) {
+                    Ok(byte) => Ok(byte),
+                    Err(_) => fail!(ErrorCode::InvalidArgument, "Cannot encode `{c}` as latin1"),
+                })
+                .collect::<Result<_>>()?,
+            (Object::String(_), encoding) => fail!(
+                ErrorCode::InvalidArgument,
+                "Unknown encoding `{encoding}`, expected utf8 or latin1"
+            ),
+            (Object::Bytes(bytes), _) => bytes.clone(),
+            (Object::Array(items), _) => items
+                .iter()
+                .map(|item| match item {
+                    Object::Int(value) if (0..=255).contains(value) => Ok(*value as u8),
+                    o => fail!(
+                        ErrorCode::InvalidArgument,
+                        "Invalid byte {o} for builtin function `bytes`, expected int from 0 to 255"
+                    ),
+                })
+                .collect::<Result<_>>()?,
+            (o, _) => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `bytes`, expected string, bytes or array, found {o}"
+            ),
+        };
+        Ok(Object::Bytes(bytes))
+    }
+
+    fn call_decode(&self, args: Vec<Object>) -> Result<Object> {
+        let (bytes, encoding) = match &args[..] {
+            [Object::Bytes(bytes)] => (bytes, "utf8"),
+            [Object::Bytes(bytes), Object::String(encoding)] => (bytes, encoding.as_str()),
+            [Object::Bytes(_), o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `decode`, expected string, found {o}"
+            ),
+            [o] | [o, _] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `decode`, expected bytes, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `decode` expects 1 or 2 args, found {}.",
+                args.len()
+            ),
+        };
+        Ok(Object::String(match encoding {
+            "utf8" => match String::from_utf8(bytes.clone()) {
+                Ok(string) => string,
+                Err(_) => fail!(
+                    ErrorCode::InvalidArgument,
+                    "Cannot decode {} as utf8",
+                    Object::Bytes(bytes.clone())
+                ),
+            },
+            "latin1" => bytes.iter().map(|&byte| char::from(byte)).collect(),
+            encoding => fail!(
+                ErrorCode::InvalidArgument,
+                "Unknown encoding `{encoding}`, expected utf8 or latin1"
+            ),
+        }))
+    }
+
+    /// Encodes or hashes bytes, strings being encoded in UTF-8 first.
+    fn call_encode(&self, args: Vec<Object>) -> Result<Object> {
+        let data = match &args[..] {
+            [Object::String(string)] => string.as_bytes(),
+            [Object::Bytes(bytes)] => bytes,
+            [o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `{self}`, expected bytes or string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `{self}` expects 1 arg, found {}.",
+                args.len()
+            ),
+        };
+        Ok(match self {
+            BuiltinFunction::Hex => Object::String(encoding::hex(data)),
+            BuiltinFunction::Base64 => Object::String(encoding::base64(data)),
+            #[cfg(feature = "crypto")]
+            BuiltinFunction::HashSha256 => Object::Bytes(encoding::sha256(data)),
+            _ => unreachable!(),
+        })
+    }
+
+    fn call_from_encoded(&self, args: Vec<Object>) -> Result<Object> {
+        let string = match &args[..] {
+            [Object::String(string)] => string,
+            [o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `{self}`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `{self}` expects 1 arg, found {}.",
+                args.len()
+            ),
+        };
+        Ok(Object::Bytes(match self {
+            BuiltinFunction::FromHex => encoding::from_hex(string)?,
+            BuiltinFunction::FromBase64 => encoding::from_base64(string)?,
+            _ => unreachable!(),
+        }))
+    }
+
+    #[cfg(feature = "crypto")]
+    fn call_hmac_sha256(&self, args: Vec<Object>) -> Result<Object> {
+        let data = |position: &str, value: &Object| -> Result<Vec<u8>> {
+            match value {
+                Object::String(string) => Ok(string.as_bytes().to_vec()),
+                Object::Bytes(bytes) => Ok(bytes.clone()),
+                o => fail!(
+                    ErrorCode::InvalidArgument,
+                    "Invalid {position} argument for builtin function `hmac_sha256`, expected bytes or string, found {o}"
+                ),
+            }
+        };
+        match &args[..] {
+            [key, message] => Ok(Object::Bytes(encoding::hmac_sha256(
+                &data("first", key)?,
+                &data("second", message)?,
+            ))),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `hmac_sha256` expects 2 args, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    fn call_csv_parse(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [Object::String(input)] => csv::parse(input),
+            [o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `csv_parse`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `csv_parse` expects 1 arg, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn call_csv_read(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [Object::String(path)] => {
+                policy::check_caller_fs_read(path)?;
+                csv::read(path)
+            }
+            [o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `csv_read`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `csv_read` expects 1 arg, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn call_csv_write(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [Object::String(path), Object::Array(rows)] => csv::write(path, rows, None),
+            [
+                Object::String(path),
+                Object::Array(rows),
+                Object::Array(columns),
+            ] => csv::write(path, rows, Some(columns)),
+            [Object::String(_), Object::Array(_), o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid third argument for builtin function `csv_write`, expected array, found {o}"
+            ),
+            [Object::String(_), o, ..] if args.len() <= 3 => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `csv_write`, expected array, found {o}"
+            ),
+            [o, _, ..] if args.len() <= 3 => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `csv_write`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `csv_write` expects 2 or 3 args, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    /// `where`, `select`, `group_by` and `order_by`, taking an array of rows first.
+    fn call_query(&self, args: Vec<Object>) -> Result<Object> {
+        let max = if *self == BuiltinFunction::OrderBy {
+            3
+        } else {
+            2
+        };
+        if args.len() < 2 || args.len() > max {
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `{self}` expects {} args, found {}.",
+                self.info().arity,
+                args.len()
+            );
+        }
+        let mut args = args.into_iter();
+        let rows = match args.next() {
+            Some(Object::Array(rows)) => rows,
+            o => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `{self}`, expected array, found {}",
+                o.unwrap_or(Object::Null)
+            ),
+        };
+        let by = args.next().unwrap_or(Object::Null);
+        match self {
+            BuiltinFunction::Where => match by {
+                Object::Function { .. } | Object::Builtin(_) | Object::Closure(_) => {
+                    query::where_(&rows, by)
+                }
+                o => fail!(
+                    ErrorCode::InvalidArgument,
+                    "Invalid second argument for builtin function `where`, expected function, found {o}"
+                ),
+            },
+            BuiltinFunction::Select => query::select(&rows, by),
+            BuiltinFunction::GroupBy => query::group_by(&rows, by),
+            _ => match args.next() {
+                None => query::order_by(&rows, by, false),
+                Some(Object::Bool(descending)) => query::order_by(&rows, by, descending),
+                Some(o) => fail!(
+                    ErrorCode::InvalidArgument,
+                    "Invalid third argument for builtin function `order_by`, expected bool, found {o}"
+                ),
+            },
+        }
+    }
+
+    /// Prints to the sink set with [`set_output`](crate::eval::set_output), if any.
+    #[cfg(feature = "std")]
+    fn call_puts(&self, args: Vec<Object>) -> Result<Object> {
+        for arg in args {
+            output::print(&format!("{arg}\n"))?;
+        }
+        Ok(Object::Null)
+    }
+
+    /// Pretty-prints a value, optionally with the maximum depth and number of items.
+    #[cfg(feature = "std")]
+    fn call_table(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [Object::Array(rows)] => {
+                output::print(&table::format(rows)?)?;
+                Ok(Object::Null)
+            }
+            [o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `table`, expected array, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `table` expects 1 arg, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    fn call_dump(&self, args: Vec<Object>) -> Result<Object> {
+        let mut options = PrettyOptions::default();
+        match &args[..] {
+            [_] => {}
+            [_, Object::Int(max_depth), Object::Int(max_items)]
+                if *max_depth >= 0 && *max_items >= 0 =>
+            {
+                options.max_depth = *max_depth as usize;
+                options.max_items = *max_items as usize;
+            }
+            [_, max_depth, max_items] => {
+                let o = match max_depth {
+                    Object::Int(n) if *n >= 0 => max_items,
+                    o => o,
+                };
+                fail!(
+                    ErrorCode::InvalidArgument,
+                    "Invalid argument for builtin function `dump`, expected non-negative int, found {o}"
+                )
+            }
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `dump` expects 1 or 3 args, found {}.",
+                args.len()
+            ),
+        }
+        Ok(Object::String(args[0].pretty(&options)))
+    }
+
+    /// Binds the first arguments of a function, returning a function taking the rest.
+    /// The bound values live in its environment under names scripts can't write.
+    fn call_copy(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [obj] => obj.shallow_copy(),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `copy` expects 1 arg, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    fn call_deepcopy(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [obj] => obj.deep_copy(),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `deepcopy` expects 1 arg, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    fn call_partial(&self, args: Vec<Object>) -> Result<Object> {
+        let (parameters, environment, doc) = match args.first() {
+            Some(Object::Function {
+                parameters,
+                environment,
+                doc,
+                ..
+            }) => (parameters.clone(), environment.clone(), doc.clone()),
+            Some(Object::Closure(closure)) => {
+                let function = closure.function();
+                let doc = function.doc().map(str::to_owned);
+                (function.parameters.clone(), Environment::default(), doc)
+            }
+            Some(o) => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `partial`, expected function, found {o}"
+            ),
+            None => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `partial` expects at least 1 arg, found 0."
             ),
-            None => unreachable!(),
+        };
+        let bound = &args[1..];
+        if bound.len() > parameters.len() {
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Function `fn({})` expects {} argument{}, got {}.",
+                parameters.join(", "),
+                parameters.len(),
+                if parameters.len() == 1 { "" } else { "s" },
+                bound.len()
+            );
+        }
+
+        let env = Environment::init_with_outer(Rc::new(environment));
+        let mut call_args = vec![];
+        for (i, value) in bound.iter().enumerate() {
+            env.set(format!("${i}"), value.clone());
+            call_args.push(Expression::Ident(format!("${i}")));
+        }
+        let rest = parameters[bound.len()..].to_vec();
+        call_args.extend(rest.iter().cloned().map(Expression::Ident));
+        env.set("$f", args[0].clone());
+        Ok(Object::Function {
+            parameters: rest,
+            body: vec![Statement::Expr(call("$f", call_args))],
+            environment: env,
+            doc,
+        })
+    }
+
+    /// `compose(f, g)` is `fn(x) { f(g(x)) }`, for builtins as well as functions.
+    fn call_compose(&self, args: Vec<Object>) -> Result<Object> {
+        let [f, g] = &args[..] else {
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `compose` expects 2 args, found {}.",
+                args.len()
+            );
+        };
+        for func in [f, g] {
+            if !matches!(
+                func,
+                Object::Function { .. }
+                    | Object::Builtin(_)
+                    | Object::Closure(_)
+                    | Object::StructType(_)
+            ) {
+                fail!(
+                    ErrorCode::InvalidArgument,
+                    "Invalid argument for builtin function `compose`, expected function, found {func}"
+                );
+            }
+        }
+        let env = match [f, g] {
+            [Object::Function { environment, .. }, _]
+            | [_, Object::Function { environment, .. }] => {
+                Environment::init_with_outer(Rc::new(environment.clone()))
+            }
+            _ => Environment::default(),
+        };
+        env.set("$f", f.clone());
+        env.set("$g", g.clone());
+        let inner = call("$g", vec![Expression::Ident("x".to_owned())]);
+        Ok(Object::Function {
+            parameters: vec!["x".to_owned()],
+            body: vec![Statement::Expr(call("$f", vec![inner]))],
+            environment: env,
+            doc: None,
         })
     }
 
+    /// Usage and description of a builtin, given by name or value, or signature and
+    /// docstring of a function.
+    fn call_help(&self, args: Vec<Object>) -> Result<Object> {
+        let builtin = match &args[..] {
+            [Object::String(name)] => match BuiltinFunction::from_name(name) {
+                Some(builtin) => builtin.info(),
+                None => fail!(
+                    ErrorCode::InvalidArgument,
+                    "Invalid argument for builtin function `help`, `{name}` is not a builtin"
+                ),
+            },
+            [Object::Builtin(builtin)] => builtin.info(),
+            [
+                Object::Function {
+                    parameters, doc, ..
+                },
+            ] => return Ok(function_help(parameters, doc.as_deref())),
+            [Object::Closure(closure)] => {
+                let function = closure.function();
+                return Ok(function_help(&function.parameters, function.doc()));
+            }
+            [o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `help`, expected string or function, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `help` expects 1 arg, found {}.",
+                args.len()
+            ),
+        };
+        Ok(Object::String(format!(
+            "{}\n{}",
+            builtin.usage, builtin.help
+        )))
+    }
+
+    fn call_error(&self, args: Vec<Object>) -> Result<Object> {
+        let (message, data) = match &args[..] {
+            [Object::String(message)] => (message, &Object::Null),
+            [Object::String(message), data] => (message, data),
+            [o] | [o, _] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `error`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `error` expects 1 or 2 args, found {}.",
+                args.len()
+            ),
+        };
+        Ok(Object::Error {
+            message: message.clone(),
+            data: Box::new(data.clone()),
+        })
+    }
+
+    fn call_is_error(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [value] => Ok(Object::Bool(matches!(value, Object::Error { .. }))),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `is_error` expects 1 arg, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn call_args(&self, args: Vec<Object>) -> Result<Object> {
+        if !args.is_empty() {
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `args` expects 0 args, found {}.",
+                args.len()
+            );
+        }
+        Ok(Object::Array(
+            script_args()
+                .into_iter()
+                .map(Object::String)
+                .collect::<Vec<_>>()
+                .into(),
+        ))
+    }
+
+    #[cfg(feature = "std")]
+    fn call_parse_args(&self, args: Vec<Object>) -> Result<Object> {
+        let (spec, argv) = match &args[..] {
+            [Object::Hash(spec)] => (spec, script_args()),
+            [Object::Hash(spec), Object::Array(argv)] => {
+                let argv = argv
+                    .iter()
+                    .map(|arg| match arg {
+                        Object::String(arg) => Ok(arg.clone()),
+                        o => fail!(
+                            ErrorCode::InvalidArgument,
+                            "Invalid second argument for builtin function `parse_args`, expected array of strings, found {o}"
+                        ),
+                    })
+                    .collect::<Result<Vec<String>>>()?;
+                (spec, argv)
+            }
+            [Object::Hash(_), o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `parse_args`, expected array of strings, found {o}"
+            ),
+            [o, ..] if args.len() <= 2 => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `parse_args`, expected hash, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `parse_args` expects 1 or 2 args, found {}.",
+                args.len()
+            ),
+        };
+        parse_args(spec, argv)
+    }
+
+    #[cfg(feature = "std")]
+    fn call_send_host(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [value] => host::send_host(value),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `send_host` expects 1 arg, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn call_recv_host(&self, args: Vec<Object>) -> Result<Object> {
+        if !args.is_empty() {
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `recv_host` expects 0 args, found {}.",
+                args.len()
+            );
+        }
+        host::recv_host()
+    }
+
+    #[cfg(feature = "sync")]
+    fn call_spawn(&self, args: Vec<Object>) -> Result<Object> {
+        match args.split_first() {
+            Some((func, args)) => workers::spawn(func, args),
+            None => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `spawn` expects at least 1 arg, found 0."
+            ),
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    fn call_send(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [Object::Int(worker), value] => workers::send(*worker, value),
+            [o, _] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `send`, expected int, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `send` expects 2 args, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    fn call_recv(&self, args: Vec<Object>) -> Result<Object> {
+        if !args.is_empty() {
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `recv` expects 0 args, found {}.",
+                args.len()
+            );
+        }
+        workers::recv()
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn call_sql_open(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [Object::String(path)] => {
+                if path != ":memory:" {
+                    policy::check_caller_fs_read(path)?;
+                }
+                sqlite::open(path)
+            }
+            [o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `sql_open`, expected string, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `sql_open` expects 1 arg, found {}.",
+                args.len()
+            ),
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn call_sql_query(&self, args: Vec<Object>) -> Result<Object> {
+        sqlite::query("sql_query", &args)
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn call_sql_exec(&self, args: Vec<Object>) -> Result<Object> {
+        sqlite::exec("sql_exec", &args)
+    }
+
+    /// Reads the whole standard input.
+    #[cfg(feature = "stdin")]
+    fn call_read_stdin(&self, args: Vec<Object>) -> Result<Object> {
+        use std::io::Read;
+
+        if !args.is_empty() {
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `read_stdin` expects 0 args, found {}.",
+                args.len()
+            );
+        }
+        let mut input = String::new();
+        if let Err(err) = std::io::stdin().read_to_string(&mut input) {
+            fail!(
+                ErrorCode::InvalidInput,
+                "Cannot read the standard input: {err}"
+            );
+        }
+        Ok(Object::String(input))
+    }
+
+    fn call_len(&self, args: Vec<Object>) -> Result<Object> {
+        let [arg] = match <[Object; 1]>::try_from(args) {
+            Ok(args) => args,
+            Err(args) => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `len` expects 1 arg, found {}.",
+                args.len()
+            ),
+        };
+        let len = match arg {
+            Object::String(string) => string.len(),
+            Object::Bytes(bytes) => bytes.len(),
+            Object::Array(content) => content.len(),
+            Object::Tuple(values) => values.len(),
+            Object::Hash(hashmap) => hashmap.len(),
+            o => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid argument for builtin function `len`, expected string or array, found {o}"
+            ),
+        };
+        match i64::try_from(len) {
+            Ok(len) => Ok(Object::Int(len)),
+            Err(_) => fail!(
+                ErrorCode::LimitExceeded,
+                "The length {len} found by builtin function `len` doesn't fit in an int"
+            ),
+        }
+    }
+
     fn call_first(&self, args: Vec<Object>) -> Result<Object> {
         if args.len() != 1 {
-            bail!(
+            fail!(
+                ErrorCode::ArgumentCount,
                 "Builtin function `first` expects 1 arg, found {}.",
                 args.len()
             );
         }
-        let arg = args.first().unwrap();
-        Ok(match arg {
-            Object::String(string) if string.is_empty() => Object::Null,
-            Object::String(string) => Object::String(string.chars().next().unwrap().into()),
-            Object::Array(content) if content.is_empty() => Object::Null,
-            Object::Array(content) => content.first().unwrap().to_owned(),
-            o => bail!(
+        Ok(match &args[0] {
+            Object::String(string) => string
+                .chars()
+                .next()
+                .map_or(Object::Null, |ch| Object::String(ch.into())),
+            Object::Array(content) => content.first().cloned().unwrap_or(Object::Null),
+            o => fail!(
+                ErrorCode::InvalidArgument,
                 "Invalid argument for builtin function `first`, expected string or array, found {o}"
             ),
         })
@@ -73,18 +1569,20 @@ impl BuiltinFunction {
 
     fn call_last(&self, args: Vec<Object>) -> Result<Object> {
         if args.len() != 1 {
-            bail!(
+            fail!(
+                ErrorCode::ArgumentCount,
                 "Builtin function `last` expects 1 arg, found {}.",
                 args.len()
             );
         }
-        let arg = args.first().unwrap();
-        Ok(match arg {
-            Object::String(string) if string.is_empty() => Object::Null,
-            Object::String(string) => Object::String(string.chars().last().unwrap().into()),
-            Object::Array(content) if content.is_empty() => Object::Null,
-            Object::Array(content) => content.last().unwrap().to_owned(),
-            o => bail!(
+        Ok(match &args[0] {
+            Object::String(string) => string
+                .chars()
+                .last()
+                .map_or(Object::Null, |ch| Object::String(ch.into())),
+            Object::Array(content) => content.last().cloned().unwrap_or(Object::Null),
+            o => fail!(
+                ErrorCode::InvalidArgument,
                 "Invalid argument for builtin function `last`, expected string or array, found {o}"
             ),
         })
@@ -92,80 +1590,197 @@ impl BuiltinFunction {
 
     fn call_rest(&self, args: Vec<Object>) -> Result<Object> {
         if args.len() != 1 {
-            bail!(
+            fail!(
+                ErrorCode::ArgumentCount,
                 "Builtin function `rest` expects 1 arg, found {}.",
                 args.len()
             );
         }
-        let arg = args.first().unwrap();
 
-        Ok(match arg {
+        Ok(match &args[0] {
             Object::String(string) if string.is_empty() => Object::Null,
-            Object::String(string) if string.len() == 1 => Object::String("".into()),
-            Object::String(string) => Object::String(string[1..].into()),
+            Object::String(string) => Object::String(string.chars().skip(1).collect()),
             Object::Array(content) if content.is_empty() => Object::Null,
-            Object::Array(content) if content.len() == 1 => Object::Array(vec![]),
-            Object::Array(content) => Object::Array(content[1..].into()),
-            o => bail!(
+            Object::Array(content) if content.len() == 1 => Object::Array(Rc::default()),
+            Object::Array(content) => Object::Array(content[1..].to_vec().into()),
+            o => fail!(
+                ErrorCode::InvalidArgument,
                 "Invalid argument for builtin function `rest`, expected string or array, found {o}"
             ),
         })
     }
 
-    fn call_push(&self, args: Vec<Object>) -> Result<Object> {
-        if args.len() < 2 {
-            bail!(
-                "Builtin function `push` expects 2 args, found {}.",
+    fn call_insert(&self, args: Vec<Object>) -> Result<Object> {
+        let [hash, key, value] = match <[Object; 3]>::try_from(args) {
+            Ok(args) => args,
+            Err(args) => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `insert` expects 3 args, found {}.",
                 args.len()
+            ),
+        };
+        let Object::Hash(mut map) = hash else {
+            fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `insert`, expected hash, found {hash}"
             );
+        };
+        let key = match key {
+            Object::Bool(key) => HashMapKey::Bool(key),
+            Object::Int(key) => HashMapKey::Int(key),
+            Object::String(key) => HashMapKey::String(key),
+            _ => fail!(
+                ErrorCode::InvalidHashKey,
+                "Invalid object type for an hash key, must be int, str or bool!"
+            ),
+        };
+        map.insert(key, value);
+        Ok(Object::Hash(map))
+    }
+
+    fn call_merge(&self, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [Object::Hash(map), Object::Hash(other)] => {
+                let mut map = map.clone();
+                for (key, value) in other {
+                    map.insert(key.clone(), value.clone());
+                }
+                Ok(Object::Hash(map))
+            }
+            [Object::Hash(_), o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `merge`, expected hash, found {o}"
+            ),
+            [o, _] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `merge`, expected hash, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `merge` expects 2 args, found {}.",
+                args.len()
+            ),
         }
-        let (arg1, arg2) = (&args[0], &args[1]);
+    }
 
-        Ok(match arg1 {
-            Object::String(string1) => match arg2 {
-                Object::String(string2) => Object::String(format!("{string1}{string2}")),
-                _ => bail!(
-                    "Invalid second argument for builtin function `push`, expected string or array, found {arg2}"
-                ),
-            },
-            Object::Array(content) => {
-                let mut new_content = content.clone();
-                new_content.push(arg2.to_owned());
-                Object::Array(new_content)
-            }
-            Object::Hash(content1) => {
-                let mut new_content = content1.clone();
-                match arg2 {
+    fn call_slice(&self, args: Vec<Object>) -> Result<Object> {
+        let (collection, start, end) = match &args[..] {
+            [collection, Object::Int(start)] => (collection, *start, None),
+            [collection, Object::Int(start), Object::Int(end)] => (collection, *start, Some(*end)),
+            [_, Object::Int(_), o] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid third argument for builtin function `slice`, expected int, found {o}"
+            ),
+            [_, o] | [_, o, _] => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `slice`, expected int, found {o}"
+            ),
+            _ => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `slice` expects 2 or 3 args, found {}.",
+                args.len()
+            ),
+        };
+        let range = |len: usize| {
+            let clamp = |index: i64| index.clamp(0, len as i64) as usize;
+            let start = clamp(start);
+            start..clamp(end.unwrap_or(len as i64)).max(start)
+        };
+        Ok(match collection {
+            Object::Array(items) => Object::Array(items[range(items.len())].to_vec().into()),
+            Object::Bytes(bytes) => Object::Bytes(bytes[range(bytes.len())].to_vec()),
+            o => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid first argument for builtin function `slice`, expected array or bytes, found {o}"
+            ),
+        })
+    }
+
+    /// Appends the items to the array in place when the caller holds its only copy.
+    fn call_push(&self, args: Vec<Object>) -> Result<Object> {
+        let count = args.len();
+        let mut args = args.into_iter();
+        let (Some(collection), Some(item)) = (args.next(), args.next()) else {
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `push` expects at least 2 args, found {count}."
+            );
+        };
+
+        Ok(match collection {
+            Object::String(mut string) => {
+                for item in core::iter::once(item).chain(args) {
+                    match item {
+                        Object::String(item) => string.push_str(&item),
+                        o => fail!(
+                            ErrorCode::InvalidArgument,
+                            "Invalid item {o} for builtin function `push` on a string, expected string"
+                        ),
+                    }
+                }
+                Object::String(string)
+            }
+            Object::Array(mut content) => {
+                Rc::make_mut(&mut content).extend(core::iter::once(item).chain(args));
+                Object::Array(content)
+            }
+            Object::Hash(_) if count > 2 => fail!(
+                ErrorCode::ArgumentCount,
+                "Builtin function `push` expects 2 args with a hash, found {count}."
+            ),
+            Object::Hash(mut new_content) => {
+                match item {
                     Object::Array(content2) if content2.len() == 2 => {
                         new_content.insert(
                             match content2[0].clone() {
                                 Object::Bool(c) => HashMapKey::Bool(c),
                                 Object::Int(c) => HashMapKey::Int(c),
                                 Object::String(c) => HashMapKey::String(c),
-                                _ => bail!(
+                                _ => fail!(
+                                    ErrorCode::InvalidHashKey,
                                     "Invalid object type for an hash key, must be int, str or bool!"
                                 ),
                             },
                             content2[1].clone(),
                         );
                     }
-                    Object::Array(_) => bail!(
+                    Object::Array(_) => fail!(
+                        ErrorCode::InvalidArgument,
                         "Invalid second argument for builtin function `push`, expected array with 2 elements"
                     ),
                     Object::Hash(content2) => {
-                        for (k, v) in content2 {
-                            new_content.insert(k.clone(), v.clone());
-                        }
+                        new_content.extend(content2);
                     }
-                    _ => bail!(
+                    _ => fail!(
+                        ErrorCode::InvalidArgument,
                         "Invalid second argument for builtin function `push`, expected array with 2 elements or another hashmap"
                     ),
                 }
                 Object::Hash(new_content)
             }
-            o => bail!(
+            o => fail!(
+                ErrorCode::InvalidArgument,
                 "Invalid first argument for builtin function `push`, expected string or array, found {o}"
             ),
         })
     }
 }
+
+/// Signature and docstring of a function, for `help`.
+fn function_help(parameters: &[String], doc: Option<&str>) -> Object {
+    let signature = format!("fn({})", parameters.join(", "));
+    Object::String(match doc {
+        Some(doc) => format!("{signature}\n{doc}"),
+        None => signature,
+    })
+}
+
+/// A call to the function stored in the variable `name`.
+fn call(name: &str, args: Vec<Expression>) -> Expression {
+    Expression::Call {
+        func: Box::new(Expression::Ident(name.to_owned())),
+        args,
+        kwargs: vec![],
+        span: CallSpan::default(),
+    }
+}
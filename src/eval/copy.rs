@@ -0,0 +1,86 @@
+//! What `copy` and `deepcopy` share and what they copy.
+//!
+//! Arrays, hashes, tuples, struct instances and errors are values: a container is never
+//! changed in place, so its copies can't observe each other. Copying one makes new storage
+//! all the same, holding the same items for `copy` and copies of them for `deepcopy`.
+//!
+//! Iterators are shared by their copies, which advance together. Copying one makes an
+//! iterator over the items left, advancing on its own: see [`Iter::tee`]. Its items are not
+//! copied, even by `deepcopy`.
+//!
+//! Functions, builtins, struct types and handles are returned as they are: a function
+//! keeps sharing the variables it captured, a type its identity and a handle its resource.
+//!
+//! `deepcopy` copies each iterator or array reached several times once, so the copy shares
+//! them like the original does and a value holding itself would still be copied once.
+
+use crate::error::Result;
+use crate::eval::Object;
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+
+impl Object {
+    /// The value of the `copy` builtin.
+    pub fn shallow_copy(&self) -> Result<Object> {
+        Ok(match self {
+            Object::Array(items) => Object::Array(Rc::new(items.to_vec())),
+            Object::Iterator(iter) => Object::Iterator(iter.tee()?),
+            obj => obj.clone(),
+        })
+    }
+
+    /// The value of the `deepcopy` builtin.
+    pub fn deep_copy(&self) -> Result<Object> {
+        self.deep_copy_with(&mut BTreeMap::new())
+    }
+
+    /// `copies` maps the address of the arrays and iterators already reached to their copy.
+    fn deep_copy_with(&self, copies: &mut BTreeMap<usize, Object>) -> Result<Object> {
+        let key = match self {
+            Object::Array(items) => Rc::as_ptr(items) as usize,
+            Object::Iterator(iter) => iter.id(),
+            _ => 0,
+        };
+        if let Some(copy) = copies.get(&key) {
+            return Ok(copy.clone());
+        }
+        let copy = match self {
+            Object::Array(items) => Object::Array(Rc::new(
+                items
+                    .iter()
+                    .map(|item| item.deep_copy_with(copies))
+                    .collect::<Result<_>>()?,
+            )),
+            Object::Tuple(values) => Object::Tuple(
+                values
+                    .iter()
+                    .map(|value| value.deep_copy_with(copies))
+                    .collect::<Result<_>>()?,
+            ),
+            Object::Struct { ty, values } => Object::Struct {
+                ty: Rc::clone(ty),
+                values: values
+                    .iter()
+                    .map(|value| value.deep_copy_with(copies))
+                    .collect::<Result<_>>()?,
+            },
+            Object::Hash(map) => {
+                let mut map = map.clone();
+                for value in map.values_mut() {
+                    *value = value.deep_copy_with(copies)?;
+                }
+                Object::Hash(map)
+            }
+            Object::Error { message, data } => Object::Error {
+                message: message.clone(),
+                data: Box::new(data.deep_copy_with(copies)?),
+            },
+            obj => obj.shallow_copy()?,
+        };
+        if key != 0 {
+            copies.insert(key, copy.clone());
+        }
+        Ok(copy)
+    }
+}
@@ -0,0 +1,250 @@
+//! CSV following RFC 4180: comma-separated fields, quoted with `"` when they contain
+//! commas, quotes or line breaks, a quote being escaped as `""`.
+
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{HashMapKey, HashObject, Object};
+use crate::prelude::*;
+
+/// Parses `input`, its first record being the header: returns a hash per following
+/// record, from column names to fields. Fields holding an int are parsed as one.
+pub(crate) fn parse(input: &str) -> Result<Object> {
+    let mut records = records(input)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(Object::Array(vec![].into()));
+    };
+    let mut rows = vec![];
+    for (i, record) in records.enumerate() {
+        if record.len() != header.len() {
+            fail!(
+                ErrorCode::InvalidInput,
+                "Invalid CSV, record {} has {} fields but the header has {}",
+                i + 2,
+                record.len(),
+                header.len()
+            );
+        }
+        let mut row = HashObject::default();
+        for (column, field) in header.iter().zip(record) {
+            let value = match field.parse::<i64>() {
+                Ok(value) if value.to_string() == field => Object::Int(value),
+                _ => Object::String(field),
+            };
+            row.insert(HashMapKey::String(column.clone()), value);
+        }
+        rows.push(Object::Hash(row));
+    }
+    Ok(Object::Array(rows.into()))
+}
+
+fn records(input: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => fail!(ErrorCode::InvalidInput, "Invalid CSV, unterminated quote"),
+                }
+            },
+            ',' => record.push(core::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(core::mem::take(&mut field));
+                records.push(core::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Formats `rows`, either hashes or arrays of fields. Hashes are written under a header,
+/// `columns` or else their sorted keys; arrays as they are, under `columns` if given.
+#[cfg(feature = "std")]
+pub(crate) fn format(rows: &[Object], columns: Option<&[Object]>) -> Result<String> {
+    let mut header = match columns {
+        Some(columns) => Some(
+            columns
+                .iter()
+                .map(|column| match column {
+                    Object::String(key) => Ok(HashMapKey::String(key.clone())),
+                    Object::Int(key) => Ok(HashMapKey::Int(*key)),
+                    Object::Bool(key) => Ok(HashMapKey::Bool(*key)),
+                    o => fail!(
+                        ErrorCode::InvalidArgument,
+                        "Invalid column {o} for builtin function `csv_write`, expected string, int or bool"
+                    ),
+                })
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        None => None,
+    };
+    if header.is_none() && rows.iter().any(|row| matches!(row, Object::Hash(_))) {
+        let mut keys: Vec<HashMapKey> = vec![];
+        for row in rows {
+            if let Object::Hash(map) = row {
+                keys.extend(map.keys().cloned());
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        header = Some(keys);
+    }
+
+    let mut output = String::new();
+    if let Some(header) = &header {
+        let fields: Vec<Object> = header.iter().cloned().map(Object::from).collect();
+        record(&mut output, fields.iter())?;
+    }
+    for row in rows {
+        match (row, &header) {
+            (Object::Array(fields), _) => record(&mut output, fields.iter())?,
+            (Object::Hash(map), Some(header)) => {
+                let fields = header
+                    .iter()
+                    .map(|key| map.get(key).unwrap_or(&Object::Null));
+                record(&mut output, fields)?
+            }
+            (o, _) => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid row {o} for builtin function `csv_write`, expected hash or array"
+            ),
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(feature = "std")]
+fn record<'a>(output: &mut String, fields: impl Iterator<Item = &'a Object>) -> Result<()> {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        let field = match field {
+            Object::Null => String::new(),
+            Object::Int(_) | Object::Float(_) | Object::Bool(_) | Object::String(_) => {
+                field.to_string()
+            }
+            o => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid field {o} for builtin function `csv_write`, expected null, int, float, bool or string"
+            ),
+        };
+        if field.contains([',', '"', '\n', '\r']) {
+            output.push('"');
+            output.push_str(&field.replace('"', "\"\""));
+            output.push('"');
+        } else {
+            output.push_str(&field);
+        }
+    }
+    output.push('\n');
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn read(path: &str) -> Result<Object> {
+    match std::fs::read_to_string(path) {
+        Ok(input) => parse(&input),
+        Err(err) => fail!(ErrorCode::InvalidInput, "Cannot read {path}: {err}"),
+    }
+}
+
+/// Writes `rows` to `path` as [`format`]ted, returning how many were written.
+#[cfg(feature = "std")]
+pub(crate) fn write(path: &str, rows: &[Object], columns: Option<&[Object]>) -> Result<Object> {
+    let output = format(rows, columns)?;
+    match std::fs::write(path, output) {
+        Ok(()) => Ok(Object::Int(rows.len() as i64)),
+        Err(err) => fail!(ErrorCode::InvalidInput, "Cannot write {path}: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, PrettyOptions, eval_with_env};
+    use alloc::rc::Rc;
+
+    #[test]
+    fn parsing() {
+        let input = "dump(csv_parse(\"name,n\r\nx,-3\ny,007\n\"))";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .pretty(&PrettyOptions::default()),
+            "[\n    { n: -3, name: x },\n    { n: 007, name: y },\n]"
+        );
+        assert_eq!(
+            records("a,\"b \"\"c\"\", d\",\n\"\"\"\"").unwrap(),
+            vec![vec!["a", "b \"c\", d", ""], vec!["\""]]
+        );
+        for input in ["a,b\n1", "a\n\"1"] {
+            assert_eq!(parse(input).unwrap_err().code, ErrorCode::InvalidInput);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sandboxed_paths() {
+        use crate::eval::Policy;
+
+        let dir = std::env::temp_dir().join(format!("waiir-csv-{}", std::process::id()));
+        let allowed = dir.join("data");
+        std::fs::create_dir_all(&allowed).unwrap();
+        let (inside, outside) = (allowed.join("a.csv"), dir.join("secret.csv"));
+        std::fs::write(&inside, "n\n1\n").unwrap();
+        std::fs::write(&outside, "n\n2\n").unwrap();
+        let (inside, outside) = (inside.to_str().unwrap(), outside.to_str().unwrap());
+        let policy = Policy::locked_down().allow_fs_read([allowed.to_str().unwrap()]);
+        let env = || Rc::new(Environment::default().with_policy(policy.clone()));
+
+        let input = format!("csv_read(\"{inside}\")");
+        assert_eq!(
+            eval_with_env(&input, env()).unwrap().to_string(),
+            "[{ n: 1 }]"
+        );
+        for input in [
+            format!("csv_read(\"{outside}\")"),
+            format!("collect(map([\"{outside}\"], csv_read))"),
+        ] {
+            let err = eval_with_env(&input, env()).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SandboxViolation, "{input}");
+            let err = crate::vm::run(&input, env()).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SandboxViolation, "{input}");
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn formatting() {
+        let rows = parse("b,a\n\"x,y\",1\n,2\n").unwrap();
+        let Object::Array(rows) = rows else {
+            unreachable!()
+        };
+        assert_eq!(format(&rows, None).unwrap(), "a,b\n1,\"x,y\"\n2,\n");
+        let columns = [Object::String("b".into())];
+        assert_eq!(format(&rows, Some(&columns)).unwrap(), "b\n\"x,y\"\n\n");
+        assert_eq!(
+            format(
+                &[Object::Array(vec![Object::Int(1), Object::Null].into())],
+                None
+            )
+            .unwrap(),
+            "1,\n"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Text encodings of bytes and, with the `crypto` feature, digests.
+
+use crate::error::{ErrorCode, Result, fail};
+use crate::prelude::*;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub(crate) fn from_hex(string: &str) -> Result<Vec<u8>> {
+    let digits = string.as_bytes();
+    if !digits.len().is_multiple_of(2) || !digits.iter().all(u8::is_ascii_hexdigit) {
+        fail!(ErrorCode::InvalidArgument, "Invalid hex string `{string}`");
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| {
+            let digit = |d: u8| (d as char).to_digit(16).unwrap_or_default() as u8;
+            digit(pair[0]) << 4 | digit(pair[1])
+        })
+        .collect())
+}
+
+/// Standard base64, padded with `=`.
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | u32::from(*byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// Decodes standard base64, the padding being optional.
+pub(crate) fn from_base64(string: &str) -> Result<Vec<u8>> {
+    let unpadded = string
+        .strip_suffix("==")
+        .or_else(|| string.strip_suffix('='));
+    let digits = unpadded.unwrap_or(string).as_bytes();
+    let values: Option<Vec<u32>> = digits
+        .iter()
+        .map(|d| BASE64.iter().position(|c| c == d).map(|v| v as u32))
+        .collect();
+    let (Some(values), false) = (
+        values,
+        digits.len() % 4 == 1 || (unpadded.is_some() && !string.len().is_multiple_of(4)),
+    ) else {
+        fail!(
+            ErrorCode::InvalidArgument,
+            "Invalid base64 string `{string}`"
+        );
+    };
+    let mut output = vec![];
+    for chunk in values.chunks(4) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, value)| n | value << (18 - 6 * i));
+        output.extend((0..chunk.len() - 1).map(|i| (n >> (16 - 8 * i)) as u8));
+    }
+    Ok(output)
+}
+
+#[cfg(feature = "crypto")]
+pub(crate) fn sha256(bytes: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(bytes).to_vec()
+}
+
+/// HMAC (RFC 2104) of `message` with SHA-256.
+#[cfg(feature = "crypto")]
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    const BLOCK: usize = 64;
+    let mut block = if key.len() > BLOCK {
+        sha256(key)
+    } else {
+        key.to_vec()
+    };
+    block.resize(BLOCK, 0);
+    let pad = |byte: u8| block.iter().map(|k| k ^ byte).collect::<Vec<u8>>();
+    let mut inner = pad(0x36);
+    inner.extend(message);
+    let mut outer = pad(0x5c);
+    outer.extend(sha256(&inner));
+    sha256(&outer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, Object, eval_with_env};
+    use alloc::rc::Rc;
+
+    #[test]
+    fn round_trips() {
+        for input in ["", "f", "fo", "foo", "foob", "fooba", "foobar"] {
+            let encoded = base64(input.as_bytes());
+            assert_eq!(from_base64(&encoded).unwrap(), input.as_bytes());
+            assert_eq!(
+                from_base64(encoded.trim_end_matches('=')).unwrap(),
+                input.as_bytes()
+            );
+            assert_eq!(from_hex(&hex(input.as_bytes())).unwrap(), input.as_bytes());
+        }
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        for input in ["Zm9vY", "Zm9v=", "Zm9v!", "Zg=", "Zm9vYg=x"] {
+            assert!(from_base64(input).is_err(), "{input}");
+        }
+        for input in ["abc", "0g"] {
+            assert!(from_hex(input).is_err(), "{input}");
+        }
+
+        let input = "[hex(\"hi\"), decode(from_hex(\"6869\")), base64(bytes([255, 0])), from_base64(\"/wA\")]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default())).unwrap(),
+            Object::Array(
+                vec![
+                    Object::String("6869".into()),
+                    Object::String("hi".into()),
+                    Object::String("/wA=".into()),
+                    Object::Bytes(vec![255, 0]),
+                ]
+                .into()
+            )
+        );
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn digests() {
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // RFC 4231, test case 2.
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let input = "[hash_sha256(\"abc\"), hmac_sha256(bytes(\"Jefe\"), \"what do ya want for nothing?\")]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default())).unwrap(),
+            Object::Array(
+                vec![
+                    Object::Bytes(sha256(b"abc")),
+                    Object::Bytes(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+                ]
+                .into()
+            )
+        );
+    }
+}
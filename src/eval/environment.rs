@@ -1,37 +1,551 @@
+use super::builtin_names;
 use super::object::Object;
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use super::policy::Policy;
+use super::stats::{Budget, Meter};
+use crate::ast::dot::escape;
+use crate::error::{Error, ErrorCode, Result, did_you_mean, fail};
+use crate::prelude::HashMap;
+use crate::prelude::*;
+use crate::trace::event;
+use alloc::collections::BTreeSet;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::cell::RefCell;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-#[derive(Default, PartialEq, Eq, Debug, Clone)]
+#[derive(Default, Clone)]
 pub struct Environment {
     variables: Rc<RefCell<HashMap<String, Object>>>,
+    /// The variables bound with `let mut`, the only ones [`assign`](Self::assign) changes.
+    mutable: Rc<RefCell<BTreeSet<String>>>,
     outer: Option<Rc<Environment>>,
+    interrupt: Interrupt,
+    observer: Option<Rc<dyn Observer>>,
+    policy: Rc<Policy>,
+    meter: Rc<Meter>,
+}
+
+/// What a builtin knows of the environment calling it, see [`Environment::calling`].
+#[cfg(feature = "std")]
+pub(crate) struct Caller {
+    pub(crate) policy: Rc<Policy>,
+    #[cfg(feature = "sync")]
+    pub(crate) budget: Budget,
+    #[cfg(feature = "sync")]
+    pub(crate) interrupt: Interrupt,
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static CALLER: RefCell<Option<Caller>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with the caller of the builtin running, `None` for builtins called by the
+/// host outside any evaluation.
+#[cfg(feature = "std")]
+pub(crate) fn with_caller<T>(f: impl FnOnce(Option<&Caller>) -> T) -> T {
+    CALLER.with_borrow(|caller| f(caller.as_ref()))
+}
+
+/// Puts back the previous caller, even if the builtin panicked.
+#[cfg(feature = "std")]
+struct RestoreCaller(Option<Caller>);
+
+#[cfg(feature = "std")]
+impl Drop for RestoreCaller {
+    fn drop(&mut self) {
+        CALLER.set(self.0.take());
+    }
+}
+
+/// What [`Environment::debug_graph`] found reachable from an environment.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EnvironmentGraph {
+    /// The environment, the ones it's nested in and those captured by the functions and
+    /// generators it holds, recursively.
+    pub environments: usize,
+    /// Values bound in them, counting those nested in containers and iterators. Arrays and
+    /// iterators are counted once however many times they're reached.
+    pub objects: usize,
+    pub functions: usize,
+    /// Reference cycles, each as the bindings, or `outer`, leading from an environment back
+    /// to itself, e.g. `["f"]` for a function bound in the environment it captured. Their
+    /// environments are never freed, even once the script is done with them.
+    pub cycles: Vec<Vec<String>>,
+}
+
+impl fmt::Display for EnvironmentGraph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} environments, {} objects, {} functions reachable",
+            self.environments, self.objects, self.functions
+        )?;
+        for cycle in &self.cycles {
+            write!(f, "\ncycle: {}", cycle.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Depth-first walk of [`Environment::debug_graph`].
+#[derive(Default)]
+struct GraphWalk {
+    graph: EnvironmentGraph,
+    /// The environments being walked, each with the binding it was reached through.
+    path: Vec<(usize, String)>,
+    /// The environments, arrays and iterators already reached.
+    reached: BTreeSet<usize>,
+}
+
+impl GraphWalk {
+    fn environment(&mut self, env: &Environment, label: &str) {
+        let id = Rc::as_ptr(&env.variables) as usize;
+        if let Some(start) = self.path.iter().position(|(p, _)| *p == id) {
+            let mut cycle: Vec<String> = self.path[start + 1..]
+                .iter()
+                .map(|(_, label)| label.clone())
+                .collect();
+            cycle.push(label.to_owned());
+            self.graph.cycles.push(cycle);
+            return;
+        }
+        if !self.reached.insert(id) {
+            return;
+        }
+        self.graph.environments += 1;
+        self.path.push((id, label.to_owned()));
+        for (name, obj) in env.bindings() {
+            self.object(&obj, &name);
+        }
+        if let Some(outer) = &env.outer {
+            self.environment(outer, "outer");
+        }
+        self.path.pop();
+    }
+
+    fn object(&mut self, obj: &Object, label: &str) {
+        let id = match obj {
+            Object::Array(items) => Rc::as_ptr(items) as usize,
+            Object::Iterator(iter) => iter.id(),
+            _ => 0,
+        };
+        if id != 0 && !self.reached.insert(id) {
+            return;
+        }
+        self.graph.objects += 1;
+        match obj {
+            Object::Array(items) => items.iter().for_each(|item| self.object(item, label)),
+            Object::Tuple(values) | Object::Struct { values, .. } => {
+                values.iter().for_each(|value| self.object(value, label));
+            }
+            Object::Hash(map) => map.values().for_each(|value| self.object(value, label)),
+            Object::Error { data, .. } => self.object(data, label),
+            Object::Function { environment, .. } => {
+                self.graph.functions += 1;
+                self.environment(environment, label);
+            }
+            Object::Iterator(iter) => {
+                let (objects, env) = iter.references();
+                objects.iter().for_each(|obj| self.object(obj, label));
+                if let Some(env) = env {
+                    self.environment(&env, label);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Hooks called by an environment and by the environments nested in it, e.g. to audit
+/// what a script touches or to load host data the first time a script asks for it.
+pub trait Observer {
+    fn on_set(&self, _name: &str, _obj: &Object) {}
+
+    /// Called when a variable isn't defined anywhere; the value returned, if any, is
+    /// defined in the outermost environment and used instead of failing.
+    fn on_get_miss(&self, _name: &str) -> Option<Object> {
+        None
+    }
+
+    /// Called when a script uses a deprecated feature, with what to use instead.
+    fn on_warning(&self, _message: &str) {}
+
+    /// Called when a script does something likely unintended that doesn't fail it, like
+    /// giving a key twice in a hash literal, with where in the source when known. Reported
+    /// as a warning by default.
+    fn on_diagnostic(&self, diagnostic: &Error) {
+        self.on_warning(&diagnostic.message);
+    }
+}
+
+/// Stops the evaluations running in an environment and in the environments nested in it
+/// with an [`ErrorCode::Interrupted`] error, at the next function call or loop iteration.
+/// Can be triggered from a signal handler or another thread.
+#[derive(Default, Debug, Clone)]
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl Interrupt {
+    pub fn trigger(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the interrupt was triggered and not noticed by an evaluation yet.
+    pub fn is_triggered(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Fails if the interrupt was triggered, resetting it so that later evaluations run.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.0.swap(false, Ordering::SeqCst) {
+            fail!(ErrorCode::Interrupted, "Interrupted");
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for Interrupt {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Interrupt {}
+
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut Vec::new())
+    }
+}
+
+impl Eq for Environment {}
+
+/// Lists the names of the variables only: closures are usually stored in the
+/// environment they capture, so printing their values would never end.
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<String> = self.variables.borrow().keys().cloned().collect();
+        names.sort();
+        f.debug_struct("Environment")
+            .field("variables", &names)
+            .field("outer", &self.outer)
+            .finish()
+    }
 }
 
 impl Environment {
-    pub fn get(&self, var_name: impl AsRef<str>) -> Object {
-        match self.variables.borrow().get(var_name.as_ref()) {
-            Some(obj) => obj.to_owned(),
-            None => match &self.outer {
-                Some(env) => env.get(var_name),
-                None => Object::Null,
-            },
+    /// Compares the variables of both environments and of their outer ones. `visited`
+    /// holds the pairs met so far, which are taken as equal when met again.
+    pub(crate) fn equals(&self, other: &Self, visited: &mut Vec<(usize, usize)>) -> bool {
+        let pair = (
+            Rc::as_ptr(&self.variables) as usize,
+            Rc::as_ptr(&other.variables) as usize,
+        );
+        if visited.contains(&pair) {
+            return true;
+        }
+        visited.push(pair);
+
+        let (variables, others) = (self.variables.borrow(), other.variables.borrow());
+        self.interrupt == other.interrupt
+            && variables.len() == others.len()
+            && variables.iter().all(|(name, obj)| {
+                others
+                    .get(name)
+                    .is_some_and(|other| obj.equals(other, visited))
+            })
+            && match (&self.outer, &other.outer) {
+                (Some(outer), Some(others)) => outer.equals(others, visited),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+
+    pub fn with_observer(self, observer: impl Observer + 'static) -> Self {
+        Self {
+            observer: Some(Rc::new(observer)),
+            ..self
+        }
+    }
+
+    pub fn with_policy(self, policy: Policy) -> Self {
+        Self {
+            policy: Rc::new(policy),
+            ..self
         }
     }
 
+    /// Shared by the environment and all the environments created from it.
+    pub fn with_budget(self, budget: Budget) -> Self {
+        Self {
+            meter: Rc::new(Meter::with_budget(budget)),
+            ..self
+        }
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Runs `call`, a call of a builtin from this environment, which the builtin can
+    /// inspect with [`with_caller`].
+    pub(crate) fn calling<T>(&self, call: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "std")]
+        {
+            let caller = Caller {
+                policy: Rc::clone(&self.policy),
+                #[cfg(feature = "sync")]
+                budget: self.meter.budget(),
+                #[cfg(feature = "sync")]
+                interrupt: self.interrupt.clone(),
+            };
+            let _restore = RestoreCaller(CALLER.replace(Some(caller)));
+            call()
+        }
+        #[cfg(not(feature = "std"))]
+        call()
+    }
+
+    pub(crate) fn meter(&self) -> &Rc<Meter> {
+        &self.meter
+    }
+
+    /// The variable, `None` if it isn't defined anywhere.
+    pub fn get(&self, var_name: impl AsRef<str>) -> Option<Object> {
+        let var_name = var_name.as_ref();
+        if let Some(obj) = self.variables.borrow().get(var_name) {
+            return Some(obj.to_owned());
+        }
+        match &self.outer {
+            Some(env) => env.get(var_name),
+            None => {
+                let obj = self.observer.as_ref()?.on_get_miss(var_name)?;
+                let mut variables = self.variables.borrow_mut();
+                variables.insert(var_name.to_owned(), obj.clone());
+                Some(obj)
+            }
+        }
+    }
+
+    /// Like [`Environment::get`], without asking the observer for the variables that
+    /// aren't defined.
+    pub(crate) fn get_defined(&self, var_name: &str) -> Option<Object> {
+        if let Some(obj) = self.variables.borrow().get(var_name) {
+            return Some(obj.to_owned());
+        }
+        self.outer.as_ref()?.get_defined(var_name)
+    }
+
+    /// Like [`Environment::get`], failing if the variable isn't defined, with the closest
+    /// variable or builtin as a suggestion.
+    pub(crate) fn lookup(&self, var_name: &str) -> Result<Object> {
+        if let Some(obj) = self.get(var_name) {
+            return Ok(obj);
+        }
+        let names = self.names();
+        let mut candidates: Vec<&str> = builtin_names().collect();
+        candidates.extend(names.iter().map(String::as_str));
+        match did_you_mean(var_name, candidates) {
+            Some(suggestion) => fail!(
+                ErrorCode::UnknownIdentifier,
+                "identifier not found: {var_name}, did you mean `{suggestion}`?"
+            ),
+            None => fail!(
+                ErrorCode::UnknownIdentifier,
+                "identifier not found: {var_name}"
+            ),
+        }
+    }
+
+    /// Calls `f` on the variable if it's defined directly in this environment, to change
+    /// it in place.
+    pub(crate) fn update<R>(&self, var_name: &str, f: impl FnOnce(&mut Object) -> R) -> Option<R> {
+        self.variables.borrow_mut().get_mut(var_name).map(f)
+    }
+
+    /// Like [`update`](Self::update), in the environment where the variable is defined,
+    /// this one or an outer one, if [`assign`](Self::assign) may change it there.
+    pub(crate) fn update_mutable<R>(
+        &self,
+        var_name: &str,
+        f: impl FnOnce(&mut Object) -> R,
+    ) -> Option<R> {
+        if self.variables.borrow().contains_key(var_name) {
+            if !self.mutable.borrow().contains(var_name) {
+                return None;
+            }
+            return self.update(var_name, f);
+        }
+        self.outer.as_ref()?.update_mutable(var_name, f)
+    }
+
+    pub(crate) fn warn(&self, message: &str) {
+        if let Some(observer) = &self.observer {
+            observer.on_warning(message);
+        }
+    }
+
+    pub(crate) fn diagnose(&self, diagnostic: &Error) {
+        if let Some(observer) = &self.observer {
+            observer.on_diagnostic(diagnostic);
+        }
+    }
+
+    /// Binds the variable in this environment, as a constant unless it's bound again with
+    /// [`set_mut`](Self::set_mut).
     pub fn set(&self, var_name: impl Into<String>, obj: impl Into<Object>) -> Object {
-        let obj = obj.into();
+        let (var_name, obj) = (var_name.into(), obj.into());
+        self.mutable.borrow_mut().remove(&var_name);
+        self.write(var_name, obj)
+    }
+
+    /// Like [`set`](Self::set), for a variable that can be assigned afterwards.
+    pub fn set_mut(&self, var_name: impl Into<String>, obj: impl Into<Object>) -> Object {
+        let (var_name, obj) = (var_name.into(), obj.into());
+        self.mutable.borrow_mut().insert(var_name.clone());
+        self.write(var_name, obj)
+    }
+
+    fn write(&self, var_name: String, obj: Object) -> Object {
+        if let Some(observer) = &self.observer {
+            observer.on_set(&var_name, &obj);
+        }
         self.variables
             .borrow_mut()
-            .entry(var_name.into())
+            .entry(var_name)
             .and_modify(|curr| *curr = obj.clone())
             .or_insert(obj)
             .to_owned()
     }
 
+    /// Changes the variable in the environment where it's defined, this one or an outer
+    /// one, instead of shadowing it. Fails if it isn't defined anywhere or isn't mutable.
+    pub fn assign(&self, var_name: &str, obj: impl Into<Object>) -> Result<Object> {
+        let obj = obj.into();
+        if self.variables.borrow().contains_key(var_name) {
+            if !self.mutable.borrow().contains(var_name) {
+                fail!(
+                    ErrorCode::ImmutableAssignment,
+                    "Cannot assign to `{var_name}`, it isn't mutable: use `let mut` to define it"
+                );
+            }
+            return Ok(self.write(var_name.to_owned(), obj));
+        }
+        match &self.outer {
+            Some(env) => env.assign(var_name, obj),
+            None => fail!(
+                ErrorCode::UnknownIdentifier,
+                "Cannot assign to `{var_name}`, it isn't defined: use `let` to define it"
+            ),
+        }
+    }
+
+    /// Variables defined directly in this environment, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings: Vec<(String, Object)> = self
+            .variables
+            .borrow()
+            .iter()
+            .map(|(name, obj)| (name.clone(), obj.clone()))
+            .collect();
+        bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+        bindings
+    }
+
+    /// Removes the variables defined directly in this environment.
+    pub fn clear(&self) {
+        self.variables.borrow_mut().clear();
+        self.mutable.borrow_mut().clear();
+    }
+
+    /// Names of all the variables visible from this environment, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.variables.borrow().keys().cloned().collect();
+        if let Some(outer) = &self.outer {
+            names.extend(outer.names());
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
     pub fn init_with_outer(outer: Rc<Self>) -> Self {
+        event!("environment created");
         Self {
+            interrupt: outer.interrupt.clone(),
+            observer: outer.observer.clone(),
+            policy: Rc::clone(&outer.policy),
+            meter: Rc::clone(&outer.meter),
             outer: Some(outer.clone()),
             ..Default::default()
         }
     }
+
+    /// Stopped by `interrupt` rather than by an interrupt of its own.
+    #[cfg(feature = "sync")]
+    pub(crate) fn with_interrupt(self, interrupt: Interrupt) -> Self {
+        Self { interrupt, ..self }
+    }
+
+    /// Shared by the environment and all the environments created from it.
+    pub fn interrupt(&self) -> &Interrupt {
+        &self.interrupt
+    }
+
+    /// Sizes of what the environment keeps alive, and the reference cycles keeping it alive
+    /// in turn: environments hold strong references only, so a cycle is never freed. Meant
+    /// to diagnose leaks, e.g. with `waiir run --leak-check`.
+    pub fn debug_graph(&self) -> EnvironmentGraph {
+        let mut walk = GraphWalk::default();
+        walk.environment(self, "");
+        walk.graph
+    }
+
+    /// Renders the environment, the ones it's nested in and the ones captured by the
+    /// functions it holds as a Graphviz graph.
+    pub fn to_dot(&self) -> String {
+        let mut output = String::from("digraph environment {\n    node [shape=box];\n");
+        self.dot_node(&mut output, &mut vec![]);
+        output.push_str("}\n");
+        output
+    }
+
+    /// Writes the node of the environment unless `visited` already holds it, returning
+    /// its index in `visited`.
+    fn dot_node(&self, output: &mut String, visited: &mut Vec<usize>) -> usize {
+        let ptr = Rc::as_ptr(&self.variables) as usize;
+        if let Some(id) = visited.iter().position(|v| *v == ptr) {
+            return id;
+        }
+        let id = visited.len();
+        visited.push(ptr);
+
+        let bindings = self.bindings();
+        let label: String = bindings
+            .iter()
+            .map(|(name, obj)| {
+                let full = obj.to_string();
+                let mut value: String = full.chars().take(40).collect();
+                if value.len() < full.len() {
+                    value.push_str("...");
+                }
+                format!("{name} = {}\\l", escape(&value))
+            })
+            .collect();
+        output.push_str(&format!("    e{id} [label=\"{label}\"];\n"));
+        for (name, obj) in &bindings {
+            if let Object::Function { environment, .. } = obj {
+                let captured = environment.dot_node(output, visited);
+                output.push_str(&format!(
+                    "    e{id} -> e{captured} [label=\"{name}\", style=dashed];\n"
+                ));
+            }
+        }
+        if let Some(outer) = &self.outer {
+            let outer = outer.dot_node(output, visited);
+            output.push_str(&format!("    e{id} -> e{outer} [label=\"outer\"];\n"));
+        }
+        id
+    }
 }
@@ -0,0 +1,176 @@
+use crate::error::Result;
+use crate::eval::{Environment, Eval, Iter, Object};
+use crate::prelude::*;
+use crate::{Expression, Program, Statement};
+use alloc::rc::Rc;
+
+/// Evaluation of a program that can be paused between statements, so that a host can
+/// bound how long it runs at a time.
+/// Statements of blocks, `if`, `for` and `while` statements are run one by one, any other
+/// statement, such as a function call, runs to completion once started.
+pub struct Execution {
+    env: Rc<Environment>,
+    frames: Vec<Frame>,
+    /// Value of the last statement evaluated, the result of the program once it ends.
+    last: Object,
+    result: Option<Object>,
+}
+
+enum Frame {
+    Block {
+        stmts: Vec<Statement>,
+        index: usize,
+    },
+    For {
+        name: String,
+        iter: Iter,
+        body: Vec<Statement>,
+    },
+    While {
+        cond: Expression,
+        body: Vec<Statement>,
+    },
+}
+
+impl Frame {
+    fn is_loop(&self) -> bool {
+        matches!(self, Frame::For { .. } | Frame::While { .. })
+    }
+}
+
+impl Execution {
+    pub fn new(program: Program, env: Rc<Environment>) -> Self {
+        Self {
+            env,
+            frames: vec![Frame::Block {
+                stmts: program.statements,
+                index: 0,
+            }],
+            last: Object::Null,
+            result: None,
+        }
+    }
+
+    /// Runs at most `fuel` statements, returning the value of the program once it ends.
+    /// Every statement costs one unit of fuel, however long it takes.
+    pub fn run(&mut self, fuel: usize) -> Result<Option<Object>> {
+        for _ in 0..fuel {
+            if self.result.is_none() {
+                self.step()?;
+            }
+            if self.result.is_some() {
+                break;
+            }
+        }
+        Ok(self.result.clone())
+    }
+
+    /// Statement evaluated by the next step, `None` if the step only moves between
+    /// blocks, binds the next item of a loop or checks its condition.
+    pub fn peek(&self) -> Option<&Statement> {
+        match self.frames.last()? {
+            Frame::Block { stmts, index } => stmts.get(*index),
+            Frame::For { .. } | Frame::While { .. } => None,
+        }
+    }
+
+    fn step(&mut self) -> Result<()> {
+        let Some(frame) = self.frames.last_mut() else {
+            self.result = Some(self.last.clone());
+            return Ok(());
+        };
+        let statement = match frame {
+            Frame::Block { stmts, index } => match stmts.get(*index) {
+                Some(statement) => {
+                    *index += 1;
+                    statement.clone()
+                }
+                None => {
+                    self.frames.pop();
+                    return Ok(());
+                }
+            },
+            Frame::For { name, iter, body } => {
+                self.env.interrupt().check()?;
+                match iter.next()? {
+                    Some(item) => {
+                        self.env.set(name.as_str(), item);
+                        let stmts = body.clone();
+                        self.frames.push(Frame::Block { stmts, index: 0 });
+                    }
+                    None => {
+                        self.frames.pop();
+                        self.last = Object::Null;
+                    }
+                }
+                return Ok(());
+            }
+            Frame::While { cond, body } => {
+                self.env.interrupt().check()?;
+                if cond.clone().eval(Rc::clone(&self.env))?.to_bool() {
+                    let stmts = body.clone();
+                    self.frames.push(Frame::Block { stmts, index: 0 });
+                } else {
+                    self.frames.pop();
+                    self.last = Object::Null;
+                }
+                return Ok(());
+            }
+        };
+
+        // The statements run by the frames, the others are counted by `eval`.
+        if matches!(
+            statement,
+            Statement::Block(_)
+                | Statement::Expr(
+                    Expression::Cond { .. } | Expression::For { .. } | Expression::While { .. }
+                )
+        ) {
+            self.env.meter().step()?;
+        }
+        match statement {
+            Statement::Block(stmts) => self.enter(stmts),
+            Statement::Expr(Expression::Cond { cond, then_, else_ }) => {
+                if cond.eval(Rc::clone(&self.env))?.to_bool() {
+                    self.enter(then_);
+                } else {
+                    self.enter(else_.unwrap_or_default());
+                }
+            }
+            Statement::Expr(Expression::For {
+                name,
+                iterable,
+                body,
+            }) => {
+                let iter = Iter::from_object(iterable.eval(Rc::clone(&self.env))?)?;
+                self.frames.push(Frame::For { name, iter, body });
+            }
+            Statement::Expr(Expression::While { cond, body }) => {
+                self.frames.push(Frame::While { cond: *cond, body });
+            }
+            statement => match statement.eval(Rc::clone(&self.env))? {
+                Object::Return(value) => self.result = Some(*value),
+                Object::Break => {
+                    while let Some(frame) = self.frames.pop() {
+                        if frame.is_loop() {
+                            break;
+                        }
+                    }
+                    self.last = Object::Null;
+                }
+                Object::Continue => {
+                    while self.frames.last().is_some_and(|frame| !frame.is_loop()) {
+                        self.frames.pop();
+                    }
+                }
+                value => self.last = value,
+            },
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, stmts: Vec<Statement>) {
+        self.last = Object::Null;
+        self.frames.push(Frame::Block { stmts, index: 0 });
+    }
+}
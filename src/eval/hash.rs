@@ -0,0 +1,170 @@
+use crate::Expression;
+use crate::ast::printer::expression_to_source;
+use crate::error::{Error, ErrorCode, Result};
+use crate::eval::{Environment, HashMapKey, Object};
+use crate::prelude::HashMap;
+use crate::prelude::*;
+use core::hash::{BuildHasher, Hasher};
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+#[cfg(feature = "std")]
+type RandomState = std::collections::hash_map::RandomState;
+#[cfg(not(feature = "std"))]
+type RandomState = hashbrown::DefaultHashBuilder;
+
+/// Contents of an [`Object::Hash`].
+pub type HashObject = HashMap<HashMapKey, Object, HashState>;
+
+/// The hash of a literal, built entry by entry by the evaluator and the VM alike. A key
+/// given twice is reported through [`Environment::diagnose`], or fails with
+/// [`ErrorCode::DuplicateHashKey`] under a [strict](crate::eval::Policy::strict) policy:
+/// otherwise the later value replaces the earlier one.
+#[derive(Default)]
+pub(crate) struct HashLiteral {
+    map: HashObject,
+}
+
+impl HashLiteral {
+    /// Adds an entry, `span` being where its key is in the source, if known.
+    pub(crate) fn insert(
+        &mut self,
+        key: HashMapKey,
+        span: Option<Range<usize>>,
+        value: Object,
+        env: &Environment,
+    ) -> Result<()> {
+        if self.map.contains_key(&key) {
+            let source = expression_to_source(&match &key {
+                HashMapKey::Int(value) => Expression::Int(*value),
+                HashMapKey::String(value) => Expression::String(value.clone()),
+                HashMapKey::Bool(value) => Expression::from(*value),
+            });
+            let err = Error::new(
+                ErrorCode::DuplicateHashKey,
+                format!("Duplicate key {source} in hash literal"),
+            )
+            .or_span(span);
+            if env.policy().is_strict() {
+                return Err(err);
+            }
+            env.diagnose(&err);
+        }
+        self.map.insert(key, value);
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> HashObject {
+        self.map
+    }
+}
+
+static SEEDED: AtomicBool = AtomicBool::new(false);
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+/// Makes the hashes created from now on iterate in an order that only depends on `seed`
+/// and on their keys, e.g. for reproducible fuzzing or snapshot tests. `None` goes back
+/// to randomly seeded hashing.
+pub fn set_hash_seed(seed: Option<u64>) {
+    SEED.store(seed.unwrap_or_default(), Ordering::SeqCst);
+    SEEDED.store(seed.is_some(), Ordering::SeqCst);
+}
+
+/// Builds the hashers of [`HashObject`]s, following [`set_hash_seed`] by default.
+#[derive(Clone)]
+pub struct HashState(State);
+
+#[derive(Clone)]
+enum State {
+    Random(RandomState),
+    Seeded(u64),
+}
+
+impl Default for HashState {
+    fn default() -> Self {
+        if SEEDED.load(Ordering::SeqCst) {
+            Self::with_seed(SEED.load(Ordering::SeqCst))
+        } else {
+            Self(State::Random(RandomState::default()))
+        }
+    }
+}
+
+impl HashState {
+    /// Deterministic hashing, not resistant to keys crafted to collide.
+    pub fn with_seed(seed: u64) -> Self {
+        Self(State::Seeded(seed))
+    }
+}
+
+impl BuildHasher for HashState {
+    type Hasher = ObjectHasher;
+
+    fn build_hasher(&self) -> ObjectHasher {
+        ObjectHasher(match &self.0 {
+            State::Random(state) => Inner::Random(state.build_hasher()),
+            State::Seeded(seed) => Inner::Seeded(FNV_OFFSET ^ seed),
+        })
+    }
+}
+
+pub struct ObjectHasher(Inner);
+
+enum Inner {
+    Random(<RandomState as BuildHasher>::Hasher),
+    /// FNV-1a.
+    Seeded(u64),
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+impl Hasher for ObjectHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        match &mut self.0 {
+            Inner::Random(hasher) => hasher.write(bytes),
+            Inner::Seeded(state) => {
+                for byte in bytes {
+                    *state = (*state ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+                }
+            }
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        match &self.0 {
+            Inner::Random(hasher) => hasher.finish(),
+            Inner::Seeded(state) => *state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_iteration_order() {
+        let order = |seed: u64| {
+            let mut map = HashObject::with_hasher(HashState::with_seed(seed));
+            for key in 0..50 {
+                map.insert(HashMapKey::Int(key), Object::Null);
+            }
+            map.into_keys().collect::<Vec<HashMapKey>>()
+        };
+        assert_eq!(order(7), order(7));
+        assert_ne!(order(7), order(8));
+    }
+
+    #[test]
+    fn global_seed() {
+        set_hash_seed(Some(3));
+        let (mut a, mut b) = (HashObject::default(), HashObject::default());
+        set_hash_seed(None);
+        for key in 0..50 {
+            a.insert(HashMapKey::Int(key), Object::Null);
+            b.insert(HashMapKey::Int(key), Object::Null);
+        }
+        assert!(a.keys().eq(b.keys()));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{Object, Value};
+use std::cell::RefCell;
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// Host end of the channel between a host and the scripts evaluated on a thread,
+/// which use the `send_host` and `recv_host` builtins. Can be moved to another
+/// thread; dropping it disconnects the scripts.
+pub struct HostChannel {
+    sender: Sender<Value>,
+    receiver: Receiver<Value>,
+}
+
+impl HostChannel {
+    /// Queues `value` for `recv_host`, returning `false` once the scripts' thread is gone.
+    pub fn send(&self, value: Value) -> bool {
+        self.sender.send(value).is_ok()
+    }
+
+    /// Waits for the next value passed to `send_host`, `None` once the scripts' thread
+    /// is gone.
+    pub fn recv(&self) -> Option<Value> {
+        self.receiver.recv().ok()
+    }
+
+    /// The next value passed to `send_host`, if any, without waiting.
+    pub fn try_recv(&self) -> Option<Value> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+struct ScriptChannel {
+    sender: Sender<Value>,
+    receiver: Receiver<Value>,
+}
+
+thread_local! {
+    static SCRIPT_CHANNEL: RefCell<Option<ScriptChannel>> = const { RefCell::new(None) };
+}
+
+/// Connects the scripts evaluated on the current thread from now on to the host
+/// holding the returned channel, replacing any previous connection.
+pub fn connect_host() -> HostChannel {
+    let (to_script, from_host) = channel();
+    let (to_host, from_script) = channel();
+    SCRIPT_CHANNEL.set(Some(ScriptChannel {
+        sender: to_host,
+        receiver: from_host,
+    }));
+    HostChannel {
+        sender: to_script,
+        receiver: from_script,
+    }
+}
+
+/// Sends a copy of `value` to the host, returning whether it's still listening.
+pub(crate) fn send_host(value: &Object) -> Result<Object> {
+    let value = Value::from_object(value)?;
+    SCRIPT_CHANNEL.with_borrow(|channel| match channel {
+        Some(channel) => Ok(Object::Bool(channel.sender.send(value).is_ok())),
+        None => fail!(ErrorCode::InvalidInput, "No host is connected"),
+    })
+}
+
+/// Waits for the next value sent by the host, `null` once it dropped its channel.
+pub(crate) fn recv_host() -> Result<Object> {
+    SCRIPT_CHANNEL.with_borrow(|channel| match channel {
+        Some(channel) => Ok(channel.receiver.recv().map_or(Object::Null, Object::from)),
+        None => fail!(ErrorCode::InvalidInput, "No host is connected"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, eval_with_env};
+    use alloc::rc::Rc;
+    use std::thread;
+
+    #[test]
+    fn streaming() {
+        let host = connect_host();
+        let commander = thread::spawn(move || {
+            for n in 1..=3 {
+                host.send(Value::Int(n));
+            }
+            let results: Vec<Value> = (1..=3).map_while(|_| host.recv()).collect();
+            drop(host);
+            results
+        });
+        let input = "let serve = fn(total) { let n = recv_host(); \
+                     if (n) { send_host([n, n * n]); serve(total + n) } else { total } }; serve(0)";
+        let total = eval_with_env(input, Rc::new(Environment::default())).unwrap();
+        assert_eq!(total, Object::Int(6));
+        assert_eq!(
+            commander.join().unwrap()[2],
+            Value::Array(vec![Value::Int(3), Value::Int(9)])
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{Environment, Eval, HashMapKey, Object, apply};
+use crate::prelude::*;
+use crate::vm::Generator;
+use crate::{Expression, Statement};
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+/// Lazy sequence. Copies share their state, so advancing one advances all of them: see
+/// [`Iter::tee`] for independent ones.
+#[derive(Debug, Clone)]
+pub struct Iter(Rc<RefCell<IterState>>);
+
+#[derive(Debug)]
+enum IterState {
+    Range {
+        next: i64,
+        end: i64,
+        step: i64,
+    },
+    Items {
+        items: Rc<Vec<Object>>,
+        index: usize,
+    },
+    Chars {
+        string: String,
+        offset: usize,
+    },
+    Map {
+        inner: Iter,
+        func: Object,
+    },
+    Filter {
+        inner: Iter,
+        func: Object,
+    },
+    /// `frames` is `None` while the body is being evaluated.
+    Generator {
+        env: Rc<Environment>,
+        frames: Option<Vec<Frame>>,
+    },
+    /// A generator compiled for the VM, `None` while it runs.
+    Compiled(Option<Generator>),
+    /// One of the iterators made by [`Iter::tee`], reading the items of `source` from
+    /// `next` on.
+    Tee {
+        source: Iter,
+        next: Rc<TeeLink>,
+    },
+}
+
+/// An item of the source shared by tee'd iterators, produced by the first of them to
+/// reach it and dropped once all of them are past it.
+#[derive(Debug, Default)]
+struct TeeLink(RefCell<Option<(Object, Rc<TeeLink>)>>);
+
+impl TeeLink {
+    /// The item of this link and the link to the following one, `None` past the end.
+    fn get(&self, source: &Iter) -> Result<Option<(Object, Rc<TeeLink>)>> {
+        if let Some(filled) = &*self.0.borrow() {
+            return Ok(Some(filled.clone()));
+        }
+        let Some(item) = source.next()? else {
+            return Ok(None);
+        };
+        let filled = (item, Rc::default());
+        *self.0.borrow_mut() = Some(filled.clone());
+        Ok(Some(filled))
+    }
+}
+
+// Unlinks the chain one link at a time: dropping it recursively would overflow the stack
+// when an iterator is far behind the others.
+impl Drop for TeeLink {
+    fn drop(&mut self) {
+        let mut next = self.0.get_mut().take();
+        while let Some((_, link)) = next {
+            next = Rc::try_unwrap(link)
+                .ok()
+                .and_then(|mut link| link.0.get_mut().take());
+        }
+    }
+}
+
+/// Where a suspended generator resumes: the innermost frame is the last one.
+#[derive(Debug)]
+enum Frame {
+    Block {
+        stmts: Vec<Statement>,
+        index: usize,
+    },
+    For {
+        name: String,
+        iter: Iter,
+        body: Vec<Statement>,
+    },
+    While {
+        cond: Expression,
+        body: Vec<Statement>,
+    },
+}
+
+impl Frame {
+    fn is_loop(&self) -> bool {
+        matches!(self, Frame::For { .. } | Frame::While { .. })
+    }
+}
+
+impl PartialEq for Iter {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Iter {}
+
+impl Iter {
+    fn new(state: IterState) -> Self {
+        Self(Rc::new(RefCell::new(state)))
+    }
+
+    /// Integers from `start` (included) to `end` (excluded), `step` apart.
+    pub fn range(start: i64, end: i64, step: i64) -> Result<Self> {
+        if step == 0 {
+            fail!(ErrorCode::InvalidArgument, "The step of a range can't be 0");
+        }
+        Ok(Self::new(IterState::Range {
+            next: start,
+            end,
+            step,
+        }))
+    }
+
+    /// Iterates arrays by element, strings by char and hashes by `[key, value]` pairs
+    /// in key order. Iterators are returned as they are.
+    pub fn from_object(obj: Object) -> Result<Self> {
+        Ok(match obj {
+            Object::Iterator(iter) => iter,
+            Object::Array(items) => Self::new(IterState::Items { items, index: 0 }),
+            Object::Bytes(bytes) => Self::new(IterState::Items {
+                items: bytes
+                    .into_iter()
+                    .map(|b| Object::Int(i64::from(b)))
+                    .collect::<Vec<_>>()
+                    .into(),
+                index: 0,
+            }),
+            Object::String(string) => Self::new(IterState::Chars { string, offset: 0 }),
+            Object::Hash(map) => {
+                let mut pairs: Vec<(HashMapKey, Object)> = map.into_iter().collect();
+                pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+                let items = pairs
+                    .into_iter()
+                    .map(|(k, v)| Object::Array(vec![Object::from(k), v].into()))
+                    .collect::<Vec<_>>()
+                    .into();
+                Self::new(IterState::Items { items, index: 0 })
+            }
+            obj => fail!(ErrorCode::InvalidArgument, "Cannot iterate over {obj}"),
+        })
+    }
+
+    pub fn map(self, func: Object) -> Self {
+        Self::new(IterState::Map { inner: self, func })
+    }
+
+    pub fn filter(self, func: Object) -> Self {
+        Self::new(IterState::Filter { inner: self, func })
+    }
+
+    /// Body of a function containing `yield`, run up to the next `yield` on every call
+    /// to [`Iter::next`]. `env` already binds the arguments.
+    pub(crate) fn generator(env: Rc<Environment>, body: Vec<Statement>) -> Self {
+        Self::new(IterState::Generator {
+            env,
+            frames: Some(vec![Frame::Block {
+                stmts: body,
+                index: 0,
+            }]),
+        })
+    }
+
+    /// Items yielded by a call of a generator function compiled for the VM.
+    pub(crate) fn compiled(generator: Generator) -> Self {
+        Self::new(IterState::Compiled(Some(generator)))
+    }
+
+    /// Another iterator over the items left in this one, the two advancing independently.
+    /// The items are still produced once, by whichever gets to them first, and kept until
+    /// the other one reads them too.
+    pub fn tee(&self) -> Result<Self> {
+        let mut state = self.0.borrow_mut();
+        match &*state {
+            IterState::Tee { source, next } => {
+                return Ok(Self::new(IterState::Tee {
+                    source: source.clone(),
+                    next: Rc::clone(next),
+                }));
+            }
+            IterState::Generator { frames: None, .. } | IterState::Compiled(None) => fail!(
+                ErrorCode::InvalidYield,
+                "A generator can't be copied while it is running"
+            ),
+            _ => {}
+        }
+        let empty = IterState::Items {
+            items: Rc::default(),
+            index: 0,
+        };
+        let source = Self::new(core::mem::replace(&mut *state, empty));
+        let next = Rc::new(TeeLink::default());
+        *state = IterState::Tee {
+            source: source.clone(),
+            next: Rc::clone(&next),
+        };
+        Ok(Self::new(IterState::Tee { source, next }))
+    }
+
+    /// The values and the environment the iterator keeps alive, see
+    /// [`Environment::debug_graph`].
+    pub(crate) fn references(&self) -> (Vec<Object>, Option<Rc<Environment>>) {
+        match &*self.0.borrow() {
+            IterState::Range { .. } | IterState::Chars { .. } => (vec![], None),
+            IterState::Items { items, .. } => (items.to_vec(), None),
+            IterState::Map { inner, func } | IterState::Filter { inner, func } => {
+                (vec![Object::Iterator(inner.clone()), func.clone()], None)
+            }
+            IterState::Generator { env, .. } => (vec![], Some(Rc::clone(env))),
+            IterState::Compiled(generator) => {
+                (vec![], generator.as_ref().map(|g| Rc::clone(g.env())))
+            }
+            IterState::Tee { source, .. } => (vec![Object::Iterator(source.clone())], None),
+        }
+    }
+
+    /// Identifies the state shared by the copies of this iterator.
+    pub(crate) fn id(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+
+    pub fn next(&self) -> Result<Option<Object>> {
+        let generator = match &mut *self.0.borrow_mut() {
+            IterState::Generator { env, frames } => Some((Rc::clone(env), frames.take())),
+            _ => None,
+        };
+        if let Some((env, frames)) = generator {
+            let Some(mut frames) = frames else {
+                fail!(
+                    ErrorCode::InvalidYield,
+                    "A generator can't be resumed while it is running"
+                );
+            };
+            let result = resume(&env, &mut frames);
+            if result.is_err() {
+                frames.clear();
+            }
+            if let IterState::Generator { frames: slot, .. } = &mut *self.0.borrow_mut() {
+                *slot = Some(frames);
+            }
+            return result;
+        }
+
+        let compiled = match &mut *self.0.borrow_mut() {
+            IterState::Compiled(generator) => Some(generator.take()),
+            _ => None,
+        };
+        if let Some(generator) = compiled {
+            let Some(mut generator) = generator else {
+                fail!(
+                    ErrorCode::InvalidYield,
+                    "A generator can't be resumed while it is running"
+                );
+            };
+            let result = generator.resume();
+            if let IterState::Compiled(slot) = &mut *self.0.borrow_mut() {
+                *slot = Some(generator);
+            }
+            return result;
+        }
+
+        let shared = match &*self.0.borrow() {
+            IterState::Tee { source, next } => Some((source.clone(), Rc::clone(next))),
+            _ => None,
+        };
+        if let Some((source, link)) = shared {
+            let Some((item, following)) = link.get(&source)? else {
+                return Ok(None);
+            };
+            if let IterState::Tee { next, .. } = &mut *self.0.borrow_mut() {
+                *next = following;
+            }
+            return Ok(Some(item));
+        }
+
+        // The adapters call back into Monkey code, which may use this iterator again:
+        // the state must not stay borrowed meanwhile.
+        let (inner, func, is_map) = match &mut *self.0.borrow_mut() {
+            IterState::Range { next, end, step } => {
+                if (*step > 0 && *next >= *end) || (*step < 0 && *next <= *end) {
+                    return Ok(None);
+                }
+                let value = *next;
+                *next = next.checked_add(*step).unwrap_or(*end);
+                return Ok(Some(Object::Int(value)));
+            }
+            IterState::Items { items, index } => {
+                let item = items.get(*index).cloned();
+                *index += 1;
+                return Ok(item);
+            }
+            IterState::Chars { string, offset } => {
+                let ch = string[*offset..].chars().next();
+                *offset += ch.map_or(0, char::len_utf8);
+                return Ok(ch.map(|ch| Object::String(ch.to_string())));
+            }
+            IterState::Map { inner, func } => (inner.clone(), func.clone(), true),
+            IterState::Filter { inner, func } => (inner.clone(), func.clone(), false),
+            // Advanced above.
+            IterState::Generator { .. } | IterState::Compiled(_) | IterState::Tee { .. } => {
+                return Ok(None);
+            }
+        };
+        while let Some(item) = inner.next()? {
+            if is_map {
+                return apply(func, vec![item], None).map(Some);
+            }
+            if apply(func.clone(), vec![item.clone()], None)?.to_bool() {
+                return Ok(Some(item));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Runs the generator until it yields a value or finishes. Only `yield`s that are
+/// statements, possibly nested in `if`, `for` and `while` statements, can suspend it: the other
+/// statements are evaluated in one go.
+fn resume(env: &Rc<Environment>, frames: &mut Vec<Frame>) -> Result<Option<Object>> {
+    while let Some(frame) = frames.last_mut() {
+        let statement = match frame {
+            Frame::Block { stmts, index } => match stmts.get(*index) {
+                Some(statement) => {
+                    *index += 1;
+                    statement.clone()
+                }
+                None => {
+                    frames.pop();
+                    continue;
+                }
+            },
+            Frame::For { name, iter, body } => {
+                env.interrupt().check()?;
+                match iter.next()? {
+                    Some(item) => {
+                        env.set(name.as_str(), item);
+                        let stmts = body.clone();
+                        frames.push(Frame::Block { stmts, index: 0 });
+                    }
+                    None => {
+                        frames.pop();
+                    }
+                }
+                continue;
+            }
+            Frame::While { cond, body } => {
+                env.interrupt().check()?;
+                if cond.clone().eval(Rc::clone(env))?.to_bool() {
+                    let stmts = body.clone();
+                    frames.push(Frame::Block { stmts, index: 0 });
+                } else {
+                    frames.pop();
+                }
+                continue;
+            }
+        };
+
+        // The statements run by the frames, the others are counted by `eval`.
+        if statement.yields() || matches!(statement, Statement::Block(_)) {
+            env.meter().step()?;
+        }
+        match statement {
+            Statement::Yield { value } => {
+                let obj = value.eval(Rc::clone(env))?;
+                if !unwind(frames, &obj) {
+                    return Ok(Some(obj));
+                }
+            }
+            Statement::Block(stmts) => frames.push(Frame::Block { stmts, index: 0 }),
+            Statement::Expr(Expression::Cond { cond, then_, else_ })
+                if then_
+                    .iter()
+                    .chain(else_.iter().flatten())
+                    .any(Statement::yields) =>
+            {
+                let stmts = if cond.eval(Rc::clone(env))?.to_bool() {
+                    then_
+                } else {
+                    else_.unwrap_or_default()
+                };
+                frames.push(Frame::Block { stmts, index: 0 });
+            }
+            Statement::Expr(Expression::For {
+                name,
+                iterable,
+                body,
+            }) if body.iter().any(Statement::yields) => {
+                let iter = Iter::from_object(iterable.eval(Rc::clone(env))?)?;
+                frames.push(Frame::For { name, iter, body });
+            }
+            Statement::Expr(Expression::While { cond, body })
+                if body.iter().any(Statement::yields) =>
+            {
+                frames.push(Frame::While { cond: *cond, body });
+            }
+            statement => {
+                unwind(frames, &statement.eval(Rc::clone(env))?);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Leaves the frames that the `return`, `break` or `continue` signalled by `obj` jumps
+/// out of, returning whether it was one.
+fn unwind(frames: &mut Vec<Frame>, obj: &Object) -> bool {
+    match obj {
+        Object::Return(_) => frames.clear(),
+        Object::Break => {
+            while let Some(frame) = frames.pop() {
+                if frame.is_loop() {
+                    break;
+                }
+            }
+        }
+        Object::Continue => {
+            while frames.last().is_some_and(|frame| !frame.is_loop()) {
+                frames.pop();
+            }
+        }
+        _ => return false,
+    }
+    true
+}
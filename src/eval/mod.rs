@@ -1,13 +1,63 @@
+// Conversions that can fail, e.g. of lengths on 32-bit targets, surface errors to the
+// script instead of panicking.
+#![cfg_attr(not(test), deny(clippy::unwrap_used))]
+
+#[cfg(feature = "std")]
+mod args;
 mod builtin;
+mod copy;
+mod csv;
+mod encoding;
 mod environment;
+mod execution;
+mod hash;
+#[cfg(feature = "std")]
+mod host;
+mod iterator;
 mod object;
+#[cfg(feature = "std")]
+mod output;
+pub(crate) mod pattern;
+#[cfg(feature = "std")]
+pub mod plugin;
+pub(crate) mod policy;
+mod pretty;
+mod query;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+#[cfg(feature = "std")]
+mod table;
+mod value;
+#[cfg(feature = "sync")]
+mod workers;
 
+use crate::ast::printer::expression_to_source;
+use crate::ast::{CallSpan, MatchArm};
+use crate::error::{Error, ErrorCode, Result, catch_unwind, fail};
+use crate::prelude::*;
+use crate::trace::{event, span};
 use crate::{Expression, InfixOperator, Parser, PrefixOperator, Program, Statement};
-use anyhow::{Result, bail};
-use builtin::BuiltinFunction;
-pub use environment::Environment;
-use object::{HashMapKey, Object};
-use std::{collections::HashMap, rc::Rc};
+use alloc::rc::Rc;
+#[cfg(feature = "std")]
+pub use args::set_script_args;
+pub use builtin::{
+    Arity, BUILTINS, BuiltinFunction, BuiltinInfo, all_builtins, builtins_reference,
+};
+pub use environment::{Environment, EnvironmentGraph, Interrupt, Observer};
+pub use execution::Execution;
+pub(crate) use hash::HashLiteral;
+pub use hash::{HashObject, HashState, ObjectHasher, set_hash_seed};
+#[cfg(feature = "std")]
+pub use host::{HostChannel, connect_host};
+pub use iterator::Iter;
+pub use object::{Handle, HashMapKey, Object, StructType};
+#[cfg(feature = "std")]
+pub use output::{reset_output, set_output};
+pub use policy::{Capability, Policy};
+pub use pretty::PrettyOptions;
+pub use stats::{Budget, ExecutionStats};
+pub use value::Value;
 
 pub trait Eval {
     fn eval(self, env: Rc<Environment>) -> Result<Object>;
@@ -15,6 +65,7 @@ pub trait Eval {
 
 impl Eval for Program {
     fn eval(self, env: Rc<Environment>) -> Result<Object> {
+        span!("eval_program");
         let mut result = Object::Null;
         for statement in self.statements {
             result = statement.eval(Rc::clone(&env))?;
@@ -26,210 +77,862 @@ impl Eval for Program {
     }
 }
 
+/// Evaluates an operand of an expression or statement, returning early from it with the `return`
+/// the operand ran into, e.g. through a `?`, or the `break` or `continue`.
+macro_rules! operand {
+    ($expr:expr, $env:expr) => {
+        match $expr.eval(Rc::clone($env))? {
+            signal @ (Object::Return(_) | Object::Break | Object::Continue) => return Ok(signal),
+            obj => obj,
+        }
+    };
+}
+
 impl Eval for Statement {
     fn eval(self, env: Rc<Environment>) -> Result<Object> {
+        env.meter().step()?;
         match self {
             Statement::Expr(expr) => expr.eval(Rc::clone(&env)),
             Statement::Block(stmts) => {
                 let mut result: Object = Object::Null;
                 for statement in stmts {
                     result = statement.eval(Rc::clone(&env))?;
-                    if matches!(result, Object::Return(_)) {
+                    if matches!(result, Object::Return(_) | Object::Break | Object::Continue) {
                         return Ok(result);
                     }
                 }
                 Ok(result)
             }
-            Statement::Let { name, value } => {
-                let obj = value.eval(Rc::clone(&env))?;
-                Ok(env.set(name, obj))
+            Statement::Let {
+                name,
+                mutable,
+                value,
+                ..
+            } => {
+                let obj = match push_in_place(&name, &value, &env, false)? {
+                    Some(obj) => obj,
+                    None => operand!(value, &env),
+                };
+                if mutable {
+                    Ok(env.set_mut(name, obj))
+                } else {
+                    Ok(env.set(name, obj))
+                }
             }
-            Statement::Return { value } => {
-                Ok(Object::Return(Box::new(value.eval(Rc::clone(&env))?)))
+            Statement::Assign { name, value } => {
+                let obj = match push_in_place(&name, &value, &env, true)? {
+                    Some(obj) => obj,
+                    None => operand!(value, &env),
+                };
+                env.assign(&name, obj)
             }
+            Statement::Destructure { pattern, value } => {
+                let obj = operand!(value, &env);
+                let mut bindings = vec![];
+                if !pattern::matches(&pattern, &obj, &mut bindings) {
+                    fail!(
+                        ErrorCode::PatternMismatch,
+                        "{obj} doesn't match the pattern {pattern}"
+                    );
+                }
+                for (name, value) in bindings {
+                    env.set(name, value);
+                }
+                Ok(obj)
+            }
+            Statement::Struct { name, fields } => {
+                let ty = Rc::new(StructType {
+                    name: name.clone(),
+                    fields,
+                });
+                Ok(env.set(name, Object::StructType(ty)))
+            }
+            Statement::Return { value } => Ok(Object::Return(Box::new(operand!(value, &env)))),
+            Statement::Break => Ok(Object::Break),
+            Statement::Continue => Ok(Object::Continue),
+            Statement::Yield { .. } => fail!(
+                ErrorCode::InvalidYield,
+                "`yield` can only be used as a statement of a generator"
+            ),
         }
     }
 }
 
 impl Eval for Expression {
     fn eval(self, env: Rc<Environment>) -> Result<Object> {
-        Ok(match self {
+        let lookup = matches!(self, Expression::Ident(_));
+        let obj = match self {
             Expression::Bool(value) => Object::Bool(value),
             Expression::Int(value) => Object::Int(value),
+            Expression::Float(value) => Object::Float(value),
             Expression::String(string) => Object::String(string),
             Expression::Ident(ident) if ident == "null" => Object::Null,
-            Expression::Ident(ident) => match ident.as_str() {
-                "len" => Object::Builtin(BuiltinFunction::Len),
-                "first" => Object::Builtin(BuiltinFunction::First),
-                "last" => Object::Builtin(BuiltinFunction::Last),
-                "rest" => Object::Builtin(BuiltinFunction::Rest),
-                "push" => Object::Builtin(BuiltinFunction::Push),
-                _ => env.get(ident),
+            // Bindings shadow the builtins of the same name.
+            Expression::Ident(ident) => match env.get_defined(&ident) {
+                Some(obj) => obj,
+                None => match BuiltinFunction::from_name(&ident) {
+                    Some(builtin_fn) => {
+                        env.policy().check_builtin(&builtin_fn)?;
+                        Object::Builtin(builtin_fn)
+                    }
+                    None => env.lookup(&ident)?,
+                },
             },
-            Expression::Array(content) => Object::Array(
-                content
-                    .iter()
-                    .map(|e| e.to_owned().eval(Rc::clone(&env)))
-                    .collect::<Result<Vec<Object>>>()?,
-            ),
-            Expression::Hash(hash_vec) => {
-                let mut _map = HashMap::new();
-                for (k, v) in hash_vec {
-                    let key_obj = k.eval(Rc::clone(&env))?;
-                    let value = v.eval(Rc::clone(&env))?;
+            Expression::Array(content) => {
+                let mut items = Vec::with_capacity(content.len());
+                for item in content {
+                    items.push(operand!(item, &env));
+                }
+                Object::Array(items.into())
+            }
+            Expression::Tuple(content) => {
+                let mut values = Vec::with_capacity(content.len());
+                for value in content {
+                    values.push(operand!(value, &env));
+                }
+                Object::Tuple(values)
+            }
+            Expression::Hash { pairs, span } => {
+                let mut literal = HashLiteral::default();
+                for (index, (k, v)) in pairs.into_iter().enumerate() {
+                    let key_obj = operand!(k, &env);
+                    let value = operand!(v, &env);
                     let key = match key_obj {
                         Object::Int(key) => HashMapKey::Int(key),
                         Object::String(key) => HashMapKey::String(key),
                         Object::Bool(key) => HashMapKey::Bool(key),
                         _ => {
-                            bail!("Invalid object type for an hash key, must be int, str or bool!",);
+                            fail!(
+                                ErrorCode::InvalidHashKey,
+                                "Invalid object type for an hash key, must be int, str or bool!",
+                            );
                         }
                     };
-                    _map.insert(key, value);
+                    literal.insert(key, span.key(index), value, &env)?;
                 }
-                Object::Hash(_map)
+                Object::Hash(literal.finish())
             }
             Expression::Prefix { operator, right } => {
-                Expression::eval_prefix(operator, right.eval(Rc::clone(&env))?)?
+                Expression::eval_prefix(operator, operand!(right, &env))?
             }
+            Expression::Field { object, name } => field(operand!(object, &env), &name)?,
             Expression::Infix {
-                operator,
+                operator: operator @ (InfixOperator::And | InfixOperator::Or),
                 left,
                 right,
-            } => Expression::eval_infix(
+            } => {
+                let left = operand!(left, &env).to_bool();
+                Object::Bool(match operator {
+                    InfixOperator::And => left && operand!(right, &env).to_bool(),
+                    _ => left || operand!(right, &env).to_bool(),
+                })
+            }
+            Expression::Infix {
                 operator,
-                left.eval(Rc::clone(&env))?,
-                right.eval(Rc::clone(&env))?,
-            )?,
+                left,
+                right,
+            } => {
+                let left = operand!(left, &env);
+                Expression::eval_infix(operator, left, operand!(right, &env))?
+            }
             Expression::Cond { cond, then_, else_ } => {
-                let evaluated_cond = cond.eval(Rc::clone(&env))?.to_bool();
+                let evaluated_cond = operand!(cond, &env).to_bool();
 
                 if evaluated_cond {
-                    Statement::Block(then_).eval(env)?
+                    Statement::Block(then_).eval(Rc::clone(&env))?
                 } else if let Some(stmts) = else_ {
-                    Statement::Block(stmts).eval(env)?
+                    Statement::Block(stmts).eval(Rc::clone(&env))?
                 } else {
                     Object::Null
                 }
             }
-            Expression::Func { args, body } => Object::Function {
+            Expression::Func { args, body, .. } => Object::Function {
+                doc: Statement::docstring(&body).map(str::to_owned),
                 parameters: args,
                 body,
                 environment: Environment::init_with_outer(Rc::clone(&env)),
             },
-            Expression::Call { func, args } => {
-                let func_to_call = func.eval(Rc::clone(&env))?;
-
-                let arguments = args
-                    .into_iter()
-                    .map(|arg| arg.eval(Rc::clone(&env)))
-                    .collect::<Result<Vec<Object>>>()?;
-
-                match func_to_call {
-                    Object::Function {
-                        parameters,
-                        body,
-                        environment: func_env,
-                    } => {
-                        let func_env = Rc::new(Environment::init_with_outer(Rc::new(func_env)));
-
-                        let n_params = parameters.len();
-                        let n_args = arguments.len();
-                        if n_params != n_args {
-                            bail!(
-                                "Invalid function call argument counts, {n_params} requested, {n_args} provided.",
-                            );
-                        }
-
-                        for (name, val) in parameters.iter().zip(arguments) {
-                            func_env.set(name, val);
-                        }
-
-                        let evaluated_func = Statement::Block(body).eval(Rc::clone(&func_env))?;
-                        if let Object::Return(obj) = evaluated_func {
-                            *obj
-                        } else {
-                            evaluated_func
-                        }
+            Expression::Call {
+                func,
+                args,
+                kwargs,
+                span,
+            } => eval_call(*func, args, kwargs, span, &env)?,
+            Expression::For {
+                name,
+                iterable,
+                body,
+            } => {
+                let iter = Iter::from_object(operand!(iterable, &env))?;
+                while let Some(item) = iter.next()? {
+                    env.interrupt().check()?;
+                    env.set(name.as_str(), item);
+                    match Statement::Block(body.clone()).eval(Rc::clone(&env))? {
+                        returned @ Object::Return(_) => return Ok(returned),
+                        Object::Break => break,
+                        _ => {}
+                    }
+                }
+                Object::Null
+            }
+            Expression::While { cond, body } => {
+                while operand!(cond.clone(), &env).to_bool() {
+                    env.interrupt().check()?;
+                    match Statement::Block(body.clone()).eval(Rc::clone(&env))? {
+                        returned @ Object::Return(_) => return Ok(returned),
+                        Object::Break => break,
+                        _ => {}
                     }
-                    Object::Builtin(builtin_fn) => builtin_fn.call(arguments)?,
-                    _ => bail!("{func_to_call} is not a function"),
                 }
+                Object::Null
+            }
+            Expression::Match { subject, arms } => eval_match(*subject, arms, &env)?,
+        };
+        if !lookup && obj.is_allocated() {
+            env.meter().allocated()?;
+        }
+        Ok(obj)
+    }
+}
+
+/// Evaluates the first arm of `match (subject) { arms }` whose pattern matches, and whose
+/// guard holds if it has one, binding the names of the pattern in `env`.
+fn eval_match(subject: Expression, arms: Vec<MatchArm>, env: &Rc<Environment>) -> Result<Object> {
+    let subject = operand!(subject, env);
+    let mut result = Object::Null;
+    for arm in arms {
+        let mut bindings = vec![];
+        if !pattern::matches(&arm.pattern, &subject, &mut bindings) {
+            continue;
+        }
+        // The guard sees the bindings, which only leak to `env` if it holds.
+        if let Some(guard) = arm.guard {
+            let guard_env = Rc::new(Environment::init_with_outer(Rc::clone(env)));
+            for (name, value) in bindings.clone() {
+                guard_env.set(name, value);
+            }
+            if !operand!(guard, &guard_env).to_bool() {
+                continue;
             }
+        }
+        for (name, value) in bindings {
+            env.set(name, value);
+        }
+        result = arm.value.eval(Rc::clone(env))?;
+        break;
+    }
+    Ok(result)
+}
+
+/// Evaluates `func(args, kwargs)`. Errors left without a span get the one of the call.
+fn eval_call(
+    func: Expression,
+    args: Vec<Expression>,
+    kwargs: Vec<(String, Expression)>,
+    span: CallSpan,
+    env: &Rc<Environment>,
+) -> Result<Object> {
+    let func_to_call = operand!(func.clone(), env);
+
+    let mut arguments = Vec::with_capacity(args.len());
+    for arg in args {
+        arguments.push(operand!(arg, env));
+    }
+    let mut keywords = Vec::with_capacity(kwargs.len());
+    for (keyword, value) in kwargs {
+        keywords.push((keyword, operand!(value, env)));
+    }
+
+    if !matches!(
+        func_to_call,
+        Object::Function { .. } | Object::Builtin(_) | Object::Closure(_) | Object::StructType(_)
+    ) {
+        return Err(
+            not_a_function(&expression_to_source(&func), &func_to_call).or_span(span.callee())
+        );
+    }
+    if let Object::Builtin(builtin) = &func_to_call
+        && let Some(message) = builtin.deprecation(&arguments)
+    {
+        env.warn(message);
+    }
+    // `$` names are those of the functions wrapped by builtins like `partial`.
+    let name = match &func {
+        Expression::Ident(name) if !name.starts_with('$') => Some(name.as_str()),
+        _ => None,
+    };
+    let at_call = |err: Error| err.or_span(span.call());
+    let arguments = bind_keywords(&func_to_call, arguments, keywords, name).map_err(at_call)?;
+    env.calling(|| apply(func_to_call, arguments, name))
+        .map_err(at_call)
+}
+
+/// Calls a function or builtin with already evaluated arguments, `name` being the
+/// identifier it was called through, if any.
+pub(crate) fn apply(func: Object, arguments: Vec<Object>, name: Option<&str>) -> Result<Object> {
+    Ok(match func {
+        Object::Function {
+            parameters,
+            body,
+            environment: func_env,
+            ..
+        } => {
+            span!("call", ?parameters);
+            event!(?arguments, "function invoked");
+            let func_env = Rc::new(Environment::init_with_outer(Rc::new(func_env)));
+            func_env.interrupt().check()?;
+            let meter = Rc::clone(func_env.meter());
+            let _call = meter.call()?;
+
+            let n_params = parameters.len();
+            let n_args = arguments.len();
+            if n_params != n_args {
+                fail!(
+                    ErrorCode::ArgumentCount,
+                    "Function `{}({})` expects {n_params} argument{}, got {n_args}.",
+                    name.unwrap_or("fn"),
+                    parameters.join(", "),
+                    if n_params == 1 { "" } else { "s" },
+                );
+            }
+
+            for (name, val) in parameters.iter().zip(arguments) {
+                func_env.set(name, val);
+            }
+
+            if body.iter().any(Statement::yields) {
+                return Ok(Object::Iterator(Iter::generator(func_env, body)));
+            }
+
+            let evaluated_func = Statement::Block(body)
+                .eval(Rc::clone(&func_env))
+                .map_err(|err| err.in_frame(format!("fn({})", parameters.join(", "))))?;
+            if let Object::Return(obj) = evaluated_func {
+                *obj
+            } else {
+                evaluated_func
+            }
+        }
+        Object::Closure(closure) => crate::vm::call(closure, arguments)?,
+        Object::StructType(ty) => {
+            if arguments.len() != ty.fields.len() {
+                let n = ty.fields.len();
+                fail!(
+                    ErrorCode::ArgumentCount,
+                    "Struct `{}({})` expects {n} argument{}, got {}.",
+                    ty.name,
+                    ty.fields.join(", "),
+                    if n == 1 { "" } else { "s" },
+                    arguments.len()
+                );
+            }
+            Object::Struct {
+                ty,
+                values: arguments,
+            }
+        }
+        Object::Builtin(builtin_fn) => {
+            event!(builtin = %builtin_fn, ?arguments, "builtin invoked");
+            builtin_fn
+                .call(arguments)
+                .map_err(|err| err.in_frame(format!("builtin {builtin_fn}")))?
+        }
+        _ => fail!(ErrorCode::NotAFunction, "{func} is not a function"),
+    })
+}
+
+/// The error of calling `obj`, the value of the expression printed as `callee`.
+pub(crate) fn not_a_function(callee: &str, obj: &Object) -> Error {
+    Error::new(
+        ErrorCode::NotAFunction,
+        format!("`{callee}` is not a function, found {obj}"),
+    )
+}
+
+/// Places keyword arguments among the positional ones, in the order of the parameters
+/// of `func`. Fails on names that aren't parameters or that are already given, and on
+/// parameters left without an argument.
+pub(crate) fn bind_keywords(
+    func: &Object,
+    arguments: Vec<Object>,
+    keywords: Vec<(String, Object)>,
+    name: Option<&str>,
+) -> Result<Vec<Object>> {
+    if keywords.is_empty() {
+        return Ok(arguments);
+    }
+    let (kind, parameters, own_name) = match func {
+        Object::Function { parameters, .. } => ("Function", parameters, None),
+        Object::Closure(closure) => (
+            "Function",
+            &closure.function().parameters,
+            closure.function().name.as_deref(),
+        ),
+        Object::StructType(ty) => ("Struct", &ty.fields, Some(ty.name.as_str())),
+        _ => fail!(
+            ErrorCode::InvalidArgument,
+            "{func} doesn't take keyword arguments"
+        ),
+    };
+    let signature = || {
+        let name = name.or(own_name).unwrap_or("fn");
+        format!("{kind} `{name}({})`", parameters.join(", "))
+    };
+    let mut slots: Vec<Option<Object>> = arguments.into_iter().map(Some).collect();
+    if slots.len() < parameters.len() {
+        slots.resize(parameters.len(), None);
+    }
+    for (keyword, value) in keywords {
+        let Some(index) = parameters.iter().position(|p| *p == keyword) else {
+            fail!(
+                ErrorCode::InvalidArgument,
+                "{} has no parameter `{keyword}`",
+                signature()
+            );
+        };
+        if slots[index].replace(value).is_some() {
+            fail!(
+                ErrorCode::InvalidArgument,
+                "{} got the argument `{keyword}` twice",
+                signature()
+            );
+        }
+    }
+    slots
+        .into_iter()
+        .enumerate()
+        .map(|(i, slot)| match slot {
+            Some(obj) => Ok(obj),
+            None => fail!(
+                ErrorCode::ArgumentCount,
+                "{} is missing the argument `{}`",
+                signature(),
+                parameters[i]
+            ),
         })
+        .collect()
+}
+
+/// `object.name`: the field of a struct, failing when it has none of that name, or else
+/// `object["name"]`.
+pub(crate) fn field(object: Object, name: &str) -> Result<Object> {
+    match object {
+        Object::Struct { ty, mut values } => match ty.fields.iter().position(|f| f == name) {
+            Some(index) => Ok(values.swap_remove(index)),
+            None => fail!(
+                ErrorCode::InvalidOperation,
+                "Struct `{}` has no field `{name}`",
+                ty.name
+            ),
+        },
+        object => Expression::eval_infix(
+            InfixOperator::Index,
+            object,
+            Object::String(name.to_owned()),
+        ),
+    }
+}
+
+/// Evaluates `let name = push(name, items...)` when `name` is an array defined in `env`
+/// itself by appending to it in place rather than to a copy, so that pushing in a loop
+/// doesn't copy the array every time. With `assign`, evaluates `name = push(name, items...)`
+/// the same way when `name` is a mutable array, wherever it's defined. `None` when `value`
+/// is anything else.
+fn push_in_place(
+    name: &str,
+    value: &Expression,
+    env: &Rc<Environment>,
+    assign: bool,
+) -> Result<Option<Object>> {
+    let Expression::Call {
+        func, args, kwargs, ..
+    } = value
+    else {
+        return Ok(None);
+    };
+    if !kwargs.is_empty() {
+        return Ok(None);
+    }
+    let update = |f: &mut dyn FnMut(&mut Object) -> Option<Object>| {
+        if assign {
+            env.update_mutable(name, f)
+        } else {
+            env.update(name, f)
+        }
+    };
+    let mut is_array = |obj: &mut Object| matches!(obj, Object::Array(_)).then_some(Object::Null);
+    match (&**func, &args[..]) {
+        // A binding named `push` shadows the builtin.
+        (Expression::Ident(func), [Expression::Ident(collection), _, ..])
+            if func == "push"
+                && collection == name
+                && env.get_defined("push").is_none()
+                && update(&mut is_array).flatten().is_some() => {}
+        _ => return Ok(None),
     }
+    env.policy().check_builtin(&BuiltinFunction::Push)?;
+    let mut items = args[1..]
+        .iter()
+        .map(|item| item.clone().eval(Rc::clone(env)))
+        .collect::<Result<Vec<Object>>>()?;
+    // An `if` among the items may have rebound `name` to something else.
+    let pushed = update(&mut |obj| match obj {
+        Object::Array(content) => {
+            Rc::make_mut(content).append(&mut items);
+            Some(obj.clone())
+        }
+        _ => None,
+    });
+    let obj = match pushed.flatten() {
+        Some(obj) => obj,
+        None => {
+            items.insert(0, env.lookup(name)?);
+            apply(Object::Builtin(BuiltinFunction::Push), items, Some("push"))?
+        }
+    };
+    env.meter().allocated()?;
+    Ok(Some(obj))
 }
 
 impl Expression {
-    fn eval_prefix(operator: PrefixOperator, right: Object) -> Result<Object> {
+    pub(crate) fn eval_prefix(operator: PrefixOperator, right: Object) -> Result<Object> {
         match operator {
             PrefixOperator::Neg => Self::eval_neg(right),
             PrefixOperator::Not => Ok(Object::Bool(!right.to_bool())),
+            PrefixOperator::Plus => match right {
+                Object::Int(_) | Object::Float(_) => Ok(right),
+                _ => fail!(
+                    ErrorCode::InvalidOperation,
+                    "Unary `+` expects a number, found {right}!"
+                ),
+            },
         }
     }
 
     fn eval_neg(right: Object) -> Result<Object> {
         match right {
-            Object::Int(value) => Ok(Object::Int(-value)),
+            Object::Int(value) => match value.checked_neg() {
+                Some(value) => Ok(Object::Int(value)),
+                None => fail!(
+                    ErrorCode::IntegerOverflow,
+                    "Integer overflow in the negation of {value}"
+                ),
+            },
+            Object::Float(value) => Ok(Object::Float(-value)),
             Object::Return(value) => Self::eval_neg(*value),
-            _ => bail!("{right} cannot be negated!"),
+            _ => fail!(ErrorCode::InvalidNegation, "{right} cannot be negated!"),
         }
     }
 
-    fn eval_infix(operator: InfixOperator, left: Object, right: Object) -> Result<Object> {
+    pub(crate) fn eval_infix(
+        operator: InfixOperator,
+        left: Object,
+        right: Object,
+    ) -> Result<Object> {
+        if let Some(obj) = Self::eval_float(&operator, &left, &right) {
+            return Ok(obj);
+        }
         match (left, right, operator) {
             (Object::Null, Object::Null, _) => Ok(Object::Null),
             (Object::Bool(l), Object::Bool(r), InfixOperator::Eq) => Ok(Object::Bool(l == r)),
             (Object::Bool(l), Object::Bool(r), InfixOperator::NotEq) => Ok(Object::Bool(l != r)),
-            (Object::Int(l), Object::Int(r), InfixOperator::Add) => Ok(Object::Int(l + r)),
-            (Object::Int(l), Object::Int(r), InfixOperator::Sub) => Ok(Object::Int(l - r)),
-            (Object::Int(l), Object::Int(r), InfixOperator::Mul) => Ok(Object::Int(l * r)),
-            (Object::Int(l), Object::Int(r), InfixOperator::Div) => Ok(Object::Int(l / r)),
+            (l, r, InfixOperator::And) => Ok(Object::Bool(l.to_bool() && r.to_bool())),
+            (l, r, InfixOperator::Or) => Ok(Object::Bool(l.to_bool() || r.to_bool())),
+            (Object::Int(_), Object::Int(0), InfixOperator::Div | InfixOperator::Mod) => {
+                fail!(ErrorCode::DivisionByZero, "Division by zero")
+            }
+            (
+                Object::Int(l),
+                Object::Int(r),
+                operator @ (InfixOperator::Add
+                | InfixOperator::Sub
+                | InfixOperator::Mul
+                | InfixOperator::Div),
+            ) => {
+                let result = match operator {
+                    InfixOperator::Add => l.checked_add(r),
+                    InfixOperator::Sub => l.checked_sub(r),
+                    InfixOperator::Mul => l.checked_mul(r),
+                    _ => l.checked_div(r),
+                };
+                match result {
+                    Some(result) => Ok(Object::Int(result)),
+                    None => fail!(
+                        ErrorCode::IntegerOverflow,
+                        "Integer overflow in {operator} of {l} and {r}"
+                    ),
+                }
+            }
+            (Object::Int(l), Object::Int(r), InfixOperator::Mod) => {
+                Ok(Object::Int(l.wrapping_rem(r)))
+            }
+            (Object::Int(l), Object::Int(r), InfixOperator::Pow) => match u32::try_from(r) {
+                Ok(exponent) => Ok(Object::Int(l.wrapping_pow(exponent))),
+                Err(_) => fail!(
+                    ErrorCode::InvalidOperation,
+                    "Invalid exponent {r}, must be between 0 and {}",
+                    u32::MAX
+                ),
+            },
             (Object::Int(l), Object::Int(r), InfixOperator::Eq) => Ok(Object::Bool(l == r)),
             (Object::Int(l), Object::Int(r), InfixOperator::NotEq) => Ok(Object::Bool(l != r)),
             (Object::Int(l), Object::Int(r), InfixOperator::Gt) => Ok(Object::Bool(l > r)),
             (Object::Int(l), Object::Int(r), InfixOperator::Lt) => Ok(Object::Bool(l < r)),
+            (Object::Int(l), Object::Int(r), InfixOperator::GtEq) => Ok(Object::Bool(l >= r)),
+            (Object::Int(l), Object::Int(r), InfixOperator::LtEq) => Ok(Object::Bool(l <= r)),
             (Object::String(l), Object::String(r), InfixOperator::Add) => {
                 Ok(Object::String(l + &r))
             }
+            (Object::String(l), Object::String(r), InfixOperator::Eq) => Ok(Object::Bool(l == r)),
+            (Object::String(l), Object::String(r), InfixOperator::NotEq) => {
+                Ok(Object::Bool(l != r))
+            }
+            (Object::String(l), Object::String(r), InfixOperator::Gt) => Ok(Object::Bool(l > r)),
+            (Object::String(l), Object::String(r), InfixOperator::Lt) => Ok(Object::Bool(l < r)),
+            (Object::String(l), Object::String(r), InfixOperator::GtEq) => Ok(Object::Bool(l >= r)),
+            (Object::String(l), Object::String(r), InfixOperator::LtEq) => Ok(Object::Bool(l <= r)),
+            (Object::Bytes(mut l), Object::Bytes(r), InfixOperator::Add) => {
+                l.extend(r);
+                Ok(Object::Bytes(l))
+            }
+            (Object::Bytes(bytes), Object::Int(index), InfixOperator::Index) => {
+                Ok(usize::try_from(index)
+                    .ok()
+                    .and_then(|index| bytes.get(index))
+                    .map_or(Object::Null, |byte| Object::Int(i64::from(*byte))))
+            }
             (Object::Array(content), Object::Int(index), InfixOperator::Index) => {
-                if index < 0 || index >= content.len().try_into().unwrap() {
-                    return Ok(Object::Null);
+                Ok(usize::try_from(index)
+                    .ok()
+                    .and_then(|index| content.get(index))
+                    .map_or(Object::Null, Object::clone))
+            }
+            (Object::Tuple(mut values), Object::Int(index), InfixOperator::Index) => {
+                Ok(match usize::try_from(index) {
+                    Ok(index) if index < values.len() => values.swap_remove(index),
+                    _ => Object::Null,
+                })
+            }
+            (Object::Error { message, data }, Object::String(key), InfixOperator::Index) => {
+                Ok(match key.as_str() {
+                    "message" => Object::String(message),
+                    "data" => *data,
+                    _ => Object::Null,
+                })
+            }
+            (l @ Object::Tuple(_), r @ Object::Tuple(_), InfixOperator::Eq) => {
+                Ok(Object::Bool(l == r))
+            }
+            (l @ Object::Tuple(_), r @ Object::Tuple(_), InfixOperator::NotEq) => {
+                Ok(Object::Bool(l != r))
+            }
+            (l @ Object::Struct { .. }, r @ Object::Struct { .. }, InfixOperator::Eq) => {
+                Ok(Object::Bool(l == r))
+            }
+            (l @ Object::Struct { .. }, r @ Object::Struct { .. }, InfixOperator::NotEq) => {
+                Ok(Object::Bool(l != r))
+            }
+            (Object::Struct { ty, values }, Object::String(key), InfixOperator::Index) => {
+                match ty.fields.iter().position(|f| *f == key) {
+                    Some(index) => Ok(values[index].clone()),
+                    None => Self::overloaded(
+                        &InfixOperator::Index,
+                        Object::Struct { ty, values },
+                        Object::String(key),
+                    )
+                    .map(|obj| obj.unwrap_or(Object::Null)),
                 }
-                Ok(content[index as usize].clone())
             }
             (Object::Hash(map), key_object, InfixOperator::Index) => {
-                let value = match key_object {
-                    Object::Bool(key) => map.get(&HashMapKey::Bool(key)),
-                    Object::Int(key) => map.get(&HashMapKey::Int(key)),
-                    Object::String(key) => map.get(&HashMapKey::String(key)),
+                let value = match &key_object {
+                    Object::Bool(key) => map.get(&HashMapKey::Bool(*key)),
+                    Object::Int(key) => map.get(&HashMapKey::Int(*key)),
+                    Object::String(key) => map.get(&HashMapKey::String(key.clone())),
                     _ => {
-                        bail!(
+                        fail!(
+                            ErrorCode::InvalidOperation,
                             "Invalid operation ({}) between {} and {key_object}!",
                             Object::Hash(map),
                             InfixOperator::Index
                         );
                     }
                 };
-                Ok(match value {
-                    Some(v) => v.clone(),
-                    None => Object::Null,
-                })
-            }
-            (l, r, op) => {
-                bail!("Invalid operation ({op}) between {l} and {r}!");
+                match value {
+                    Some(v) => Ok(v.clone()),
+                    None => Self::overloaded(&InfixOperator::Index, Object::Hash(map), key_object)
+                        .map(|obj| obj.unwrap_or(Object::Null)),
+                }
             }
+            (l, r, op) => match Self::overloaded(&op, l.clone(), r.clone())? {
+                Some(obj) => Ok(obj),
+                None => fail!(
+                    ErrorCode::InvalidOperation,
+                    "Invalid operation ({op}) between {l} and {r}!"
+                ),
+            },
         }
     }
+
+    /// Calls the hook of the left operand overloading `operator`, like `__add` for `+`,
+    /// with both operands: a function in the hash or the struct under that name. `!=`
+    /// negates `__eq`, and `__index` is only called for keys that are missing. `None`
+    /// when there's no such hook.
+    fn overloaded(operator: &InfixOperator, left: Object, right: Object) -> Result<Option<Object>> {
+        let name = match operator {
+            InfixOperator::Add => "__add",
+            InfixOperator::Sub => "__sub",
+            InfixOperator::Mul => "__mul",
+            InfixOperator::Div => "__div",
+            InfixOperator::Mod => "__mod",
+            InfixOperator::Pow => "__pow",
+            InfixOperator::Eq | InfixOperator::NotEq => "__eq",
+            InfixOperator::Gt => "__gt",
+            InfixOperator::Lt => "__lt",
+            InfixOperator::GtEq => "__ge",
+            InfixOperator::LtEq => "__le",
+            InfixOperator::Index => "__index",
+            InfixOperator::And | InfixOperator::Or => return Ok(None),
+        };
+        let hook = match &left {
+            Object::Hash(map) => map.get(&HashMapKey::String(name.to_owned())).cloned(),
+            Object::Struct { ty, values } => ty
+                .fields
+                .iter()
+                .position(|f| f == name)
+                .map(|index| values[index].clone()),
+            _ => None,
+        };
+        let Some(hook) = hook.filter(|hook| {
+            matches!(
+                hook,
+                Object::Function { .. }
+                    | Object::Builtin(_)
+                    | Object::Closure(_)
+                    | Object::StructType(_)
+            )
+        }) else {
+            return Ok(None);
+        };
+        let output = apply(hook, vec![left, right], Some(name))?;
+        Ok(Some(match operator {
+            InfixOperator::NotEq => Object::Bool(!output.to_bool()),
+            _ => output,
+        }))
+    }
+
+    /// Arithmetic and comparisons where an operand is a float, the other one being
+    /// converted if it's an int. `None` for any other operation.
+    fn eval_float(operator: &InfixOperator, left: &Object, right: &Object) -> Option<Object> {
+        let (l, r) = match (left, right) {
+            (Object::Float(l), Object::Float(r)) => (*l, *r),
+            (Object::Int(l), Object::Float(r)) => (*l as f64, *r),
+            (Object::Float(l), Object::Int(r)) => (*l, *r as f64),
+            _ => return None,
+        };
+        Some(match operator {
+            InfixOperator::Add => Object::Float(l + r),
+            InfixOperator::Sub => Object::Float(l - r),
+            InfixOperator::Mul => Object::Float(l * r),
+            InfixOperator::Div => Object::Float(l / r),
+            InfixOperator::Mod => Object::Float(l % r),
+            InfixOperator::Eq => Object::Bool(l == r),
+            InfixOperator::NotEq => Object::Bool(l != r),
+            InfixOperator::Gt => Object::Bool(l > r),
+            InfixOperator::Lt => Object::Bool(l < r),
+            InfixOperator::GtEq => Object::Bool(l >= r),
+            InfixOperator::LtEq => Object::Bool(l <= r),
+            InfixOperator::Pow | InfixOperator::And | InfixOperator::Or | InfixOperator::Index => {
+                return None;
+            }
+        })
+    }
+}
+
+/// Whether `name` is the name of a builtin, which bindings of the same name shadow.
+pub fn is_builtin(name: &str) -> bool {
+    name == "null" || BuiltinFunction::from_name(name).is_some()
 }
 
+/// Names resolving to builtins, `null` included.
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    all_builtins()
+        .into_iter()
+        .map(|builtin| builtin.name)
+        .chain(["null"])
+}
+
+/// Parses and evaluates `input`; with `std`, panics are reported as [`ErrorCode::Internal`] errors.
 pub fn eval_with_env(input: &str, env: Rc<Environment>) -> Result<Object> {
-    Parser::init(input).parse_program()?.eval(env)
+    catch_unwind(|| {
+        let program = Parser::init(input).parse_program()?;
+        #[cfg(feature = "std")]
+        crate::analysis::check_assignments(&crate::cst::parse(input))?;
+        program.eval(env)
+    })
+}
+
+/// Like [`eval_with_env`], also reporting the resources the evaluation used.
+pub fn eval_with_stats(input: &str, env: Rc<Environment>) -> (Result<Object>, ExecutionStats) {
+    measure(&env, || eval_with_env(input, Rc::clone(&env)))
+}
+
+/// Runs `eval`, counting what it does in `env` and the environments created from it.
+pub(crate) fn measure<T>(
+    env: &Environment,
+    eval: impl FnOnce() -> Result<T>,
+) -> (Result<T>, ExecutionStats) {
+    #[cfg(feature = "std")]
+    let start = std::time::Instant::now();
+    env.meter().reset();
+    let result = eval();
+    #[cfg(feature = "std")]
+    let duration = start.elapsed();
+    #[cfg(not(feature = "std"))]
+    let duration = core::time::Duration::ZERO;
+    (result, env.meter().stats(duration))
+}
+
+/// Like [`eval_with_env`], but gives control back to the executor after every `fuel`
+/// statements (see [`Execution`]), so that many evaluations can share a thread.
+/// Doesn't depend on a specific async runtime.
+#[cfg(feature = "async")]
+pub async fn eval_async(input: &str, env: Rc<Environment>, fuel: usize) -> Result<Object> {
+    let program = Parser::init(input).parse_program()?;
+    let mut execution = Execution::new(program, env);
+    loop {
+        if let Some(obj) = catch_unwind(|| execution.run(fuel.max(1)))? {
+            return Ok(obj);
+        }
+        YieldNow(false).await;
+    }
+}
+
+/// Returns `Pending` once, asking to be polled again right away.
+#[cfg(feature = "async")]
+struct YieldNow(bool);
+
+#[cfg(feature = "async")]
+impl core::future::Future for YieldNow {
+    type Output = ();
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context,
+    ) -> core::task::Poll<()> {
+        if self.0 {
+            return core::task::Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        core::task::Poll::Pending
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::printer::statement_to_source;
+    use core::cell::RefCell;
 
     fn assert_eval(input: &str, expected: Object) {
         let env = Environment::default();
@@ -254,6 +957,86 @@ mod tests {
         assert_eval("3 * 3 * 3 + 10", Object::Int(37));
         assert_eval("3 * (3 * 3) + 10", Object::Int(37));
         assert_eval("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Int(50));
+        assert_eval("+5", Object::Int(5));
+        assert_eval("1 - +2 * -+3", Object::Int(7));
+        assert_eval("+1.5", Object::Float(1.5));
+        let err = eval_with_env("+\"5\"", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.message, "Unary `+` expects a number, found 5!");
+    }
+
+    #[test]
+    fn remainder_and_power() {
+        assert_eval("17 % 5", Object::Int(2));
+        assert_eval("-17 % 5", Object::Int(-2));
+        assert_eval("1 + 10 % 4 * 3", Object::Int(7));
+        assert_eval("2 ** 10", Object::Int(1024));
+        assert_eval("2 ** 3 ** 2", Object::Int(512));
+        assert_eval("2 * 3 ** 2", Object::Int(18));
+        assert_eval("-2 ** 2", Object::Int(4));
+        assert_eval("5 ** 0", Object::Int(1));
+        assert_eval("2 ** 64", Object::Int(0));
+        assert_eval("7.5 % 2", Object::Float(1.5));
+        assert_eval("(-9223372036854775807 - 1) % -1", Object::Int(0));
+        for input in ["1 / 0", "1 % 0"] {
+            let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(err.code, ErrorCode::DivisionByZero);
+        }
+        for input in [
+            "9223372036854775807 + 1",
+            "-9223372036854775807 - 2",
+            "4294967296 * 4294967296",
+            "(-9223372036854775807 - 1) / -1",
+            "-(-9223372036854775807 - 1)",
+        ] {
+            let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(err.code, ErrorCode::IntegerOverflow, "{input}");
+            let err = crate::vm::run(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(err.code, ErrorCode::IntegerOverflow, "{input}");
+        }
+        let err = eval_with_env("2 ** -1", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidOperation);
+        let err = eval_with_env("2.0 ** 2", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidOperation);
+    }
+
+    #[test]
+    fn float_expression() {
+        assert_eval("1.5", Object::Float(1.5));
+        assert_eval("-2.5e-1", Object::Float(-0.25));
+        assert_eval("0.1 + 0.2", Object::Float(0.1 + 0.2));
+        assert_eval("7 / 2.0", Object::Float(3.5));
+        assert_eval("2.5 * 2", Object::Float(5.0));
+        assert_eval("1 - 0.5 - 0.5", Object::Float(0.0));
+        assert_eval("1.0 / 0", Object::Float(f64::INFINITY));
+        assert_eval("1.5 > 1", Object::Bool(true));
+        assert_eval("2 < 1.5", Object::Bool(false));
+        assert_eval("1.5 >= 1.5", Object::Bool(true));
+        assert_eval("2 <= 1.5", Object::Bool(false));
+        assert_eval("2 == 2.0", Object::Bool(true));
+        assert_eval("0.1 + 0.2 != 0.3", Object::Bool(true));
+        assert_eval("!0.0", Object::Bool(true));
+        assert_eval(
+            "[1.0, 2.5e20, 1e-7, -0.0]",
+            Object::Array(
+                vec![
+                    Object::Float(1.0),
+                    Object::Float(2.5e20),
+                    Object::Float(1e-7),
+                    Object::Float(-0.0),
+                ]
+                .into(),
+            ),
+        );
+        assert_eq!(
+            eval_with_env("[1.0, 2.5e20, 1e-7, 0.5]", Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[1.0, 2.5e20, 1e-7, 0.5]"
+        );
+        let err = eval_with_env("[1][0.0]", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidOperation);
+        let err = eval_with_env("{1.5: 1}", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidHashKey);
     }
 
     #[test]
@@ -285,6 +1068,45 @@ mod tests {
         assert_eval("(1 < 2) == false", Object::Bool(false));
         assert_eval("(1 > 2) == true", Object::Bool(false));
         assert_eval("(1 > 2) == false", Object::Bool(true));
+        assert_eval("1 <= 1", Object::Bool(true));
+        assert_eval("2 <= 1", Object::Bool(false));
+        assert_eval("1 >= 2", Object::Bool(false));
+        assert_eval("2 >= 2", Object::Bool(true));
+        assert_eval("1 + 1 >= 2 == true", Object::Bool(true));
+        assert_eval("\"abc\" <= \"abd\"", Object::Bool(true));
+        assert_eval("\"b\" >= \"abc\"", Object::Bool(true));
+        assert_eval("\"ab\" >= \"abc\"", Object::Bool(false));
+        assert_eval("\"a\" <= \"a\" && \"a\" >= \"a\"", Object::Bool(true));
+        assert_eval(
+            "[\"a\" == \"a\", \"a\" != \"a\", \"a\" < \"B\"]",
+            Object::Array(Rc::new(vec![
+                Object::Bool(true),
+                Object::Bool(false),
+                Object::Bool(false),
+            ])),
+        );
+        assert_eval("1 < 2 && 2 < 3", Object::Bool(true));
+        assert_eval("1 > 2 || 2 > 3", Object::Bool(false));
+        assert_eval("true || false && false", Object::Bool(true));
+        assert_eval("\"a\" && [1]", Object::Bool(true));
+        assert_eval("0 || null", Object::Bool(false));
+    }
+
+    #[test]
+    fn short_circuit() {
+        assert_eval("false && missing", Object::Bool(false));
+        assert_eval("true || 1(2)", Object::Bool(true));
+        assert_eval(
+            "let mut calls = 0; let f = fn(x) { calls = calls + 1; x }; \
+             [f(false) && f(true), f(true) || f(true), f(0) || f(1), calls]",
+            Object::Array(Rc::new(vec![
+                Object::Bool(false),
+                Object::Bool(true),
+                Object::Bool(true),
+                Object::Int(4),
+            ])),
+        );
+        assert!(crate::eval("true && missing").is_err());
     }
 
     #[test]
@@ -370,13 +1192,60 @@ mod tests {
         assert_eval("len(\"\")", Object::Int(0));
         assert_eval("len(\"four\")", Object::Int(4));
         assert_eval("len(\"hello world\")", Object::Int(11));
+        assert_eval("rest(\"héllo\")", Object::String("éllo".into()));
+        assert_eval("rest(\"été\")", Object::String("té".into()));
+        assert_eval("rest(\"é\")", Object::String("".into()));
+    }
+
+    #[test]
+    fn shadowed_builtins() {
+        assert_eval("let lines = 5; lines", Object::Int(5));
+        assert_eval("let map = fn(a, b, c) { a }; map(1, 2, 3)", Object::Int(1));
+        assert_eval("let f = fn(len) { len * 2 }; f(4)", Object::Int(8));
+        assert_eval(
+            "let f = fn() { let first = 3; first }; [f(), first([1])]",
+            Object::Array(vec![Object::Int(3), Object::Int(1)].into()),
+        );
+    }
+
+    #[test]
+    fn builtin_registry() {
+        for builtin in BUILTINS {
+            assert_eq!(
+                BuiltinFunction::from_name(builtin.name),
+                Some(builtin.function.clone())
+            );
+            assert_eq!(builtin.function.to_string(), builtin.name);
+        }
+        assert_eq!(BuiltinFunction::Range.info().arity.to_string(), "1 to 3");
+        assert_eq!(
+            eval_with_env("help(\"first\")", Rc::new(Environment::default())).unwrap(),
+            Object::String(
+                "first(value)\nFirst character of a string or element of an array, `null` when empty."
+                    .to_owned()
+            )
+        );
+        assert!(builtins_reference().contains("\n## `fields(string, separator)`\n"));
+    }
+
+    #[test]
+    fn docstrings() {
+        let input = "let area = fn(w, h) { \"Area of a w by h rectangle.\"; w * h }; \
+                     [area(2, 3), help(area), help(fn(x) { x }), help(fn() { \"value\" }), help(iter)]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[6, fn(w, h)\nArea of a w by h rectangle., fn(x), fn(), \
+             iter(iterable)\nIterator over a string, array, hash or iterator.]"
+        );
     }
 
     #[test]
     fn array_literals() {
         assert_eval(
             "[1, 2 * 2, 3 + 3]",
-            Object::Array(vec![Object::Int(1), Object::Int(4), Object::Int(6)]),
+            Object::Array(vec![Object::Int(1), Object::Int(4), Object::Int(6)].into()),
         );
     }
 
@@ -398,6 +1267,7 @@ mod tests {
         );
         assert_eval("[1, 2, 3][3]", Object::Null);
         assert_eval("[1, 2, 3][-1]", Object::Null);
+        assert_eval("[1, 2, 3][4000000000 * 2000000000]", Object::Null);
     }
 
     #[test]
@@ -420,12 +1290,15 @@ mod tests {
 
         assert_eval(
             input,
-            Object::Array(vec![
-                Object::Int(2),
-                Object::Int(4),
-                Object::Int(6),
-                Object::Int(8),
-            ]),
+            Object::Array(
+                vec![
+                    Object::Int(2),
+                    Object::Int(4),
+                    Object::Int(6),
+                    Object::Int(8),
+                ]
+                .into(),
+            ),
         );
     }
 
@@ -465,16 +1338,9 @@ mod tests {
             }\n\
         ";
 
-        assert_eval(
+        crate::assert_monkey_eq!(
             input,
-            Object::Hash(HashMap::from([
-                (HashMapKey::String(String::from("one")), Object::Int(1)),
-                (HashMapKey::String(String::from("two")), Object::Int(2)),
-                (HashMapKey::String(String::from("three")), Object::Int(3)),
-                (HashMapKey::Int(4), Object::Int(4)),
-                (HashMapKey::Bool(true), Object::Int(5)),
-                (HashMapKey::Bool(false), Object::Int(6)),
-            ])),
+            "{\"one\": 1, \"two\": 2, \"three\": 3, 4: 4, true: 5, false: 6}"
         );
     }
 
@@ -488,4 +1354,1055 @@ mod tests {
         assert_eval("{true: 5}[true]", Object::Int(5));
         assert_eval("{false: 5}[false]", Object::Int(5));
     }
+
+    #[test]
+    fn iterators() {
+        let ints = |values: &[i64]| {
+            Object::Array(Rc::new(values.iter().map(|&v| Object::Int(v)).collect()))
+        };
+        assert_eval("collect(range(3))", ints(&[0, 1, 2]));
+        assert_eval("collect(range(5, 0, -2))", ints(&[5, 3, 1]));
+        assert_eval(
+            "collect(map(filter([1, 2, 3, 4], fn(x) { x / 2 * 2 == x }), fn(x) { x * 10 }))",
+            ints(&[20, 40]),
+        );
+        assert_eval("let it = iter([1, 2]); next(it); [next(it), next(it)]", {
+            Object::Array(vec![Object::Int(2), Object::Null].into())
+        });
+        // Adapters are lazy: only the consumed elements are computed.
+        assert_eval(
+            "next(map(range(1000000000), fn(x) { x + 1 }))",
+            Object::Int(1),
+        );
+        assert_eval(
+            "collect({\"b\": 2, \"a\": 1})",
+            Object::Array(
+                vec![
+                    Object::Array(vec![Object::String(String::from("a")), Object::Int(1)].into()),
+                    Object::Array(vec![Object::String(String::from("b")), Object::Int(2)].into()),
+                ]
+                .into(),
+            ),
+        );
+
+        let env = Rc::new(Environment::default());
+        for (input, message) in [
+            ("range(1, 2, 0)", "The step of a range can't be 0"),
+            (
+                "range()",
+                "Builtin function `range` expects 1 to 3 args, found 0.",
+            ),
+            ("iter(5)", "Cannot iterate over 5"),
+            (
+                "map([1], 2)",
+                "Invalid second argument for builtin function `map`, expected function, found 2",
+            ),
+        ] {
+            let err = eval_with_env(input, Rc::clone(&env)).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn for_loops() {
+        assert_eval(
+            "let total = 0; for (x in range(1, 5)) { let total = total + x; }; total",
+            Object::Int(10),
+        );
+        assert_eval(
+            "let s = \"\"; for (c in \"abc\") { let s = c + s; }; s",
+            Object::String(String::from("cba")),
+        );
+        assert_eval("for (x in []) { x }", Object::Null);
+        assert_eval("for (x in [1, 2, 3]) { x }; x", Object::Int(3));
+        assert_eval(
+            "let find = fn(items) { for (x in items) { if (x > 1) { return x; } }; 0 }; find([1, 5, 9])",
+            Object::Int(5),
+        );
+    }
+
+    #[test]
+    fn while_loops() {
+        assert_eval(
+            "let i = 0; let total = 0; while (i < 5) { let total = total + i; let i = i + 1; }; total",
+            Object::Int(10),
+        );
+        assert_eval("while (false) { 1 }", Object::Null);
+        assert_eval("let i = 3; while (i) { let i = i - 1; }", Object::Null);
+        assert_eval(
+            "let find = fn(items) { let i = 0; while (i < len(items)) { if (items[i] > 1) { return items[i]; }; let i = i + 1; }; 0 }; [find([1, 5, 9]), find([])]",
+            Object::Array(vec![Object::Int(5), Object::Int(0)].into()),
+        );
+        assert_eval(
+            "let countdown = fn(n) { while (n > 0) { yield n; let n = n - 1; } }; collect(countdown(3))",
+            Object::Array(vec![Object::Int(3), Object::Int(2), Object::Int(1)].into()),
+        );
+    }
+
+    #[test]
+    fn loop_control() {
+        crate::assert_monkey_eq!(
+            "let xs = []; for (x in range(10)) { if (x == 5) { break; }; if (x / 2 * 2 == x) { continue; }; let xs = push(xs, x); }; xs",
+            "[1, 3]",
+        );
+        crate::assert_monkey_eq!(
+            "let i = 0; let xs = []; while (true) { let i = i + 1; if (i > 4) { break; }; if (i == 2) { continue; }; let xs = push(xs, i); }; xs",
+            "[1, 3, 4]",
+        );
+        // Only the innermost loop is left, even from an expression.
+        crate::assert_monkey_eq!(
+            "let xs = []; for (x in range(3)) { for (y in range(3)) { let v = if (y > x) { break; } else { y }; let xs = push(xs, [x, v]); } }; xs",
+            "[[0, 0], [1, 0], [1, 1], [2, 0], [2, 1], [2, 2]]",
+        );
+        crate::assert_monkey_eq!(
+            "let g = fn() { for (x in range(10)) { if (x == 1) { continue; }; if (x == 4) { break; }; yield x; }; yield 10; }; collect(g())",
+            "[0, 2, 3, 10]",
+        );
+    }
+
+    #[test]
+    fn assignments() {
+        crate::assert_monkey_eq!("let mut x = 1; x = x + 1; x", "2");
+        crate::assert_monkey_eq!(
+            "let mut i = 0; let mut xs = []; while (i < 3) { i = i + 1; xs = push(xs, i) }; [i, xs]",
+            "[3, [1, 2, 3]]",
+        );
+        // Closures update the variable they captured instead of shadowing it.
+        crate::assert_monkey_eq!(
+            "let counter = fn() { let mut n = 0; fn() { n = n + 1 } }; let c = counter(); c(); c(); [c(), counter()()]",
+            "[3, 1]",
+        );
+        crate::assert_monkey_eq!(
+            "let x = 1; let f = fn(x) { let mut x = x; x = 10; x }; [f(2), x]",
+            "[10, 1]",
+        );
+        assert_eq!(
+            crate::eval("let f = fn() { y = 1 }; f()")
+                .unwrap_err()
+                .to_string(),
+            "Cannot assign to `y`, it isn't defined: use `let` to define it"
+        );
+    }
+
+    #[test]
+    fn immutable_bindings() {
+        // Rejected before anything runs when the binding is in the same source.
+        #[cfg(feature = "std")]
+        {
+            let env = Rc::new(Environment::default());
+            let err = eval_with_env("let x = 1; puts(x); x = 2;", Rc::clone(&env)).unwrap_err();
+            assert_eq!(
+                (err.code, err.span, env.get("x")),
+                (ErrorCode::ImmutableAssignment, Some(20..21), None)
+            );
+        }
+        for input in [
+            "let f = fn(x) { x = 2 }; f(1)",
+            "for (x in [1]) { x = 2 }",
+            "let mut x = 1; let g = fn() { let x = 2; x = 3 }; g()",
+        ] {
+            let err = crate::eval(input).unwrap_err();
+            assert_eq!(err.code, ErrorCode::ImmutableAssignment, "{input}");
+        }
+        // Otherwise when it runs, a plain `let` making the variable constant again.
+        let env = Rc::new(Environment::default());
+        eval_with_env("let mut total = 1; let limit = 3;", Rc::clone(&env)).unwrap();
+        eval_with_env("total = total + 1;", Rc::clone(&env)).unwrap();
+        let err = eval_with_env("limit = 4;", Rc::clone(&env)).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Cannot assign to `limit`, it isn't mutable: use `let mut` to define it"
+        );
+        eval_with_env("let total = total;", Rc::clone(&env)).unwrap();
+        assert!(eval_with_env("total = 0;", Rc::clone(&env)).is_err());
+        assert_eq!(env.get("total"), Some(Object::Int(2)));
+    }
+
+    #[test]
+    fn generators() {
+        let ints = |values: &[i64]| {
+            Object::Array(Rc::new(values.iter().map(|&v| Object::Int(v)).collect()))
+        };
+        let naturals = "let naturals = fn() { let n = 0; for (x in range(1000000000)) { yield n; let n = n + 1; } };";
+        assert_eval(
+            &format!(
+                "{naturals} collect(map(filter(range(6), fn(x) {{ x > 2 }}), fn(x) {{ x * 2 }}))"
+            ),
+            ints(&[6, 8, 10]),
+        );
+        assert_eval(
+            &format!("{naturals} let it = naturals(); next(it); next(it); next(it)"),
+            Object::Int(2),
+        );
+        assert_eval(
+            "let g = fn(a) { yield a; if (a > 1) { yield a * 10; return 0; yield 5; } else { yield -1; }; yield 99; }; [collect(g(2)), collect(g(1))]",
+            Object::Array(vec![ints(&[2, 20]), ints(&[1, -1, 99])].into()),
+        );
+        // Generators are lazy and independent of each other.
+        assert_eval(
+            "let pairs = fn(xs) { for (x in xs) { for (y in xs) { yield [x, y]; } } }; let a = pairs([1, 2]); let b = pairs([3]); next(a); [next(a), next(b), next(a), next(a), next(a)]",
+            Object::Array(
+                vec![
+                    ints(&[1, 2]),
+                    ints(&[3, 3]),
+                    ints(&[2, 1]),
+                    ints(&[2, 2]),
+                    Object::Null,
+                ]
+                .into(),
+            ),
+        );
+
+        let env = Rc::new(Environment::default());
+        for input in [
+            "yield 1;",
+            "let g = fn() { let x = if (true) { yield 1; }; }; next(g())",
+            "let g = fn() { yield next(it); }; let it = g(); next(it)",
+        ] {
+            let err = eval_with_env(input, Rc::clone(&env)).unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidYield, "{input}");
+        }
+        // A generator that failed is exhausted.
+        eval_with_env(
+            "let g = fn() { yield 1 + true; yield 2; }; let it = g(); next(it)",
+            Rc::clone(&env),
+        )
+        .unwrap_err();
+        assert_eq!(eval_with_env("next(it)", env).unwrap(), Object::Null);
+    }
+
+    #[test]
+    fn text_builtins() {
+        let strings = |values: &[&str]| {
+            Object::Array(Rc::new(
+                values
+                    .iter()
+                    .map(|&v| Object::String(v.to_owned()))
+                    .collect(),
+            ))
+        };
+        assert_eval("lines(\"a\r\nb\n\nc\n\")", strings(&["a", "b", "", "c"]));
+        assert_eval(
+            "words(\"  one\ttwo \n three \")",
+            strings(&["one", "two", "three"]),
+        );
+        assert_eval("fields(\"1,,2\", \",\")", strings(&["1", "", "2"]));
+        assert_eval("fields(\"a::b\", \"::\")", strings(&["a", "b"]));
+        assert_eval("lines(\"\")", strings(&[]));
+
+        let env = Rc::new(Environment::default());
+        for (input, message) in [
+            (
+                "lines(1)",
+                "Invalid argument for builtin function `lines`, expected string, found 1",
+            ),
+            (
+                "words()",
+                "Builtin function `words` expects 1 arg, found 0.",
+            ),
+            (
+                "fields(\"a\", \"\")",
+                "Invalid second argument for builtin function `fields`, expected non-empty string, found ",
+            ),
+        ] {
+            let err = eval_with_env(input, Rc::clone(&env)).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn interrupts() {
+        let env = Rc::new(Environment::default());
+        env.interrupt().trigger();
+        // Nothing to interrupt without calls or loops.
+        assert_eq!(
+            eval_with_env("1 + 1", Rc::clone(&env)).unwrap(),
+            Object::Int(2)
+        );
+        let err = eval_with_env("for (x in range(10)) { x }", Rc::clone(&env)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Interrupted);
+        env.interrupt().trigger();
+        let err = eval_with_env("while (true) {}", Rc::clone(&env)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Interrupted);
+        assert!(!env.interrupt().is_triggered());
+        assert_eq!(
+            eval_with_env("let f = fn() { 1 }; f()", Rc::clone(&env)).unwrap(),
+            Object::Int(1)
+        );
+
+        let child = Environment::init_with_outer(Rc::clone(&env));
+        env.interrupt().trigger();
+        let err = eval_with_env("f()", Rc::new(child)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Interrupted);
+    }
+
+    #[test]
+    fn executions() {
+        let run = |input: &str, fuel: usize| {
+            let program = Parser::init(input).parse_program().unwrap();
+            let mut execution = Execution::new(program, Rc::new(Environment::default()));
+            let mut slices = 1;
+            loop {
+                if let Some(obj) = execution.run(fuel).unwrap() {
+                    return (obj, slices);
+                }
+                slices += 1;
+            }
+        };
+        let program = "let total = 0; for (x in range(5)) { let total = total + x; }; total";
+        assert_eq!(run(program, 1000), (Object::Int(10), 1));
+        assert_eq!(run(program, 2).0, Object::Int(10));
+        assert!(run(program, 2).1 > 5);
+        assert_eq!(run("if (false) { 1 }", 1).0, Object::Null);
+        assert_eq!(run("if (true) { 1 } else { 2 }; 3", 1).0, Object::Int(3));
+        assert_eq!(
+            run("if (true) { if (true) { return 4; } }; 5", 1).0,
+            Object::Int(4)
+        );
+        assert_eq!(run("for (x in [1]) { x }", 1).0, Object::Null);
+        let program = "let i = 0; while (i < 5) { let i = i + 1; }; i";
+        assert_eq!(run(program, 1000), (Object::Int(5), 1));
+        assert!(run(program, 2).1 > 5);
+        let program =
+            "let i = 0; while (true) { let i = i + 1; if (i < 3) { continue; }; break; }; i";
+        assert_eq!(run(program, 1).0, Object::Int(3));
+        assert_eq!(run("let a = 7;", 1).0, Object::Int(7));
+
+        let program = Parser::init("let a = 1; if (a) { a + 1 }")
+            .parse_program()
+            .unwrap();
+        let mut execution = Execution::new(program, Rc::new(Environment::default()));
+        let mut statements = vec![];
+        loop {
+            if let Some(statement) = execution.peek() {
+                statements.push(statement_to_source(statement));
+            }
+            if execution.run(1).unwrap().is_some() {
+                break;
+            }
+        }
+        assert_eq!(
+            statements,
+            vec!["let a = 1;", "if (a) {\n    a + 1;\n};", "a + 1;"]
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_evaluation() {
+        use core::future::Future;
+        use core::task::{Context, Poll, Waker};
+
+        let env = Rc::new(Environment::default());
+        let mut future = core::pin::pin!(eval_async(
+            "let total = 0; for (x in range(100)) { let total = total + x; }; total",
+            env,
+            10,
+        ));
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut pending = 0;
+        let result = loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => break result,
+                Poll::Pending => pending += 1,
+            }
+        };
+        assert_eq!(result.unwrap(), Object::Int(4950));
+        assert!(pending >= 20, "{pending}");
+    }
+
+    #[test]
+    fn environment_names() {
+        let env = Rc::new(Environment::default());
+        eval_with_env("let b = 1; let a = 2;", Rc::clone(&env)).unwrap();
+        let inner = Environment::init_with_outer(env);
+        inner.set("c", Object::Null);
+        inner.set("a", Object::Null);
+        assert_eq!(inner.names(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn execution_stats() {
+        let env = Rc::new(Environment::default());
+        let input = "let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5)";
+        let (result, stats) = eval_with_stats(input, Rc::clone(&env));
+        assert_eq!(result.unwrap(), Object::Int(120));
+        assert_eq!(
+            (stats.steps, stats.max_depth, stats.objects_allocated),
+            (22, 5, 1)
+        );
+
+        let (result, stats) = eval_with_stats("[\"a\", \"b\"]", env);
+        assert_eq!(result.unwrap().to_string(), "[a, b]");
+        assert_eq!(
+            (stats.steps, stats.max_depth, stats.objects_allocated),
+            (1, 0, 3)
+        );
+
+        let input = "let f = fn(n) { if (n > 0) { f(n - 1) } else { !(n * 2 == -n) } }; f(10)";
+        let (result, stats) = eval_with_stats(input, Rc::new(Environment::default()));
+        assert_eq!(result.unwrap(), Object::FALSE);
+        assert_eq!(stats.objects_allocated, 1);
+    }
+
+    #[test]
+    fn error_stacks() {
+        let input = "let f = fn(a) { g(a, 1) }; let g = fn(x, y) { len(x) }; f(5)";
+        let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+        assert_eq!(err.stack, vec!["builtin len", "fn(x, y)", "fn(a)"]);
+    }
+
+    #[test]
+    fn partial_application() {
+        let input = "let add = fn(x, y, z) { x + y + z }; let inc = partial(add, 1); \
+                     [inc, inc(2, 3), partial(inc, 2, 3)(), collect(map([1, 2], partial(add, 1, 1)))]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[fn(y, z) {...}, 6, 6, [3, 4]]"
+        );
+        for (input, code) in [
+            ("partial(len, 1)", ErrorCode::InvalidArgument),
+            ("partial(fn(x) { x }, 1, 2)", ErrorCode::ArgumentCount),
+            ("partial(fn(x, y) { x }, 1)()", ErrorCode::ArgumentCount),
+        ] {
+            let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(err.code, code);
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let input = "let b = bytes(\"hé\") + bytes([0, 34]); \
+                     [b, len(b), b[1], b[9], slice(b, 1, 3), decode(slice(b, -1, 3)), \
+                     decode(bytes(\"é\", \"latin1\"), \"latin1\"), collect(bytes(\"ab\"))]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[b\"h\\xc3\\xa9\\x00\\\"\", 5, 195, null, b\"\\xc3\\xa9\", hé, é, [97, 98]]"
+        );
+        for input in [
+            "bytes([256])",
+            "bytes(\"€\", \"latin1\")",
+            "decode(bytes([255]))",
+            "decode(bytes(\"a\"), \"utf16\")",
+        ] {
+            let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidArgument, "{input}");
+        }
+    }
+
+    #[test]
+    fn hash_updates() {
+        let input =
+            "let h = {1: 2}; let g = insert(h, true, 3); [len(g), g[true], merge(h, {1: 4}), h]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[2, 3, { 1: 4 }, { 1: 2 }]"
+        );
+    }
+
+    #[test]
+    fn variadic_push() {
+        let input = "let a = [1]; let b = push(a, 2, 3); let f = fn() { let a = push(a, 4); a }; \
+                     [a, b, f(), a, push(\"a\", \"b\", \"c\")]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[[1], [1, 2, 3], [1, 4], [1], abc]"
+        );
+        for (input, code) in [
+            ("push([])", ErrorCode::ArgumentCount),
+            ("push({}, [1, 2], [3, 4])", ErrorCode::ArgumentCount),
+            ("push(\"a\", \"b\", 1)", ErrorCode::InvalidArgument),
+        ] {
+            let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(err.code, code, "{input}");
+        }
+    }
+
+    #[test]
+    fn pattern_matching() {
+        let input = "let area = fn(shape) { match (shape) { \
+                         {\"type\": \"circle\", \"r\": r} => 3 * r * r, \
+                         {\"type\": \"rect\", \"w\": w, \"h\": h} if w == h => w * w, \
+                         {\"type\": \"rect\", \"w\": w, \"h\": h} => [w, h], \
+                         _ => \"unknown\" \
+                     } }; \
+                     let sum = fn(xs) { match (xs) { [] => 0, [head, ...tail] => head + sum(tail) } }; \
+                     let [a, [b, _], ...others] = [1, [2, 3], 4, 5]; \
+                     [area({\"type\": \"circle\", \"r\": 2}), area({\"type\": \"rect\", \"w\": 3, \"h\": 3}), \
+                      area({\"type\": \"rect\", \"w\": 2, \"h\": 5}), area(1), sum([1, 2, 3]), a, b, others, \
+                      match (-1) { 1 => true }, match (null) { null => \"none\" }]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[12, 9, [2, 5], unknown, 6, 1, 2, [4, 5], null, none]"
+        );
+
+        let input = "let sign = fn(x) { match (x) { 0.5 => \"half\", -1.5 => \"minus\", _ => \"other\" } }; \
+                     [sign(0.5), sign(-1.5), sign(1.5), sign(1)]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[half, minus, other, other]"
+        );
+
+        let input = "let x = 0; let y = match ([1]) { [x] if x > 1 => x, [z] => z + x }; [x, y]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[0, 1]"
+        );
+
+        let err = eval_with_env("let [a, b] = [1];", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::PatternMismatch);
+        assert_eq!(err.to_string(), "[1] doesn't match the pattern [a, b]");
+    }
+
+    #[test]
+    fn tuples() {
+        let input = "let divmod = fn(a, b) { (a / b, a % b) }; \
+                     let (q, r) = divmod(17, 5); \
+                     let swap = fn((a, b)) { (b, a) }; \
+                     let t = swap((1, \"x\")); \
+                     [q, r, t, t[0], t[2], len(t), (1,), (), (1, 2) == (1, 2), (1, 2) != (2, 1), \
+                      match (t) { (a, 1) => a, _ => null }]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[3, 2, (x, 1), x, null, 2, (1,), (), true, true, x]"
+        );
+        assert_eval("(1 + 2) * 3", Object::Int(9));
+
+        let err =
+            eval_with_env("let (a, b) = [1, 2];", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::PatternMismatch);
+        let err = eval_with_env(
+            "fn((a, b)) { a }((1, 2, 3))",
+            Rc::new(Environment::default()),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "(1, 2, 3) doesn't match the pattern (a, b)"
+        );
+    }
+
+    #[test]
+    fn copies() {
+        let input = "let it = iter([1, 2, 3]); next(it); \
+                     let c = copy(it); let same = it; \
+                     let g = fn() { yield 1; yield 2; }(); let h = copy(g); \
+                     let r = range(3); let d = deepcopy([r, r, (r,)]); next(d[0]); \
+                     [next(it), next(c), next(c), next(same), next(c), next(it), \
+                      next(g), next(g), next(h), next(d[1]), next(d[2][0]), next(r), \
+                      copy([1, [2]]), deepcopy({\"k\": (1, [2])}), copy(len)]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[2, 2, 3, 3, null, null, 1, 2, 1, 1, 2, 0, [1, [2]], { k: (1, [2]) }, Builtin function 'len']"
+        );
+
+        let err = eval_with_env(
+            "let g = fn() { yield copy(g); }(); next(g)",
+            Rc::new(Environment::default()),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidYield);
+    }
+
+    #[test]
+    fn error_propagation() {
+        let env = Rc::new(Environment::default());
+        env.set(
+            "failure",
+            Object::Error {
+                message: "no luck".into(),
+                data: Box::new(Object::Null),
+            },
+        );
+        let input = "let inc = fn(x) { x + 1 }; \
+                     let f = fn(x) { let y = inc(x?)?; [y, inc(x)?] }; \
+                     let g = fn(x) { [1, if (x) { return 2; } else { 3 }] }; \
+                     [f(1), f(failure), is_error(f(failure)), is_error(1), g(true), g(false)]";
+        assert_eq!(
+            eval_with_env(input, env).unwrap().to_string(),
+            "[[2, 2], Error: no luck, true, false, 2, [1, 3]]"
+        );
+
+        let input = "let parse = fn(s) { if (len(s) == 0) { return error(\"empty\", {\"code\": 400}); }; len(s) }; \
+                     let total = fn(a, b) { parse(a)? + parse(b)? }; \
+                     let e = total(\"ab\", \"\"); \
+                     [total(\"ab\", \"c\"), e, e[\"message\"], e[\"data\"][\"code\"], e[\"other\"], error(\"x\")[\"data\"]]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[3, Error: empty, empty, 400, null, null]"
+        );
+        let err = eval_with_env("error(1)", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn push_in_loops() {
+        let start = std::time::Instant::now();
+        let input =
+            "let a = []; for (i in range(100000)) { let a = push(a, i); }; [len(a), a[99999]]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[100000, 99999]"
+        );
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+        let start = std::time::Instant::now();
+        let input =
+            "let mut a = []; for (i in range(100000)) { a = push(a, i); }; [len(a), a[99999]]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[100000, 99999]"
+        );
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        let input = "let mut a = [1]; let f = fn() { a = push(a, 2); }; f(); \
+                     let b = a; a = push(a, 3); let c = [0]; [a, b, c]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[[1, 2, 3], [1, 2], [0]]"
+        );
+        let err = eval_with_env(
+            "let a = []; a = push(a, 1)",
+            Rc::new(Environment::default()),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::ImmutableAssignment);
+    }
+
+    #[test]
+    fn composition() {
+        let input = "let double = fn(x) { x * 2 }; let f = compose(double, len); \
+                     [f, f(\"abc\"), compose(f, rest)([1, 2]), compose(partial(fn(a, x) { push(a, x) }, [0]), double)(4)]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[fn(x) {...}, 6, 2, [0, 8]]"
+        );
+        let err = eval_with_env(
+            "compose(len, fn(a, b) { a })(1)",
+            Rc::new(Environment::default()),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Function `fn(a, b)` expects 2 arguments, got 1."
+        );
+    }
+
+    #[test]
+    fn calling_non_functions() {
+        let input = "let h = {\"f\": 5}; h[\"f\"](1)";
+        let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::NotAFunction);
+        assert_eq!(err.message, "`h[\"f\"]` is not a function, found 5");
+        assert_eq!(err.span, Some(18..24));
+    }
+
+    #[test]
+    fn argument_counts() {
+        let message = |input| {
+            let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(err.code, ErrorCode::ArgumentCount);
+            err.message
+        };
+        assert_eq!(
+            message("let add = fn(x, y) { x + y }; add(1, 2, 3)"),
+            "Function `add(x, y)` expects 2 arguments, got 3."
+        );
+        assert_eq!(
+            message("fn(x) { x }()"),
+            "Function `fn(x)` expects 1 argument, got 0."
+        );
+
+        // Errors are located at the innermost call they leave.
+        let located = |input: &str| {
+            let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            (err.span.clone(), err.located_in(input).message)
+        };
+        let add = "let add = fn(x, y) {\n  x + y\n};\n";
+        assert_eq!(
+            located(&format!("{add}add(1, 2, 3)")),
+            (
+                Some(32..44),
+                "Function `add(x, y)` expects 2 arguments, got 3 at line 4, column 1".to_owned()
+            )
+        );
+        assert_eq!(
+            located(&format!(
+                "{add}let twice = fn(x) {{ add(x, x) }};\ntwice(true)"
+            )),
+            (
+                Some(52..61),
+                "Invalid operation (`+`) between true and true! at line 4, column 21".to_owned()
+            )
+        );
+        assert_eq!(located("1 + true").0, None);
+    }
+
+    #[test]
+    fn keyword_arguments() {
+        let input = "let sub = fn(a, b) { a - b };";
+        assert_eval(&format!("{input} sub(b: 1, a: 5)"), Object::Int(4));
+        assert_eval(&format!("{input} sub(5, b: 1)"), Object::Int(4));
+        let error = |call: &str| {
+            let err = eval_with_env(&format!("{input} {call}"), Rc::new(Environment::default()))
+                .unwrap_err();
+            (err.code, err.message)
+        };
+        assert_eq!(
+            error("sub(5, c: 1)"),
+            (
+                ErrorCode::InvalidArgument,
+                "Function `sub(a, b)` has no parameter `c`".into()
+            )
+        );
+        assert_eq!(
+            error("sub(5, a: 1)"),
+            (
+                ErrorCode::InvalidArgument,
+                "Function `sub(a, b)` got the argument `a` twice".into()
+            )
+        );
+        assert_eq!(
+            error("sub(b: 1)"),
+            (
+                ErrorCode::ArgumentCount,
+                "Function `sub(a, b)` is missing the argument `a`".into()
+            )
+        );
+        assert_eq!(
+            error("len(x: [1])"),
+            (
+                ErrorCode::InvalidArgument,
+                "Builtin function 'len' doesn't take keyword arguments".into()
+            )
+        );
+    }
+
+    #[test]
+    fn method_calls() {
+        let counter = "let counter = fn(n) { {\"n\": n, \"add\": fn(self, by) { counter(self[\"n\"] + by) }, \
+                       \"get\": fn(self) { self[\"n\"] }} };";
+        assert_eval(
+            &format!("{counter} counter(1).add(2).add(by: 3).get()"),
+            Object::Int(6),
+        );
+        assert_eval(
+            &format!(
+                "{counter} let mut calls = 0; let make = fn() {{ calls = calls + 1; counter(5) }}; make().get(); calls"
+            ),
+            Object::Int(1),
+        );
+        let err = eval_with_env(
+            &format!("{counter} counter(1).reset()"),
+            Rc::new(Environment::default()),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::NotAFunction);
+        assert_eq!(
+            err.message,
+            "`$self[\"reset\"]` is not a function, found null"
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let point = "struct Point { x, y }";
+        assert_eval(
+            &format!("{point} let p = Point(1, y: 2); [p.x, p.y, p[\"y\"], p[\"z\"]]"),
+            Object::Array(Rc::new(vec![
+                Object::Int(1),
+                Object::Int(2),
+                Object::Int(2),
+                Object::Null,
+            ])),
+        );
+        assert_eval(
+            &format!(
+                "{point} let f = fn(p) {{ match (p) {{ Point(0, y) => y, Point(x, _) => x, _ => null }} }}; \
+                 [f(Point(0, 5)), f(Point(3, 4)), f({{\"x\": 1}})]"
+            ),
+            Object::Array(Rc::new(vec![Object::Int(5), Object::Int(3), Object::Null])),
+        );
+        // Nominal: equal fields don't make equal types.
+        assert_eval(
+            &format!(
+                "{point} struct Pair {{ x, y }} [Point(1, 2) == Point(1, 2), Point(1, 2) != Pair(1, 2)]"
+            ),
+            Object::Array(Rc::new(vec![Object::TRUE, Object::TRUE])),
+        );
+        assert_eval(
+            &format!(
+                "{point} struct Pair {{ x, y }} match (Pair(1, 2)) {{ Point(x, y) => 0, _ => 1 }}"
+            ),
+            Object::Int(1),
+        );
+        assert_eval("{\"x\": 1}.x", Object::Int(1));
+        assert_eq!(
+            eval_with_env(point, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "struct Point { x, y }"
+        );
+        assert_eq!(
+            eval_with_env(
+                &format!("{point} Point(1, [2])"),
+                Rc::new(Environment::default())
+            )
+            .unwrap()
+            .to_string(),
+            "Point(x: 1, y: [2])"
+        );
+        let error = |input: &str| {
+            let err = eval_with_env(&format!("{point} {input}"), Rc::new(Environment::default()))
+                .unwrap_err();
+            (err.code, err.message)
+        };
+        assert_eq!(
+            error("Point(1)"),
+            (
+                ErrorCode::ArgumentCount,
+                "Struct `Point(x, y)` expects 2 arguments, got 1.".into()
+            )
+        );
+        assert_eq!(
+            error("Point(1, 2).z"),
+            (
+                ErrorCode::InvalidOperation,
+                "Struct `Point` has no field `z`".into()
+            )
+        );
+        assert_eq!(
+            error("Point(1, z: 2)"),
+            (
+                ErrorCode::InvalidArgument,
+                "Struct `Point(x, y)` has no parameter `z`".into()
+            )
+        );
+    }
+
+    #[test]
+    fn overloaded_operators() {
+        let complex = "struct Complex { re, im, __add, __eq } \
+                       let add = fn(a, b) { Complex(a.re + b.re, a.im + b.im, add, same) }; \
+                       let same = fn(a, n) { if (a.im == 0) { a.re == n } else { false } }; \
+                       let c = fn(re, im) { Complex(re, im, add, same) };";
+        assert_eval(
+            &format!("{complex} let z = c(1, 2) + c(3, 4); [z.re, z.im]"),
+            Object::Array(Rc::new(vec![Object::Int(4), Object::Int(6)])),
+        );
+        // The builtin equality of structs comes first, the hooks only replace errors.
+        assert_eval(
+            &format!("{complex} [c(1, 2) == c(1, 3), c(1, 0) == 1, c(1, 2) != 1]"),
+            Object::Array(Rc::new(vec![Object::FALSE, Object::TRUE, Object::TRUE])),
+        );
+        let vector = "let v = fn(x) { {\"x\": x, \"__mul\": fn(a, k) { v(a[\"x\"] * k) }, \
+                      \"__lt\": fn(a, b) { a[\"x\"] < b[\"x\"] }, \
+                      \"__index\": fn(a, key) { \"no \" + key }} };";
+        assert_eval(
+            &format!("{vector} [(v(2) * 3).x, v(1) < v(2), v(1)[\"y\"], v(1).y]"),
+            Object::Array(Rc::new(vec![
+                Object::Int(6),
+                Object::TRUE,
+                Object::String("no y".into()),
+                Object::String("no y".into()),
+            ])),
+        );
+        let err = eval_with_env(
+            &format!("{vector} v(1) + 1"),
+            Rc::new(Environment::default()),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidOperation);
+    }
+
+    #[test]
+    fn duplicate_hash_keys() {
+        struct Warnings(Rc<RefCell<Vec<Error>>>);
+        impl Observer for Warnings {
+            fn on_diagnostic(&self, diagnostic: &Error) {
+                self.0.borrow_mut().push(diagnostic.clone());
+            }
+        }
+
+        let log = Rc::new(RefCell::new(vec![]));
+        let env = Rc::new(Environment::default().with_observer(Warnings(Rc::clone(&log))));
+        let input = "let k = \"a\"; [{\"a\": 1, \"a\" + \"\": 2, k: 3}, {1: 1, 2 - 1: 2}]";
+        assert_eq!(
+            eval_with_env(input, env).unwrap().to_string(),
+            "[{ a: 3 }, { 1: 2 }]"
+        );
+        let log = log.borrow();
+        let spans: Vec<_> = log.iter().map(|warning| warning.span.clone()).collect();
+        assert_eq!(spans, vec![Some(23..31), Some(36..37), Some(50..55)]);
+        assert_eq!(log[0].message, "Duplicate key \"a\" in hash literal");
+        assert_eq!(log[2].message, "Duplicate key 1 in hash literal");
+
+        let env = Rc::new(Environment::default().with_policy(Policy::default().strict()));
+        let input = "{\"a\": 1,\n \"a\": 2}";
+        let err = eval_with_env(input, env).unwrap_err();
+        assert_eq!(
+            (err.code, err.span.clone(), err.located_in(input).message),
+            (
+                ErrorCode::DuplicateHashKey,
+                Some(10..13),
+                "Duplicate key \"a\" in hash literal at line 2, column 2".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn observers() {
+        struct Audit(Rc<RefCell<Vec<String>>>);
+        impl Observer for Audit {
+            fn on_set(&self, name: &str, obj: &Object) {
+                self.0.borrow_mut().push(format!("set {name} = {obj}"));
+            }
+            fn on_get_miss(&self, name: &str) -> Option<Object> {
+                self.0.borrow_mut().push(format!("miss {name}"));
+                (name == "answer").then_some(Object::Int(42))
+            }
+            fn on_warning(&self, message: &str) {
+                self.0.borrow_mut().push(format!("warning {message}"));
+            }
+        }
+
+        let log = Rc::new(RefCell::new(vec![]));
+        let env = Rc::new(Environment::default().with_observer(Audit(Rc::clone(&log))));
+        let input = "let f = fn(x) { x + answer }; [f(1), answer, len(push({}, [1, 2]))]";
+        assert_eq!(
+            eval_with_env(input, Rc::clone(&env)).unwrap().to_string(),
+            "[43, 42, 1]"
+        );
+        assert_eq!(
+            eval_with_env("unknown", env).unwrap_err().code,
+            ErrorCode::UnknownIdentifier
+        );
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "set f = fn(x) { x + answer; }",
+                "set x = 1",
+                "miss answer",
+                "warning `push` on hashes is deprecated, use `insert(hash, key, value)` or `merge(hash, other)`",
+                "miss unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_identifiers() {
+        let message = |input: &str| {
+            let err = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(err.code, ErrorCode::UnknownIdentifier);
+            err.message
+        };
+        assert_eq!(message("foobar"), "identifier not found: foobar");
+        assert_eq!(message("let x = 1; x + y"), "identifier not found: y");
+        // A `let` in a block shadows the outer name, which stays defined after it.
+        assert_eval(
+            "let x = 1; let f = fn() { let x = 2; x }; [f(), x]",
+            Object::Array(Rc::new(vec![Object::Int(2), Object::Int(1)])),
+        );
+        assert_eq!(
+            message("let f = fn() { let local = 1; local }; f(); local"),
+            "identifier not found: local"
+        );
+        // Closures see what the enclosing scope defines, even after they're created.
+        assert_eval("let f = fn() { later }; let later = 3; f()", Object::Int(3));
+        assert_eq!(
+            message("let make = fn(a) { fn() { a + b } }; make(1)()"),
+            "identifier not found: b"
+        );
+        assert_eq!(
+            message("let count = 1; coutn"),
+            "identifier not found: coutn, did you mean `count`?"
+        );
+        assert_eq!(
+            message("lenn([1])"),
+            "identifier not found: lenn, did you mean `len`?"
+        );
+        assert_eq!(
+            message("let f = fn(total) { fn() { totl } }; f(1)()"),
+            "identifier not found: totl, did you mean `total`?"
+        );
+    }
+
+    #[test]
+    fn environment_graph() {
+        let env = Rc::new(Environment::default());
+        eval_with_env("let x = \"a\\b\"; let f = fn(y) { y };", Rc::clone(&env)).unwrap();
+        let inner = Environment::init_with_outer(env);
+        inner.set("z", Object::Int(1));
+        assert_eq!(
+            inner.to_dot(),
+            "digraph environment {\n    node [shape=box];\n    \
+             e0 [label=\"z = 1\\l\"];\n    \
+             e1 [label=\"f = fn(y) { y; }\\lx = a\\\\b\\l\"];\n    \
+             e2 [label=\"\"];\n    \
+             e2 -> e1 [label=\"outer\"];\n    \
+             e1 -> e2 [label=\"f\", style=dashed];\n    \
+             e0 -> e1 [label=\"outer\"];\n}\n"
+        );
+    }
+
+    #[test]
+    fn debug_graph() {
+        let env = Rc::new(Environment::default());
+        let input = "let make = fn() { let g = fn() { 1 }; [g, (2, 3)] }; \
+                     let pair = make(); let it = map(range(3), make); let n = 1;";
+        eval_with_env(input, Rc::clone(&env)).unwrap();
+        let graph = env.debug_graph();
+        // Each function captures an environment of its own, nested in the one defining it.
+        assert_eq!(
+            graph.to_string(),
+            "4 environments, 11 objects, 4 functions reachable\n\
+             cycle: it -> outer\n\
+             cycle: outer -> g"
+        );
+
+        let env = Rc::new(Environment::default());
+        env.set("n", Object::Int(1));
+        assert_eq!(env.debug_graph().cycles, Vec::<Vec<String>>::new());
+    }
+
+    #[test]
+    fn recursive_closures() {
+        let input = "let f = fn() { f }; let g = fn() { f }; let h = fn() { g }; [f, g, h]";
+        let obj = eval_with_env(input, Rc::new(Environment::default())).unwrap();
+        let Object::Array(funcs) = &obj else {
+            panic!("{obj}");
+        };
+        assert_eq!(funcs[0], funcs[1]);
+        assert_ne!(funcs[1], funcs[2]);
+        assert!(format!("{obj:?}").contains(r#"variables: ["f", "g", "h"]"#));
+    }
 }
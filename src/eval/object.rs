@@ -1,10 +1,14 @@
 use crate::Statement;
-use crate::eval::Environment;
 use crate::eval::builtin::BuiltinFunction;
-use std::collections::HashMap;
-use std::fmt;
+use crate::eval::pretty::function_source;
+use crate::eval::{Environment, HashObject, Iter, PrettyOptions};
+use crate::prelude::*;
+use crate::vm::Closure;
+use alloc::rc::Rc;
+use core::any::Any;
+use core::fmt;
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 pub enum HashMapKey {
     Bool(bool),
     Int(i64),
@@ -21,21 +25,113 @@ impl fmt::Display for HashMapKey {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// Resource owned by native code, such as a database connection, that scripts can
+/// pass around but not look into. Copies share the resource, released with the last one.
+#[derive(Clone)]
+pub struct Handle {
+    kind: &'static str,
+    resource: Rc<dyn Any>,
+}
+
+impl Handle {
+    /// `kind` names the resource in messages, e.g. `database`.
+    pub fn new<T: 'static>(kind: &'static str, resource: T) -> Self {
+        Self {
+            kind,
+            resource: Rc::new(resource),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.resource.downcast_ref()
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.kind).finish()
+    }
+}
+
+/// Handles are equal when they share the resource.
+impl PartialEq for Handle {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.resource, &other.resource)
+    }
+}
+
+/// A type declared with `struct Name { fields }`.
+#[derive(Debug, PartialEq)]
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
 pub enum Object {
     Null,
     Int(i64),
+    Float(f64),
     Bool(bool),
     String(String),
+    Bytes(Vec<u8>),
     Return(Box<Object>),
+    /// Signals of `break` and `continue`, leaving the blocks of the loop body like
+    /// [`Object::Return`] leaves those of a function.
+    Break,
+    Continue,
+    /// A failure as a value rather than as an [`Error`](crate::error::Error), which a
+    /// postfix `?` returns from the enclosing function.
+    Error {
+        message: String,
+        data: Box<Object>,
+    },
     Function {
         parameters: Vec<String>,
         body: Vec<Statement>,
         environment: Environment,
+        /// See [`Statement::docstring`].
+        doc: Option<String>,
     },
     Builtin(BuiltinFunction),
-    Array(Vec<Object>),
-    Hash(HashMap<HashMapKey, Object>),
+    /// A function compiled for the [`vm`](crate::vm).
+    Closure(Rc<Closure>),
+    /// The constructor of a `struct`, returning an instance when called.
+    StructType(Rc<StructType>),
+    /// An instance of a `struct`, with the values of its fields in order. Instances of
+    /// types declared apart are never equal, even when their names are.
+    Struct {
+        ty: Rc<StructType>,
+        values: Vec<Object>,
+    },
+    /// Shared by its copies, so copying it is cheap: see [`Rc::make_mut`].
+    Array(Rc<Vec<Object>>),
+    /// `(a, b)`, a fixed number of values that can't be changed, e.g. the several
+    /// results of a function.
+    Tuple(Vec<Object>),
+    Hash(HashObject),
+    Iterator(Iter),
+    Handle(Handle),
+}
+
+impl From<bool> for Object {
+    fn from(value: bool) -> Self {
+        if value { Object::TRUE } else { Object::FALSE }
+    }
+}
+
+impl From<HashMapKey> for Object {
+    fn from(key: HashMapKey) -> Self {
+        match key {
+            HashMapKey::Bool(value) => Object::Bool(value),
+            HashMapKey::Int(value) => Object::Int(value),
+            HashMapKey::String(value) => Object::String(value),
+        }
+    }
 }
 
 impl fmt::Display for Object {
@@ -43,18 +139,47 @@ impl fmt::Display for Object {
         match self {
             Object::Null => write!(f, "null"),
             Object::Int(value) => write!(f, "{value}"),
+            // Always with a fractional part or an exponent, unlike ints.
+            Object::Float(value) => write!(f, "{value:?}"),
             Object::Bool(value) => write!(f, "{value}"),
             Object::String(value) => write!(f, "{value}"),
+            Object::Bytes(value) => write!(f, "b\"{}\"", value.escape_ascii()),
             Object::Return(value) => write!(f, "Return {value}"),
+            Object::Break => write!(f, "Break"),
+            Object::Continue => write!(f, "Continue"),
+            Object::Error { message, .. } => write!(f, "Error: {message}"),
+            // On one line, cut like `pretty` does by default.
             Object::Function {
-                parameters,
-                body: _,
-                environment: _,
+                parameters, body, ..
             } => {
-                let params = parameters.join(", ");
-                write!(f, "fn({params}) {{...}}")
+                let max_len = PrettyOptions::default().max_source_len;
+                f.write_str(&function_source(parameters, body, max_len, None))
+            }
+            Object::Closure(closure) => {
+                let function = closure.function();
+                let max_len = PrettyOptions::default().max_source_len;
+                f.write_str(&function_source(
+                    &function.parameters,
+                    &function.body,
+                    max_len,
+                    None,
+                ))
             }
             Object::Builtin(value) => write!(f, "Builtin function '{value}'"),
+            Object::StructType(ty) => {
+                write!(f, "struct {} {{ {} }}", ty.name, ty.fields.join(", "))
+            }
+            Object::Struct { ty, values } => {
+                let fields: Vec<String> = ty
+                    .fields
+                    .iter()
+                    .zip(values)
+                    .map(|(field, value)| format!("{field}: {value}"))
+                    .collect();
+                write!(f, "{}({})", ty.name, fields.join(", "))
+            }
+            Object::Iterator(_) => write!(f, "<iterator>"),
+            Object::Handle(handle) => write!(f, "<{}>", handle.kind),
             Object::Array(content) => {
                 write!(
                     f,
@@ -66,6 +191,13 @@ impl fmt::Display for Object {
                         .join(", ")
                 )
             }
+            Object::Tuple(values) => match &values[..] {
+                [value] => write!(f, "({value},)"),
+                _ => {
+                    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                    write!(f, "({})", values.join(", "))
+                }
+            },
             Object::Hash(map) => {
                 write!(
                     f,
@@ -80,18 +212,127 @@ impl fmt::Display for Object {
     }
 }
 
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut Vec::new())
+    }
+}
+
 impl Object {
+    /// The book's `TRUE`, `FALSE` and `NULL` singletons.
+    pub const TRUE: Object = Object::Bool(true);
+    pub const FALSE: Object = Object::Bool(false);
+    pub const NULL: Object = Object::Null;
+
+    /// Structural equality, safe on closures that capture themselves.
+    pub(crate) fn equals(&self, other: &Self, visited: &mut Vec<(usize, usize)>) -> bool {
+        match (self, other) {
+            (Object::Null, Object::Null) => true,
+            (Object::Int(l), Object::Int(r)) => l == r,
+            (Object::Float(l), Object::Float(r)) => l == r,
+            (Object::Bool(l), Object::Bool(r)) => l == r,
+            (Object::String(l), Object::String(r)) => l == r,
+            (Object::Bytes(l), Object::Bytes(r)) => l == r,
+            (Object::Builtin(l), Object::Builtin(r)) => l == r,
+            (Object::Closure(l), Object::Closure(r)) => Rc::ptr_eq(l, r),
+            (Object::StructType(l), Object::StructType(r)) => Rc::ptr_eq(l, r),
+            (Object::Struct { ty: lt, values: lv }, Object::Struct { ty: rt, values: rv }) => {
+                Rc::ptr_eq(lt, rt) && lv.iter().zip(rv).all(|(l, r)| l.equals(r, visited))
+            }
+            (Object::Iterator(l), Object::Iterator(r)) => l == r,
+            (Object::Handle(l), Object::Handle(r)) => l == r,
+            (Object::Return(l), Object::Return(r)) => l.equals(r, visited),
+            (Object::Break, Object::Break) | (Object::Continue, Object::Continue) => true,
+            (
+                Object::Error {
+                    message: lm,
+                    data: ld,
+                },
+                Object::Error {
+                    message: rm,
+                    data: rd,
+                },
+            ) => lm == rm && ld.equals(rd, visited),
+            (Object::Array(l), Object::Array(r)) => {
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| l.equals(r, visited))
+            }
+            (Object::Tuple(l), Object::Tuple(r)) => {
+                l.len() == r.len() && l.iter().zip(r).all(|(l, r)| l.equals(r, visited))
+            }
+            (Object::Hash(l), Object::Hash(r)) => {
+                l.len() == r.len()
+                    && l.iter()
+                        .all(|(k, l)| r.get(k).is_some_and(|r| l.equals(r, visited)))
+            }
+            (
+                Object::Function {
+                    parameters: lp,
+                    body: lb,
+                    environment: le,
+                    ..
+                },
+                Object::Function {
+                    parameters: rp,
+                    body: rb,
+                    environment: re,
+                    ..
+                },
+            ) => lp == rp && lb == rb && le.equals(re, visited),
+            _ => false,
+        }
+    }
+
+    /// Whether the value owns heap memory.
+    pub fn is_allocated(&self) -> bool {
+        matches!(
+            self,
+            Object::String(_)
+                | Object::Bytes(_)
+                | Object::Error { .. }
+                | Object::Array(_)
+                | Object::Tuple(_)
+                | Object::Hash(_)
+                | Object::Function { .. }
+                | Object::Closure(_)
+                | Object::StructType(_)
+                | Object::Struct { .. }
+                | Object::Iterator(_)
+                | Object::Handle(_)
+        )
+    }
+
     pub fn to_bool(&self) -> bool {
         match self {
             Object::Bool(value) => *value,
             Object::Int(value) => *value != 0,
+            Object::Float(value) => *value != 0.0,
             Object::String(value) => !value.is_empty(),
+            Object::Bytes(value) => !value.is_empty(),
             Object::Null => false,
             Object::Return(value) => value.to_bool(),
+            Object::Break | Object::Continue => false,
+            Object::Error { .. } => false,
             Object::Function { .. } => true,
+            Object::Closure(_) => true,
+            Object::StructType(_) | Object::Struct { .. } => true,
             Object::Builtin(_) => true,
+            Object::Iterator(_) => true,
+            Object::Handle(_) => true,
             Object::Array(content) => !content.is_empty(),
+            Object::Tuple(values) => !values.is_empty(),
             Object::Hash(map) => !map.is_empty(),
         }
     }
+
+    /// Exit status of a script evaluating to this value: an int is the status itself
+    /// (1 if it doesn't fit an `i32`), `true` is 0, `false` and errors 1, anything else is 0.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Object::Int(value) => i32::try_from(*value).unwrap_or(1),
+            Object::Bool(value) => i32::from(!value),
+            Object::Return(value) => value.exit_code(),
+            Object::Error { .. } => 1,
+            _ => 0,
+        }
+    }
 }
@@ -0,0 +1,79 @@
+use crate::error::{ErrorCode, Result, fail};
+use std::cell::RefCell;
+use std::io::Write;
+
+type Sink = Box<dyn FnMut(&str)>;
+
+thread_local! {
+    static OUTPUT: RefCell<Option<Sink>> = const { RefCell::new(None) };
+}
+
+/// Redirects what the scripts evaluated on the current thread print, e.g. with `table`,
+/// to `sink` instead of the standard output, replacing any previous sink.
+pub fn set_output(sink: impl FnMut(&str) + 'static) {
+    OUTPUT.set(Some(Box::new(sink)));
+}
+
+/// Prints to the standard output again on the current thread.
+pub fn reset_output() {
+    OUTPUT.set(None);
+}
+
+/// Passes `text` to the sink of the current thread, or writes it to the standard output.
+pub(crate) fn print(text: &str) -> Result<()> {
+    let printed = OUTPUT.with_borrow_mut(|sink| match sink {
+        Some(sink) => {
+            sink(text);
+            true
+        }
+        None => false,
+    });
+    if !printed {
+        let mut stdout = std::io::stdout().lock();
+        if let Err(err) = stdout
+            .write_all(text.as_bytes())
+            .and_then(|()| stdout.flush())
+        {
+            fail!(
+                ErrorCode::InvalidInput,
+                "Cannot write to the standard output: {err}"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, Object, eval_with_env};
+    use alloc::rc::Rc;
+
+    #[test]
+    fn redirection() {
+        let printed = Rc::new(RefCell::new(String::new()));
+        let sink = Rc::clone(&printed);
+        set_output(move |text| sink.borrow_mut().push_str(text));
+        let result = eval_with_env(
+            "table([{\"a\": 1}]); table([]); 2",
+            Rc::new(Environment::default()),
+        );
+        reset_output();
+        assert_eq!(result.unwrap(), Object::Int(2));
+        assert_eq!(*printed.borrow(), "+---+\n| a |\n+---+\n| 1 |\n+---+\n");
+    }
+
+    #[test]
+    fn puts() {
+        let printed = Rc::new(RefCell::new(String::new()));
+        let sink = Rc::clone(&printed);
+        set_output(move |text| sink.borrow_mut().push_str(text));
+        let result = eval_with_env(
+            "puts(\"a\", [1, \"b\"]); puts(); puts(null)",
+            Rc::new(Environment::default()),
+        );
+        reset_output();
+        assert_eq!(result.unwrap(), Object::Null);
+        assert_eq!(*printed.borrow(), "a\n[1, b]\nnull\n");
+    }
+}
@@ -0,0 +1,75 @@
+//! Matching values against the patterns of `match` arms and `let` destructuring.
+
+use crate::Expression;
+use crate::ast::Pattern;
+use crate::eval::{HashMapKey, Object};
+use crate::prelude::*;
+use alloc::rc::Rc;
+
+/// Whether `obj` matches `pattern`, pushing the values of the names it binds to
+/// `bindings` in the order of [`Pattern::names`]. They are incomplete on a mismatch.
+pub(crate) fn matches(
+    pattern: &Pattern,
+    obj: &Object,
+    bindings: &mut Vec<(String, Object)>,
+) -> bool {
+    match (pattern, obj) {
+        (Pattern::Wildcard, _) => true,
+        (Pattern::Ident(name), _) => {
+            bindings.push((name.clone(), obj.clone()));
+            true
+        }
+        (Pattern::Literal(literal), _) => literal_value(literal) == *obj,
+        (Pattern::Array { items, rest }, Object::Array(content)) => {
+            let fits = match rest {
+                Some(_) => content.len() >= items.len(),
+                None => content.len() == items.len(),
+            };
+            fits && items
+                .iter()
+                .zip(content.iter())
+                .all(|(item, obj)| matches(item, obj, bindings))
+                && rest.as_deref().is_none_or(|rest| {
+                    let others = Object::Array(Rc::new(content[items.len()..].to_vec()));
+                    matches(rest, &others, bindings)
+                })
+        }
+        (Pattern::Tuple(items), Object::Tuple(values)) => {
+            items.len() == values.len()
+                && items
+                    .iter()
+                    .zip(values)
+                    .all(|(item, obj)| matches(item, obj, bindings))
+        }
+        (Pattern::Hash(pairs), Object::Hash(map)) => pairs.iter().all(|(key, pattern)| {
+            let key = match key {
+                Expression::Int(key) => HashMapKey::Int(*key),
+                Expression::Bool(key) => HashMapKey::Bool(*key),
+                Expression::String(key) => HashMapKey::String(key.clone()),
+                _ => return false,
+            };
+            map.get(&key)
+                .is_some_and(|obj| matches(pattern, obj, bindings))
+        }),
+        // Nominal: another struct with the same fields does not match.
+        (Pattern::Struct { name, fields }, Object::Struct { ty, values }) => {
+            ty.name == *name
+                && fields.len() == values.len()
+                && fields
+                    .iter()
+                    .zip(values)
+                    .all(|(field, obj)| matches(field, obj, bindings))
+        }
+        _ => false,
+    }
+}
+
+fn literal_value(literal: &Expression) -> Object {
+    match literal {
+        Expression::Int(value) => Object::Int(*value),
+        Expression::Float(value) => Object::Float(*value),
+        Expression::Bool(value) => Object::Bool(*value),
+        Expression::String(string) => Object::String(string.clone()),
+        _ => Object::Null,
+    }
+}
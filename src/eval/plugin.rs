@@ -0,0 +1,157 @@
+//! Builtins contributed at runtime by other crates, linked in or loaded as plugins.
+
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{Arity, BUILTINS, BuiltinFunction, BuiltinInfo, Object};
+use crate::prelude::*;
+use std::sync::RwLock;
+
+/// Runs a builtin, receiving the builtin itself and the evaluated arguments.
+pub type Handler = fn(&BuiltinFunction, Vec<Object>) -> Result<Object>;
+
+/// Builtins a crate contributes, installed with [`register_builtins`].
+#[derive(Default)]
+pub struct BuiltinPack {
+    builtins: Vec<(&'static str, Arity, &'static str, &'static str, Handler)>,
+}
+
+impl BuiltinPack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a builtin, `usage` and `help` documenting it like the ones in [`BUILTINS`].
+    pub fn builtin(
+        mut self,
+        name: &'static str,
+        arity: Arity,
+        usage: &'static str,
+        help: &'static str,
+        handler: Handler,
+    ) -> Self {
+        self.builtins.push((name, arity, usage, help, handler));
+        self
+    }
+}
+
+static PLUGINS: RwLock<Vec<&'static BuiltinInfo>> = RwLock::new(Vec::new());
+
+/// Makes the builtins of `pack` available to every environment. Fails without
+/// registering any of them if one is named after an existing builtin.
+pub fn register_builtins(pack: BuiltinPack) -> Result<()> {
+    let mut plugins = PLUGINS.write().unwrap_or_else(|err| err.into_inner());
+    for (i, (name, ..)) in pack.builtins.iter().enumerate() {
+        let taken = BUILTINS
+            .iter()
+            .chain(plugins.iter().copied())
+            .any(|b| b.name == *name)
+            || pack.builtins[..i].iter().any(|(other, ..)| other == name);
+        if taken {
+            fail!(
+                ErrorCode::InvalidInput,
+                "Cannot register builtin function `{name}`, the name is taken"
+            );
+        }
+    }
+    for (name, arity, usage, help, handler) in pack.builtins {
+        let function = BuiltinFunction::Plugin(plugins.len());
+        let info = BuiltinInfo::new(name, function, arity, usage, help, handler);
+        // Registered builtins live as long as the program.
+        plugins.push(Box::leak(Box::new(info)));
+    }
+    Ok(())
+}
+
+pub(crate) fn plugin_builtins() -> Vec<&'static BuiltinInfo> {
+    PLUGINS
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+}
+
+pub(crate) fn find_plugin(name: &str) -> Option<BuiltinFunction> {
+    PLUGINS
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .iter()
+        .find(|builtin| builtin.name == name)
+        .map(|builtin| builtin.function.clone())
+}
+
+pub(crate) fn plugin_info(index: usize) -> &'static BuiltinInfo {
+    PLUGINS.read().unwrap_or_else(|err| err.into_inner())[index]
+}
+
+/// Loads the dynamic library at `path` and registers the builtins added by its
+/// `waiir_plugin` function, declared as
+///
+/// ```ignore
+/// #[unsafe(no_mangle)]
+/// pub fn waiir_plugin(pack: BuiltinPack) -> BuiltinPack
+/// ```
+///
+/// The library must be built by the same compiler against the same version of this
+/// crate, as Rust has no stable ABI; it's never unloaded.
+#[cfg(feature = "plugins")]
+pub fn load_plugin(path: &str) -> Result<()> {
+    // SAFETY: loading runs the library's initializers and calling `waiir_plugin`
+    // trusts its signature: plugins are as trusted as the binary loading them.
+    let pack = unsafe {
+        let library = match libloading::Library::new(path) {
+            Ok(library) => library,
+            Err(err) => fail!(ErrorCode::InvalidInput, "Cannot load plugin {path}: {err}"),
+        };
+        let pack = match library.get::<fn(BuiltinPack) -> BuiltinPack>(b"waiir_plugin") {
+            Ok(init) => init(BuiltinPack::new()),
+            Err(err) => fail!(ErrorCode::InvalidInput, "Invalid plugin {path}: {err}"),
+        };
+        core::mem::forget(library);
+        pack
+    };
+    register_builtins(pack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, eval_with_env};
+    use alloc::rc::Rc;
+
+    fn call_twice(_: &BuiltinFunction, args: Vec<Object>) -> Result<Object> {
+        match &args[..] {
+            [Object::String(s)] => Ok(Object::String(s.repeat(2))),
+            _ => fail!(ErrorCode::InvalidArgument, "`twice` expects a string"),
+        }
+    }
+
+    #[test]
+    fn registration() {
+        let pack = BuiltinPack::new().builtin(
+            "twice",
+            Arity::exactly(1),
+            "twice(string)",
+            "The string repeated twice.",
+            call_twice,
+        );
+        register_builtins(pack).unwrap();
+        let input = "[twice(\"ab\"), help(\"twice\")]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[abab, twice(string)\nThe string repeated twice.]"
+        );
+
+        let taken = BuiltinPack::new().builtin("len", Arity::exactly(1), "", "", call_twice);
+        assert_eq!(
+            register_builtins(taken).unwrap_err().code,
+            ErrorCode::InvalidInput
+        );
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn missing_plugin() {
+        let err = load_plugin("/nonexistent/libplugin.so").unwrap_err();
+        assert!(err.message.starts_with("Cannot load plugin"));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::builtin::BuiltinFunction;
+#[cfg(feature = "std")]
+use crate::eval::environment::with_caller;
+use crate::prelude::*;
+use core::fmt;
+
+/// Access to the outside world a builtin may need.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Stdin,
+    FsRead,
+    FsWrite,
+    Net,
+    Env,
+    Eval,
+    Time,
+    Threads,
+    Host,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Capability::Stdin => write!(f, "reading the standard input"),
+            Capability::FsRead => write!(f, "reading files"),
+            Capability::FsWrite => write!(f, "writing files"),
+            Capability::Net => write!(f, "network access"),
+            Capability::Env => write!(f, "reading environment variables"),
+            Capability::Eval => write!(f, "evaluating code"),
+            Capability::Time => write!(f, "reading the clock"),
+            Capability::Threads => write!(f, "spawning threads"),
+            Capability::Host => write!(f, "communicating with the host"),
+        }
+    }
+}
+
+/// What the builtins of an environment are allowed to do. Scripts referring to a
+/// builtin that needs a denied capability fail with [`ErrorCode::SandboxViolation`].
+/// The default policy allows everything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    allowed: Vec<Capability>,
+    /// Prefixes of the paths files can be read from, `None` meaning any path.
+    fs_read: Option<Vec<String>>,
+    strict: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            allowed: vec![
+                Capability::Stdin,
+                Capability::FsRead,
+                Capability::FsWrite,
+                Capability::Net,
+                Capability::Env,
+                Capability::Eval,
+                Capability::Time,
+                Capability::Threads,
+                Capability::Host,
+            ],
+            fs_read: None,
+            strict: false,
+        }
+    }
+}
+
+impl Policy {
+    /// Denies every capability.
+    pub fn locked_down() -> Self {
+        Self {
+            allowed: vec![],
+            fs_read: Some(vec![]),
+            strict: false,
+        }
+    }
+
+    /// Fails on what is otherwise only warned about, e.g. a key given twice in a hash
+    /// literal.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn allow(mut self, capability: Capability) -> Self {
+        if !self.allowed.contains(&capability) {
+            self.allowed.push(capability);
+        }
+        self
+    }
+
+    /// Allows reading the files under the given paths only.
+    pub fn allow_fs_read<S: Into<String>>(mut self, paths: impl IntoIterator<Item = S>) -> Self {
+        self.fs_read = Some(paths.into_iter().map(Into::into).collect());
+        self.allow(Capability::FsRead)
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.allowed.contains(&capability)
+    }
+
+    pub(crate) fn check_builtin(&self, builtin: &BuiltinFunction) -> Result<()> {
+        match builtin.capability() {
+            Some(capability) if !self.allows(capability) => fail!(
+                ErrorCode::SandboxViolation,
+                "Builtin function `{builtin}` is not allowed: {capability} is denied by the sandbox policy"
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails unless files can be read and `path` is under one of the allowed paths.
+    pub fn check_fs_read(&self, path: &str) -> Result<()> {
+        let allowed = self.allows(Capability::FsRead)
+            && self.fs_read.as_ref().is_none_or(|prefixes| {
+                prefixes
+                    .iter()
+                    .any(|prefix| is_under(path, prefix.trim_end_matches('/')))
+            });
+        if !allowed {
+            fail!(
+                ErrorCode::SandboxViolation,
+                "Reading `{path}` is denied by the sandbox policy"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Fails unless the policy of the environment calling the builtin allows reading `path`.
+/// Builtins called by the host, outside any evaluation, can read any path.
+#[cfg(feature = "std")]
+pub(crate) fn check_caller_fs_read(path: &str) -> Result<()> {
+    with_caller(|caller| match caller {
+        Some(caller) => caller.policy.check_fs_read(path),
+        None => Ok(()),
+    })
+}
+
+fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        && !path.split('/').any(|part| part == "..")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities() {
+        let policy = Policy::locked_down().allow_fs_read(["/data/"]);
+        assert!(policy.check_fs_read("/data/a.csv").is_ok());
+        assert!(policy.check_fs_read("/data").is_ok());
+        for path in ["/database", "/data/../etc/passwd", "/etc/passwd"] {
+            let err = policy.check_fs_read(path).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SandboxViolation);
+        }
+        assert!(!policy.allows(Capability::Net));
+        assert!(Policy::default().check_fs_read("/etc/passwd").is_ok());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn database_paths() {
+        use crate::eval::{Environment, eval_with_env};
+        use alloc::rc::Rc;
+
+        let policy = Policy::locked_down()
+            .allow(Capability::FsWrite)
+            .allow_fs_read(["/nonexistent/data"]);
+        let env = || Rc::new(Environment::default().with_policy(policy.clone()));
+        assert!(eval_with_env("sql_open(\":memory:\")", env()).is_ok());
+        for input in [
+            "sql_open(\"/nonexistent/secret.db\")",
+            "collect(map([\"/nonexistent/secret.db\"], sql_open))",
+        ] {
+            let err = eval_with_env(input, env()).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SandboxViolation, "{input}");
+            let err = crate::vm::run(input, env()).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SandboxViolation, "{input}");
+        }
+    }
+
+    #[cfg(feature = "stdin")]
+    #[test]
+    fn locked_down_builtins() {
+        use crate::eval::{Environment, Object, eval_with_env};
+        use alloc::rc::Rc;
+
+        let env = Rc::new(Environment::default().with_policy(Policy::locked_down()));
+        assert_eq!(
+            eval_with_env("len(\"abc\")", Rc::clone(&env)).unwrap(),
+            Object::Int(3)
+        );
+        let err = eval_with_env("let f = read_stdin; 1", env).unwrap_err();
+        assert_eq!(err.code, ErrorCode::SandboxViolation);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::ast::printer::expression_to_source;
+use crate::ast::{Expression, Statement};
+use crate::eval::{HashMapKey, Object};
+use crate::prelude::*;
+
+/// Bounds on how much of a value [`Object::pretty`] renders; what's left out is
+/// replaced by `...`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrettyOptions {
+    /// Nesting level past which arrays and hashes are rendered as `[...]` and `{...}`.
+    pub max_depth: usize,
+    /// Elements of an array or entries of a hash rendered before the ellipsis.
+    pub max_items: usize,
+    /// Characters of a function's source rendered before the ellipsis.
+    pub max_source_len: usize,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            max_items: 100,
+            max_source_len: 400,
+        }
+    }
+}
+
+impl Object {
+    /// Renders the value like its `Display` does, except that containers holding other
+    /// containers and functions span several indented lines, hash entries are sorted by
+    /// key and the ellipses are placed as `options` say.
+    pub fn pretty(&self, options: &PrettyOptions) -> String {
+        let mut output = String::new();
+        pretty(self, options, 0, &mut output);
+        output
+    }
+}
+
+fn pretty(obj: &Object, options: &PrettyOptions, depth: usize, output: &mut String) {
+    let (open, close, items): (&str, &str, Vec<(Option<&HashMapKey>, &Object)>) = match obj {
+        Object::Return(value) => {
+            output.push_str("Return ");
+            return pretty(value, options, depth, output);
+        }
+        Object::Array(content) => ("[", "]", content.iter().map(|v| (None, v)).collect()),
+        Object::Hash(map) => {
+            let mut entries: Vec<_> = map.iter().map(|(k, v)| (Some(k), v)).collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            ("{", "}", entries)
+        }
+        Object::Function {
+            parameters, body, ..
+        } => {
+            let source = function_source(parameters, body, options.max_source_len, Some(depth));
+            return output.push_str(&source);
+        }
+        Object::Closure(closure) => {
+            let function = closure.function();
+            let source = function_source(
+                &function.parameters,
+                &function.body,
+                options.max_source_len,
+                Some(depth),
+            );
+            return output.push_str(&source);
+        }
+        obj => return output.push_str(&obj.to_string()),
+    };
+
+    if items.is_empty() {
+        output.push_str(open);
+        output.push_str(close);
+        return;
+    }
+    if depth >= options.max_depth {
+        output.push_str(open);
+        output.push_str("...");
+        output.push_str(close);
+        return;
+    }
+
+    let multiline = items.iter().any(|(_, v)| is_nested(v));
+    let padded = matches!(obj, Object::Hash(_)) && !multiline;
+    output.push_str(open);
+    if padded {
+        output.push(' ');
+    }
+    let truncated = items.len() > options.max_items;
+    for (i, (key, value)) in items.into_iter().take(options.max_items).enumerate() {
+        separate(output, i, multiline, depth);
+        if let Some(key) = key {
+            output.push_str(&format!("{key}: "));
+        }
+        pretty(value, options, depth + 1, output);
+    }
+    if truncated {
+        separate(output, options.max_items, multiline, depth);
+        output.push_str("...");
+    }
+    if multiline {
+        output.push_str(",\n");
+        output.push_str(&"    ".repeat(depth));
+    } else if padded {
+        output.push(' ');
+    }
+    output.push_str(close);
+}
+
+fn separate(output: &mut String, index: usize, multiline: bool, depth: usize) {
+    if multiline {
+        if index > 0 {
+            output.push(',');
+        }
+        output.push('\n');
+        output.push_str(&"    ".repeat(depth + 1));
+    } else if index > 0 {
+        output.push_str(", ");
+    }
+}
+
+/// The source of a function, cut after `max_len` characters: its lines indented by
+/// `depth` levels, or joined into one when `depth` is `None`.
+pub(crate) fn function_source(
+    parameters: &[String],
+    body: &[Statement],
+    max_len: usize,
+    depth: Option<usize>,
+) -> String {
+    if is_synthesized(body) {
+        return format!("fn({}) {{...}}", parameters.join(", "));
+    }
+    let func = Expression::Func {
+        args: parameters.to_vec(),
+        arg_types: vec![],
+        return_type: None,
+        body: body.to_vec(),
+    };
+    let source = expression_to_source(&func);
+    let mut chars = source.chars();
+    let shown: String = chars.by_ref().take(max_len).collect();
+    let mut shown = match depth {
+        Some(depth) => shown.replace('\n', &format!("\n{}", "    ".repeat(depth))),
+        None => shown.lines().map(str::trim).collect::<Vec<_>>().join(" "),
+    };
+    if chars.next().is_some() {
+        shown.push_str("...");
+    }
+    shown
+}
+
+/// Whether `body` is that of a function built by `partial` or `compose`, calling the
+/// functions it wraps through `$` variables that can't be written in Monkey.
+fn is_synthesized(body: &[Statement]) -> bool {
+    matches!(
+        body,
+        [Statement::Expr(Expression::Call { func, .. })]
+            if matches!(&**func, Expression::Ident(name) if name.starts_with('$'))
+    )
+}
+
+fn is_nested(obj: &Object) -> bool {
+    match obj {
+        Object::Array(content) => !content.is_empty(),
+        Object::Hash(map) => !map.is_empty(),
+        Object::Function { body, .. } => !body.is_empty() && !is_synthesized(body),
+        Object::Closure(closure) => !closure.function().body.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, eval_with_env};
+    use alloc::rc::Rc;
+
+    fn pretty(input: &str, max_depth: usize, max_items: usize) -> String {
+        let obj = eval_with_env(input, Rc::new(Environment::default())).unwrap();
+        obj.pretty(&PrettyOptions {
+            max_depth,
+            max_items,
+            ..PrettyOptions::default()
+        })
+    }
+
+    #[test]
+    fn truncation_and_layout() {
+        assert_eq!(pretty("[1, \"a\", []]", 8, 100), "[1, a, []]");
+        assert_eq!(pretty("{2: 1, 1: true}", 8, 100), "{ 1: true, 2: 1 }");
+        assert_eq!(pretty("collect(range(1000))", 8, 3), "[0, 1, 2, ...]");
+        assert_eq!(
+            pretty("[1, {\"b\": [2], \"a\": {}}]", 8, 100),
+            "[\n    1,\n    {\n        a: {},\n        b: [2],\n    },\n]"
+        );
+        assert_eq!(
+            pretty("[[[[1]]], [2, 3, 4]]", 2, 2),
+            "[\n    [\n        [...],\n    ],\n    [2, 3, ...],\n]"
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            pretty("[[1], fn(x, y) { let z = x; z + y }]", 8, 100),
+            "[\n    [1],\n    fn(x, y) {\n        let z = x;\n        z + y;\n    },\n]"
+        );
+        let obj = eval_with_env("fn() { 123456 }", Rc::new(Environment::default())).unwrap();
+        let options = PrettyOptions {
+            max_source_len: 10,
+            ..PrettyOptions::default()
+        };
+        assert_eq!(obj.pretty(&options), "fn() {\n   ...");
+
+        let display = |input: &str| {
+            let obj = eval_with_env(input, Rc::new(Environment::default())).unwrap();
+            obj.to_string()
+        };
+        assert_eq!(
+            display("[fn(x, y) { let z = x; z + y }]"),
+            "[fn(x, y) { let z = x; z + y; }]"
+        );
+        let long = display(&format!("fn() {{ {}1 }}", "1 + ".repeat(200)));
+        assert!(long.len() <= 403 && long.ends_with(" + 1..."), "{long}");
+        let closure = crate::vm::run("fn(x) { x * 2 }", Rc::new(Environment::default())).unwrap();
+        assert_eq!(closure.to_string(), "fn(x) { x * 2; }");
+    }
+}
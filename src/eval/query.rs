@@ -0,0 +1,246 @@
+//! Queries over rows, usually hashes like those of `csv_parse`, with columns picked by
+//! key or computed by a function of the row.
+
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{HashMapKey, HashObject, Object, apply};
+use crate::prelude::*;
+use core::cmp::Ordering;
+
+/// What `select`, `group_by` and `order_by` compute for each row.
+enum By {
+    Column(HashMapKey),
+    Function(Object),
+}
+
+impl By {
+    fn new(builtin: &str, by: Object) -> Result<Self> {
+        Ok(match by {
+            Object::Function { .. } | Object::Builtin(_) | Object::Closure(_) => By::Function(by),
+            Object::Int(key) => By::Column(HashMapKey::Int(key)),
+            Object::String(key) => By::Column(HashMapKey::String(key)),
+            Object::Bool(key) => By::Column(HashMapKey::Bool(key)),
+            o => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid second argument for builtin function `{builtin}`, expected key or function, found {o}"
+            ),
+        })
+    }
+
+    fn get(&self, builtin: &str, row: &Object) -> Result<Object> {
+        match (self, row) {
+            (By::Function(func), _) => apply(func.clone(), vec![row.clone()], None),
+            (By::Column(key), Object::Hash(map)) => {
+                Ok(map.get(key).cloned().unwrap_or(Object::Null))
+            }
+            (By::Column(_), o) => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid row {o} for builtin function `{builtin}`, expected hash"
+            ),
+        }
+    }
+}
+
+/// Rows for which `func` is truthy.
+pub(crate) fn where_(rows: &[Object], func: Object) -> Result<Object> {
+    let mut selected = vec![];
+    for row in rows {
+        if apply(func.clone(), vec![row.clone()], None)?.to_bool() {
+            selected.push(row.clone());
+        }
+    }
+    Ok(Object::Array(selected.into()))
+}
+
+/// Rows projected on the `columns`, missing ones being `null`, or mapped by a function.
+pub(crate) fn select(rows: &[Object], columns: Object) -> Result<Object> {
+    let projected = match columns {
+        Object::Array(columns) => {
+            let keys = columns
+                .iter()
+                .map(|column| match By::new("select", column.clone())? {
+                    By::Column(key) => Ok(key),
+                    By::Function(_) => fail!(
+                        ErrorCode::InvalidArgument,
+                        "Invalid column {column} for builtin function `select`, expected string, int or bool"
+                    ),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            rows.iter()
+                .map(|row| {
+                    let mut projection = HashObject::default();
+                    for key in &keys {
+                        let value = By::Column(key.clone()).get("select", row)?;
+                        projection.insert(key.clone(), value);
+                    }
+                    Ok(Object::Hash(projection))
+                })
+                .collect::<Result<Vec<_>>>()?
+        }
+        func @ (Object::Function { .. } | Object::Builtin(_) | Object::Closure(_)) => rows
+            .iter()
+            .map(|row| apply(func.clone(), vec![row.clone()], None))
+            .collect::<Result<Vec<_>>>()?,
+        o => fail!(
+            ErrorCode::InvalidArgument,
+            "Invalid second argument for builtin function `select`, expected array or function, found {o}"
+        ),
+    };
+    Ok(Object::Array(projected.into()))
+}
+
+/// Hash from the values of `by` to the rows having them, in order.
+pub(crate) fn group_by(rows: &[Object], by: Object) -> Result<Object> {
+    let by = By::new("group_by", by)?;
+    let mut groups: HashMap<HashMapKey, Vec<Object>> = HashMap::default();
+    for row in rows {
+        let key = match by.get("group_by", row)? {
+            Object::Int(key) => HashMapKey::Int(key),
+            Object::String(key) => HashMapKey::String(key),
+            Object::Bool(key) => HashMapKey::Bool(key),
+            _ => fail!(
+                ErrorCode::InvalidHashKey,
+                "Invalid object type for an hash key, must be int, str or bool!"
+            ),
+        };
+        groups.entry(key).or_default().push(row.clone());
+    }
+    let mut hash = HashObject::default();
+    for (key, group) in groups {
+        hash.insert(key, Object::Array(group.into()));
+    }
+    Ok(Object::Hash(hash))
+}
+
+/// Rows sorted by the values of `by`, stably, `null` coming first in ascending order.
+/// The values must otherwise all be numbers, ints and floats, strings or bools.
+pub(crate) fn order_by(rows: &[Object], by: Object, descending: bool) -> Result<Object> {
+    let by = By::new("order_by", by)?;
+    let mut keyed = vec![];
+    let mut kind: Option<&Object> = None;
+    let keys = rows
+        .iter()
+        .map(|row| by.get("order_by", row))
+        .collect::<Result<Vec<_>>>()?;
+    for (key, row) in keys.iter().zip(rows) {
+        match (key, kind) {
+            (Object::Null, _) => {}
+            (Object::Int(_) | Object::Float(_) | Object::String(_) | Object::Bool(_), None) => {
+                kind = Some(key)
+            }
+            (Object::Int(_) | Object::Float(_), Some(Object::Int(_) | Object::Float(_)))
+            | (Object::String(_), Some(Object::String(_)))
+            | (Object::Bool(_), Some(Object::Bool(_))) => {}
+            (key, _) => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid sort key {key} for builtin function `order_by`, expected number, string or bool like the others"
+            ),
+        }
+        keyed.push((key, row));
+    }
+    keyed.sort_by(|(a, _), (b, _)| {
+        let ordering = compare(a, b);
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    let sorted: Vec<Object> = keyed.into_iter().map(|(_, row)| row.clone()).collect();
+    Ok(Object::Array(sorted.into()))
+}
+
+fn compare(a: &Object, b: &Object) -> Ordering {
+    match (a, b) {
+        (Object::Null, Object::Null) => Ordering::Equal,
+        (Object::Null, _) => Ordering::Less,
+        (_, Object::Null) => Ordering::Greater,
+        (Object::Int(a), Object::Int(b)) => a.cmp(b),
+        (Object::Int(a), Object::Float(b)) => (*a as f64).total_cmp(b),
+        (Object::Float(a), Object::Int(b)) => a.total_cmp(&(*b as f64)),
+        (Object::Float(a), Object::Float(b)) => a.total_cmp(b),
+        (Object::String(a), Object::String(b)) => a.cmp(b),
+        (Object::Bool(a), Object::Bool(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorCode;
+    use crate::eval::{Environment, eval_with_env};
+    use crate::prelude::*;
+    use alloc::rc::Rc;
+
+    fn eval(input: &str) -> crate::error::Result<String> {
+        let env = Rc::new(Environment::default());
+        let rows = "let rows = [\
+            {\"name\": \"ada\", \"team\": \"x\", \"age\": 36}, \
+            {\"name\": \"bob\", \"team\": \"y\", \"age\": 29}, \
+            {\"name\": \"eve\", \"team\": \"x\"}, \
+            {\"name\": \"joe\", \"team\": \"y\", \"age\": 29}];";
+        eval_with_env(rows, Rc::clone(&env))?;
+        Ok(eval_with_env(input, env)?.to_string())
+    }
+
+    #[test]
+    fn queries() {
+        for (input, expected) in [
+            (
+                "select(where(rows, fn(r) { r[\"age\"] }), fn(r) { r[\"name\"] })",
+                "[ada, bob, joe]",
+            ),
+            (
+                "select(where(where(rows, fn(r) { r[\"age\"] }), fn(r) { r[\"age\"] > 30 }), [\"name\"])",
+                "[{ name: ada }]",
+            ),
+            (
+                "select(rows, fn(r) { r[\"name\"] })",
+                "[ada, bob, eve, joe]",
+            ),
+            (
+                "select(order_by(rows, \"age\"), fn(r) { r[\"name\"] })",
+                "[eve, bob, joe, ada]",
+            ),
+            (
+                "select(order_by(rows, \"age\", true), fn(r) { r[\"name\"] })",
+                "[ada, bob, joe, eve]",
+            ),
+            (
+                "select(order_by(rows, fn(r) { len(r) }), fn(r) { r[\"name\"] })",
+                "[eve, ada, bob, joe]",
+            ),
+            (
+                "select(group_by(rows, \"team\")[\"y\"], fn(r) { r[\"name\"] })",
+                "[bob, joe]",
+            ),
+            ("len(group_by(rows, fn(r) { len(r) > 2 })[true])", "3"),
+            ("select([{}], [\"age\"])", "[{ age: null }]"),
+            (
+                "order_by([{\"x\": 2}, {\"x\": 0.5}, {\"x\": -1e3}], \"x\")",
+                "[{ x: -1000.0 }, { x: 0.5 }, { x: 2 }]",
+            ),
+        ] {
+            assert_eq!(eval(input).unwrap(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn invalid_queries() {
+        for (input, code) in [
+            ("where(rows)", ErrorCode::ArgumentCount),
+            ("where({}, fn(r) { r })", ErrorCode::InvalidArgument),
+            ("where(rows, \"age\")", ErrorCode::InvalidArgument),
+            ("select(rows, [[1]])", ErrorCode::InvalidArgument),
+            ("select([1], [\"a\"])", ErrorCode::InvalidArgument),
+            ("group_by(rows, \"age\")", ErrorCode::InvalidHashKey),
+            ("order_by(rows, fn(r) { r })", ErrorCode::InvalidArgument),
+            (
+                "order_by([{\"a\": 1}, {\"a\": \"b\"}], \"a\")",
+                ErrorCode::InvalidArgument,
+            ),
+            ("order_by(rows, \"age\", 1)", ErrorCode::InvalidArgument),
+        ] {
+            assert_eq!(eval(input).unwrap_err().code, code, "{input}");
+        }
+    }
+}
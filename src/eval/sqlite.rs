@@ -0,0 +1,169 @@
+//! SQLite databases, which scripts hold as handles.
+
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{Handle, HashMapKey, HashObject, Object};
+use crate::prelude::*;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, params_from_iter};
+
+/// Opens the database at `path`, creating it if needed, or an in-memory one for `:memory:`.
+pub(crate) fn open(path: &str) -> Result<Object> {
+    match Connection::open(path) {
+        Ok(connection) => Ok(Object::Handle(Handle::new("database", connection))),
+        Err(err) => fail!(
+            ErrorCode::InvalidInput,
+            "Cannot open database {path}: {err}"
+        ),
+    }
+}
+
+/// Runs the query `sql`, returning one hash per row, from column names to values.
+pub(crate) fn query(name: &str, args: &[Object]) -> Result<Object> {
+    let (connection, sql, params) = arguments(name, args)?;
+    let mut statement = prepare(connection, sql)?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let mut rows = match statement.query(params_from_iter(params)) {
+        Ok(rows) => rows,
+        Err(err) => fail!(ErrorCode::InvalidInput, "Cannot run `{sql}`: {err}"),
+    };
+    let mut result = vec![];
+    loop {
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(err) => fail!(ErrorCode::InvalidInput, "Cannot run `{sql}`: {err}"),
+        };
+        let mut hash = HashObject::default();
+        for (i, column) in columns.iter().enumerate() {
+            let value = match row.get_ref(i) {
+                Ok(ValueRef::Null) => Object::Null,
+                Ok(ValueRef::Integer(value)) => Object::Int(value),
+                Ok(ValueRef::Real(value)) => Object::Float(value),
+                Ok(ValueRef::Text(text)) => Object::String(String::from_utf8_lossy(text).into()),
+                Ok(ValueRef::Blob(bytes)) => Object::Bytes(bytes.to_vec()),
+                Err(err) => fail!(ErrorCode::Internal, "Cannot read column {column}: {err}"),
+            };
+            hash.insert(HashMapKey::String(column.clone()), value);
+        }
+        result.push(Object::Hash(hash));
+    }
+    Ok(Object::Array(result.into()))
+}
+
+/// Runs the statement `sql`, returning how many rows it changed.
+pub(crate) fn exec(name: &str, args: &[Object]) -> Result<Object> {
+    let (connection, sql, params) = arguments(name, args)?;
+    match prepare(connection, sql)?.execute(params_from_iter(params)) {
+        Ok(changed) => Ok(Object::Int(changed as i64)),
+        Err(err) => fail!(ErrorCode::InvalidInput, "Cannot run `{sql}`: {err}"),
+    }
+}
+
+fn prepare<'c>(connection: &'c Connection, sql: &str) -> Result<rusqlite::Statement<'c>> {
+    match connection.prepare(sql) {
+        Ok(statement) => Ok(statement),
+        Err(err) => fail!(ErrorCode::InvalidInput, "Cannot run `{sql}`: {err}"),
+    }
+}
+
+/// The database, the SQL and its parameters: `(db, sql[, params])`, `params` being an
+/// array of null, ints, bools, strings and bytes bound to the `?` placeholders in order.
+fn arguments<'a>(
+    name: &str,
+    args: &'a [Object],
+) -> Result<(&'a Connection, &'a str, Vec<SqlValue>)> {
+    let (db, sql, params) = match args {
+        [db, sql] => (db, sql, &[][..]),
+        [db, sql, Object::Array(params)] => (db, sql, &params[..]),
+        [_, _, o] => fail!(
+            ErrorCode::InvalidArgument,
+            "Invalid third argument for builtin function `{name}`, expected array, found {o}"
+        ),
+        _ => fail!(
+            ErrorCode::ArgumentCount,
+            "Builtin function `{name}` expects 2 or 3 args, found {}.",
+            args.len()
+        ),
+    };
+    let connection = match db {
+        Object::Handle(handle) => handle.downcast_ref::<Connection>(),
+        _ => None,
+    };
+    let Some(connection) = connection else {
+        fail!(
+            ErrorCode::InvalidArgument,
+            "Invalid first argument for builtin function `{name}`, expected database, found {db}"
+        );
+    };
+    let Object::String(sql) = sql else {
+        fail!(
+            ErrorCode::InvalidArgument,
+            "Invalid second argument for builtin function `{name}`, expected string, found {sql}"
+        );
+    };
+    let params = params
+        .iter()
+        .map(|param| {
+            Ok(match param {
+                Object::Null => SqlValue::Null,
+                Object::Int(value) => SqlValue::Integer(*value),
+                Object::Float(value) => SqlValue::Real(*value),
+                Object::Bool(value) => SqlValue::Integer(i64::from(*value)),
+                Object::String(value) => SqlValue::Text(value.clone()),
+                Object::Bytes(value) => SqlValue::Blob(value.clone()),
+                o => fail!(
+                    ErrorCode::InvalidArgument,
+                    "Invalid parameter {o} for builtin function `{name}`, expected null, int, float, bool, string or bytes"
+                ),
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok((connection, sql, params))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, Policy, eval_with_env};
+    use alloc::rc::Rc;
+
+    #[test]
+    fn queries() {
+        let input = "let db = sql_open(\":memory:\"); \
+                     sql_exec(db, \"create table t (name text, n int, x real)\"); \
+                     sql_exec(db, \"insert into t values (?, ?, ?), (?, ?, ?)\", [\"a\", 1, 0.5, \"b\", null, 2]); \
+                     [sql_exec(db, \"update t set n = n + 1\"), \
+                      sql_query(db, \"select n from t where name = ?\", [\"a\"]), \
+                      sql_query(db, \"select x * 2 as x from t\")]";
+        assert_eq!(
+            eval_with_env(input, Rc::new(Environment::default()))
+                .unwrap()
+                .to_string(),
+            "[2, [{ n: 2 }], [{ x: 1.0 }, { x: 4.0 }]]"
+        );
+
+        let err = eval_with_env(
+            "sql_query(sql_open(\":memory:\"), \"select * from missing\")",
+            Rc::new(Environment::default()),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        let err = eval_with_env(
+            "sql_query(1, \"select 1\")",
+            Rc::new(Environment::default()),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn sandboxed() {
+        let env = Rc::new(Environment::default().with_policy(Policy::locked_down()));
+        let err = eval_with_env("sql_open(\":memory:\")", env).unwrap_err();
+        assert_eq!(err.code, ErrorCode::SandboxViolation);
+    }
+}
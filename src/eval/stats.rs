@@ -0,0 +1,141 @@
+use crate::error::{ErrorCode, Result, fail};
+use crate::prelude::*;
+use core::cell::Cell;
+use core::fmt;
+use core::time::Duration;
+
+/// Resources used by an evaluation, see [`eval_with_stats`](crate::eval::eval_with_stats).
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionStats {
+    /// Statements evaluated, those of function bodies and loops included.
+    pub steps: u64,
+    /// Deepest nesting of function calls.
+    pub max_depth: usize,
+    /// Strings, arrays, hashes, functions and iterators created by expressions.
+    pub objects_allocated: u64,
+    /// Wall-clock time of the evaluation; always zero without `std`.
+    pub duration: Duration,
+}
+
+impl fmt::Display for ExecutionStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} steps, max depth {}, {} objects allocated in {:?}",
+            self.steps, self.max_depth, self.objects_allocated, self.duration
+        )
+    }
+}
+
+/// Bounds on the resources counted in [`ExecutionStats`], past which evaluations fail
+/// with [`ErrorCode::LimitExceeded`]; `None` means unlimited. Steps and objects are
+/// counted since the evaluation started when measured, e.g. by
+/// [`eval_with_stats`](crate::eval::eval_with_stats), or else over the environment's life.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub max_steps: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub max_objects: Option<u64>,
+}
+
+/// Counters shared by an environment and the environments created from it.
+#[derive(Default, Debug)]
+pub(crate) struct Meter {
+    steps: Cell<u64>,
+    depth: Cell<usize>,
+    max_depth: Cell<usize>,
+    objects_allocated: Cell<u64>,
+    budget: Cell<Budget>,
+}
+
+impl Meter {
+    pub(crate) fn with_budget(budget: Budget) -> Self {
+        Self {
+            budget: Cell::new(budget),
+            ..Self::default()
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn budget(&self) -> Budget {
+        self.budget.get()
+    }
+
+    pub(crate) fn step(&self) -> Result<()> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if let Some(max) = self.budget.get().max_steps
+            && steps > max
+        {
+            fail!(
+                ErrorCode::LimitExceeded,
+                "The evaluation ran more than the {max} statements allowed"
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn allocated(&self) -> Result<()> {
+        let objects = self.objects_allocated.get() + 1;
+        self.objects_allocated.set(objects);
+        if let Some(max) = self.budget.get().max_objects
+            && objects > max
+        {
+            fail!(
+                ErrorCode::LimitExceeded,
+                "The evaluation allocated more than the {max} objects allowed"
+            );
+        }
+        Ok(())
+    }
+
+    /// Counts a function call until the returned guard is dropped.
+    pub(crate) fn call(&self) -> Result<CallGuard<'_>> {
+        self.enter_call()?;
+        Ok(CallGuard(self))
+    }
+
+    /// Counts a function call until [`Meter::leave_call`], for callers that can't hold
+    /// a [`CallGuard`] across it, like the VM.
+    pub(crate) fn enter_call(&self) -> Result<()> {
+        let depth = self.depth.get() + 1;
+        if let Some(max) = self.budget.get().max_depth
+            && depth > max
+        {
+            fail!(
+                ErrorCode::LimitExceeded,
+                "The evaluation nested more than the {max} function calls allowed"
+            );
+        }
+        self.depth.set(depth);
+        self.max_depth.set(self.max_depth.get().max(depth));
+        Ok(())
+    }
+
+    pub(crate) fn leave_call(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    pub(crate) fn reset(&self) {
+        self.steps.set(0);
+        self.max_depth.set(self.depth.get());
+        self.objects_allocated.set(0);
+    }
+
+    pub(crate) fn stats(&self, duration: Duration) -> ExecutionStats {
+        ExecutionStats {
+            steps: self.steps.get(),
+            max_depth: self.max_depth.get(),
+            objects_allocated: self.objects_allocated.get(),
+            duration,
+        }
+    }
+}
+
+pub(crate) struct CallGuard<'a>(&'a Meter);
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.0.leave_call();
+    }
+}
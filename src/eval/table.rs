@@ -0,0 +1,131 @@
+//! ASCII tables of rows, like those of `csv_parse` and the queries, for `table`.
+
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{HashMapKey, Object};
+use crate::prelude::*;
+
+/// Formats `rows`, hashes, as a table with a column per key, sorted, and a line per row.
+/// Missing and `null` cells are blank, and columns of numbers are aligned to the right.
+/// Nothing is formatted without any column.
+pub(crate) fn format(rows: &[Object]) -> Result<String> {
+    let mut maps = vec![];
+    for row in rows {
+        match row {
+            Object::Hash(map) => maps.push(map),
+            o => fail!(
+                ErrorCode::InvalidArgument,
+                "Invalid row {o} for builtin function `table`, expected hash"
+            ),
+        }
+    }
+    let mut columns: Vec<&HashMapKey> = maps.iter().flat_map(|map| map.keys()).collect();
+    columns.sort();
+    columns.dedup();
+    if columns.is_empty() {
+        return Ok(String::new());
+    }
+
+    let cells: Vec<Vec<String>> = maps
+        .iter()
+        .map(|map| {
+            columns
+                .iter()
+                .map(|column| match map.get(column) {
+                    None | Some(Object::Null) => String::new(),
+                    Some(value) => value.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+    let numeric: Vec<bool> = columns
+        .iter()
+        .map(|column| {
+            maps.iter().all(|map| {
+                matches!(
+                    map.get(*column),
+                    None | Some(Object::Null | Object::Int(_) | Object::Float(_))
+                )
+            })
+        })
+        .collect();
+    let header: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            cells
+                .iter()
+                .chain([&header])
+                .map(|line| line[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let border: String = widths
+        .iter()
+        .map(|width| format!("+{}", "-".repeat(width + 2)))
+        .collect::<String>()
+        + "+\n";
+    let line = |fields: &[String], right: &[bool]| {
+        let mut line = String::new();
+        for ((field, width), right) in fields.iter().zip(&widths).zip(right) {
+            let padding = " ".repeat(width - field.chars().count());
+            if *right {
+                line.push_str(&format!("| {padding}{field} "));
+            } else {
+                line.push_str(&format!("| {field}{padding} "));
+            }
+        }
+        line + "|\n"
+    };
+    let mut output = border.clone();
+    output.push_str(&line(&header, &vec![false; columns.len()]));
+    output.push_str(&border);
+    for row in &cells {
+        output.push_str(&line(row, &numeric));
+    }
+    output.push_str(&border);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Environment, eval_with_env};
+    use alloc::rc::Rc;
+
+    fn table(input: &str) -> Result<String> {
+        match eval_with_env(input, Rc::new(Environment::default()))? {
+            Object::Array(rows) => format(&rows),
+            o => panic!("expected array, found {o}"),
+        }
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!(
+            table("[{\"name\": \"ada\", \"age\": 36}, {\"name\": \"bob\", \"age\": 7, \"tags\": [1]}, {\"name\": \"évelyne\", \"age\": null}]")
+                .unwrap(),
+            "+-----+---------+------+\n\
+             | age | name    | tags |\n\
+             +-----+---------+------+\n\
+             |  36 | ada     |      |\n\
+             |   7 | bob     | [1]  |\n\
+             |     | évelyne |      |\n\
+             +-----+---------+------+\n"
+        );
+        assert_eq!(
+            table("[{1: 0.5, true: \"x\"}]").unwrap(),
+            "+------+-----+\n\
+             | true | 1   |\n\
+             +------+-----+\n\
+             | x    | 0.5 |\n\
+             +------+-----+\n"
+        );
+        assert_eq!(table("[]").unwrap(), "");
+        assert_eq!(table("[{}]").unwrap(), "");
+        assert_eq!(
+            table("[{\"a\": 1}, 2]").unwrap_err().to_string(),
+            "Invalid row 2 for builtin function `table`, expected hash"
+        );
+    }
+}
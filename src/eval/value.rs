@@ -0,0 +1,191 @@
+use crate::error::{Error, ErrorCode, Result, fail};
+use crate::eval::{HashMapKey, HashObject, Object};
+use crate::prelude::*;
+
+/// Copy of an [`Object`] holding plain data only, which unlike objects can be sent
+/// to other threads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    Hash(Vec<(HashMapKey, Value)>),
+    Error { message: String, data: Box<Value> },
+}
+
+impl Value {
+    /// Fails on functions, builtins and iterators, at any depth.
+    pub fn from_object(obj: &Object) -> Result<Self> {
+        Ok(match obj {
+            Object::Null => Value::Null,
+            Object::Int(value) => Value::Int(*value),
+            Object::Float(value) => Value::Float(*value),
+            Object::Bool(value) => Value::Bool(*value),
+            Object::String(value) => Value::String(value.clone()),
+            Object::Bytes(value) => Value::Bytes(value.clone()),
+            Object::Return(value) => Value::from_object(value)?,
+            Object::Error { message, data } => Value::Error {
+                message: message.clone(),
+                data: Box::new(Value::from_object(data)?),
+            },
+            Object::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(Value::from_object)
+                    .collect::<Result<_>>()?,
+            ),
+            // Native code sees the values of a tuple as an array.
+            Object::Tuple(values) => Value::Array(
+                values
+                    .iter()
+                    .map(Value::from_object)
+                    .collect::<Result<_>>()?,
+            ),
+            Object::Hash(map) => Value::Hash(
+                map.iter()
+                    .map(|(key, value)| Ok((key.clone(), Value::from_object(value)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            o => fail!(
+                ErrorCode::InvalidArgument,
+                "Cannot send {o}, only null, ints, floats, bools, strings, bytes, arrays, hashes and errors can be"
+            ),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Value {
+    /// Parses JSON, objects becoming hashes with string keys. Numbers are ints when they
+    /// are integers fitting in an `i64`, floats otherwise.
+    pub fn from_json(json: &str) -> Result<Self> {
+        match serde_json::from_str(json) {
+            Ok(value) => Self::from_json_value(value),
+            Err(err) => fail!(ErrorCode::InvalidInput, "Invalid JSON: {err}"),
+        }
+    }
+
+    fn from_json_value(value: serde_json::Value) -> Result<Self> {
+        use serde_json::Value as Json;
+
+        Ok(match value {
+            Json::Null => Value::Null,
+            Json::Bool(value) => Value::Bool(value),
+            Json::Number(number) => match (number.as_i64(), number.as_f64()) {
+                (Some(value), _) => Value::Int(value),
+                (None, Some(value)) => Value::Float(value),
+                (None, None) => fail!(ErrorCode::InvalidInput, "Invalid JSON number {number}"),
+            },
+            Json::String(value) => Value::String(value),
+            Json::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .map(Self::from_json_value)
+                    .collect::<Result<_>>()?,
+            ),
+            Json::Object(entries) => Value::Hash(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| {
+                        Ok((HashMapKey::String(key), Self::from_json_value(value)?))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+
+impl From<Value> for Object {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Object::Null,
+            Value::Int(value) => Object::Int(value),
+            Value::Float(value) => Object::Float(value),
+            Value::Bool(value) => Object::Bool(value),
+            Value::String(value) => Object::String(value),
+            Value::Bytes(value) => Object::Bytes(value),
+            Value::Array(items) => Object::Array(
+                items
+                    .into_iter()
+                    .map(Object::from)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            Value::Hash(entries) => {
+                let mut map = HashObject::default();
+                for (key, value) in entries {
+                    map.insert(key, value.into());
+                }
+                Object::Hash(map)
+            }
+            Value::Error { message, data } => Object::Error {
+                message,
+                data: Box::new((*data).into()),
+            },
+        }
+    }
+}
+
+/// Conversions of values to Rust types, failing on values of other types.
+macro_rules! try_from_value {
+    ($ty:ty, $variant:ident, $expected:literal) => {
+        impl TryFrom<Value> for $ty {
+            type Error = Error;
+
+            fn try_from(value: Value) -> Result<Self> {
+                match value {
+                    Value::$variant(value) => Ok(value),
+                    value => fail!(
+                        ErrorCode::TypeMismatch,
+                        "Expected {}, found {}",
+                        $expected,
+                        Object::from(value)
+                    ),
+                }
+            }
+        }
+    };
+}
+
+try_from_value!(i64, Int, "an int");
+try_from_value!(f64, Float, "a float");
+try_from_value!(bool, Bool, "a bool");
+try_from_value!(String, String, "a string");
+try_from_value!(Vec<u8>, Bytes, "bytes");
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json() {
+        let value = Value::from_json(r#"{"name": "Ada", "tags": [1, true, null]}"#).unwrap();
+        assert_eq!(
+            value,
+            Value::Hash(vec![
+                (
+                    HashMapKey::String("name".into()),
+                    Value::String("Ada".into())
+                ),
+                (
+                    HashMapKey::String("tags".into()),
+                    Value::Array(vec![Value::Int(1), Value::Bool(true), Value::Null])
+                ),
+            ])
+        );
+        assert_eq!(
+            Value::from_json("[1.5, 2e0]").unwrap(),
+            Value::Array(vec![Value::Float(1.5), Value::Float(2.0)])
+        );
+        assert_eq!(
+            Value::from_json("18446744073709551615").unwrap(),
+            Value::Float(18446744073709551615.0)
+        );
+        let err = Value::from_json("{").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+    }
+}
@@ -0,0 +1,242 @@
+//! Workers: functions running on their own thread, in an environment of their own,
+//! exchanging copies of values through mailboxes.
+
+use crate::error::{Error, ErrorCode, Result, fail};
+use crate::eval::environment::with_caller;
+use crate::eval::{Environment, Object, Value, apply};
+use crate::prelude::*;
+use std::cell::RefCell;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+enum Message {
+    Value(Value),
+    /// The worker with the given id failed.
+    Failed(i64, Error),
+}
+
+/// Mailbox of the current thread and handles of the workers it spawned, the worker
+/// with id `n` being `workers[n - 1]`.
+struct Mailbox {
+    sender: Sender<Message>,
+    receiver: Receiver<Message>,
+    parent: Option<Sender<Message>>,
+    workers: Vec<(Sender<Message>, JoinHandle<()>)>,
+}
+
+impl Mailbox {
+    fn new(parent: Option<Sender<Message>>) -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver,
+            parent,
+            workers: vec![],
+        }
+    }
+}
+
+thread_local! {
+    static MAILBOX: RefCell<Mailbox> = RefCell::new(Mailbox::new(None));
+}
+
+/// Starts running `func` with `args` on a new thread. The function only sees its
+/// parameters and the builtins: the variables it captured are left behind. It runs
+/// under the policy, budget and interrupt of the environment calling `spawn`.
+pub(crate) fn spawn(func: &Object, args: &[Object]) -> Result<Object> {
+    let (parameters, body, doc) = match func {
+        Object::Function {
+            parameters,
+            body,
+            doc,
+            ..
+        } => (parameters.clone(), body.clone(), doc.clone()),
+        Object::Closure(closure) => {
+            let function = closure.function();
+            (
+                function.parameters.clone(),
+                function.body.clone(),
+                function.doc().map(str::to_owned),
+            )
+        }
+        _ => fail!(
+            ErrorCode::InvalidArgument,
+            "Invalid first argument for builtin function `spawn`, expected function, found {func}"
+        ),
+    };
+    let args = args
+        .iter()
+        .map(Value::from_object)
+        .collect::<Result<Vec<Value>>>()?;
+
+    let (policy, budget, interrupt) = with_caller(|caller| match caller {
+        Some(caller) => (
+            (*caller.policy).clone(),
+            caller.budget,
+            caller.interrupt.clone(),
+        ),
+        None => Default::default(),
+    });
+
+    MAILBOX.with_borrow_mut(|mailbox| {
+        let id = mailbox.workers.len() as i64 + 1;
+        let parent = mailbox.sender.clone();
+        let (sender, receiver) = channel();
+        let own = sender.clone();
+        let handle = thread::Builder::new()
+            .name(format!("worker {id}"))
+            .stack_size(8 << 20)
+            .spawn(move || {
+                MAILBOX.set(Mailbox {
+                    sender: own,
+                    receiver,
+                    parent: Some(parent.clone()),
+                    workers: vec![],
+                });
+                let func = Object::Function {
+                    parameters,
+                    body,
+                    environment: Environment::default()
+                        .with_policy(policy)
+                        .with_budget(budget)
+                        .with_interrupt(interrupt),
+                    doc,
+                };
+                let args = args.into_iter().map(Object::from).collect();
+                if let Err(err) = apply(func, args, None) {
+                    let _ = parent.send(Message::Failed(id, err));
+                }
+            });
+        match handle {
+            Ok(handle) => mailbox.workers.push((sender, handle)),
+            Err(err) => fail!(ErrorCode::Internal, "Cannot spawn a worker: {err}"),
+        }
+        Ok(Object::Int(id))
+    })
+}
+
+/// Sends a copy of `value` to the worker with id `worker`, or with id 0 to the one
+/// that spawned the current worker.
+pub(crate) fn send(worker: i64, value: &Object) -> Result<Object> {
+    let value = Value::from_object(value)?;
+    MAILBOX.with_borrow(|mailbox| {
+        let sender = match worker {
+            0 => mailbox.parent.as_ref(),
+            id => usize::try_from(id - 1)
+                .ok()
+                .and_then(|index| mailbox.workers.get(index))
+                .map(|(sender, _)| sender),
+        };
+        match sender {
+            Some(sender) => {
+                let _ = sender.send(Message::Value(value));
+                Ok(Object::Null)
+            }
+            None => fail!(ErrorCode::InvalidArgument, "Unknown worker {worker}"),
+        }
+    })
+}
+
+/// Waits for the next message to the current thread. Without a parent, returns `null`
+/// once no message is left and every worker spawned has finished.
+pub(crate) fn recv() -> Result<Object> {
+    MAILBOX.with_borrow(|mailbox| {
+        loop {
+            match mailbox.receiver.recv_timeout(Duration::from_millis(10)) {
+                Ok(message) => return receive(message),
+                Err(RecvTimeoutError::Timeout)
+                    if mailbox.parent.is_none()
+                        && mailbox
+                            .workers
+                            .iter()
+                            .all(|(_, handle)| handle.is_finished()) =>
+                {
+                    // The last workers may have sent a message right before finishing.
+                    return match mailbox.receiver.try_recv() {
+                        Ok(message) => receive(message),
+                        Err(_) => Ok(Object::Null),
+                    };
+                }
+                Err(_) => {}
+            }
+        }
+    })
+}
+
+/// A failed worker makes the thread receiving its failure fail too.
+fn receive(message: Message) -> Result<Object> {
+    match message {
+        Message::Value(value) => Ok(value.into()),
+        Message::Failed(id, err) => Err(err.in_frame(format!("worker {id}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Budget, Capability, Policy, eval_with_env};
+    use alloc::rc::Rc;
+
+    fn eval(input: &str) -> Result<Object> {
+        eval_with_env(input, Rc::new(Environment::default()))
+    }
+
+    #[test]
+    fn message_passing() {
+        let input = "let square = fn(n) { send(0, n * n) }; spawn(square, 3); spawn(square, 4); \
+                     let echo = spawn(fn() { send(0, push(recv(), \"!\")) }); \
+                     send(echo, [\"hi\"]); \
+                     let a = recv(); let b = recv(); let c = recv(); [recv(), a, b, c]";
+        let result = eval(input).unwrap().to_string();
+        assert!(result.starts_with("[null, "), "{result}");
+        for message in ["9", "16", "[hi, !]"] {
+            assert!(result.contains(message), "{result}");
+        }
+    }
+
+    #[test]
+    fn closures_of_the_vm() {
+        let input = "spawn(fn(n) { send(0, n * 2) }, 21); recv()";
+        let result = crate::vm::run(input, Rc::new(Environment::default())).unwrap();
+        assert_eq!(result, Object::Int(42));
+    }
+
+    #[test]
+    fn failures() {
+        let err = eval("spawn(fn(x) { x + true }, 1); recv()").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidOperation);
+        assert_eq!(err.stack, vec!["fn(x)", "worker 1", "builtin recv"]);
+
+        let err = eval("spawn(fn(f) { f() }, len)").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+        let err = eval("send(9, 2)").unwrap_err();
+        assert_eq!(err.message, "Unknown worker 9");
+
+        let input = "spawn(fn() { send(0, error(\"bad\", {\"code\": 400})) }); \
+                     let e = recv(); [e, e[\"data\"]]";
+        assert_eq!(
+            eval(input).unwrap().to_string(),
+            "[Error: bad, { code: 400 }]"
+        );
+    }
+
+    #[test]
+    fn inherited_sandbox() {
+        let policy = Policy::locked_down().allow(Capability::Threads);
+        let env = || Rc::new(Environment::default().with_policy(policy.clone()));
+        let input = "spawn(fn() { args() }); recv()";
+        let err = eval_with_env(input, env()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::SandboxViolation);
+        let err = crate::vm::run(input, env()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::SandboxViolation);
+
+        let budget = Budget {
+            max_steps: Some(100),
+            ..Budget::default()
+        };
+        let env = Rc::new(Environment::default().with_budget(budget));
+        let err = eval_with_env("spawn(fn() { while (true) {} }); recv()", env).unwrap_err();
+        assert_eq!(err.code, ErrorCode::LimitExceeded);
+    }
+}
@@ -0,0 +1,244 @@
+//! Interpreters embedded in a host with preset rules, like a formula engine evaluating
+//! untrusted expressions with [`Interpreter::expression_sandbox`].
+
+use crate::analysis::{SymbolTable, check_assignments};
+use crate::cst;
+use crate::error::{Error, ErrorCode, Result, catch_unwind, did_you_mean, fail};
+use crate::eval::{
+    Budget, BuiltinFunction, Environment, Eval, Object, Policy, Value, builtin_names, measure,
+};
+use crate::{Parser, Program, Statement};
+use std::rc::Rc;
+
+/// An environment and the rules the evaluations in it follow. The default interpreter
+/// runs whole programs like [`eval_with_env`](crate::eval::eval_with_env).
+pub struct Interpreter {
+    env: Rc<Environment>,
+    /// Whether inputs must be a single expression. Its bindings, e.g. in `if` blocks,
+    /// are then dropped after the evaluation.
+    pub expression_only: bool,
+    /// Whether identifiers that are neither bound nor builtins are rejected before
+    /// the evaluation, instead of evaluating to `null`.
+    pub strict: bool,
+    /// Whether builtins registered at runtime, which may do anything, can be called.
+    pub allow_plugins: bool,
+    /// Length in bytes of the source of a single evaluation, `None` meaning unlimited.
+    pub max_source_len: Option<usize>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new(Rc::new(Environment::default()))
+    }
+}
+
+impl Interpreter {
+    pub fn new(env: Rc<Environment>) -> Self {
+        Self {
+            env,
+            expression_only: false,
+            strict: false,
+            allow_plugins: true,
+            max_source_len: None,
+        }
+    }
+
+    /// A safe formula engine, like the cells of a spreadsheet: inputs are single
+    /// expressions over the variables the host [`set`](Self::set), which can't name
+    /// unknown identifiers, call builtins touching the outside world or run for long.
+    pub fn expression_sandbox() -> Self {
+        let env = Environment::default()
+            .with_policy(Policy::locked_down())
+            .with_budget(Budget {
+                max_steps: Some(10_000),
+                max_depth: Some(32),
+                max_objects: Some(10_000),
+            });
+        Self {
+            expression_only: true,
+            strict: true,
+            allow_plugins: false,
+            max_source_len: Some(4096),
+            ..Self::new(Rc::new(env))
+        }
+    }
+
+    pub fn env(&self) -> &Rc<Environment> {
+        &self.env
+    }
+
+    /// Binds an input of the evaluations, e.g. a cell referenced by a formula.
+    pub fn set(&self, name: impl Into<String>, value: impl Into<Object>) {
+        self.env.set(name, value);
+    }
+
+    pub fn eval(&self, input: &str) -> Result<Object> {
+        if let Some(max) = self.max_source_len
+            && input.len() > max
+        {
+            fail!(
+                ErrorCode::LimitExceeded,
+                "The source is longer than the {max} bytes allowed"
+            );
+        }
+        let mut parser = Parser::init(input);
+        let (program, env) = if self.expression_only {
+            let expr = parser.parse_expression_only()?;
+            let program = Program {
+                statements: vec![Statement::Expr(expr)],
+            };
+            let env = Environment::init_with_outer(Rc::clone(&self.env));
+            (program, Rc::new(env))
+        } else {
+            (parser.parse_program()?, Rc::clone(&self.env))
+        };
+        if self.strict || !self.allow_plugins {
+            self.check_identifiers(input)?;
+        }
+        check_assignments(&cst::parse(input))?;
+        let (result, _) = measure(&env, || catch_unwind(|| program.eval(Rc::clone(&env))));
+        result
+    }
+
+    /// Evaluates `input` and converts its value, e.g. `eval_as::<i64>("price * qty")`.
+    pub fn eval_as<T: TryFrom<Value, Error = Error>>(&self, input: &str) -> Result<T> {
+        let obj = self.eval(input)?;
+        match Value::from_object(&obj) {
+            Ok(value) => T::try_from(value),
+            Err(_) => fail!(ErrorCode::TypeMismatch, "Expected plain data, found {obj}"),
+        }
+    }
+
+    /// Fails at the first identifier of `input` that is unknown in strict mode, or that
+    /// is a builtin registered at runtime when they aren't allowed.
+    fn check_identifiers(&self, input: &str) -> Result<()> {
+        let known = self.env.names();
+        for (name, span) in SymbolTable::build(&cst::parse(input)).unresolved {
+            let err = match BuiltinFunction::from_name(&name) {
+                Some(BuiltinFunction::Plugin(_)) if !self.allow_plugins => Error::new(
+                    ErrorCode::SandboxViolation,
+                    format!(
+                        "Builtin function `{name}` is not allowed: builtins registered at runtime are denied by the interpreter"
+                    ),
+                ),
+                Some(_) => continue,
+                None if !self.strict || name == "null" || known.contains(&name) => continue,
+                None => {
+                    let mut names: Vec<&str> = builtin_names().collect();
+                    names.extend(known.iter().map(String::as_str));
+                    let message = match did_you_mean(&name, names) {
+                        Some(suggestion) => {
+                            format!("Unknown identifier `{name}`, did you mean `{suggestion}`?")
+                        }
+                        None => format!("Unknown identifier `{name}`"),
+                    };
+                    Error::new(ErrorCode::UnknownIdentifier, message)
+                }
+            };
+            return Err(err.with_span(span));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::Arity;
+    use crate::eval::plugin::{BuiltinPack, register_builtins};
+
+    #[test]
+    fn formulas() {
+        let sandbox = Interpreter::expression_sandbox();
+        sandbox.set("price", Object::Int(12));
+        sandbox.set("quantity", Object::Int(3));
+        assert_eq!(sandbox.eval_as::<i64>("price * quantity;").unwrap(), 36);
+        assert!(
+            sandbox
+                .eval_as::<bool>("if (quantity > 2) { let bulk = true; bulk } else { false }")
+                .unwrap()
+        );
+        assert_eq!(
+            sandbox.eval_as::<String>("len(\"abc\")").unwrap_err().code,
+            ErrorCode::TypeMismatch
+        );
+        assert_eq!(
+            sandbox.eval_as::<i64>("fn(x) { x }").unwrap_err().code,
+            ErrorCode::TypeMismatch
+        );
+        // Nothing a formula binds outlives it.
+        assert_eq!(sandbox.env().names(), vec!["price", "quantity"]);
+
+        let err = sandbox.eval("let total = price * quantity").unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnexpectedToken);
+        assert!(sandbox.eval("price; quantity").is_err());
+        let err = sandbox.eval("price * quantiy").unwrap_err();
+        assert_eq!(
+            (err.to_string(), err.code, err.span),
+            (
+                "Unknown identifier `quantiy`, did you mean `quantity`?".to_owned(),
+                ErrorCode::UnknownIdentifier,
+                Some(8..15)
+            )
+        );
+        assert_eq!(
+            sandbox.eval("map([1], fn(x) { x + y })").unwrap_err().code,
+            ErrorCode::UnknownIdentifier
+        );
+        let err = Interpreter::default().eval("let x = y; x").unwrap_err();
+        assert_eq!(
+            (err.code, err.message),
+            (
+                ErrorCode::UnknownIdentifier,
+                "identifier not found: y".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn sandboxing() {
+        let sandbox = Interpreter::expression_sandbox();
+        for input in ["send_host(1)", "csv_read(\"/etc/passwd\")"] {
+            let err = sandbox.eval(input).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SandboxViolation, "{input}");
+        }
+
+        fn call_probe(_: &BuiltinFunction, _: Vec<Object>) -> Result<Object> {
+            Ok(Object::Null)
+        }
+        let pack =
+            BuiltinPack::new().builtin("sandbox_probe", Arity::exactly(0), "", "", call_probe);
+        register_builtins(pack).unwrap();
+        let err = sandbox.eval("sandbox_probe()").unwrap_err();
+        assert_eq!(err.code, ErrorCode::SandboxViolation);
+        assert_eq!(
+            Interpreter::default().eval("sandbox_probe()").unwrap(),
+            Object::Null
+        );
+
+        for input in [
+            "for (x in range(100000)) { x }",
+            "fn(f) { f(f) }(fn(f) { f(f) })",
+            &"1 + ".repeat(2000),
+        ] {
+            let err = sandbox.eval(input).unwrap_err();
+            assert_eq!(err.code, ErrorCode::LimitExceeded, "{input}");
+        }
+        // Budgets are per evaluation.
+        for _ in 0..3 {
+            assert!(
+                sandbox
+                    .eval("collect(map(range(3000), fn(x) { x }))")
+                    .is_ok()
+            );
+        }
+        let interpreter = Interpreter::new(Rc::new(Environment::default().with_budget(Budget {
+            max_objects: Some(100),
+            ..Budget::default()
+        })));
+        let err = interpreter
+            .eval("collect(map(range(1000), fn(x) { [x] }))")
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::LimitExceeded);
+    }
+}
@@ -0,0 +1,174 @@
+//! Notebook kernel: cells run in one persistent environment and results come
+//! back with both a plain and an HTML rendering.
+
+use crate::eval::{Environment, HashMapKey, Object, eval_with_env};
+use std::io::{BufRead, Read, Write};
+use std::rc::Rc;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct DisplayData {
+    pub plain: String,
+    pub html: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ExecuteReply {
+    pub execution_count: usize,
+    pub result: Result<DisplayData, String>,
+}
+
+impl ExecuteReply {
+    /// Reply as a single JSON line, shaped after the Jupyter `execute_result`/`error` contents.
+    pub fn to_json(&self) -> String {
+        match &self.result {
+            Ok(data) => format!(
+                "{{\"status\": \"ok\", \"execution_count\": {}, \"data\": {{\"text/plain\": {}, \"text/html\": {}}}}}",
+                self.execution_count,
+                json_string(&data.plain),
+                json_string(&data.html)
+            ),
+            Err(message) => format!(
+                "{{\"status\": \"error\", \"execution_count\": {}, \"ename\": \"Error\", \"evalue\": {}}}",
+                self.execution_count,
+                json_string(message)
+            ),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Kernel {
+    env: Rc<Environment>,
+    execution_count: usize,
+}
+
+impl Kernel {
+    pub fn execute(&mut self, code: &str) -> ExecuteReply {
+        self.execution_count += 1;
+        ExecuteReply {
+            execution_count: self.execution_count,
+            result: eval_with_env(code, Rc::clone(&self.env))
+                .map(|obj| DisplayData {
+                    plain: obj.to_string(),
+                    html: to_html(&obj),
+                })
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    /// Serves cells framed as `<byte length>\n<code>`, answering each with a JSON line.
+    /// A thin shim translating to ZeroMQ is enough to plug this into Jupyter.
+    pub fn serve(&mut self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        let mut input = input;
+        loop {
+            let mut header = String::new();
+            if input.read_line(&mut header)? == 0 {
+                return Ok(());
+            }
+            let Ok(len) = header.trim().parse::<u64>() else {
+                if header.trim().is_empty() {
+                    continue;
+                }
+                writeln!(
+                    output,
+                    "{{\"status\": \"error\", \"ename\": \"ProtocolError\", \"evalue\": {}}}",
+                    json_string(&format!("Invalid frame header `{}`", header.trim()))
+                )?;
+                output.flush()?;
+                continue;
+            };
+            let mut code = String::new();
+            (&mut input).take(len).read_to_string(&mut code)?;
+            writeln!(output, "{}", self.execute(&code).to_json())?;
+            output.flush()?;
+        }
+    }
+}
+
+pub fn to_html(obj: &Object) -> String {
+    match obj {
+        Object::String(value) => format!("<code>{}</code>", escape_html(&format!("{value:?}"))),
+        Object::Array(content) => format!(
+            "<ol start=\"0\">{}</ol>",
+            content
+                .iter()
+                .map(|c| format!("<li>{}</li>", to_html(c)))
+                .collect::<String>()
+        ),
+        Object::Hash(map) => {
+            let mut pairs: Vec<(&HashMapKey, &Object)> = map.iter().collect();
+            pairs.sort_by_key(|(k, _)| k.to_string());
+            format!(
+                "<table>{}</table>",
+                pairs
+                    .into_iter()
+                    .map(|(k, v)| format!(
+                        "<tr><th>{}</th><td>{}</td></tr>",
+                        escape_html(&k.to_string()),
+                        to_html(v)
+                    ))
+                    .collect::<String>()
+            )
+        }
+        obj => format!("<code>{}</code>", escape_html(&obj.to_string())),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn json_string(text: &str) -> String {
+    let mut output = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn persistent_cells() {
+        let mut kernel = Kernel::default();
+        assert_eq!(kernel.execute("let x = [1, \"<a>\"];").execution_count, 1);
+        let reply = kernel.execute("x");
+        assert_eq!(reply.execution_count, 2);
+        assert_eq!(
+            reply.result,
+            Ok(DisplayData {
+                plain: "[1, <a>]".to_owned(),
+                html: "<ol start=\"0\"><li><code>1</code></li><li><code>&quot;&lt;a&gt;&quot;</code></li></ol>".to_owned()
+            })
+        );
+        assert!(kernel.execute("x + 1").result.is_err());
+    }
+
+    #[test]
+    fn wire() {
+        let mut output = vec![];
+        Kernel::default()
+            .serve("5\n1 + 2\nnope\n5\n-true".as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"status\": \"ok\", \"execution_count\": 1, \"data\": {\"text/plain\": \"3\", \"text/html\": \"<code>3</code>\"}}\n\
+             {\"status\": \"error\", \"ename\": \"ProtocolError\", \"evalue\": \"Invalid frame header `nope`\"}\n\
+             {\"status\": \"error\", \"execution_count\": 2, \"ename\": \"Error\", \"evalue\": \"true cannot be negated!\"}\n"
+        );
+    }
+}
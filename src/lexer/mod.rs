@@ -1,33 +1,122 @@
-use std::iter::Peekable;
-use std::str::Chars;
+use crate::prelude::*;
+use crate::trace::event;
+use core::fmt;
+use core::iter::Peekable;
+use core::mem;
+use core::ops::Range;
+use core::str::CharIndices;
 
 mod token;
+mod trivia;
 
-pub use token::Token;
+pub use token::{KEYWORDS, Token};
+pub use trivia::{TokenWithTrivia, Trivia};
+
+/// Where a token starts: its 1-based line and column, the column counted in chars, and
+/// its byte offset.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            line: 1,
+            column: 1,
+            offset: 0,
+        }
+    }
+}
+
+impl Position {
+    /// Position of the byte `offset` of `input`.
+    pub fn at(input: &str, offset: usize) -> Self {
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
 
 pub struct Lexer<'a> {
-    chars_iter: Peekable<Chars<'a>>,
+    input: &'a str,
+    chars_iter: Peekable<CharIndices<'a>>,
     ch: Option<char>,
+    position: usize,
+    line: usize,
+    column: usize,
+    token_start: usize,
+    token_position: Position,
+    keep_trivia: bool,
+    trivia: Vec<Trivia>,
+    keywords: &'a [(&'a str, Token)],
 }
 
 impl<'a> Lexer<'a> {
     pub fn init(input: &'a str) -> Self {
         let mut lexer = Self {
-            chars_iter: input.chars().peekable(),
+            input,
+            chars_iter: input.char_indices().peekable(),
             ch: None,
+            position: 0,
+            line: 1,
+            column: 1,
+            token_start: 0,
+            token_position: Position::default(),
+            keep_trivia: false,
+            trivia: vec![],
+            keywords: KEYWORDS,
         };
         lexer.advance_char();
         lexer
     }
 
+    /// Lexer that keeps whitespace and comments as trivia instead of discarding them,
+    /// so that the original source can be reconstructed from the tokens.
+    pub fn with_trivia(input: &'a str) -> Self {
+        let mut lexer = Self::init(input);
+        lexer.keep_trivia = true;
+        lexer
+    }
+
+    /// Lexer that reads keywords from `keywords` instead of [`KEYWORDS`], e.g. to spell
+    /// `fn` as `function`: the words left out of the table are plain identifiers.
+    pub fn with_keywords(input: &'a str, keywords: &'a [(&'a str, Token)]) -> Self {
+        let mut lexer = Self::init(input);
+        lexer.keywords = keywords;
+        lexer
+    }
+
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        self.token_start = self.position;
+        self.token_position = Position {
+            line: self.line,
+            column: self.column,
+            offset: self.position,
+        };
 
         let token = match self.ch {
             Some('=') => {
                 if self.peek_char() == Some('=') {
                     self.advance_char();
                     Token::Eq
+                } else if self.peek_char() == Some('>') {
+                    self.advance_char();
+                    Token::FatArrow
                 } else {
                     Token::Assign
                 }
@@ -40,22 +129,97 @@ impl<'a> Lexer<'a> {
                     Token::Bang
                 }
             }
+            Some('&') => {
+                if self.peek_char() == Some('&') {
+                    self.advance_char();
+                    Token::And
+                } else {
+                    Token::Illegal
+                }
+            }
+            Some('|') => {
+                if self.peek_char() == Some('|') {
+                    self.advance_char();
+                    Token::Or
+                } else {
+                    Token::Illegal
+                }
+            }
             Some('+') => Token::Plus,
-            Some('-') => Token::Minus,
-            Some('*') => Token::Asterisk,
-            Some('/') => Token::Slash,
-            Some('<') => Token::Lt,
-            Some('>') => Token::Gt,
+            Some('-') => {
+                if self.peek_char() == Some('>') {
+                    self.advance_char();
+                    Token::Arrow
+                } else {
+                    Token::Minus
+                }
+            }
+            Some('*') => {
+                if self.peek_char() == Some('*') {
+                    self.advance_char();
+                    Token::Power
+                } else {
+                    Token::Asterisk
+                }
+            }
+            Some('%') => Token::Percent,
+            Some('/') => {
+                if self.peek_char() == Some('*') {
+                    // Only unterminated block comments are left by `skip_whitespace`.
+                    while self.peek_char().is_some() {
+                        self.advance_char();
+                    }
+                    Token::Illegal
+                } else {
+                    Token::Slash
+                }
+            }
+            Some('<') => {
+                if self.peek_char() == Some('=') {
+                    self.advance_char();
+                    Token::LtEq
+                } else {
+                    Token::Lt
+                }
+            }
+            Some('>') => {
+                if self.peek_char() == Some('=') {
+                    self.advance_char();
+                    Token::GtEq
+                } else {
+                    Token::Gt
+                }
+            }
             Some(',') => Token::Comma,
             Some(';') => Token::Semicolon,
             Some(':') => Token::Colon,
+            Some('?') => Token::Question,
+            Some('.') => {
+                if self.peek_char() != Some('.') {
+                    Token::Dot
+                } else {
+                    self.advance_char();
+                    if self.peek_char() == Some('.') {
+                        self.advance_char();
+                        Token::Ellipsis
+                    } else {
+                        Token::Illegal
+                    }
+                }
+            }
+            Some('_') => match self.peek_char() {
+                // Only the names of hooks like `__add` may start with underscores.
+                Some('_') => self.parse_identifier(),
+                Some(c) if c.is_ascii_alphanumeric() => Token::Illegal,
+                _ => Token::Underscore,
+            },
             Some('(') => Token::LParen,
             Some(')') => Token::RParen,
             Some('{') => Token::LBrace,
             Some('}') => Token::RBrace,
             Some('[') => Token::LBracket,
             Some(']') => Token::RBracket,
-            Some('a'..='z') => self.parse_identifier(),
+            Some('a'..='z' | 'A'..='Z') => self.parse_identifier(),
             Some('0'..='9') => self.parse_number(),
             Some('"') => self.parse_string(),
             None => Token::Eof,
@@ -64,9 +228,25 @@ impl<'a> Lexer<'a> {
 
         self.advance_char();
 
+        event!(?token, offset = self.token_start, "token consumed");
         token
     }
 
+    /// Byte range of the last token returned by [`Lexer::next_token`].
+    pub fn span(&self) -> Range<usize> {
+        self.token_start..self.position
+    }
+
+    /// Source text at `span`, e.g. of a token.
+    pub fn text(&self, span: Range<usize>) -> &'a str {
+        &self.input[span]
+    }
+
+    /// Where the last token returned by [`Lexer::next_token`] starts.
+    pub fn token_position(&self) -> Position {
+        self.token_position
+    }
+
     pub fn get_all_tokens(&mut self) -> Vec<Token> {
         let mut output: Vec<Token> = vec![];
         loop {
@@ -78,59 +258,178 @@ impl<'a> Lexer<'a> {
         output
     }
 
+    pub fn next_token_with_trivia(&mut self) -> TokenWithTrivia {
+        let token = self.next_token();
+        TokenWithTrivia {
+            leading: mem::take(&mut self.trivia),
+            token,
+            text: self.input[self.token_start..self.position].to_owned(),
+            offset: self.token_start,
+        }
+    }
+
+    pub fn get_all_tokens_with_trivia(&mut self) -> Vec<TokenWithTrivia> {
+        let mut output: Vec<TokenWithTrivia> = vec![];
+        loop {
+            output.push(self.next_token_with_trivia());
+            if output.last().unwrap().token == Token::Eof {
+                break;
+            }
+        }
+        output
+    }
+
     fn advance_char(&mut self) {
-        self.ch = self.chars_iter.next()
+        match self.ch {
+            Some('\n') => {
+                self.line += 1;
+                self.column = 1;
+            }
+            Some(_) => self.column += 1,
+            None => {}
+        }
+        match self.chars_iter.next() {
+            Some((position, ch)) => {
+                self.position = position;
+                self.ch = Some(ch);
+            }
+            None => {
+                self.position = self.input.len();
+                self.ch = None;
+            }
+        }
     }
 
     fn peek_char(&mut self) -> Option<char> {
-        self.chars_iter.peek().copied()
+        self.chars_iter.peek().map(|(_, ch)| *ch)
     }
 
+    /// Skips whitespace and comments, keeping them as trivia if asked to. An unterminated
+    /// block comment is left for [`Lexer::next_token`] to return as an illegal token.
     fn skip_whitespace(&mut self) {
-        while self.ch == Some(' ')
-            || self.ch == Some('\t')
-            || self.ch == Some('\n')
-            || self.ch == Some('\r')
-        {
-            self.advance_char();
+        loop {
+            let start = self.position;
+            while self.ch == Some(' ')
+                || self.ch == Some('\t')
+                || self.ch == Some('\n')
+                || self.ch == Some('\r')
+            {
+                self.advance_char();
+            }
+            if self.keep_trivia && self.position > start {
+                let text = self.input[start..self.position].to_owned();
+                self.trivia.push(Trivia::Whitespace(text));
+            }
+            let start = self.position;
+            match (self.ch, self.peek_char()) {
+                (Some('/'), Some('/')) => {
+                    while self.ch.is_some() && self.ch != Some('\n') {
+                        self.advance_char();
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    let Some(length) = self.block_comment_len() else {
+                        return;
+                    };
+                    while self.position < start + length {
+                        self.advance_char();
+                    }
+                }
+                _ => return,
+            }
+            if self.keep_trivia {
+                let text = self.input[start..self.position].to_owned();
+                self.trivia.push(Trivia::Comment(text));
+            }
         }
     }
 
+    /// Length in bytes of the block comment starting at the current char, including the
+    /// comments nested in it, `None` if it is unterminated.
+    fn block_comment_len(&self) -> Option<usize> {
+        let rest = &self.input.as_bytes()[self.position..];
+        let mut depth = 0;
+        let mut i = 0;
+        while i < rest.len() {
+            if rest[i..].starts_with(b"/*") {
+                depth += 1;
+                i += 2;
+            } else if rest[i..].starts_with(b"*/") {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Some(i);
+                }
+            } else {
+                i += 1;
+            }
+        }
+        None
+    }
+
     fn parse_identifier(&mut self) -> Token {
         let mut output = String::new();
         while let Some(ch) = self.ch {
             output.push(ch);
             let peek = self.peek_char();
-            if peek.is_some() && peek.unwrap().is_alphabetic() {
+            if peek.is_some_and(|ch| ch.is_alphabetic() || ch.is_ascii_digit() || ch == '_') {
                 self.advance_char();
             } else {
                 break;
             }
         }
-        match output.as_str() {
-            "fn" => Token::Function,
-            "let" => Token::Let,
-            "true" => Token::True,
-            "false" => Token::False,
-            "if" => Token::If,
-            "else" => Token::Else,
-            "return" => Token::Return,
-            _ => Token::Ident(output),
+        match self.keywords.iter().find(|(keyword, _)| *keyword == output) {
+            Some((_, token)) => token.clone(),
+            None => Token::Ident(output),
         }
     }
 
     fn parse_number(&mut self) -> Token {
-        let mut output = 0;
-        while let Some(ch) = self.ch {
-            output = output * 10 + ch.to_digit(10).unwrap();
-            let peek = self.peek_char();
-            if peek.is_some() && peek.unwrap().is_numeric() {
+        if let Some(length) = self.float_len() {
+            let literal = &self.input[self.position..self.position + length];
+            for _ in 1..length {
                 self.advance_char();
-            } else {
-                break;
+            }
+            return match literal.parse() {
+                Ok(value) => Token::Float(value),
+                Err(_) => Token::Illegal,
+            };
+        }
+        let start = self.position;
+        while self.peek_char().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.advance_char();
+        }
+        match self.input[start..=self.position].parse() {
+            Ok(value) => Token::Int(value),
+            Err(_) => Token::Illegal,
+        }
+    }
+
+    /// Length in bytes of the number starting at the current char if it is a float, with
+    /// a fractional part, an exponent or both, like `1.5`, `2e-3` or `0.5E10`.
+    fn float_len(&self) -> Option<usize> {
+        let bytes = &self.input.as_bytes()[self.position..];
+        let digits = |from: usize| {
+            bytes[from.min(bytes.len())..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count()
+        };
+        let mut end = digits(0);
+        let mut float = false;
+        if bytes.get(end) == Some(&b'.') && digits(end + 1) > 0 {
+            end += 1 + digits(end + 1);
+            float = true;
+        }
+        if let Some(b'e' | b'E') = bytes.get(end) {
+            let sign = usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+            let exponent = digits(end + 1 + sign);
+            if exponent > 0 {
+                end += 1 + sign + exponent;
+                float = true;
             }
         }
-        Token::Int(output as i64)
+        float.then_some(end)
     }
 
     fn parse_string(&mut self) -> Token {
@@ -142,10 +441,35 @@ impl<'a> Lexer<'a> {
             string.push(ch);
             self.advance_char();
         }
+        // Left to the parser, which reports the missing quote.
+        if self.ch.is_none() {
+            return Token::Illegal;
+        }
         Token::String(string)
     }
 }
 
+/// Whether `input` ends inside a string or a block comment, or with a `{`, `(` or `[`
+/// left open, so that a REPL should read more lines before parsing it.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut lexer = Lexer::init(input);
+    let mut depth: i64 = 0;
+    loop {
+        match lexer.next_token() {
+            Token::Eof => return depth > 0,
+            Token::LBrace | Token::LParen | Token::LBracket => depth += 1,
+            Token::RBrace | Token::RParen | Token::RBracket => depth -= 1,
+            Token::Illegal
+                if lexer.text(lexer.span()).starts_with("/*")
+                    || lexer.text(lexer.span()).starts_with('"') =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,6 +493,44 @@ mod tests {
         )
     }
 
+    #[test]
+    fn incomplete_inputs() {
+        assert!(is_incomplete("let f = fn(x) {"));
+        assert!(is_incomplete("let a = [1, (2"));
+        assert!(is_incomplete("\"a ( b"));
+        assert!(is_incomplete("\""));
+        assert!(is_incomplete("1 /* a"));
+        assert!(is_incomplete("puts(\"a);\n"));
+        assert!(!is_incomplete("let f = fn(x) { x };"));
+        assert!(!is_incomplete("\"{\" // ("));
+        assert!(!is_incomplete("1 }"));
+        assert!(!is_incomplete(""));
+    }
+
+    #[test]
+    fn positions() {
+        let mut lexer = Lexer::init("let x = 1;\n  \"é\" +\n\nx");
+        let mut positions = vec![];
+        while lexer.next_token() != Token::Eof {
+            let position = lexer.token_position();
+            positions.push((position.line, position.column, position.offset));
+        }
+        assert_eq!(
+            positions,
+            vec![
+                (1, 1, 0),
+                (1, 5, 4),
+                (1, 7, 6),
+                (1, 9, 8),
+                (1, 10, 9),
+                (2, 3, 13),
+                (2, 7, 18),
+                (4, 1, 21),
+            ]
+        );
+        assert_eq!(lexer.token_position().to_string(), "line 4, column 2");
+    }
+
     #[test]
     fn extended_test() {
         let mut lexer = Lexer::init(
@@ -180,7 +542,7 @@ mod tests {
             }; \n\
             \n\
             let result = add(five, ten); \n\
-            !-/*5; \n\
+            !-/ *5; \n\
             5 < 10 > 5; \n\
             \n\
             if (5 < 10) { \n\
@@ -289,4 +651,254 @@ mod tests {
             ]
         )
     }
+
+    #[test]
+    fn trivia_round_trip() {
+        let input =
+            "// adds two numbers\nlet add = fn(x, y) { x + y; };\n\n\n  add(1, 2) // call\n";
+        let tokens = Lexer::with_trivia(input).get_all_tokens_with_trivia();
+        let output: String = tokens.iter().map(|t| t.to_string()).collect();
+        assert_eq!(output, input);
+
+        assert_eq!(
+            tokens[0].leading,
+            vec![
+                Trivia::Comment(String::from("// adds two numbers")),
+                Trivia::Whitespace(String::from("\n"))
+            ]
+        );
+        assert_eq!(tokens[0].blank_lines(), 0);
+
+        let call = tokens.iter().filter(|t| t.text == "add").nth(1).unwrap();
+        assert_eq!(call.blank_lines(), 2);
+        assert_eq!(call.token, Token::Ident(String::from("add")));
+
+        let eof = tokens.last().unwrap();
+        assert_eq!(eof.comments().collect::<Vec<_>>(), vec!["// call"]);
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(
+            Lexer::init("1.5 2e3 0.25E-2 7. 1.x 3e 10").get_all_tokens(),
+            vec![
+                Token::Float(1.5),
+                Token::Float(2000.0),
+                Token::Float(0.0025),
+                Token::Int(7),
+                Token::Dot,
+                Token::Int(1),
+                Token::Dot,
+                Token::Ident(String::from("x")),
+                Token::Int(3),
+                Token::Ident(String::from("e")),
+                Token::Int(10),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let input = "1 // one\n/* two /* nested */ */ 2 /**/ / /* * / */ 3 /* é";
+        let mut lexer = Lexer::init(input);
+        let mut tokens = vec![];
+        loop {
+            let token = lexer.next_token();
+            tokens.push((token.clone(), lexer.span()));
+            if token == Token::Eof {
+                break;
+            }
+        }
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::Int(1), 0..1),
+                (Token::Int(2), 32..33),
+                (Token::Slash, 39..40),
+                (Token::Int(3), 51..52),
+                (Token::Illegal, 53..58),
+                (Token::Eof, 58..58),
+            ]
+        );
+
+        let tokens = Lexer::with_trivia(input).get_all_tokens_with_trivia();
+        assert_eq!(
+            tokens[1].comments().collect::<Vec<_>>(),
+            vec!["// one", "/* two /* nested */ */"]
+        );
+        assert_eq!(
+            tokens.iter().map(|t| t.to_string()).collect::<String>(),
+            input
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let tokens = Lexer::init("fn let iffy if true").get_all_tokens();
+        let keywords: Vec<bool> = tokens.iter().map(Token::is_keyword).collect();
+        assert_eq!(keywords, vec![true, true, false, true, true, false]);
+        assert_eq!(tokens[3].keyword(), Some("if"));
+    }
+
+    #[test]
+    fn custom_keywords() {
+        let keywords = [("function", Token::Function), ("se", Token::If)];
+        let tokens = Lexer::with_keywords("function fn se if", &keywords).get_all_tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Function,
+                Token::Ident(String::from("fn")),
+                Token::If,
+                Token::Ident(String::from("if")),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(
+            Lexer::init("4294967296 9223372036854775807 9223372036854775808 1").get_all_tokens(),
+            vec![
+                Token::Int(4294967296),
+                Token::Int(i64::MAX),
+                Token::Illegal,
+                Token::Int(1),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn snake_case_identifiers() {
+        let tokens = Lexer::init("read_stdin _a __add").get_all_tokens();
+        assert_eq!(tokens[0], Token::Ident(String::from("read_stdin")));
+        assert_eq!(tokens[1], Token::Illegal);
+        assert_eq!(tokens[3], Token::Ident(String::from("__add")));
+    }
+
+    #[test]
+    fn identifiers_with_digits() {
+        let tokens = Lexer::init("base64 x2y 1x").get_all_tokens();
+        assert_eq!(tokens[0], Token::Ident(String::from("base64")));
+        assert_eq!(tokens[1], Token::Ident(String::from("x2y")));
+        assert_eq!(tokens[2], Token::Int(1));
+    }
+
+    #[test]
+    fn dots() {
+        let tokens = Lexer::init("a.b(1.5) . ..").get_all_tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident(String::from("a")),
+                Token::Dot,
+                Token::Ident(String::from("b")),
+                Token::LParen,
+                Token::Float(1.5),
+                Token::RParen,
+                Token::Dot,
+                Token::Illegal,
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn patterns() {
+        let tokens = Lexer::init("match [_, ...r] => .. x?").get_all_tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Match,
+                Token::LBracket,
+                Token::Underscore,
+                Token::Comma,
+                Token::Ellipsis,
+                Token::Ident(String::from("r")),
+                Token::RBracket,
+                Token::FatArrow,
+                Token::Illegal,
+                Token::Ident(String::from("x")),
+                Token::Question,
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        let tokens = Lexer::init("a<=b >= c < =").get_all_tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident(String::from("a")),
+                Token::LtEq,
+                Token::Ident(String::from("b")),
+                Token::GtEq,
+                Token::Ident(String::from("c")),
+                Token::Lt,
+                Token::Assign,
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        let tokens = Lexer::init("a%b ** c * *d").get_all_tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident(String::from("a")),
+                Token::Percent,
+                Token::Ident(String::from("b")),
+                Token::Power,
+                Token::Ident(String::from("c")),
+                Token::Asterisk,
+                Token::Asterisk,
+                Token::Ident(String::from("d")),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn logical_operators() {
+        let tokens = Lexer::init("a&&b || c & d").get_all_tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident(String::from("a")),
+                Token::And,
+                Token::Ident(String::from("b")),
+                Token::Or,
+                Token::Ident(String::from("c")),
+                Token::Illegal,
+                Token::Ident(String::from("d")),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let tokens = Lexer::init("struct P { x } p.x").get_all_tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Struct,
+                Token::Ident(String::from("P")),
+                Token::LBrace,
+                Token::Ident(String::from("x")),
+                Token::RBrace,
+                Token::Ident(String::from("p")),
+                Token::Dot,
+                Token::Ident(String::from("x")),
+                Token::Eof,
+            ]
+        );
+    }
 }
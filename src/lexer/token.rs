@@ -1,6 +1,8 @@
-use std::fmt;
+use crate::prelude::*;
+use core::fmt;
 
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Token {
     Illegal,
     Eof,
@@ -8,6 +10,7 @@ pub enum Token {
     // Identifiers and Literals
     Ident(String),
     Int(i64),
+    Float(f64),
     String(String),
 
     // Operators
@@ -17,17 +20,30 @@ pub enum Token {
     Bang,     // !
     Asterisk, // *
     Slash,    // /
+    Percent,  // %
+    Power,    // **
+    Question, // ?
 
-    Lt, // <
-    Gt, // >
+    Lt,   // <
+    Gt,   // >
+    LtEq, // <=
+    GtEq, // >=
 
     Eq,    // ==
     NotEq, // !=
 
+    And, // &&
+    Or,  // ||
+
     // Delimiters
-    Comma,     // ,
-    Semicolon, // ;
-    Colon,     // :
+    Comma,      // ,
+    Semicolon,  // ;
+    Colon,      // :
+    Arrow,      // ->
+    FatArrow,   // =>
+    Dot,        // .
+    Ellipsis,   // ...
+    Underscore, // _
 
     LParen,   // (
     RParen,   // )
@@ -44,6 +60,50 @@ pub enum Token {
     If,
     Else,
     Return,
+    For,
+    While,
+    Break,
+    Continue,
+    In,
+    Yield,
+    Match,
+    Struct,
+    Mut,
+}
+
+/// The reserved keywords of the language and their tokens, the default table of the
+/// lexer.
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("true", Token::True),
+    ("false", Token::False),
+    ("fn", Token::Function),
+    ("let", Token::Let),
+    ("if", Token::If),
+    ("else", Token::Else),
+    ("return", Token::Return),
+    ("for", Token::For),
+    ("while", Token::While),
+    ("break", Token::Break),
+    ("continue", Token::Continue),
+    ("in", Token::In),
+    ("yield", Token::Yield),
+    ("match", Token::Match),
+    ("struct", Token::Struct),
+    ("mut", Token::Mut),
+];
+
+impl Token {
+    /// Source text of the token if it is a reserved keyword.
+    pub fn keyword(&self) -> Option<&'static str> {
+        KEYWORDS
+            .iter()
+            .find(|(_, token)| token == self)
+            .map(|(keyword, _)| *keyword)
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        self.keyword().is_some()
+    }
 }
 
 impl fmt::Display for Token {
@@ -53,6 +113,7 @@ impl fmt::Display for Token {
             Token::Eof => write!(f, "EOF"),
             Token::Ident(value) => write!(f, "<identifier={value}>"),
             Token::Int(value) => write!(f, "<int={value}>"),
+            Token::Float(value) => write!(f, "<float={value:?}>"),
             Token::String(value) => write!(f, "<str={value}>"),
             Token::Assign => write!(f, "`=`"),
             Token::Plus => write!(f, "`+`"),
@@ -60,13 +121,25 @@ impl fmt::Display for Token {
             Token::Bang => write!(f, "`!`"),
             Token::Asterisk => write!(f, "`*`"),
             Token::Slash => write!(f, "`/`"),
+            Token::Percent => write!(f, "`%`"),
+            Token::Power => write!(f, "`**`"),
             Token::Lt => write!(f, "`<`"),
             Token::Gt => write!(f, "`>`"),
+            Token::LtEq => write!(f, "`<=`"),
+            Token::GtEq => write!(f, "`>=`"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
             Token::Eq => write!(f, "`==`"),
             Token::NotEq => write!(f, "`!=`"),
+            Token::Question => write!(f, "`?`"),
             Token::Comma => write!(f, "`,`"),
             Token::Semicolon => write!(f, "`;`"),
             Token::Colon => write!(f, "`:`"),
+            Token::Arrow => write!(f, "`->`"),
+            Token::FatArrow => write!(f, "`=>`"),
+            Token::Dot => write!(f, "`.`"),
+            Token::Ellipsis => write!(f, "`...`"),
+            Token::Underscore => write!(f, "`_`"),
             Token::LParen => write!(f, "`(`"),
             Token::RParen => write!(f, "`)`"),
             Token::LBrace => write!(f, "`{{`"),
@@ -80,6 +153,15 @@ impl fmt::Display for Token {
             Token::If => write!(f, "`if`"),
             Token::Else => write!(f, "`else`"),
             Token::Return => write!(f, "`return`"),
+            Token::For => write!(f, "`for`"),
+            Token::While => write!(f, "`while`"),
+            Token::Break => write!(f, "`break`"),
+            Token::Continue => write!(f, "`continue`"),
+            Token::In => write!(f, "`in`"),
+            Token::Yield => write!(f, "`yield`"),
+            Token::Match => write!(f, "`match`"),
+            Token::Struct => write!(f, "`struct`"),
+            Token::Mut => write!(f, "`mut`"),
         }
     }
 }
@@ -0,0 +1,65 @@
+use crate::Token;
+use crate::prelude::*;
+use core::fmt;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Trivia {
+    Whitespace(String),
+    Comment(String),
+}
+
+impl Trivia {
+    pub fn text(&self) -> &str {
+        match self {
+            Trivia::Whitespace(text) => text,
+            Trivia::Comment(text) => text,
+        }
+    }
+
+    pub fn newlines(&self) -> usize {
+        self.text().matches('\n').count()
+    }
+}
+
+impl fmt::Display for Trivia {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// A token together with the source text it was lexed from and the trivia preceding it.
+/// Writing out every token of a source in order reproduces it byte for byte.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TokenWithTrivia {
+    pub leading: Vec<Trivia>,
+    pub token: Token,
+    pub text: String,
+    pub offset: usize,
+}
+
+impl TokenWithTrivia {
+    /// Number of empty lines between the previous token and this one.
+    pub fn blank_lines(&self) -> usize {
+        self.leading
+            .iter()
+            .filter(|t| matches!(t, Trivia::Whitespace(_)))
+            .map(|t| t.newlines().saturating_sub(1))
+            .sum()
+    }
+
+    pub fn comments(&self) -> impl Iterator<Item = &str> {
+        self.leading.iter().filter_map(|t| match t {
+            Trivia::Comment(text) => Some(text.as_str()),
+            Trivia::Whitespace(_) => None,
+        })
+    }
+}
+
+impl fmt::Display for TokenWithTrivia {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for trivia in &self.leading {
+            write!(f, "{trivia}")?;
+        }
+        write!(f, "{}", self.text)
+    }
+}
@@ -1,10 +1,144 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+
 pub mod ast;
-pub use ast::{Expression, InfixOperator, PrefixOperator, Program, Statement};
+pub use ast::{Expression, InfixOperator, PrefixOperator, Program, Statement, TypeAnnotation};
+
+pub mod compiler;
+
+#[cfg(feature = "std")]
+pub mod cst;
+
+#[cfg(feature = "std")]
+pub mod doctest;
+
+pub mod error;
 
 pub mod eval;
 
+#[cfg(feature = "std")]
+pub mod interpreter;
+#[cfg(feature = "std")]
+pub use interpreter::Interpreter;
+
+#[cfg(feature = "kernel")]
+pub mod kernel;
+
 pub mod lexer;
 pub use lexer::{Lexer, Token};
 
+#[cfg(feature = "manifest")]
+pub mod manifest;
+
+#[cfg(feature = "std")]
+pub mod minify;
+
+#[cfg(feature = "std")]
+pub mod notebook;
+
+#[cfg(feature = "std")]
+pub mod pager;
+
 pub mod parser;
 pub use parser::Parser;
+
+mod prelude;
+
+#[cfg(feature = "std")]
+pub mod reactive;
+
+#[cfg(feature = "std")]
+pub mod refactor;
+
+#[cfg(feature = "repl")]
+pub mod repl;
+
+#[cfg(feature = "serde")]
+pub mod replay;
+
+mod trace;
+
+#[cfg(feature = "std")]
+pub mod script;
+
+pub mod session;
+
+pub mod stats;
+
+pub mod template;
+#[cfg(feature = "std")]
+pub use script::run_script;
+pub use template::render_template;
+
+pub mod testing;
+
+#[cfg(feature = "std")]
+pub mod transpile;
+
+#[cfg(feature = "std")]
+pub mod typecheck;
+
+pub mod vm;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::eval::{Environment, Object};
+use crate::prelude::*;
+use alloc::rc::Rc;
+
+/// Tokens of `input`, `Eof` excluded. Fails at the first character that starts no token.
+pub fn lex(input: &str) -> Result<Vec<Token>> {
+    let mut lexer = Lexer::init(input);
+    let mut tokens = vec![];
+    loop {
+        match lexer.next_token() {
+            Token::Eof => return Ok(tokens),
+            Token::Illegal => {
+                let span = lexer.span();
+                let source = input.get(span.clone()).unwrap_or_default();
+                let message = format!("Illegal token `{source}` at byte {}", span.start);
+                return Err(Error::new(ErrorCode::UnexpectedToken, message).with_span(span));
+            }
+            token => tokens.push(token),
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Program> {
+    Parser::init(input).parse_program()
+}
+
+/// Evaluates `input` in a new environment, see [`eval::eval_with_env`] to keep bindings
+/// across evaluations.
+pub fn eval(input: &str) -> Result<Object> {
+    eval::eval_with_env(input, Rc::new(Environment::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcuts() {
+        assert_eq!(
+            lex("let x = 1;").unwrap(),
+            vec![
+                Token::Let,
+                Token::Ident("x".to_owned()),
+                Token::Assign,
+                Token::Int(1),
+                Token::Semicolon
+            ]
+        );
+        let err = lex("1 # 2").unwrap_err();
+        assert_eq!(
+            (err.code, err.span),
+            (ErrorCode::UnexpectedToken, Some(2..3))
+        );
+        assert_eq!(parse("1 + 2").unwrap().statements.len(), 1);
+        assert_eq!(eval("let x = 2; x * 3").unwrap(), Object::Int(6));
+    }
+}
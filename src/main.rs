@@ -1,18 +1,628 @@
-use std::io::stdin;
+use std::cell::RefCell;
+use std::env::args;
+use std::fs;
+use std::io::{Read, Write, stdin, stdout};
+use std::process::exit;
 use std::rc::Rc;
 
-use waiir::eval::{Environment, eval_with_env};
+use waiir::analysis::shadowed_bindings;
+use waiir::ast::{printer::statement_to_source, sexp::to_sexp, to_dot};
+use waiir::cst;
+use waiir::doctest;
+use waiir::error::{Error, ErrorCode};
+#[cfg(feature = "repl")]
+use waiir::eval::Policy;
+#[cfg(feature = "serde")]
+use waiir::eval::Value;
+use waiir::eval::{
+    Environment, Eval, Execution, Object, Observer, builtins_reference, eval_with_env,
+    eval_with_stats, set_script_args,
+};
+#[cfg(feature = "manifest")]
+use waiir::manifest::Project;
+use waiir::notebook::run_markdown;
+#[cfg(feature = "repl")]
+use waiir::pager::{Pager, terminal_height};
+#[cfg(feature = "repl")]
+use waiir::repl::LineEditor;
+#[cfg(feature = "repl")]
+use waiir::replay::{self, Recorder, Session};
+use waiir::script::{FAILURE, INTERRUPTED};
+use waiir::{Lexer, Parser, Token, render_template};
+
+const USAGE: &str = "Usage: waiir [--plugin <library>]... [--vm] [--warn-shadow | --deny-shadow] [-e <program> [--stats | -- <args>...] | [--file] <file.monkey> | - | -n <program> | --trace-eval <file.monkey> | --record <session.json> | replay <session.json> | notebook <file.md> [-o <output.md>] | doctest <file.monkey> | run [<dir>] [--leak-check] | test [<dir>] | tokens <file.monkey> [--format text|json|sexp] | ast <file.monkey> [--format text|json|sexp] | viz [--env] <file.monkey> [-o <output.dot>] | render <template.mtpl> [--data <data.json>] | explain <code> | builtins]";
 
 fn main() {
+    let mut args: Vec<String> = args().skip(1).collect();
+    let (vm, shadow) = global_flags(&mut args);
+    match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        #[cfg(feature = "repl")]
+        [] => repl(None, vm),
+        #[cfg(feature = "repl")]
+        ["--record", path] => repl(Some(path), vm),
+        #[cfg(feature = "repl")]
+        ["replay", path] => replay(path),
+        ["-e", program] => run(program, false, vm, shadow),
+        ["-e", program, "--stats"] => run(program, true, vm, shadow),
+        ["-e", program, "--", ref script_args @ ..] => {
+            set_script_args(script_args.iter().map(ToString::to_string).collect());
+            run(program, false, vm, shadow)
+        }
+        ["-"] => run(&read_stdin(), false, vm, shadow),
+        ["--file", path] => run(&read(path), false, vm, shadow),
+        ["-n", program] => run_per_line(program, vm),
+        ["--trace-eval", path] => trace_eval(path),
+        ["notebook", path] => notebook(path, None),
+        ["notebook", path, "-o", output] => notebook(path, Some(output)),
+        ["doctest", path] => doctest(path),
+        #[cfg(feature = "manifest")]
+        ["run"] => run_project(".", false),
+        #[cfg(feature = "manifest")]
+        ["run", "--leak-check"] => run_project(".", true),
+        #[cfg(feature = "manifest")]
+        ["run", dir] => run_project(dir, false),
+        #[cfg(feature = "manifest")]
+        ["run", dir, "--leak-check"] => run_project(dir, true),
+        #[cfg(feature = "manifest")]
+        ["test"] => test_project("."),
+        #[cfg(feature = "manifest")]
+        ["test", dir] => test_project(dir),
+        ["tokens", path] => tokens(path, "text"),
+        ["tokens", path, "--format", format] => tokens(path, format),
+        ["ast", path] => ast(path, "text"),
+        ["ast", path, "--format", format] => ast(path, format),
+        ["viz", path] => viz(path, false, None),
+        ["viz", path, "-o", output] => viz(path, false, Some(output)),
+        ["viz", "--env", path] => viz(path, true, None),
+        ["viz", "--env", path, "-o", output] => viz(path, true, Some(output)),
+        ["render", path] => render(path, None),
+        #[cfg(feature = "serde")]
+        ["render", path, "--data", data] => render(path, Some(data)),
+        ["explain", code] => explain(code),
+        ["builtins"] => print!("{}", builtins_reference()),
+        [path] if !path.starts_with('-') => run(&read(path), false, vm, shadow),
+        _ => {
+            eprintln!("{USAGE}");
+            exit(2);
+        }
+    }
+}
+
+/// Takes `--plugin <library>`, `--vm`, `--warn-shadow` and `--deny-shadow` out of `args`,
+/// wherever they are before `--`, loading the plugins. Returns whether to run on the VM
+/// and what to do about shadowed bindings.
+fn global_flags(args: &mut Vec<String>) -> (bool, ShadowCheck) {
+    let mut vm = false;
+    let mut shadow = ShadowCheck::Allow;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--" => break,
+            "--vm" => vm = true,
+            "--warn-shadow" => shadow = ShadowCheck::Warn,
+            "--deny-shadow" => shadow = ShadowCheck::Deny,
+            #[cfg(feature = "plugins")]
+            "--plugin" if i + 1 < args.len() => {
+                if let Err(err) = waiir::eval::plugin::load_plugin(&args[i + 1]) {
+                    fail(err);
+                }
+                args.remove(i);
+            }
+            // The value of an option isn't a flag, even if it looks like one.
+            "-e" | "-n" | "--file" | "--trace-eval" | "--record" | "-o" | "--format" | "--data" => {
+                i += 2;
+                continue;
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        args.remove(i);
+    }
+    (vm, shadow)
+}
+
+#[cfg(feature = "repl")]
+fn repl(record: Option<&str>, vm: bool) {
     println!("Hello, this is the Monkey programming language!");
     println!("Feel free to type in commands");
-    let env = Rc::new(Environment::default());
-    loop {
-        let mut buf = String::new();
-        stdin().read_line(&mut buf).unwrap();
-        match eval_with_env(buf.as_str(), Rc::clone(&env)) {
+    // Sessions are only replayable without the builtins reading the outside world.
+    let mut recorder = match record {
+        Some(_) => Recorder::default(),
+        None => Recorder::with_policy(Policy::default()),
+    };
+    if vm {
+        recorder = recorder.with_vm();
+    }
+    let mut pager = Pager::default();
+    let mut editor = LineEditor::new().unwrap_or_else(|err| {
+        eprintln!("Cannot start the line editor: {err}");
+        exit(1);
+    });
+    while let Some(buf) = editor.read().unwrap_or_else(|err| {
+        eprintln!("Cannot read the input: {err}");
+        exit(1);
+    }) {
+        let height = terminal_height();
+        if buf.trim() == ":more" {
+            match pager.more(height.unwrap_or(usize::MAX)) {
+                Some(page) => println!("{page}"),
+                None => println!("Nothing more to show"),
+            }
+            continue;
+        }
+        let output = match buf.trim().strip_prefix(":edit ") {
+            Some(name) => edit(&mut recorder, name.trim()),
+            None => recorder.eval(&buf),
+        };
+        // Results are paged on terminals only, e.g. not when the REPL's output is piped.
+        match height {
+            Some(height) => println!("{}", pager.page(&output, height)),
+            None => println!("{output}"),
+        }
+        if let Some(path) = record {
+            write(path, &recorder.session().to_json());
+        }
+    }
+}
+
+/// `:edit name`: opens the source of the function in `$EDITOR`, `vi` by default, then
+/// evaluates the saved source, which rebinds it. The evaluation is recorded like any input.
+#[cfg(feature = "repl")]
+fn edit(recorder: &mut Recorder, name: &str) -> String {
+    let source = match recorder.function_source(name) {
+        Ok(source) => source,
+        Err(err) => return err.to_string(),
+    };
+    let path = std::env::temp_dir().join(format!("waiir-{}-{name}.monkey", std::process::id()));
+    if let Err(err) = fs::write(&path, source) {
+        return format!("Cannot write {}: {err}", path.display());
+    }
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_owned());
+    let mut command = editor.split_whitespace();
+    let status = std::process::Command::new(command.next().unwrap_or("vi"))
+        .args(command)
+        .arg(&path)
+        .status();
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    match (status, edited) {
+        (Err(err), _) => format!("Cannot run `{editor}`: {err}"),
+        (Ok(status), _) if !status.success() => {
+            format!("`{editor}` exited with {status}, `{name}` is unchanged")
+        }
+        (Ok(_), Ok(edited)) => recorder.eval(&edited),
+        (Ok(_), Err(err)) => format!("Cannot read {}: {err}", path.display()),
+    }
+}
+
+#[cfg(feature = "repl")]
+fn replay(path: &str) {
+    let session = Session::from_json(&read(path)).unwrap_or_else(|err| {
+        eprintln!("{path}: {err}");
+        exit(1);
+    });
+    match replay::replay(&session) {
+        Ok(session) => {
+            for entry in &session.entries {
+                print!(">> {}", entry.input);
+                println!("{}", entry.output);
+            }
+        }
+        Err(divergence) => {
+            eprintln!("{path}: {divergence}");
+            exit(1);
+        }
+    }
+}
+
+/// Ctrl-C stops the script at the next function call or loop iteration; a second
+/// Ctrl-C before that, e.g. while blocked reading the input, exits right away.
+fn handle_interrupts(env: &Environment) {
+    let interrupt = env.interrupt().clone();
+    let installed = ctrlc::set_handler(move || {
+        if interrupt.is_triggered() {
+            exit(INTERRUPTED);
+        }
+        interrupt.trigger();
+    });
+    if let Err(err) = installed {
+        eprintln!("Cannot handle Ctrl-C: {err}");
+    }
+}
+
+/// Prints each warning once, on the standard error, with where it is in `source`, the
+/// program run, when known.
+#[derive(Default)]
+struct Warnings {
+    seen: RefCell<Vec<String>>,
+    source: Option<String>,
+}
+
+impl Observer for Warnings {
+    fn on_diagnostic(&self, diagnostic: &Error) {
+        match &self.source {
+            Some(source) => self.on_warning(&diagnostic.clone().located_in(source).message),
+            None => self.on_warning(&diagnostic.message),
+        }
+    }
+
+    fn on_warning(&self, message: &str) {
+        let mut seen = self.seen.borrow_mut();
+        if !seen.iter().any(|m| m == message) {
+            eprintln!("warning: {message}");
+            seen.push(message.to_owned());
+        }
+    }
+}
+
+/// Environment of the scripts run from the command line.
+fn environment() -> Rc<Environment> {
+    Rc::new(Environment::default().with_observer(Warnings::default()))
+}
+
+/// Environment running `source`, locating its warnings in it.
+fn environment_for(source: &str) -> Rc<Environment> {
+    let warnings = Warnings {
+        source: Some(source.to_owned()),
+        ..Warnings::default()
+    };
+    Rc::new(Environment::default().with_observer(warnings))
+}
+
+/// Reports a failed evaluation and exits.
+fn fail(err: Error) -> ! {
+    eprintln!("{err}");
+    for frame in &err.stack {
+        eprintln!("    in {frame}");
+    }
+    exit(match err.code {
+        ErrorCode::Interrupted => INTERRUPTED,
+        _ => FAILURE,
+    });
+}
+
+/// What is done about the `let`s of a program shadowing another binding.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ShadowCheck {
+    Allow,
+    /// `--warn-shadow`: each one is reported on the standard error.
+    Warn,
+    /// `--deny-shadow`: the first one fails the program before it runs.
+    Deny,
+}
+
+/// Evaluates `program`, prints its value and exits with the status it maps to.
+/// With `stats`, the resources used are reported on the standard error. With `vm`, the
+/// program is compiled and run by the VM instead of being evaluated.
+fn run(program: &str, stats: bool, vm: bool, shadow: ShadowCheck) {
+    if shadow != ShadowCheck::Allow {
+        for shadowing in shadowed_bindings(&cst::parse(program)) {
+            match shadow {
+                ShadowCheck::Deny => fail(shadowing.to_error(program)),
+                _ => eprintln!("warning: {}", shadowing.message(program)),
+            }
+        }
+    }
+    let env = environment_for(program);
+    handle_interrupts(&env);
+    let (result, usage) = if vm {
+        waiir::vm::run_with_stats(program, env)
+    } else {
+        eval_with_stats(program, env)
+    };
+    if stats {
+        eprintln!("{usage}");
+    }
+    match result {
+        Ok(obj) => {
+            println!("{obj}");
+            exit(obj.exit_code());
+        }
+        Err(err) => fail(err.located_in(program)),
+    }
+}
+
+/// Evaluates `program` once per line of the standard input, with the line bound to
+/// `line`, and prints the values that aren't `null`. Bindings persist across lines.
+fn run_per_line(program: &str, vm: bool) {
+    if let Err(err) = Parser::init(program).parse_program() {
+        fail(err);
+    }
+    let env = environment_for(program);
+    handle_interrupts(&env);
+    for (i, line) in read_stdin().lines().enumerate() {
+        env.set("line", Object::String(line.to_owned()));
+        let result = if vm {
+            waiir::vm::run(program, Rc::clone(&env))
+        } else {
+            eval_with_env(program, Rc::clone(&env))
+        };
+        match result {
+            Ok(Object::Null) => {}
             Ok(obj) => println!("{obj}"),
-            Err(err) => println!("{err}"),
+            Err(err) => {
+                let mut err = err.located_in(program);
+                err.message = format!("line {}: {}", i + 1, err.message);
+                fail(err)
+            }
         }
     }
 }
+
+/// Evaluates a file one statement at a time, printing each statement before it runs
+/// and the bindings it changed afterwards, and waiting for Enter in between.
+fn trace_eval(path: &str) {
+    let source = read(path);
+    let program = Parser::init(&source)
+        .parse_program()
+        .unwrap_or_else(|err| fail(err));
+    let env = environment_for(&source);
+    handle_interrupts(&env);
+    let mut execution = Execution::new(program, Rc::clone(&env));
+    let mut bindings = env.bindings();
+    loop {
+        if let Some(statement) = execution.peek() {
+            print!("> {}", statement_to_source(statement).replace('\n', "\n  "));
+            let _ = stdout().flush();
+            if stdin().read_line(&mut String::new()).unwrap_or(0) == 0 {
+                println!();
+            }
+        }
+        let result = execution
+            .run(1)
+            .unwrap_or_else(|err| fail(err.located_in(&source)));
+
+        let changed = env.bindings();
+        for (name, obj) in &changed {
+            match bindings.iter().find(|(old_name, _)| old_name == name) {
+                Some((_, old)) if old == obj => {}
+                Some(_) => println!("  ~ {name} = {obj}"),
+                None => println!("  + {name} = {obj}"),
+            }
+        }
+        bindings = changed;
+
+        if let Some(obj) = result {
+            println!("{obj}");
+            exit(obj.exit_code());
+        }
+    }
+}
+
+fn read_stdin() -> String {
+    let mut input = String::new();
+    stdin().read_to_string(&mut input).unwrap_or_else(|err| {
+        eprintln!("Cannot read the standard input: {err}");
+        exit(1);
+    });
+    input
+}
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Cannot read {path}: {err}");
+        exit(1);
+    })
+}
+
+fn write(path: &str, contents: &str) {
+    fs::write(path, contents).unwrap_or_else(|err| {
+        eprintln!("Cannot write {path}: {err}");
+        exit(1);
+    })
+}
+
+fn notebook(path: &str, output: Option<&str>) {
+    let source = read(path);
+    let rendered = run_markdown(&source);
+    match output {
+        Some(output) => write(output, &rendered),
+        None => print!("{rendered}"),
+    }
+}
+
+/// Prints the template rendered in an environment holding the entries of the JSON
+/// object in `data`, if any, as variables.
+fn render(path: &str, data: Option<&str>) {
+    let env = environment();
+    #[cfg(feature = "serde")]
+    if let Some(data) = data {
+        match Value::from_json(&read(data)) {
+            Ok(Value::Hash(entries)) => {
+                for (key, value) in entries {
+                    env.set(key.to_string(), Object::from(value));
+                }
+            }
+            Ok(_) => {
+                eprintln!("{data}: the data must be a JSON object");
+                exit(1);
+            }
+            Err(err) => {
+                eprintln!("{data}: {err}");
+                exit(1);
+            }
+        }
+    }
+    #[cfg(not(feature = "serde"))]
+    let _ = data;
+    match render_template(&read(path), env) {
+        Ok(output) => print!("{output}"),
+        Err(err) => fail(err.in_frame(path.to_owned())),
+    }
+}
+
+fn doctest(path: &str) {
+    let outcomes = doctest::run(&read(path)).unwrap_or_else(|err| {
+        eprintln!("{path}: {err}");
+        exit(1);
+    });
+    let failed = report(path, &outcomes);
+    println!("{} passed, {failed} failed", outcomes.len() - failed);
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+/// Prints the outcome of each example of the file, returning how many failed.
+fn report(path: &str, outcomes: &[doctest::Outcome]) -> usize {
+    let mut failed = 0;
+    for outcome in outcomes {
+        let example = &outcome.example;
+        if outcome.passed() {
+            println!("{path}:{} ... ok", example.line);
+        } else {
+            failed += 1;
+            println!("{path}:{} ... FAILED", example.line);
+            println!("    >> {}", example.source);
+            println!("    expected: {}", example.expected);
+            println!("    found:    {}", outcome.actual);
+        }
+    }
+    failed
+}
+
+/// Runs the project of the `monkey.toml` in `dir` or its parents, like `-e` runs a program.
+/// `--leak-check` then reports what the global environment still holds on the standard
+/// error, see [`Environment::debug_graph`].
+#[cfg(feature = "manifest")]
+fn run_project(dir: &str, leak_check: bool) {
+    let project = Project::discover(dir).unwrap_or_else(|err| fail(err));
+    let env = environment();
+    handle_interrupts(&env);
+    match project.run(&env) {
+        Ok(obj) => {
+            println!("{obj}");
+            if leak_check {
+                eprintln!("{}", env.debug_graph());
+            }
+            exit(obj.exit_code());
+        }
+        Err(err) => fail(err),
+    }
+}
+
+/// Runs the examples of every file of the project, each file being evaluated after
+/// the dependencies.
+#[cfg(feature = "manifest")]
+fn test_project(dir: &str) {
+    let project = Project::discover(dir).unwrap_or_else(|err| fail(err));
+    let files = project.files().unwrap_or_else(|err| fail(err));
+    let (mut total, mut failed) = (0, 0);
+    for file in files {
+        let path = file.display().to_string();
+        let env = environment();
+        let outcomes = project
+            .load_dependencies(&env)
+            .and_then(|()| doctest::run_in(&read(&path), env))
+            .unwrap_or_else(|err| {
+                eprintln!("{path}: {err}");
+                exit(1);
+            });
+        total += outcomes.len();
+        failed += report(&path, &outcomes);
+    }
+    println!("{} passed, {failed} failed", total - failed);
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+/// Prints the tokens of a file with their byte ranges.
+fn tokens(path: &str, format: &str) {
+    let source = read(path);
+    let mut lexer = Lexer::init(&source);
+    let mut tokens = vec![];
+    loop {
+        let token = lexer.next_token();
+        if token == Token::Eof {
+            break;
+        }
+        tokens.push((lexer.span(), token));
+    }
+
+    match format {
+        "text" => {
+            for (span, token) in tokens {
+                println!("{}..{} {token}", span.start, span.end);
+            }
+        }
+        "sexp" => {
+            for (span, token) in tokens {
+                let token = match token {
+                    Token::Ident(name) => format!("ident {name}"),
+                    Token::Int(value) => format!("int {value}"),
+                    Token::Float(value) => format!("float {value:?}"),
+                    Token::String(value) => format!("string {value:?}"),
+                    token => format!("{token:?}").to_lowercase(),
+                };
+                println!("({token} {} {})", span.start, span.end);
+            }
+        }
+        #[cfg(feature = "serde")]
+        "json" => {
+            let tokens: Vec<_> = tokens
+                .into_iter()
+                .map(|(span, token)| serde_json::json!({ "token": token, "span": span }))
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&tokens).unwrap_or_default()
+            );
+        }
+        _ => unknown_format(format),
+    }
+}
+
+/// Prints the parse tree of a file.
+fn ast(path: &str, format: &str) {
+    let program = Parser::init(&read(path))
+        .parse_program()
+        .unwrap_or_else(|err| fail(err));
+    match format {
+        "text" => println!("{program:#?}"),
+        "sexp" => print!("{}", to_sexp(&program)),
+        #[cfg(feature = "serde")]
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&program).unwrap_or_default()
+        ),
+        _ => unknown_format(format),
+    }
+}
+
+/// Writes the parse tree of a file as a Graphviz graph, or with `env` the environment
+/// the file leaves once evaluated.
+fn viz(path: &str, env: bool, output: Option<&str>) {
+    let source = read(path);
+    let program = Parser::init(&source)
+        .parse_program()
+        .unwrap_or_else(|err| fail(err));
+    let graph = if env {
+        let env = Rc::new(Environment::default());
+        if let Err(err) = program.eval(Rc::clone(&env)) {
+            fail(err);
+        }
+        env.to_dot()
+    } else {
+        to_dot(&program)
+    };
+    match output {
+        Some(output) => write(output, &graph),
+        None => print!("{graph}"),
+    }
+}
+
+fn unknown_format(format: &str) -> ! {
+    eprintln!("Unknown format `{format}`, expected text, json or sexp");
+    exit(2);
+}
+
+fn explain(code: &str) {
+    let Some(code) = ErrorCode::from_code(code) else {
+        eprintln!("Unknown error code `{code}`");
+        exit(1);
+    };
+    println!("{code}: {}\n\n{}", code.title(), code.explanation());
+}
@@ -0,0 +1,212 @@
+//! `monkey.toml`, the optional manifest of a project spanning several files:
+//!
+//! ```toml
+//! entry = "src/main.monkey"
+//! paths = ["src", "lib"]
+//! dependencies = ["strings", "math"]
+//! ```
+//!
+//! Monkey has no imports: the dependencies are evaluated in order before the entry
+//! point, in the same environment, so their bindings are visible to it. A dependency
+//! `name` is the file `name.monkey` in the first search path holding one.
+
+use crate::cst;
+use crate::error::{Error, ErrorCode, Result, catch_unwind, fail};
+use crate::eval::{Environment, Eval, Object};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+pub const MANIFEST: &str = "monkey.toml";
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The script run by `waiir run`, `main.monkey` by default.
+    #[serde(default = "default_entry")]
+    pub entry: PathBuf,
+    /// Directories searched for dependencies, in order, the project root by default.
+    #[serde(default = "default_paths")]
+    pub paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+fn default_entry() -> PathBuf {
+    PathBuf::from("main.monkey")
+}
+
+fn default_paths() -> Vec<PathBuf> {
+    vec![PathBuf::from(".")]
+}
+
+impl Manifest {
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml)
+            .map_err(|err| Error::new(ErrorCode::InvalidInput, format!("Invalid manifest: {err}")))
+    }
+}
+
+/// A manifest and the directory holding it, which its paths are relative to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    /// The project of the closest `monkey.toml` in `dir` or its ancestors.
+    pub fn discover(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        // Relative paths like `.` have no parents to search otherwise.
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        for ancestor in dir.ancestors() {
+            let path = ancestor.join(MANIFEST);
+            if path.is_file() {
+                return Self::load(path);
+            }
+        }
+        fail!(
+            ErrorCode::InvalidInput,
+            "No {MANIFEST} in {} or its parents",
+            dir.display()
+        );
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let manifest = Manifest::from_toml(&read(path)?)
+            .map_err(|err| err.in_frame(path.display().to_string()))?;
+        Ok(Self {
+            root: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            manifest,
+        })
+    }
+
+    pub fn entry(&self) -> PathBuf {
+        self.root.join(&self.manifest.entry)
+    }
+
+    /// The file of the dependency `name`.
+    pub fn resolve(&self, name: &str) -> Result<PathBuf> {
+        let file = format!("{name}.monkey");
+        let found = self
+            .manifest
+            .paths
+            .iter()
+            .map(|dir| self.root.join(dir).join(&file))
+            .find(|path| path.is_file());
+        match found {
+            Some(path) => Ok(path),
+            None => fail!(
+                ErrorCode::InvalidInput,
+                "Cannot find the dependency `{name}`, no {file} in the search paths"
+            ),
+        }
+    }
+
+    /// The files of the dependencies followed by the entry point, in evaluation order.
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = self
+            .manifest
+            .dependencies
+            .iter()
+            .map(|name| self.resolve(name))
+            .collect::<Result<Vec<_>>>()?;
+        files.push(self.entry());
+        Ok(files)
+    }
+
+    /// Evaluates the dependencies in `env`.
+    pub fn load_dependencies(&self, env: &Rc<Environment>) -> Result<()> {
+        for name in &self.manifest.dependencies {
+            eval_file(&self.resolve(name)?, env)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluates the dependencies, then the entry point, in `env`.
+    pub fn run(&self, env: &Rc<Environment>) -> Result<Object> {
+        self.load_dependencies(env)?;
+        eval_file(&self.entry(), env)
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(source) => Ok(source),
+        Err(err) => fail!(
+            ErrorCode::InvalidInput,
+            "Cannot read {}: {err}",
+            path.display()
+        ),
+    }
+}
+
+fn eval_file(path: &Path, env: &Rc<Environment>) -> Result<Object> {
+    let source = read(path)?;
+    catch_unwind(|| cst::parse(&source).to_ast()?.eval(Rc::clone(env)))
+        .map_err(|err| err.in_frame(path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifests() {
+        let manifest = Manifest::from_toml("dependencies = [\"math\"]").unwrap();
+        assert_eq!(manifest.entry, PathBuf::from("main.monkey"));
+        assert_eq!(manifest.paths, vec![PathBuf::from(".")]);
+        assert_eq!(manifest.dependencies, vec!["math"]);
+        for toml in ["entry = 1", "imports = []"] {
+            assert_eq!(
+                Manifest::from_toml(toml).unwrap_err().code,
+                ErrorCode::InvalidInput
+            );
+        }
+    }
+
+    #[test]
+    fn projects() {
+        let root = std::env::temp_dir().join(format!("waiir-project-{}", std::process::id()));
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::write(
+            root.join(MANIFEST),
+            "entry = \"src/app.monkey\"\npaths = [\"lib\", \"src\"]\ndependencies = [\"math\", \"helpers\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("lib/math.monkey"),
+            "let square = fn(x) { x * x };",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/helpers.monkey"),
+            "// Applies f twice.\nlet apply = fn(f, x) { f(f(x)) };",
+        )
+        .unwrap();
+        fs::write(root.join("src/app.monkey"), "apply(square, 3)").unwrap();
+
+        let project = Project::discover(root.join("src/nested")).unwrap();
+        assert_eq!(project.root, root);
+        assert_eq!(
+            project.files().unwrap(),
+            vec![
+                root.join("lib/math.monkey"),
+                root.join("src/helpers.monkey"),
+                root.join("src/app.monkey"),
+            ]
+        );
+        let env = Rc::new(Environment::default());
+        assert_eq!(project.run(&env).unwrap(), Object::Int(81));
+
+        fs::write(root.join(MANIFEST), "dependencies = [\"missing\"]").unwrap();
+        let project = Project::discover(&root).unwrap();
+        let err = project.run(&Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
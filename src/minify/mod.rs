@@ -0,0 +1,641 @@
+use crate::ast::printer::to_compact_source;
+use crate::cst::{SyntaxKind, SyntaxNode};
+use crate::error::Result;
+use crate::eval::is_builtin;
+use crate::{Expression, Lexer, Program, Statement, Token, cst};
+use std::collections::{HashMap, HashSet};
+
+/// Minifies a Monkey program: comments are dropped, unused side-effect free locals
+/// are removed, function parameters and locals get the shortest available names and
+/// the result is printed without optional whitespace.
+/// Top-level bindings are all kept with their names, since a host may read them from
+/// the environment.
+pub fn minify(source: &str) -> Result<String> {
+    let tree = cst::parse(source);
+    let mut program = tree.to_ast()?;
+    for statement in &mut program.statements {
+        for_each_func(statement, &mut |args, body| remove_unused(body, args));
+    }
+    let mut keywords = HashSet::new();
+    collect_keywords(&tree, &mut keywords);
+    rename_locals(&mut program, keywords);
+    Ok(to_compact_source(&program))
+}
+
+/// Removes the `let` bindings of the scope that are never read, as long as dropping them
+/// cannot change the result: every definition must be side-effect free and none can be
+/// the last statement of a block, whose value is the value of the block.
+fn remove_unused(stmts: &mut Vec<Statement>, params: &[String]) {
+    loop {
+        let mut declared = vec![];
+        collect_lets(stmts, &mut declared);
+        let removable: Vec<String> = declared
+            .into_iter()
+            .filter(|name| !params.contains(name))
+            .filter(|name| !is_read(stmts, name) && can_remove(stmts, name))
+            .collect();
+        if removable.is_empty() {
+            break;
+        }
+        for name in removable {
+            remove_lets(stmts, &name);
+        }
+    }
+
+    for statement in stmts.iter_mut() {
+        for_each_func(statement, &mut |args, body| remove_unused(body, args));
+    }
+}
+
+/// Names bound with `let` in the scope, excluding nested functions.
+fn collect_lets(stmts: &[Statement], output: &mut Vec<String>) {
+    for statement in stmts {
+        match statement {
+            Statement::Let { name, value, .. } => {
+                if !output.contains(name) {
+                    output.push(name.clone());
+                }
+                collect_cond_lets(value, output);
+            }
+            Statement::Destructure { pattern, value } => {
+                for name in pattern.names() {
+                    if !output.iter().any(|n| n == name) {
+                        output.push(name.to_owned());
+                    }
+                }
+                collect_cond_lets(value, output);
+            }
+            Statement::Assign { value, .. }
+            | Statement::Return { value }
+            | Statement::Yield { value }
+            | Statement::Expr(value) => collect_cond_lets(value, output),
+            Statement::Block(stmts) => collect_lets(stmts, output),
+            // Struct names stay, as patterns name them too.
+            Statement::Struct { .. } | Statement::Break | Statement::Continue => {}
+        }
+    }
+}
+
+fn collect_cond_lets(expr: &Expression, output: &mut Vec<String>) {
+    match expr {
+        Expression::Cond { then_, else_, .. } => {
+            collect_lets(then_, output);
+            if let Some(else_) = else_ {
+                collect_lets(else_, output);
+            }
+        }
+        // The loop variable lives in the enclosing scope, like a `let`.
+        Expression::For { name, body, .. } => {
+            if !output.contains(name) {
+                output.push(name.clone());
+            }
+            collect_lets(body, output);
+        }
+        Expression::While { body, .. } => collect_lets(body, output),
+        // So are the names bound by the arms.
+        Expression::Match { arms, .. } => {
+            for arm in arms {
+                for name in arm.pattern.names() {
+                    if !output.iter().any(|n| n == name) {
+                        output.push(name.to_owned());
+                    }
+                }
+                collect_cond_lets(&arm.value, output);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether `name` is read anywhere in the scope, outside the values bound to `name` itself.
+fn is_read(stmts: &[Statement], name: &str) -> bool {
+    stmts.iter().any(|statement| match statement {
+        Statement::Let {
+            name: bound, value, ..
+        } => bound != name && reads(value, name),
+        // Keeps the `let` of a variable that's assigned, which would fail without it.
+        Statement::Assign {
+            name: assigned,
+            value,
+        } => assigned == name || reads(value, name),
+        Statement::Destructure { value, .. }
+        | Statement::Return { value }
+        | Statement::Yield { value }
+        | Statement::Expr(value) => reads(value, name),
+        Statement::Block(stmts) => is_read(stmts, name),
+        Statement::Struct { .. } | Statement::Break | Statement::Continue => false,
+    })
+}
+
+fn reads(expr: &Expression, name: &str) -> bool {
+    match expr {
+        Expression::Ident(ident) => ident == name,
+        Expression::Bool(_) | Expression::Int(_) | Expression::Float(_) | Expression::String(_) => {
+            false
+        }
+        Expression::Prefix { right, .. } | Expression::Field { object: right, .. } => {
+            reads(right, name)
+        }
+        Expression::Infix { left, right, .. } => reads(left, name) || reads(right, name),
+        Expression::Func { args, body, .. } => {
+            let mut locals = args.clone();
+            collect_lets(body, &mut locals);
+            !locals.iter().any(|l| l == name) && is_read(body, name)
+        }
+        Expression::Call {
+            func, args, kwargs, ..
+        } => {
+            reads(func, name)
+                || args.iter().any(|a| reads(a, name))
+                || kwargs.iter().any(|(_, value)| reads(value, name))
+        }
+        Expression::Cond { cond, then_, else_ } => {
+            reads(cond, name)
+                || is_read(then_, name)
+                || else_.as_ref().is_some_and(|e| is_read(e, name))
+        }
+        Expression::For { iterable, body, .. } => reads(iterable, name) || is_read(body, name),
+        Expression::While { cond, body } => reads(cond, name) || is_read(body, name),
+        Expression::Match { subject, arms } => {
+            reads(subject, name)
+                || arms.iter().any(|arm| {
+                    arm.guard.as_ref().is_some_and(|g| reads(g, name)) || reads(&arm.value, name)
+                })
+        }
+        Expression::Array(content) | Expression::Tuple(content) => {
+            content.iter().any(|e| reads(e, name))
+        }
+        Expression::Hash { pairs, .. } => {
+            pairs.iter().any(|(k, v)| reads(k, name) || reads(v, name))
+        }
+    }
+}
+
+fn can_remove(stmts: &[Statement], name: &str) -> bool {
+    stmts.iter().enumerate().all(|(i, statement)| {
+        let last = i + 1 == stmts.len();
+        match statement {
+            Statement::Let {
+                name: bound, value, ..
+            } if bound == name => !last && is_pure(value),
+            Statement::Destructure { pattern, .. } if pattern.names().contains(&name) => false,
+            Statement::Let { value, .. }
+            | Statement::Assign { value, .. }
+            | Statement::Destructure { value, .. }
+            | Statement::Return { value }
+            | Statement::Yield { value }
+            | Statement::Expr(value) => match value {
+                Expression::Cond { then_, else_, .. } => {
+                    can_remove(then_, name) && else_.as_ref().is_none_or(|e| can_remove(e, name))
+                }
+                Expression::For { name: var, .. } if var == name => false,
+                Expression::For { body, .. } | Expression::While { body, .. } => {
+                    can_remove(body, name)
+                }
+                Expression::Match { .. } => {
+                    let mut bound = vec![];
+                    collect_cond_lets(value, &mut bound);
+                    !bound.iter().any(|b| b == name)
+                }
+                _ => true,
+            },
+            Statement::Block(stmts) => can_remove(stmts, name),
+            Statement::Struct { .. } | Statement::Break | Statement::Continue => true,
+        }
+    })
+}
+
+/// Whether evaluating `expr` can neither fail nor have side effects.
+fn is_pure(expr: &Expression) -> bool {
+    match expr {
+        Expression::Bool(_)
+        | Expression::Int(_)
+        | Expression::String(_)
+        | Expression::Ident(_)
+        | Expression::Func { .. } => true,
+        Expression::Array(content) | Expression::Tuple(content) => content.iter().all(is_pure),
+        Expression::Hash { pairs, .. } => pairs.iter().all(|(k, v)| {
+            matches!(
+                k,
+                Expression::Bool(_) | Expression::Int(_) | Expression::String(_)
+            ) && is_pure(v)
+        }),
+        _ => false,
+    }
+}
+
+fn remove_lets(stmts: &mut Vec<Statement>, name: &str) {
+    stmts.retain(|s| !matches!(s, Statement::Let { name: bound, .. } if bound == name));
+    for statement in stmts.iter_mut() {
+        match statement {
+            Statement::Let { value, .. }
+            | Statement::Assign { value, .. }
+            | Statement::Destructure { value, .. }
+            | Statement::Return { value }
+            | Statement::Yield { value }
+            | Statement::Expr(value) => match value {
+                Expression::Cond { then_, else_, .. } => {
+                    remove_lets(then_, name);
+                    if let Some(else_) = else_ {
+                        remove_lets(else_, name);
+                    }
+                }
+                Expression::For { body, .. } | Expression::While { body, .. } => {
+                    remove_lets(body, name)
+                }
+                _ => {}
+            },
+            Statement::Block(stmts) => remove_lets(stmts, name),
+            Statement::Struct { .. } | Statement::Break | Statement::Continue => {}
+        }
+    }
+}
+
+/// Calls `f` on the parameters and body of every function literal directly in
+/// `statement`, without descending into the functions themselves.
+fn for_each_func(statement: &mut Statement, f: &mut impl FnMut(&[String], &mut Vec<Statement>)) {
+    match statement {
+        Statement::Let { value, .. }
+        | Statement::Assign { value, .. }
+        | Statement::Destructure { value, .. }
+        | Statement::Return { value }
+        | Statement::Yield { value }
+        | Statement::Expr(value) => for_each_func_expr(value, f),
+        Statement::Block(stmts) => {
+            for statement in stmts {
+                for_each_func(statement, f);
+            }
+        }
+        Statement::Struct { .. } | Statement::Break | Statement::Continue => {}
+    }
+}
+
+fn for_each_func_expr(expr: &mut Expression, f: &mut impl FnMut(&[String], &mut Vec<Statement>)) {
+    match expr {
+        Expression::Bool(_)
+        | Expression::Int(_)
+        | Expression::Float(_)
+        | Expression::String(_)
+        | Expression::Ident(_) => {}
+        Expression::Prefix { right, .. } | Expression::Field { object: right, .. } => {
+            for_each_func_expr(right, f)
+        }
+        Expression::Infix { left, right, .. } => {
+            for_each_func_expr(left, f);
+            for_each_func_expr(right, f);
+        }
+        Expression::Func { args, body, .. } => f(args, body),
+        Expression::Call {
+            func, args, kwargs, ..
+        } => {
+            for_each_func_expr(func, f);
+            for arg in args
+                .iter_mut()
+                .chain(kwargs.iter_mut().map(|(_, value)| value))
+            {
+                for_each_func_expr(arg, f);
+            }
+        }
+        Expression::Cond { cond, then_, else_ } => {
+            for_each_func_expr(cond, f);
+            for statement in then_.iter_mut().chain(else_.iter_mut().flatten()) {
+                for_each_func(statement, f);
+            }
+        }
+        Expression::For { iterable, body, .. } => {
+            for_each_func_expr(iterable, f);
+            for statement in body {
+                for_each_func(statement, f);
+            }
+        }
+        Expression::While { cond, body } => {
+            for_each_func_expr(cond, f);
+            for statement in body {
+                for_each_func(statement, f);
+            }
+        }
+        Expression::Match { subject, arms } => {
+            for_each_func_expr(subject, f);
+            for arm in arms {
+                if let Some(guard) = &mut arm.guard {
+                    for_each_func_expr(guard, f);
+                }
+                for_each_func_expr(&mut arm.value, f);
+            }
+        }
+        Expression::Array(content) | Expression::Tuple(content) => {
+            for e in content {
+                for_each_func_expr(e, f);
+            }
+        }
+        Expression::Hash { pairs, .. } => {
+            for (k, v) in pairs {
+                for_each_func_expr(k, f);
+                for_each_func_expr(v, f);
+            }
+        }
+    }
+}
+
+struct Renamer {
+    /// Every name of the original program: a new name must not collide with any of them.
+    forbidden: HashSet<String>,
+    /// Names of the keyword arguments of the program.
+    keywords: HashSet<String>,
+    scopes: Vec<HashMap<String, String>>,
+}
+
+fn collect_keywords(node: &SyntaxNode, keywords: &mut HashSet<String>) {
+    if node.kind == SyntaxKind::KeywordArg
+        && let Some(Token::Ident(name)) = node.child_tokens().next().map(|t| &t.token)
+    {
+        keywords.insert(name.clone());
+    }
+    for child in node.child_nodes() {
+        collect_keywords(child, keywords);
+    }
+}
+
+fn rename_locals(program: &mut Program, keywords: HashSet<String>) {
+    let mut forbidden = HashSet::new();
+    let source = to_compact_source(program);
+    let mut lexer = Lexer::init(&source);
+    loop {
+        match lexer.next_token() {
+            Token::Ident(name) => {
+                forbidden.insert(name);
+            }
+            Token::Eof => break,
+            _ => {}
+        }
+    }
+    let mut renamer = Renamer {
+        forbidden,
+        keywords,
+        scopes: vec![],
+    };
+    for statement in &mut program.statements {
+        renamer.statement(statement, 0);
+    }
+}
+
+impl Renamer {
+    fn statement(&mut self, statement: &mut Statement, next: usize) {
+        match statement {
+            Statement::Let { name, value, .. } | Statement::Assign { name, value } => {
+                self.rename(name);
+                self.expression(value, next);
+            }
+            Statement::Destructure { pattern, value } => {
+                for name in pattern.names_mut() {
+                    self.rename(name);
+                }
+                self.expression(value, next);
+            }
+            Statement::Return { value } | Statement::Yield { value } | Statement::Expr(value) => {
+                self.expression(value, next)
+            }
+            Statement::Block(stmts) => {
+                for statement in stmts {
+                    self.statement(statement, next);
+                }
+            }
+            Statement::Struct { .. } | Statement::Break | Statement::Continue => {}
+        }
+    }
+
+    /// `next` is the index of the first short name not used by the enclosing functions,
+    /// so that nested functions never hide a local of an outer one.
+    fn expression(&mut self, expr: &mut Expression, next: usize) {
+        match expr {
+            Expression::Ident(ident) => self.rename(ident),
+            Expression::Bool(_)
+            | Expression::Int(_)
+            | Expression::Float(_)
+            | Expression::String(_) => {}
+            Expression::Prefix { right, .. } | Expression::Field { object: right, .. } => {
+                self.expression(right, next)
+            }
+            Expression::Infix { left, right, .. } => {
+                self.expression(left, next);
+                self.expression(right, next);
+            }
+            Expression::Func { args, body, .. } => {
+                let mut locals = args.clone();
+                collect_lets(body, &mut locals);
+                // Builtins take precedence over variables, such a local is never read, and
+                // `$` names are those of desugared syntax like `?`, printed back as such.
+                // Parameters may be named by keyword arguments, which must keep matching.
+                locals.retain(|local| {
+                    !is_builtin(local) && !local.starts_with('$') && !self.keywords.contains(local)
+                });
+
+                let mut scope = HashMap::new();
+                let mut index = next;
+                for local in locals {
+                    let short = loop {
+                        let candidate = short_name(index);
+                        index += 1;
+                        if !self.forbidden.contains(&candidate) {
+                            break candidate;
+                        }
+                    };
+                    scope.insert(local, short);
+                }
+
+                self.scopes.push(scope);
+                for arg in args.iter_mut() {
+                    self.rename(arg);
+                }
+                for statement in body {
+                    self.statement(statement, index);
+                }
+                self.scopes.pop();
+            }
+            Expression::Call {
+                func, args, kwargs, ..
+            } => {
+                self.expression(func, next);
+                for arg in args
+                    .iter_mut()
+                    .chain(kwargs.iter_mut().map(|(_, value)| value))
+                {
+                    self.expression(arg, next);
+                }
+            }
+            Expression::Cond { cond, then_, else_ } => {
+                self.expression(cond, next);
+                for statement in then_.iter_mut().chain(else_.iter_mut().flatten()) {
+                    self.statement(statement, next);
+                }
+            }
+            Expression::For {
+                name,
+                iterable,
+                body,
+            } => {
+                self.rename(name);
+                self.expression(iterable, next);
+                for statement in body {
+                    self.statement(statement, next);
+                }
+            }
+            Expression::While { cond, body } => {
+                self.expression(cond, next);
+                for statement in body {
+                    self.statement(statement, next);
+                }
+            }
+            Expression::Match { subject, arms } => {
+                self.expression(subject, next);
+                for arm in arms {
+                    for name in arm.pattern.names_mut() {
+                        self.rename(name);
+                    }
+                    if let Some(guard) = &mut arm.guard {
+                        self.expression(guard, next);
+                    }
+                    self.expression(&mut arm.value, next);
+                }
+            }
+            Expression::Array(content) | Expression::Tuple(content) => {
+                for e in content {
+                    self.expression(e, next);
+                }
+            }
+            Expression::Hash { pairs, .. } => {
+                for (k, v) in pairs {
+                    self.expression(k, next);
+                    self.expression(v, next);
+                }
+            }
+        }
+    }
+
+    fn rename(&self, name: &mut String) {
+        if let Some(short) = self.scopes.iter().rev().find_map(|s| s.get(name.as_str())) {
+            *name = short.clone();
+        }
+    }
+}
+
+/// `a`, `b`, ..., `z`, `aa`, `ab`, ... skipping keywords and builtins.
+fn short_name(mut index: usize) -> String {
+    loop {
+        let mut name = String::new();
+        let mut n = index;
+        loop {
+            name.insert(0, (b'a' + (n % 26) as u8) as char);
+            if n < 26 {
+                break;
+            }
+            n = n / 26 - 1;
+        }
+        let is_keyword = !matches!(Lexer::init(&name).next_token(), Token::Ident(_));
+        if !is_keyword && !is_builtin(&name) {
+            return name;
+        }
+        index += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::printer::to_source;
+    use crate::eval::{Environment, eval_with_env};
+    use std::rc::Rc;
+
+    fn assert_minify(input: &str, expected: &str) {
+        let output = minify(input).unwrap();
+        assert_eq!(output, expected);
+
+        let original = to_source(&cst::parse(input).to_ast().unwrap());
+        let run = |source: &str| match eval_with_env(source, Rc::new(Environment::default())) {
+            Ok(obj) => obj.to_string(),
+            Err(err) => err.to_string(),
+        };
+        assert_eq!(run(&original), run(&output));
+    }
+
+    #[test]
+    fn minifies() {
+        assert_minify(
+            "// adds numbers\nlet add = fn(lhs, rhs) {\n    let unused = 5;\n    lhs + rhs;\n};\n\nadd(1, 2)\n",
+            "let add=fn(a,b){a+b};add(1,2)",
+        );
+        assert_minify(
+            "let x = 10; let unused = fn() { x }; let f = fn(value) { let g = fn(other) { value * other }; g(x) }; f(2)",
+            "let x=10;let unused=fn(){x};let f=fn(a){let b=fn(c){a*c};b(x)};f(2)",
+        );
+    }
+
+    #[test]
+    fn keeps_top_level_bindings() {
+        assert_minify(
+            "let unused = 1; if (true) { let config = 2; } let f = fn() { let local = 3; 4 }; f()",
+            "let unused=1;if(true){let config=2;};let f=fn(){4};f()",
+        );
+    }
+
+    #[test]
+    fn loops() {
+        assert_minify(
+            "let f = fn(items) { let total = []; for (item in items) { let unused = 1; push(total, item) }; total }; f([1, 2])",
+            "let f=fn(a){let b=[];for(c in a){push(b,c)};b};f([1,2])",
+        );
+    }
+
+    #[test]
+    fn keeps_observable_bindings() {
+        assert_minify("let a = 1 + true; 5", "let a=1+true;5");
+        assert_minify(
+            "let f = fn() { let value = 3; }; f()",
+            "let f=fn(){let a=3;};f()",
+        );
+        assert_minify(
+            "let f = fn(a) { let b = a; if (a) { let b = 2; b } else { b } }; f(1)",
+            "let f=fn(c){let d=c;if(c){let d=2;d}else{d}};f(1)",
+        );
+    }
+
+    #[test]
+    fn patterns() {
+        assert_minify(
+            "let f = fn(pair) { let unused = 1; let [left, ...others] = pair; match (others) { [right] if right > 0 => left + right, _ => left } }; f([1, 2])",
+            "let f=fn(a){let [b,...c]=a;match(c){[d] if d>0=>b+d,_=>b}};f([1,2])",
+        );
+    }
+
+    #[test]
+    fn propagation() {
+        assert_minify(
+            "let f = fn(value) { let double = value? * 2; double }; f(3)",
+            "let f=fn(a){let b=a?*2;b};f(3)",
+        );
+    }
+
+    #[test]
+    fn method_calls() {
+        assert_minify(
+            "let f = fn(counter) { let later = counter.add(1); later.get() }; f(c)",
+            "let f=fn(a){let b=a.add(1);b.get()};f(c)",
+        );
+    }
+
+    #[test]
+    fn keyword_arguments() {
+        assert_minify(
+            "let f = fn(value, step) { value + step }; f(1, step: 2)",
+            "let f=fn(a,step){a+step};f(1,step:2)",
+        );
+    }
+
+    #[test]
+    fn structs() {
+        assert_minify(
+            "let f = fn(value) { struct Point { x, y } match (Point(value, 1)) { Point(x, y) => x + value.x } }; f(1)",
+            "let f=fn(a){struct Point{x,y}match(Point(a,1)){Point(b,c)=>b+a.x}};f(1)",
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! Literate scripts: Markdown files whose ```` ```monkey ```` blocks are run in order
+//! in a shared environment.
+
+use crate::eval::{Environment, eval_with_env};
+use std::rc::Rc;
+
+const FENCE: &str = "```";
+
+/// Runs every `monkey` block of the document and returns it with the result of
+/// each block inserted right after it, in a `text` block.
+pub fn run_markdown(source: &str) -> String {
+    let env = Rc::new(Environment::default());
+    let mut output = String::new();
+    let mut code: Option<String> = None;
+
+    for line in source.split_inclusive('\n') {
+        output.push_str(line);
+        let trimmed = line.trim();
+        match code.as_mut() {
+            None if trimmed.starts_with(FENCE) && trimmed[FENCE.len()..].trim() == "monkey" => {
+                code = Some(String::new());
+            }
+            Some(block) if trimmed == FENCE => {
+                let result = match eval_with_env(block, Rc::clone(&env)) {
+                    Ok(obj) => obj.to_string(),
+                    Err(err) => format!("Error: {err}"),
+                };
+                if !line.ends_with('\n') {
+                    output.push('\n');
+                }
+                output.push_str(&format!("\n{FENCE}text\n{result}\n{FENCE}\n"));
+                code = None;
+            }
+            Some(block) => block.push_str(line),
+            None => {}
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_environment() {
+        let input = "# Tutorial\n\n```monkey\nlet x = 5;\n```\n\nThen:\n\n```monkey\nx * 2\n```\n\n```rust\nx\n```\n\n```monkey\ny\n```";
+        assert_eq!(
+            run_markdown(input),
+            "# Tutorial\n\n```monkey\nlet x = 5;\n```\n\n```text\n5\n```\n\nThen:\n\n```monkey\nx * 2\n```\n\n```text\n10\n```\n\n```rust\nx\n```\n\n```monkey\ny\n```\n\n```text\nError: identifier not found: y\n```\n"
+        );
+        assert!(run_markdown("```monkey\n1 + true\n```\n").contains("Error: "));
+    }
+}
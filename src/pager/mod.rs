@@ -0,0 +1,89 @@
+//! Paging of REPL results taller than the terminal: the first page is printed with a
+//! footer telling how much is left, the following ones on `:more`.
+
+use std::fs::File;
+use std::io::IsTerminal;
+use std::process::Command;
+
+#[derive(Debug, Default)]
+pub struct Pager {
+    lines: Vec<String>,
+    shown: usize,
+}
+
+impl Pager {
+    /// The first page of `output` for a terminal of `height` rows, or all of it if it
+    /// fits, leaving a row for the prompt. The rest is kept for [`Pager::more`].
+    pub fn page(&mut self, output: &str, height: usize) -> String {
+        self.lines = output.lines().map(str::to_owned).collect();
+        self.shown = 0;
+        self.next_page(height)
+    }
+
+    /// The next page of the last output, `None` once it was all shown.
+    pub fn more(&mut self, height: usize) -> Option<String> {
+        (self.shown < self.lines.len()).then(|| self.next_page(height))
+    }
+
+    fn next_page(&mut self, height: usize) -> String {
+        let rows = height.saturating_sub(1).max(2);
+        let left = self.lines.len() - self.shown;
+        let shown = if left <= rows { left } else { rows - 1 };
+        let mut page = self.lines[self.shown..self.shown + shown].join("\n");
+        self.shown += shown;
+        if self.shown < self.lines.len() {
+            let left = self.lines.len() - self.shown;
+            let s = if left == 1 { "" } else { "s" };
+            page.push_str(&format!("\n-- {left} more line{s}, `:more` to continue --"));
+        }
+        page
+    }
+}
+
+/// Rows of the terminal, from `$LINES` or else `stty size`, `None` when standard output
+/// isn't a terminal, e.g. when piped, and shouldn't be paged.
+pub fn terminal_height() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    if let Some(lines) = std::env::var("LINES")
+        .ok()
+        .and_then(|lines| lines.parse().ok())
+    {
+        return Some(lines);
+    }
+    let tty = File::open("/dev/tty").ok()?;
+    let output = Command::new("stty").arg("size").stdin(tty).output().ok()?;
+    let size = String::from_utf8(output.stdout).ok()?;
+    size.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paging() {
+        let mut pager = Pager::default();
+        assert_eq!(pager.page("a\nb\nc", 4), "a\nb\nc");
+        assert_eq!(pager.more(4), None);
+
+        let output: Vec<String> = (1..=8).map(|i| i.to_string()).collect();
+        assert_eq!(
+            pager.page(&output.join("\n"), 4),
+            "1\n2\n-- 6 more lines, `:more` to continue --"
+        );
+        assert_eq!(
+            pager.more(4).unwrap(),
+            "3\n4\n-- 4 more lines, `:more` to continue --"
+        );
+        assert_eq!(pager.more(6).unwrap(), "5\n6\n7\n8");
+        assert_eq!(pager.more(6), None);
+
+        assert_eq!(
+            pager.page("1\n2\n3", 0),
+            "1\n-- 2 more lines, `:more` to continue --"
+        );
+        assert_eq!(pager.more(3).unwrap(), "2\n3");
+    }
+}
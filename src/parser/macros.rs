@@ -5,9 +5,10 @@ macro_rules! assert_token {
         // variants (unit, tuple, or struct) and is more robust than the
         // previous implementation.
         if !matches!($val, $($pat)|+) {
-            // Using `bail!` from `anyhow` allows us to propagate the error
-            // gracefully, as parser functions return a `Result`.
-            ::anyhow::bail!(
+            // Using `fail!` allows us to propagate the error gracefully,
+            // as parser functions return a `Result`.
+            $crate::error::fail!(
+                $crate::error::ErrorCode::UnexpectedToken,
                 "Invalid token. Got: {:?}, Expected one of: {}",
                 $val,
                 stringify!($($pat)|+)
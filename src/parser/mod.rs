@@ -1,26 +1,51 @@
-use crate::{Expression, InfixOperator, Lexer, PrefixOperator, Program, Statement, Token};
+use crate::ast::{CallSpan, HashSpan, MatchArm, Pattern};
+use crate::lexer::Position;
+use crate::prelude::*;
+use crate::{
+    Expression, InfixOperator, Lexer, PrefixOperator, Program, Statement, Token, TypeAnnotation,
+};
 
 mod macros;
 use macros::assert_token;
 
 mod precedence;
-use anyhow::{Result, bail};
-use precedence::Precedence;
+use crate::error::{Error, ErrorCode, Result, fail};
+use crate::trace::{event, span};
+use core::ops::Range;
+pub(crate) use precedence::Precedence;
+
+/// The positional and the keyword arguments of a call.
+type Arguments = (Vec<Expression>, Vec<(String, Expression)>);
 
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     curr_token: Token,
     peek_token: Token,
+    curr_span: Range<usize>,
+    peek_span: Range<usize>,
+    curr_position: Position,
+    peek_position: Position,
+    /// Number of loops around the current token within the current function, so that
+    /// `break` and `continue` can only be used in loops.
+    loop_depth: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn init(input: &'a str) -> Self {
-        let lexer = Lexer::init(input);
+        Self::from_lexer(Lexer::init(input))
+    }
 
+    /// Parser reading the tokens of `lexer`, e.g. one with its own keywords.
+    pub fn from_lexer(lexer: Lexer<'a>) -> Self {
         let mut p = Parser {
             lexer,
             curr_token: Token::Illegal,
             peek_token: Token::Illegal,
+            curr_span: 0..0,
+            peek_span: 0..0,
+            curr_position: Position::default(),
+            peek_position: Position::default(),
+            loop_depth: 0,
         };
 
         p.advance_token();
@@ -30,20 +55,62 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_program(&mut self) -> Result<Program> {
+        span!("parse_program");
         let mut statements: Vec<Statement> = vec![];
 
         while self.curr_token != Token::Eof {
-            statements.push(self.parse_statement()?);
+            let statement = self.parse_statement().map_err(|err| self.locate(err))?;
+            event!(?statement, "statement parsed");
+            statements.push(statement);
             self.advance_token();
         }
 
         Ok(Program { statements })
     }
 
+    /// Parses the whole input as a single expression, optionally followed by a semicolon,
+    /// like a formula: statements such as `let` are rejected.
+    pub fn parse_expression_only(&mut self) -> Result<Expression> {
+        self.parse_single_expression()
+            .map_err(|err| self.locate(err))
+    }
+
+    fn parse_single_expression(&mut self) -> Result<Expression> {
+        if let Token::Let | Token::Return | Token::Yield | Token::Break | Token::Continue =
+            self.curr_token
+        {
+            return Err(Error::new(
+                ErrorCode::UnexpectedToken,
+                format!("Expected an expression, found {}", self.curr_token),
+            )
+            .with_span(self.curr_span.clone()));
+        }
+        let expr = self.parse_expression(Precedence::Lowest)?;
+        self.advance_token();
+        if self.curr_token == Token::Semicolon {
+            self.advance_token();
+        }
+        if self.curr_token != Token::Eof {
+            return Err(Error::new(
+                ErrorCode::UnexpectedToken,
+                format!(
+                    "Expected a single expression, found {} after it",
+                    self.curr_token
+                ),
+            )
+            .with_span(self.curr_span.clone()));
+        }
+        Ok(expr)
+    }
+
     fn parse_statement(&mut self) -> Result<Statement> {
         match self.curr_token {
             Token::Let => self.parse_let_statement(),
+            Token::Ident(_) if self.peek_token == Token::Assign => self.parse_assign_statement(),
+            Token::Struct => self.parse_struct_statement(),
             Token::Return => self.parse_return_statement(),
+            Token::Yield => self.parse_yield_statement(),
+            Token::Break | Token::Continue => self.parse_loop_control_statement(),
             Token::RBrace => self.parse_block_statement(),
             _ => self.parse_expression_statement(),
         }
@@ -51,17 +118,66 @@ impl<'a> Parser<'a> {
 
     fn parse_let_statement(&mut self) -> Result<Statement> {
         self.advance_token();
+        let mutable = self.curr_token == Token::Mut;
+        if mutable {
+            self.advance_token();
+        } else if let Token::LBracket | Token::LBrace | Token::LParen | Token::Underscore =
+            self.curr_token
+        {
+            return self.parse_destructure_statement();
+        }
+        self.reject_keyword()?;
 
         let name = if let Token::Ident(_name) = &self.curr_token {
             _name.clone()
         } else {
-            bail!(
+            fail!(
+                ErrorCode::UnexpectedToken,
                 "Invalid Token for let statement, expected an identifier, found {}",
                 &self.curr_token
             );
         };
         self.advance_token();
 
+        let annotation = self.parse_type_annotation(Token::Colon)?;
+
+        assert_token!(self.curr_token, Token::Assign);
+        self.advance_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        assert_token!(self.peek_token, Token::Semicolon | Token::Eof);
+        self.advance_token();
+
+        Ok(Statement::Let {
+            name,
+            mutable,
+            annotation,
+            value,
+        })
+    }
+
+    fn parse_assign_statement(&mut self) -> Result<Statement> {
+        let Token::Ident(name) = &self.curr_token else {
+            unreachable!("assignments start with an identifier");
+        };
+        let name = name.clone();
+        self.advance_token();
+        self.advance_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token == Token::Semicolon {
+            self.advance_token();
+        }
+
+        Ok(Statement::Assign { name, value })
+    }
+
+    fn parse_destructure_statement(&mut self) -> Result<Statement> {
+        let pattern = self.parse_bindings()?;
+        self.advance_token();
+
         assert_token!(self.curr_token, Token::Assign);
         self.advance_token();
 
@@ -70,7 +186,60 @@ impl<'a> Parser<'a> {
         assert_token!(self.peek_token, Token::Semicolon | Token::Eof);
         self.advance_token();
 
-        Ok(Statement::Let { name, value })
+        Ok(Statement::Destructure { pattern, value })
+    }
+
+    fn parse_struct_statement(&mut self) -> Result<Statement> {
+        self.advance_token();
+        self.reject_keyword()?;
+
+        let Token::Ident(name) = &self.curr_token else {
+            fail!(
+                ErrorCode::UnexpectedToken,
+                "Invalid Token for struct statement, expected an identifier, found {}",
+                &self.curr_token
+            );
+        };
+        let name = name.clone();
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::LBrace);
+        self.advance_token();
+
+        let mut fields: Vec<String> = vec![];
+
+        while self.curr_token != Token::RBrace {
+            self.reject_keyword()?;
+            match &self.curr_token {
+                Token::Ident(field) if fields.contains(field) => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "The field `{field}` is declared twice in struct `{name}`"
+                ),
+                Token::Ident(field) => fields.push(field.clone()),
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token for a field of struct `{name}`, expected an identifier, found {}",
+                    &self.curr_token
+                ),
+            }
+            self.advance_token();
+
+            match &self.curr_token {
+                Token::Comma => self.advance_token(),
+                Token::RBrace => break,
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token found while parsing struct fields, expected , as separator or }} to close, found {}",
+                    &self.curr_token
+                ),
+            }
+        }
+
+        if self.peek_token == Token::Semicolon {
+            self.advance_token();
+        }
+
+        Ok(Statement::Struct { name, fields })
     }
 
     fn parse_return_statement(&mut self) -> Result<Statement> {
@@ -84,10 +253,42 @@ impl<'a> Parser<'a> {
         Ok(Statement::Return { value })
     }
 
+    fn parse_yield_statement(&mut self) -> Result<Statement> {
+        self.advance_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        assert_token!(self.peek_token, Token::Semicolon);
+        self.advance_token();
+
+        Ok(Statement::Yield { value })
+    }
+
+    fn parse_loop_control_statement(&mut self) -> Result<Statement> {
+        if self.loop_depth == 0 {
+            fail!(
+                ErrorCode::UnexpectedToken,
+                "{} can only be used in a loop",
+                self.curr_token
+            );
+        }
+        let statement = match self.curr_token {
+            Token::Break => Statement::Break,
+            _ => Statement::Continue,
+        };
+
+        if self.peek_token == Token::Semicolon {
+            self.advance_token();
+        }
+
+        Ok(statement)
+    }
+
     fn parse_block_statement(&mut self) -> Result<Statement> {
         let mut statements: Vec<Statement> = vec![];
 
         while self.curr_token != Token::RBrace {
+            self.expect_more("}")?;
             statements.push(self.parse_statement()?);
             self.advance_token();
         }
@@ -106,12 +307,16 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression> {
+        let start = self.curr_span.start;
         let mut left = self.parse_prefix()?;
 
         while self.peek_token != Token::Semicolon && precedence < self.peek_precedence() {
+            let callee = start..self.curr_span.end;
             self.advance_token();
             left = match self.curr_token {
-                Token::LParen => self.parse_call_expression(left)?,
+                Token::LParen => self.parse_call_expression(left, callee)?,
+                Token::Question => Expression::propagating(left),
+                Token::Dot => self.parse_method_call(left, callee)?,
                 _ => self.parse_infix_expression(left)?,
             }
         }
@@ -121,18 +326,39 @@ impl<'a> Parser<'a> {
 
     fn parse_prefix(&mut self) -> Result<Expression> {
         match &self.curr_token {
-            Token::Bang | Token::Minus => self.parse_prefix_expression(),
+            Token::Bang | Token::Minus | Token::Plus => self.parse_prefix_expression(),
             Token::Ident(value) => Ok(Expression::Ident(value.to_owned())),
             Token::Int(value) => Ok(Expression::Int(value.to_owned())),
+            Token::Float(value) => Ok(Expression::Float(*value)),
             Token::String(string) => Ok(Expression::String(string.to_owned())),
             Token::True => Ok(Expression::from(true)),
             Token::False => Ok(Expression::from(false)),
             Token::LParen => self.parse_grouped_expression(),
             Token::If => self.parse_if_expression(),
+            Token::For => self.parse_for_expression(),
+            Token::While => self.parse_while_expression(),
+            Token::Match => self.parse_match_expression(),
             Token::Function => self.parse_fn_expression(),
             Token::LBracket => self.parse_array_expression(),
             Token::LBrace => self.parse_hash_expression(),
-            _ => bail!("{} is an invalid token as a prefix.", self.curr_token),
+            Token::Illegal if self.lexer.text(self.curr_span.clone()).starts_with("/*") => {
+                fail!(ErrorCode::UnexpectedToken, "Unterminated block comment")
+            }
+            Token::Illegal if self.lexer.text(self.curr_span.clone()).starts_with('"') => {
+                fail!(
+                    ErrorCode::UnexpectedEof,
+                    "Unexpected end of input, expected \" to close the string"
+                )
+            }
+            Token::Eof => fail!(
+                ErrorCode::UnexpectedEof,
+                "Unexpected end of input, expected an expression"
+            ),
+            _ => fail!(
+                ErrorCode::NoPrefixParse,
+                "{} is an invalid token as a prefix.",
+                self.curr_token
+            ),
         }
     }
 
@@ -167,15 +393,40 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// `(value)`, or a tuple: `()`, `(value,)` or `(first, second, ...)`.
     fn parse_grouped_expression(&mut self) -> Result<Expression> {
         self.advance_token();
+        if self.curr_token == Token::RParen {
+            return Ok(Expression::Tuple(vec![]));
+        }
 
-        let exp = self.parse_expression(Precedence::Lowest);
-
-        assert_token!(self.peek_token, Token::RParen);
+        let exp = self.parse_expression(Precedence::Lowest)?;
+        self.advance_token();
+        if self.curr_token != Token::Comma {
+            assert_token!(self.curr_token, Token::RParen);
+            return Ok(exp);
+        }
         self.advance_token();
 
-        exp
+        let mut content = vec![exp];
+
+        while self.curr_token != Token::RParen {
+            content.push(self.parse_expression(Precedence::Lowest)?);
+
+            self.advance_token();
+
+            match &self.curr_token {
+                Token::Comma => self.advance_token(),
+                Token::RParen => break,
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token found while parsing a tuple, expected , as separator or ) to close, found {}",
+                    &self.curr_token
+                ),
+            }
+        }
+
+        Ok(Expression::Tuple(content))
     }
 
     fn parse_array_expression(&mut self) -> Result<Expression> {
@@ -184,14 +435,17 @@ impl<'a> Parser<'a> {
         let mut content: Vec<Expression> = vec![];
 
         while self.curr_token != Token::RBracket {
+            self.expect_more("]")?;
             content.push(self.parse_expression(Precedence::Lowest)?);
 
             self.advance_token();
+            self.expect_more("]")?;
 
             match &self.curr_token {
                 Token::Comma => self.advance_token(),
                 Token::RBracket => break,
-                _ => bail!(
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
                     "Invalid token found while parsing array arguments, expected , as separator or ] to close, found {}",
                     &self.curr_token
                 ),
@@ -205,30 +459,40 @@ impl<'a> Parser<'a> {
         self.advance_token();
 
         let mut content: Vec<(Expression, Expression)> = vec![];
+        let mut keys = vec![];
 
         while self.curr_token != Token::RBrace {
+            self.expect_more("}")?;
+            let start = self.curr_span.start;
             let left = self.parse_expression(Precedence::Lowest)?;
+            keys.push(start..self.curr_span.end);
             self.advance_token();
 
+            self.expect_more(":")?;
             assert_token!(self.curr_token, Token::Colon);
             self.advance_token();
 
             let right = self.parse_expression(Precedence::Lowest)?;
             self.advance_token();
+            self.expect_more("}")?;
 
             content.push((left, right));
 
             match &self.curr_token {
                 Token::Comma => self.advance_token(),
                 Token::RBrace => break,
-                _ => bail!(
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
                     "Invalid token found while parsing hashmap arguments, expected , as separator or }} to close, found {}",
                     &self.curr_token
                 ),
             }
         }
 
-        Ok(Expression::Hash(content))
+        Ok(Expression::Hash {
+            pairs: content,
+            span: HashSpan::new(keys),
+        })
     }
 
     fn parse_if_expression(&mut self) -> Result<Expression> {
@@ -248,7 +512,10 @@ impl<'a> Parser<'a> {
 
         let then_ = match self.parse_block_statement()? {
             Statement::Block(statements) => statements,
-            _ => bail!("The `then` part of an if statement must be a block."),
+            _ => fail!(
+                ErrorCode::UnexpectedToken,
+                "The `then` part of an if statement must be a block."
+            ),
         };
 
         let else_ = if self.peek_token == Token::Else {
@@ -260,7 +527,10 @@ impl<'a> Parser<'a> {
 
             Some(match self.parse_block_statement()? {
                 Statement::Block(statements) => statements,
-                _ => bail!("The `else` part of an if statement must be a block."),
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "The `else` part of an if statement must be a block."
+                ),
             })
         } else {
             None
@@ -273,6 +543,329 @@ impl<'a> Parser<'a> {
         })
     }
 
+    fn parse_for_expression(&mut self) -> Result<Expression> {
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::LParen);
+        self.advance_token();
+
+        self.reject_keyword()?;
+        let name = match &self.curr_token {
+            Token::Ident(name) => name.clone(),
+            _ => fail!(
+                ErrorCode::UnexpectedToken,
+                "Invalid token for a for loop, expected an identifier, found {}",
+                &self.curr_token
+            ),
+        };
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::In);
+        self.advance_token();
+
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::RParen);
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::LBrace);
+        self.advance_token();
+
+        let body = match self.parse_loop_body()? {
+            Statement::Block(statements) => statements,
+            _ => fail!(
+                ErrorCode::UnexpectedToken,
+                "The body of a for loop must be a block."
+            ),
+        };
+
+        Ok(Expression::For {
+            name,
+            iterable: Box::new(iterable),
+            body,
+        })
+    }
+
+    fn parse_while_expression(&mut self) -> Result<Expression> {
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::LParen);
+        self.advance_token();
+
+        let cond = self.parse_expression(Precedence::Lowest)?;
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::RParen);
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::LBrace);
+        self.advance_token();
+
+        let body = match self.parse_loop_body()? {
+            Statement::Block(statements) => statements,
+            _ => fail!(
+                ErrorCode::UnexpectedToken,
+                "The body of a while loop must be a block."
+            ),
+        };
+
+        Ok(Expression::While {
+            cond: Box::new(cond),
+            body,
+        })
+    }
+
+    fn parse_loop_body(&mut self) -> Result<Statement> {
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn parse_match_expression(&mut self) -> Result<Expression> {
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::LParen);
+        self.advance_token();
+
+        let subject = self.parse_expression(Precedence::Lowest)?;
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::RParen);
+        self.advance_token();
+
+        assert_token!(self.curr_token, Token::LBrace);
+        self.advance_token();
+
+        let mut arms: Vec<MatchArm> = vec![];
+
+        while self.curr_token != Token::RBrace {
+            let pattern = self.parse_bindings()?;
+            self.advance_token();
+
+            let guard = if self.curr_token == Token::If {
+                self.advance_token();
+                let guard = self.parse_expression(Precedence::Lowest)?;
+                self.advance_token();
+                Some(guard)
+            } else {
+                None
+            };
+
+            assert_token!(self.curr_token, Token::FatArrow);
+            self.advance_token();
+
+            let value = self.parse_expression(Precedence::Lowest)?;
+            self.advance_token();
+
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                value,
+            });
+
+            match &self.curr_token {
+                Token::Comma => self.advance_token(),
+                Token::RBrace => break,
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token found while parsing match arms, expected , as separator or }} to close, found {}",
+                    &self.curr_token
+                ),
+            }
+        }
+
+        Ok(Expression::Match {
+            subject: Box::new(subject),
+            arms,
+        })
+    }
+
+    /// Parses a pattern, rejecting those binding a name twice.
+    fn parse_bindings(&mut self) -> Result<Pattern> {
+        let pattern = self.parse_pattern()?;
+        let names = pattern.names();
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                fail!(
+                    ErrorCode::UnexpectedToken,
+                    "The name `{name}` is bound twice in the pattern {pattern}"
+                );
+            }
+        }
+        Ok(pattern)
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern> {
+        let pattern = match &self.curr_token {
+            Token::Underscore => Pattern::Wildcard,
+            Token::True => Pattern::Literal(Expression::from(true)),
+            Token::False => Pattern::Literal(Expression::from(false)),
+            Token::Int(value) => Pattern::Literal(Expression::Int(*value)),
+            Token::Float(value) => Pattern::Literal(Expression::Float(*value)),
+            Token::String(string) => Pattern::Literal(Expression::String(string.to_owned())),
+            Token::Minus => {
+                self.advance_token();
+                match self.curr_token {
+                    Token::Int(value) => Pattern::Literal(Expression::Int(-value)),
+                    Token::Float(value) => Pattern::Literal(Expression::Float(-value)),
+                    _ => fail!(
+                        ErrorCode::UnexpectedToken,
+                        "Invalid token for a negative pattern, expected a number, found {}",
+                        &self.curr_token
+                    ),
+                }
+            }
+            Token::Ident(name) if name == "null" => Pattern::Literal(Expression::from("null")),
+            Token::Ident(_) if self.peek_token == Token::LParen => self.parse_struct_pattern()?,
+            Token::Ident(name) => Pattern::Ident(name.to_owned()),
+            Token::LBracket => self.parse_array_pattern()?,
+            Token::LParen => self.parse_tuple_pattern()?,
+            Token::LBrace => self.parse_hash_pattern()?,
+            _ => {
+                self.reject_keyword()?;
+                fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token for a pattern, expected a name, a literal, _, [, ( or {{, found {}",
+                    &self.curr_token
+                )
+            }
+        };
+        Ok(pattern)
+    }
+
+    fn parse_array_pattern(&mut self) -> Result<Pattern> {
+        self.advance_token();
+
+        let mut items: Vec<Pattern> = vec![];
+        let mut rest = None;
+
+        while self.curr_token != Token::RBracket {
+            if self.curr_token == Token::Ellipsis {
+                self.advance_token();
+                rest = Some(Box::new(self.parse_pattern()?));
+                self.advance_token();
+
+                assert_token!(self.curr_token, Token::RBracket);
+                break;
+            }
+
+            items.push(self.parse_pattern()?);
+            self.advance_token();
+
+            match &self.curr_token {
+                Token::Comma => self.advance_token(),
+                Token::RBracket => break,
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token found while parsing an array pattern, expected , as separator or ] to close, found {}",
+                    &self.curr_token
+                ),
+            }
+        }
+
+        Ok(Pattern::Array { items, rest })
+    }
+
+    /// `(patterns...)`, a tuple pattern unless it's a single pattern without a comma,
+    /// like grouped expressions.
+    fn parse_tuple_pattern(&mut self) -> Result<Pattern> {
+        self.advance_token();
+
+        let mut items: Vec<Pattern> = vec![];
+        let mut grouped = true;
+
+        while self.curr_token != Token::RParen {
+            items.push(self.parse_pattern()?);
+            self.advance_token();
+
+            match &self.curr_token {
+                Token::Comma => {
+                    grouped = false;
+                    self.advance_token()
+                }
+                Token::RParen => break,
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token found while parsing a tuple pattern, expected , as separator or ) to close, found {}",
+                    &self.curr_token
+                ),
+            }
+        }
+
+        match items.pop() {
+            Some(pattern) if grouped && items.is_empty() => Ok(pattern),
+            last => Ok(Pattern::Tuple(items.into_iter().chain(last).collect())),
+        }
+    }
+
+    /// `Name(patterns...)`, matching the fields of a struct in their declared order.
+    fn parse_struct_pattern(&mut self) -> Result<Pattern> {
+        let Token::Ident(name) = &self.curr_token else {
+            unreachable!("struct patterns start with an identifier");
+        };
+        let name = name.clone();
+        self.advance_token();
+        self.advance_token();
+
+        let mut fields: Vec<Pattern> = vec![];
+
+        while self.curr_token != Token::RParen {
+            fields.push(self.parse_pattern()?);
+            self.advance_token();
+
+            match &self.curr_token {
+                Token::Comma => self.advance_token(),
+                Token::RParen => break,
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token found while parsing a struct pattern, expected , as separator or ) to close, found {}",
+                    &self.curr_token
+                ),
+            }
+        }
+
+        Ok(Pattern::Struct { name, fields })
+    }
+
+    fn parse_hash_pattern(&mut self) -> Result<Pattern> {
+        self.advance_token();
+
+        let mut pairs: Vec<(Expression, Pattern)> = vec![];
+
+        while self.curr_token != Token::RBrace {
+            let key = match self.parse_pattern()? {
+                Pattern::Literal(key) if key != Expression::from("null") => key,
+                pattern => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid key {pattern} in a hash pattern, expected an int, string or bool"
+                ),
+            };
+            self.advance_token();
+
+            assert_token!(self.curr_token, Token::Colon);
+            self.advance_token();
+
+            let pattern = self.parse_pattern()?;
+            self.advance_token();
+
+            pairs.push((key, pattern));
+
+            match &self.curr_token {
+                Token::Comma => self.advance_token(),
+                Token::RBrace => break,
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Invalid token found while parsing a hash pattern, expected , as separator or }} to close, found {}",
+                    &self.curr_token
+                ),
+            }
+        }
+
+        Ok(Pattern::Hash(pairs))
+    }
+
     fn parse_fn_expression(&mut self) -> Result<Expression> {
         self.advance_token();
 
@@ -280,19 +873,34 @@ impl<'a> Parser<'a> {
         self.advance_token();
 
         let mut args: Vec<String> = vec![];
+        let mut arg_types: Vec<Option<TypeAnnotation>> = vec![];
+        let mut destructures: Vec<Statement> = vec![];
 
         while self.curr_token != Token::RParen {
+            self.reject_keyword()?;
             match &self.curr_token {
                 Token::Ident(arg) => args.push(arg.to_string()),
-                _ => bail!("A function name must be an identifier."),
+                Token::LParen | Token::LBracket | Token::LBrace | Token::Underscore => {
+                    let pattern = self.parse_bindings()?;
+                    let (arg, destructure) = Expression::pattern_parameter(args.len(), pattern);
+                    args.push(arg);
+                    destructures.push(destructure);
+                }
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "A function parameter must be an identifier or a pattern."
+                ),
             }
 
             self.advance_token();
 
+            arg_types.push(self.parse_type_annotation(Token::Colon)?);
+
             match &self.curr_token {
                 Token::Comma => self.advance_token(),
                 Token::RParen => break,
-                _ => bail!(
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
                     "Invalid token found while parsing function arguments, expected , as separator or ) to close, found {}",
                     &self.curr_token
                 ),
@@ -301,41 +909,132 @@ impl<'a> Parser<'a> {
 
         self.advance_token();
 
+        let return_type = self.parse_type_annotation(Token::Arrow)?;
+
         assert_token!(self.curr_token, Token::LBrace);
         self.advance_token();
 
-        let body = match self.parse_block_statement()? {
-            Statement::Block(statements) => statements,
-            _ => bail!("A function body must be enclosed in a block."),
+        // Loops around the function don't continue in its body.
+        let loop_depth = core::mem::replace(&mut self.loop_depth, 0);
+        let body = self.parse_block_statement();
+        self.loop_depth = loop_depth;
+        let body = match body? {
+            Statement::Block(statements) => destructures.into_iter().chain(statements).collect(),
+            _ => fail!(
+                ErrorCode::UnexpectedToken,
+                "A function body must be enclosed in a block."
+            ),
+        };
+
+        Ok(Expression::Func {
+            args,
+            arg_types,
+            return_type,
+            body,
+        })
+    }
+
+    /// Parses `<marker> <type>` if the current token is `marker`, leaving the token after the type as current.
+    fn parse_type_annotation(&mut self, marker: Token) -> Result<Option<TypeAnnotation>> {
+        if self.curr_token != marker {
+            return Ok(None);
+        }
+        self.advance_token();
+
+        let annotation = match &self.curr_token {
+            Token::Ident(name) => TypeAnnotation::try_from(name.as_str())?,
+            Token::Function => TypeAnnotation::Fn,
+            _ => fail!(
+                ErrorCode::UnexpectedToken,
+                "Invalid token for a type annotation, expected a type name, found {}",
+                &self.curr_token
+            ),
         };
+        self.advance_token();
+
+        Ok(Some(annotation))
+    }
+
+    /// `func(args)`, `callee` being the span of `func`.
+    fn parse_call_expression(
+        &mut self,
+        func: Expression,
+        callee: Range<usize>,
+    ) -> Result<Expression> {
+        let (args, kwargs) = self.parse_call_arguments()?;
+        Ok(Expression::Call {
+            func: Box::new(func),
+            args,
+            kwargs,
+            span: CallSpan::new(callee.start..self.curr_span.end, callee),
+        })
+    }
 
-        Ok(Expression::Func { args, body })
+    /// `object.name(args)`, or the field `object.name` without parentheses.
+    fn parse_method_call(
+        &mut self,
+        object: Expression,
+        object_span: Range<usize>,
+    ) -> Result<Expression> {
+        let Token::Ident(name) = &self.peek_token else {
+            fail!(
+                ErrorCode::UnexpectedToken,
+                "Expected a field or method name after `.`, found {}",
+                &self.peek_token
+            );
+        };
+        let name = name.clone();
+        self.advance_token();
+        if self.peek_token != Token::LParen {
+            return Ok(Expression::Field {
+                object: Box::new(object),
+                name,
+            });
+        }
+        let callee = object_span.start..self.curr_span.end;
+        self.advance_token();
+        let (args, kwargs) = self.parse_call_arguments()?;
+        let span = CallSpan::new(callee.start..self.curr_span.end, callee);
+        Ok(Expression::method_call(object, &name, args, kwargs, span))
     }
 
-    fn parse_call_expression(&mut self, func: Expression) -> Result<Expression> {
+    /// The arguments of a call, from its `(` to its `)`.
+    fn parse_call_arguments(&mut self) -> Result<Arguments> {
         self.advance_token();
 
         let mut args: Vec<Expression> = vec![];
+        let mut kwargs: Vec<(String, Expression)> = vec![];
 
         while self.curr_token != Token::RParen {
-            args.push(self.parse_expression(Precedence::Lowest)?);
+            match &self.curr_token {
+                Token::Ident(name) if self.peek_token == Token::Colon => {
+                    let name = name.clone();
+                    self.advance_token();
+                    self.advance_token();
+                    kwargs.push((name, self.parse_expression(Precedence::Lowest)?));
+                }
+                _ if !kwargs.is_empty() => fail!(
+                    ErrorCode::UnexpectedToken,
+                    "Positional arguments must come before keyword arguments, found {}",
+                    &self.curr_token
+                ),
+                _ => args.push(self.parse_expression(Precedence::Lowest)?),
+            }
 
             self.advance_token();
 
             match &self.curr_token {
                 Token::Comma => self.advance_token(),
                 Token::RParen => break,
-                _ => bail!(
+                _ => fail!(
+                    ErrorCode::UnexpectedToken,
                     "Invalid token found while parsing function arguments, expected , as separator or ) to close, found {}",
                     &self.curr_token
                 ),
             }
         }
 
-        Ok(Expression::Call {
-            func: Box::new(func),
-            args,
-        })
+        Ok((args, kwargs))
     }
 
     fn peek_precedence(&mut self) -> Precedence {
@@ -343,12 +1042,50 @@ impl<'a> Parser<'a> {
     }
 
     fn curr_precedence(&mut self) -> Precedence {
-        Precedence::get_from_token(&self.curr_token)
+        Precedence::of_right_operand(&self.curr_token)
     }
 
     fn advance_token(&mut self) {
         self.curr_token = self.peek_token.clone();
+        self.curr_span = self.peek_span.clone();
+        self.curr_position = self.peek_position;
         self.peek_token = self.lexer.next_token();
+        self.peek_span = self.lexer.span();
+        self.peek_position = self.lexer.token_position();
+    }
+
+    /// Adds where the current token starts, at which parsing stopped, to the message of
+    /// `err`, and its span if it has none.
+    fn locate(&self, mut err: Error) -> Error {
+        err.message = format!("{} at {}", err.message, self.curr_position);
+        if err.span.is_none() {
+            err.span = Some(self.curr_span.clone());
+        }
+        err
+    }
+
+    /// Fails if the input ended before the construct being parsed, which `expected` would
+    /// continue or close.
+    fn expect_more(&self, expected: &str) -> Result<()> {
+        if self.curr_token == Token::Eof {
+            fail!(
+                ErrorCode::UnexpectedEof,
+                "Unexpected end of input, expected {expected}"
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails with a dedicated error if the current token is a keyword in identifier position.
+    fn reject_keyword(&self) -> Result<()> {
+        match self.curr_token.keyword() {
+            Some(keyword) => Err(Error::new(
+                ErrorCode::KeywordAsIdentifier,
+                format!("Expected identifier, found keyword `{keyword}`"),
+            )
+            .with_span(self.curr_span.clone())),
+            None => Ok(()),
+        }
     }
 }
 
@@ -363,6 +1100,13 @@ mod tests {
         assert_eq!(program, Program { statements })
     }
 
+    fn hash(pairs: Vec<(Expression, Expression)>) -> Expression {
+        Expression::Hash {
+            pairs,
+            span: HashSpan::default(),
+        }
+    }
+
     #[test]
     fn init_parser() {
         let parser = Parser::init("=+(){},;");
@@ -371,25 +1115,49 @@ mod tests {
         assert_eq!(parser.peek_token, Token::Plus);
     }
 
+    #[test]
+    fn custom_keywords() {
+        let keywords = [("function", Token::Function), ("return", Token::Return)];
+        let lexer = Lexer::with_keywords("function(fn) { return fn; }", &keywords);
+        let program = Parser::from_lexer(lexer).parse_program().unwrap();
+        assert_eq!(
+            crate::ast::sexp::to_sexp(&program),
+            "(fn (fn) (block (return fn)))\n"
+        );
+    }
+
     #[test]
     fn let_stmts() {
         assert_program(
             "let five = 5; \n\
             let ten = 10; \n\
-            let foobar = 838383;",
+            let foobar = 838383; \n\
+            let mut count = 0;",
             vec![
                 Statement::Let {
                     name: String::from("five"),
+                    mutable: false,
+                    annotation: None,
                     value: Expression::from(5),
                 },
                 Statement::Let {
                     name: String::from("ten"),
+                    mutable: false,
+                    annotation: None,
                     value: Expression::from(10),
                 },
                 Statement::Let {
                     name: String::from("foobar"),
+                    mutable: false,
+                    annotation: None,
                     value: Expression::from(838383),
                 },
+                Statement::Let {
+                    name: String::from("count"),
+                    mutable: true,
+                    annotation: None,
+                    value: Expression::from(0),
+                },
             ],
         );
     }
@@ -432,7 +1200,8 @@ mod tests {
     fn prefix_expressions() {
         assert_program(
             "-5; \n\
-            !15",
+            !15; \n\
+            +2.5",
             vec![
                 Statement::Expr(Expression::Prefix {
                     operator: PrefixOperator::Neg,
@@ -442,6 +1211,10 @@ mod tests {
                     operator: PrefixOperator::Not,
                     right: Box::new(Expression::Int(15)),
                 }),
+                Statement::Expr(Expression::Prefix {
+                    operator: PrefixOperator::Plus,
+                    right: Box::new(Expression::Float(2.5)),
+                }),
             ],
         );
     }
@@ -456,7 +1229,9 @@ mod tests {
             9 > 10; \n\
             11 < 12; \n\
             13 == 14; \n\
-            15 != 16;",
+            15 != 16; \n\
+            17 >= 18; \n\
+            19 <= 20;",
             vec![
                 Statement::Expr(Expression::Infix {
                     operator: InfixOperator::Add,
@@ -498,6 +1273,16 @@ mod tests {
                     left: Box::new(Expression::Int(15)),
                     right: Box::new(Expression::Int(16)),
                 }),
+                Statement::Expr(Expression::Infix {
+                    operator: InfixOperator::GtEq,
+                    left: Box::new(Expression::Int(17)),
+                    right: Box::new(Expression::Int(18)),
+                }),
+                Statement::Expr(Expression::Infix {
+                    operator: InfixOperator::LtEq,
+                    left: Box::new(Expression::Int(19)),
+                    right: Box::new(Expression::Int(20)),
+                }),
             ],
         );
     }
@@ -714,6 +1499,7 @@ mod tests {
                 }),
                 Statement::Expr(Expression::Call {
                     func: Box::new(Expression::Ident("add".into())),
+                    kwargs: vec![],
                     args: vec![
                         Expression::Infix {
                             operator: InfixOperator::Mul,
@@ -742,6 +1528,7 @@ mod tests {
                             }),
                         },
                     ],
+                    span: CallSpan::default(),
                 }),
             ],
         );
@@ -837,6 +1624,118 @@ mod tests {
         );
     }
 
+    #[test]
+    fn for_expressions() {
+        assert_program(
+            "for (x in range(3)) { x; }",
+            vec![Statement::Expr(Expression::For {
+                name: String::from("x"),
+                iterable: Box::new(Expression::Call {
+                    func: Box::new(Expression::from("range")),
+                    kwargs: vec![],
+                    args: vec![Expression::Int(3)],
+                    span: CallSpan::default(),
+                }),
+                body: vec![Statement::Expr(Expression::from("x"))],
+            })],
+        );
+    }
+
+    #[test]
+    fn while_expressions() {
+        assert_program(
+            "while (x < 3) { x; }",
+            vec![Statement::Expr(Expression::While {
+                cond: Box::new(Expression::Infix {
+                    operator: InfixOperator::Lt,
+                    left: Box::new(Expression::from("x")),
+                    right: Box::new(Expression::Int(3)),
+                }),
+                body: vec![Statement::Expr(Expression::from("x"))],
+            })],
+        );
+        assert!(Parser::init("while x { x }").parse_program().is_err());
+    }
+
+    #[test]
+    fn loop_control_stmts() {
+        assert_program(
+            "while (true) { break; }; for (x in y) { if (x) { continue } }",
+            vec![
+                Statement::Expr(Expression::While {
+                    cond: Box::new(Expression::from(true)),
+                    body: vec![Statement::Break],
+                }),
+                Statement::Expr(Expression::For {
+                    name: String::from("x"),
+                    iterable: Box::new(Expression::from("y")),
+                    body: vec![Statement::Expr(Expression::Cond {
+                        cond: Box::new(Expression::from("x")),
+                        then_: vec![Statement::Continue],
+                        else_: None,
+                    })],
+                }),
+            ],
+        );
+        for input in [
+            "break;",
+            "if (true) { continue; }",
+            "while (true) { fn() { break; } }",
+        ] {
+            let err = Parser::init(input).parse_program().unwrap_err();
+            assert_eq!(err.code, ErrorCode::UnexpectedToken, "{input}");
+        }
+        let err = Parser::init("while (x) { 1 }; break")
+            .parse_program()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`break` can only be used in a loop at line 1, column 18"
+        );
+    }
+
+    #[test]
+    fn assign_stmts() {
+        assert_program(
+            "x = 5; while (x) { x = x - 1 }",
+            vec![
+                Statement::Assign {
+                    name: String::from("x"),
+                    value: Expression::from(5),
+                },
+                Statement::Expr(Expression::While {
+                    cond: Box::new(Expression::from("x")),
+                    body: vec![Statement::Assign {
+                        name: String::from("x"),
+                        value: Expression::Infix {
+                            operator: InfixOperator::Sub,
+                            left: Box::new(Expression::from("x")),
+                            right: Box::new(Expression::from(1)),
+                        },
+                    }],
+                }),
+            ],
+        );
+        let err = Parser::init("x == 1 = 2").parse_program().unwrap_err();
+        assert_eq!(err.code, ErrorCode::NoPrefixParse);
+    }
+
+    #[test]
+    fn yield_stmts() {
+        assert_program(
+            "fn() { yield 5; }",
+            vec![Statement::Expr(Expression::Func {
+                args: vec![],
+                arg_types: vec![],
+                return_type: None,
+                body: vec![Statement::Yield {
+                    value: Expression::Int(5),
+                }],
+            })],
+        );
+        assert!(Parser::init("yield 5").parse_program().is_err());
+    }
+
     #[test]
     fn fn_expressions() {
         assert_program(
@@ -849,18 +1748,26 @@ mod tests {
             vec![
                 Statement::Expr(Expression::Func {
                     args: vec![],
+                    arg_types: vec![None; 0],
+                    return_type: None,
                     body: vec![],
                 }),
                 Statement::Expr(Expression::Func {
                     args: vec![String::from("x")],
+                    arg_types: vec![None; 1],
+                    return_type: None,
                     body: vec![],
                 }),
                 Statement::Expr(Expression::Func {
                     args: vec![String::from("x"), String::from("y"), String::from("z")],
+                    arg_types: vec![None; 3],
+                    return_type: None,
                     body: vec![],
                 }),
                 Statement::Expr(Expression::Func {
                     args: vec![String::from("x"), String::from("y")],
+                    arg_types: vec![None; 2],
+                    return_type: None,
                     body: vec![Statement::Expr(Expression::Infix {
                         operator: InfixOperator::Add,
                         left: Box::new(Expression::from("x")),
@@ -879,6 +1786,7 @@ mod tests {
             ",
             vec![Statement::Expr(Expression::Call {
                 func: Box::new(Expression::from("add")),
+                kwargs: vec![],
                 args: vec![
                     Expression::from(1),
                     Expression::Infix {
@@ -892,8 +1800,33 @@ mod tests {
                         right: Box::new(Expression::from(5)),
                     },
                 ],
+                span: CallSpan::default(),
+            })],
+        );
+    }
+
+    #[test]
+    fn keyword_arguments() {
+        assert_program(
+            "greet(\"Ada\", excited: true, times: 2)",
+            vec![Statement::Expr(Expression::Call {
+                func: Box::new(Expression::from("greet")),
+                args: vec![Expression::String("Ada".into())],
+                kwargs: vec![
+                    ("excited".into(), Expression::Bool(true)),
+                    ("times".into(), Expression::from(2)),
+                ],
+                span: CallSpan::default(),
             })],
         );
+        let err = Parser::init("greet(name: \"Ada\", true)")
+            .parse_program()
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnexpectedToken);
+        assert_eq!(
+            err.message,
+            "Positional arguments must come before keyword arguments, found <bool=true> at line 1, column 20"
+        );
     }
 
     #[test]
@@ -912,17 +1845,20 @@ mod tests {
                         left: Box::new(Expression::from("a")),
                         right: Box::new(Expression::Call {
                             func: Box::new(Expression::from("add")),
+                            kwargs: vec![],
                             args: vec![Expression::Infix {
                                 operator: InfixOperator::Mul,
                                 left: Box::new(Expression::from("b")),
                                 right: Box::new(Expression::from("c")),
                             }],
+                            span: CallSpan::default(),
                         }),
                     }),
                     right: Box::new(Expression::from("d")),
                 }),
                 Statement::Expr(Expression::Call {
                     func: Box::new(Expression::from("add")),
+                    kwargs: vec![],
                     args: vec![
                         Expression::from("a"),
                         Expression::from("b"),
@@ -939,6 +1875,7 @@ mod tests {
                         },
                         Expression::Call {
                             func: Box::new(Expression::from("add")),
+                            kwargs: vec![],
                             args: vec![
                                 Expression::from(6),
                                 Expression::Infix {
@@ -947,11 +1884,14 @@ mod tests {
                                     right: Box::new(Expression::from(8)),
                                 },
                             ],
+                            span: CallSpan::default(),
                         },
                     ],
+                    span: CallSpan::default(),
                 }),
                 Statement::Expr(Expression::Call {
                     func: Box::new(Expression::from("add")),
+                    kwargs: vec![],
                     args: vec![Expression::Infix {
                         operator: InfixOperator::Add,
                         left: Box::new(Expression::Infix {
@@ -973,6 +1913,7 @@ mod tests {
                         }),
                         right: Box::new(Expression::from("g")),
                     }],
+                    span: CallSpan::default(),
                 }),
             ],
         );
@@ -1033,7 +1974,7 @@ mod tests {
             {}; \n\
             {\"one\": 0 + 1, two: 10 - 8, \"th\" + \"ree\": 15 / 5};",
             vec![
-                Statement::Expr(Expression::Hash(vec![
+                Statement::Expr(hash(vec![
                     (Expression::String(String::from("one")), Expression::from(1)),
                     (Expression::String(String::from("two")), Expression::from(2)),
                     (
@@ -1041,16 +1982,16 @@ mod tests {
                         Expression::from(3),
                     ),
                 ])),
-                Statement::Expr(Expression::Hash(vec![
+                Statement::Expr(hash(vec![
                     (Expression::from(true), Expression::from(1)),
                     (Expression::from(false), Expression::from(0)),
                 ])),
-                Statement::Expr(Expression::Hash(vec![
+                Statement::Expr(hash(vec![
                     (Expression::from(1), Expression::from(2)),
                     (Expression::from(2), Expression::from(4)),
                 ])),
-                Statement::Expr(Expression::Hash(vec![])),
-                Statement::Expr(Expression::Hash(vec![
+                Statement::Expr(hash(vec![])),
+                Statement::Expr(hash(vec![
                     (
                         Expression::String(String::from("one")),
                         Expression::Infix {
@@ -1083,4 +2024,377 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn error_positions() {
+        let err = Parser::init("let x = 1;\nlet y = {\n  \"a\" 2\n};")
+            .parse_program()
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnexpectedToken);
+        assert!(err.to_string().ends_with(" at line 3, column 7"), "{err}");
+        assert_eq!(err.span, Some(27..28));
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            Parser::init("let x = 1; /* a\n/* b */ */ x / // c\n2")
+                .parse_program()
+                .unwrap(),
+            Parser::init("let x = 1; x / 2").parse_program().unwrap()
+        );
+        let err = Parser::init("let x = 1;\n/* a /* b */")
+            .parse_program()
+            .unwrap_err();
+        assert_eq!(
+            (err.to_string(), err.code, err.span),
+            (
+                "Unterminated block comment at line 2, column 1".to_owned(),
+                ErrorCode::UnexpectedToken,
+                Some(11..23)
+            )
+        );
+    }
+
+    #[test]
+    fn unexpected_end_of_input() {
+        for (input, message) in [
+            (
+                "let s = \"abc",
+                "Unexpected end of input, expected \" to close the string at line 1, column 9",
+            ),
+            (
+                "let f = fn(x) {\n  x + 1;",
+                "Unexpected end of input, expected } at line 2, column 9",
+            ),
+            (
+                "if (true) {",
+                "Unexpected end of input, expected } at line 1, column 12",
+            ),
+            (
+                "[1, 2",
+                "Unexpected end of input, expected ] at line 1, column 6",
+            ),
+            (
+                "[1,",
+                "Unexpected end of input, expected ] at line 1, column 4",
+            ),
+            (
+                "{\"a\": 1",
+                "Unexpected end of input, expected } at line 1, column 8",
+            ),
+            (
+                "{\"a\"",
+                "Unexpected end of input, expected : at line 1, column 5",
+            ),
+            (
+                "{\"a\": ",
+                "Unexpected end of input, expected an expression at line 1, column 7",
+            ),
+        ] {
+            let err = Parser::init(input).parse_program().unwrap_err();
+            assert_eq!(
+                (err.code, err.to_string()),
+                (ErrorCode::UnexpectedEof, message.to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn keywords_as_identifiers() {
+        let err = Parser::init("let x = 1;\nlet if = 3;")
+            .parse_program()
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::KeywordAsIdentifier);
+        assert_eq!(
+            err.to_string(),
+            "Expected identifier, found keyword `if` at line 2, column 5"
+        );
+        assert_eq!(err.span, Some(15..17));
+
+        let err = Parser::init("fn(a, return) { a }")
+            .parse_program()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected identifier, found keyword `return` at line 1, column 7"
+        );
+        assert_eq!(err.span, Some(6..12));
+        assert!(Parser::init("let let = 5;").parse_program().is_err());
+        assert!(Parser::init("for (in in x) {}").parse_program().is_err());
+    }
+
+    #[test]
+    fn expressions_only() {
+        let program = Parser::init("a * (b + 1)").parse_program().unwrap();
+        assert_eq!(
+            vec![Statement::Expr(
+                Parser::init("a * (b + 1);")
+                    .parse_expression_only()
+                    .unwrap()
+            )],
+            program.statements
+        );
+        let err = Parser::init("let x = 1")
+            .parse_expression_only()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected an expression, found `let` at line 1, column 1"
+        );
+        assert_eq!(err.span, Some(0..3));
+        let err = Parser::init("1; 2").parse_expression_only().unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnexpectedToken);
+        assert_eq!(err.span, Some(3..4));
+        assert!(Parser::init("").parse_expression_only().is_err());
+    }
+
+    #[test]
+    fn type_annotations() {
+        assert_program(
+            "let x: int = 5; \n\
+            fn(a: str, b) -> bool { true }",
+            vec![
+                Statement::Let {
+                    name: String::from("x"),
+                    mutable: false,
+                    annotation: Some(TypeAnnotation::Int),
+                    value: Expression::from(5),
+                },
+                Statement::Expr(Expression::Func {
+                    args: vec![String::from("a"), String::from("b")],
+                    arg_types: vec![Some(TypeAnnotation::Str), None],
+                    return_type: Some(TypeAnnotation::Bool),
+                    body: vec![Statement::Expr(Expression::from(true))],
+                }),
+            ],
+        );
+        assert!(Parser::init("let x: integer = 5;").parse_program().is_err());
+    }
+
+    #[test]
+    fn patterns() {
+        assert_program(
+            "let [a, ...rest] = xs; \n\
+            match (x) { {\"r\": r, 1: _} if r > 0 => r, -1 => null, }",
+            vec![
+                Statement::Destructure {
+                    pattern: Pattern::Array {
+                        items: vec![Pattern::Ident(String::from("a"))],
+                        rest: Some(Box::new(Pattern::Ident(String::from("rest")))),
+                    },
+                    value: Expression::from("xs"),
+                },
+                Statement::Expr(Expression::Match {
+                    subject: Box::new(Expression::from("x")),
+                    arms: vec![
+                        MatchArm {
+                            pattern: Pattern::Hash(vec![
+                                (
+                                    Expression::String(String::from("r")),
+                                    Pattern::Ident(String::from("r")),
+                                ),
+                                (Expression::from(1), Pattern::Wildcard),
+                            ]),
+                            guard: Some(Expression::Infix {
+                                operator: InfixOperator::Gt,
+                                left: Box::new(Expression::from("r")),
+                                right: Box::new(Expression::from(0)),
+                            }),
+                            value: Expression::from("r"),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Literal(Expression::from(-1)),
+                            guard: None,
+                            value: Expression::from("null"),
+                        },
+                    ],
+                }),
+            ],
+        );
+        assert_program(
+            "match (x) { 0.5 => 1, -2e3 => 2 }",
+            vec![Statement::Expr(Expression::Match {
+                subject: Box::new(Expression::from("x")),
+                arms: vec![
+                    MatchArm {
+                        pattern: Pattern::Literal(Expression::Float(0.5)),
+                        guard: None,
+                        value: Expression::from(1),
+                    },
+                    MatchArm {
+                        pattern: Pattern::Literal(Expression::Float(-2000.0)),
+                        guard: None,
+                        value: Expression::from(2),
+                    },
+                ],
+            })],
+        );
+        let err = Parser::init("match (x) { -a => 1 }")
+            .parse_program()
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Invalid token for a negative pattern, expected a number, found <identifier=a> at line 1, column 14"
+        );
+        for input in [
+            "let [a, a] = xs;",
+            "let [...r, a] = xs;",
+            "let {k: v} = h;",
+            "match (x) { fn => 1 }",
+            "match (x) { 1 2 }",
+            "let (a, a) = t;",
+            "(1, 2",
+            "fn((a, b) { a }",
+        ] {
+            assert!(Parser::init(input).parse_program().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn tuples() {
+        assert_program(
+            "(1, (2,), (), (3)); let (a, _) = t; fn((x, y), z) { x }",
+            vec![
+                Statement::Expr(Expression::Tuple(vec![
+                    Expression::from(1),
+                    Expression::Tuple(vec![Expression::from(2)]),
+                    Expression::Tuple(vec![]),
+                    Expression::from(3),
+                ])),
+                Statement::Destructure {
+                    pattern: Pattern::Tuple(vec![
+                        Pattern::Ident(String::from("a")),
+                        Pattern::Wildcard,
+                    ]),
+                    value: Expression::from("t"),
+                },
+                Statement::Expr(Expression::Func {
+                    args: vec![String::from("$param0"), String::from("z")],
+                    arg_types: vec![None, None],
+                    return_type: None,
+                    body: vec![
+                        Statement::Destructure {
+                            pattern: Pattern::Tuple(vec![
+                                Pattern::Ident(String::from("x")),
+                                Pattern::Ident(String::from("y")),
+                            ]),
+                            value: Expression::from("$param0"),
+                        },
+                        Statement::Expr(Expression::from("x")),
+                    ],
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn propagation() {
+        let call = |arg| Expression::Call {
+            func: Box::new(Expression::from("f")),
+            kwargs: vec![],
+            args: vec![arg],
+            span: CallSpan::default(),
+        };
+        assert_program(
+            "-f(x?)?[0];",
+            vec![Statement::Expr(Expression::Prefix {
+                operator: PrefixOperator::Neg,
+                right: Box::new(Expression::Infix {
+                    operator: InfixOperator::Index,
+                    left: Box::new(Expression::propagating(call(Expression::propagating(
+                        Expression::from("x"),
+                    )))),
+                    right: Box::new(Expression::from(0)),
+                }),
+            })],
+        );
+        let program = Parser::init("x?").parse_program().unwrap();
+        let Statement::Expr(expr) = &program.statements[0] else {
+            unreachable!()
+        };
+        assert_eq!(expr.propagated(), Some(&Expression::from("x")));
+    }
+
+    #[test]
+    fn method_calls() {
+        assert_program(
+            "-a.f(1).g(by: 2)",
+            vec![Statement::Expr(Expression::Prefix {
+                operator: PrefixOperator::Neg,
+                right: Box::new(Expression::method_call(
+                    Expression::method_call(
+                        Expression::from("a"),
+                        "f",
+                        vec![Expression::from(1)],
+                        vec![],
+                        CallSpan::default(),
+                    ),
+                    "g",
+                    vec![],
+                    vec![("by".into(), Expression::from(2))],
+                    CallSpan::default(),
+                )),
+            })],
+        );
+        let program = Parser::init("a.f(1)").parse_program().unwrap();
+        let Statement::Expr(expr) = &program.statements[0] else {
+            unreachable!()
+        };
+        let call = expr.method_called().unwrap();
+        assert_eq!((call.object, call.name), (&Expression::from("a"), "f"));
+        assert_eq!(call.args, [Expression::from(1)]);
+        let err = Parser::init("a.1").parse_program().unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnexpectedToken);
+        assert!(
+            err.message
+                .starts_with("Expected a field or method name after `.`, found <int=1>"),
+            "{}",
+            err.message
+        );
+    }
+
+    #[test]
+    fn structs() {
+        assert_program(
+            "struct Point { x, y } match (Point(1, 2).x) { Point(a, _) => a }",
+            vec![
+                Statement::Struct {
+                    name: "Point".into(),
+                    fields: vec!["x".into(), "y".into()],
+                },
+                Statement::Expr(Expression::Match {
+                    subject: Box::new(Expression::Field {
+                        object: Box::new(Expression::Call {
+                            func: Box::new(Expression::from("Point")),
+                            args: vec![Expression::from(1), Expression::from(2)],
+                            kwargs: vec![],
+                            span: CallSpan::default(),
+                        }),
+                        name: "x".into(),
+                    }),
+                    arms: vec![MatchArm {
+                        pattern: Pattern::Struct {
+                            name: "Point".into(),
+                            fields: vec![Pattern::Ident("a".into()), Pattern::Wildcard],
+                        },
+                        guard: None,
+                        value: Expression::from("a"),
+                    }],
+                }),
+            ],
+        );
+        for (input, message) in [
+            (
+                "struct P { x, x }",
+                "The field `x` is declared twice in struct `P`",
+            ),
+            ("struct P { 1 }", "Invalid token for a field of struct `P`"),
+            ("struct { x }", "Invalid Token for struct statement"),
+        ] {
+            let err = Parser::init(input).parse_program().unwrap_err();
+            assert_eq!(err.code, ErrorCode::UnexpectedToken);
+            assert!(err.message.starts_with(message), "{}", err.message);
+        }
+    }
 }
@@ -1,12 +1,15 @@
-use crate::Token;
+use crate::{InfixOperator, Token};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Precedence {
     Lowest,
+    Or,
+    And,
     Equals,
     LessGreater,
     Sum,
     Product,
+    Power,
     Prefix,
     Call,
     Index,
@@ -15,17 +18,47 @@ pub enum Precedence {
 impl Precedence {
     pub fn get_from_token(token: &Token) -> Self {
         match token {
+            Token::Or => Precedence::Or,
+            Token::And => Precedence::And,
             Token::Eq => Precedence::Equals,
             Token::NotEq => Precedence::Equals,
             Token::Lt => Precedence::LessGreater,
             Token::Gt => Precedence::LessGreater,
+            Token::LtEq => Precedence::LessGreater,
+            Token::GtEq => Precedence::LessGreater,
             Token::Plus => Precedence::Sum,
             Token::Minus => Precedence::Sum,
             Token::Asterisk => Precedence::Product,
             Token::Slash => Precedence::Product,
+            Token::Percent => Precedence::Product,
+            Token::Power => Precedence::Power,
             Token::LParen => Precedence::Call,
-            Token::LBracket => Precedence::Index,
+            Token::LBracket | Token::Question | Token::Dot => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
+
+    /// Precedence the right operand of an infix operator is parsed with: the operator's
+    /// own one, lowered for the right-associative `**`.
+    pub fn of_right_operand(token: &Token) -> Self {
+        match token {
+            Token::Power => Precedence::Product,
+            _ => Self::get_from_token(token),
+        }
+    }
+
+    pub fn get_from_operator(operator: &InfixOperator) -> Self {
+        match operator {
+            InfixOperator::Or => Precedence::Or,
+            InfixOperator::And => Precedence::And,
+            InfixOperator::Eq | InfixOperator::NotEq => Precedence::Equals,
+            InfixOperator::Lt | InfixOperator::Gt | InfixOperator::LtEq | InfixOperator::GtEq => {
+                Precedence::LessGreater
+            }
+            InfixOperator::Add | InfixOperator::Sub => Precedence::Sum,
+            InfixOperator::Mul | InfixOperator::Div | InfixOperator::Mod => Precedence::Product,
+            InfixOperator::Pow => Precedence::Power,
+            InfixOperator::Index => Precedence::Index,
+        }
+    }
 }
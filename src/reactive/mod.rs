@@ -0,0 +1,268 @@
+//! Named expressions recomputed when what they refer to changes, like the cells of a
+//! spreadsheet:
+//!
+//! ```text
+//! price = 12
+//! qty = 3
+//! total = price * qty
+//! ```
+//!
+//! The dependencies of a formula are the free identifiers the resolver finds in it, so
+//! changing `qty` re-evaluates `total` and what depends on it, and nothing else.
+
+use crate::analysis::SymbolTable;
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{Object, is_builtin};
+use crate::{Interpreter, Lexer, Parser, Token, cst};
+use std::collections::{BTreeMap, HashSet};
+
+struct Cell {
+    /// Source of the formula and the cells it refers to, `None` for inputs.
+    formula: Option<(String, Vec<String>)>,
+    value: Result<Object>,
+}
+
+/// Inputs and formulas evaluated by an [`Interpreter`], which holds their values as
+/// variables. Failed formulas are bound to an error, so that their dependents fail too.
+pub struct Sheet {
+    interpreter: Interpreter,
+    cells: BTreeMap<String, Cell>,
+}
+
+impl Default for Sheet {
+    fn default() -> Self {
+        Self::new(Interpreter::expression_sandbox())
+    }
+}
+
+impl Sheet {
+    pub fn new(interpreter: Interpreter) -> Self {
+        Self {
+            interpreter,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the input `name`, replacing its formula if it had one. Returns the formulas
+    /// re-evaluated, in order.
+    pub fn set(&mut self, name: &str, value: impl Into<Object>) -> Result<Vec<String>> {
+        check_name(name)?;
+        let value = value.into();
+        self.interpreter.set(name, value.clone());
+        self.cells.insert(
+            name.to_owned(),
+            Cell {
+                formula: None,
+                value: Ok(value),
+            },
+        );
+        Ok(self.recompute(name))
+    }
+
+    /// Defines `name` as the single expression `formula`, evaluating it and its
+    /// dependents. Returns the formulas evaluated, in order. Fails without changing
+    /// anything if the formula doesn't parse or depends on itself.
+    pub fn define(&mut self, name: &str, formula: &str) -> Result<Vec<String>> {
+        check_name(name)?;
+        Parser::init(formula).parse_expression_only()?;
+        let mut dependencies: Vec<String> = SymbolTable::build(&cst::parse(formula))
+            .unresolved
+            .into_iter()
+            .map(|(dependency, _)| dependency)
+            .filter(|dependency| !is_builtin(dependency))
+            .collect();
+        dependencies.sort();
+        dependencies.dedup();
+        if let Some(cycle) = dependencies
+            .iter()
+            .find_map(|dependency| self.path(dependency, name))
+        {
+            fail!(
+                ErrorCode::InvalidInput,
+                "Cannot define `{name}`, it would depend on itself: {name} -> {}",
+                cycle.join(" -> ")
+            );
+        }
+        self.cells.insert(
+            name.to_owned(),
+            Cell {
+                formula: Some((formula.to_owned(), dependencies)),
+                value: Ok(Object::Null),
+            },
+        );
+        Ok(self.recompute(name))
+    }
+
+    /// Value of the cell, `None` if it isn't defined.
+    pub fn get(&self, name: &str) -> Option<Result<Object>> {
+        self.cells.get(name).map(|cell| cell.value.clone())
+    }
+
+    /// Cells the formula of `name` refers to, sorted, empty for inputs.
+    pub fn dependencies(&self, name: &str) -> &[String] {
+        match self.cells.get(name).and_then(|cell| cell.formula.as_ref()) {
+            Some((_, dependencies)) => dependencies,
+            None => &[],
+        }
+    }
+
+    /// Names of the cells, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.cells.keys().map(String::as_str).collect()
+    }
+
+    /// Cells whose formula refers to `name`.
+    fn dependents(&self, name: &str) -> Vec<String> {
+        self.cells
+            .iter()
+            .filter(|(_, cell)| {
+                cell.formula
+                    .as_ref()
+                    .is_some_and(|(_, dependencies)| dependencies.iter().any(|d| d == name))
+            })
+            .map(|(dependent, _)| dependent.clone())
+            .collect()
+    }
+
+    /// Cells leading from `from` to `to` through the dependencies, both included.
+    fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        if from == to {
+            return Some(vec![to.to_owned()]);
+        }
+        self.dependencies(from).iter().find_map(|dependency| {
+            let mut path = self.path(dependency, to)?;
+            path.insert(0, from.to_owned());
+            Some(path)
+        })
+    }
+
+    /// Evaluates the formula of `name`, if any, then those depending on it directly or
+    /// not, each after all of its own dependencies.
+    fn recompute(&mut self, name: &str) -> Vec<String> {
+        let mut order = vec![];
+        self.visit(name, &mut HashSet::new(), &mut order);
+        order.reverse();
+        let mut evaluated = vec![];
+        for name in order {
+            let Some(cell) = self.cells.get(&name) else {
+                continue;
+            };
+            let Some((formula, _)) = &cell.formula else {
+                continue;
+            };
+            let value = self.interpreter.eval(formula);
+            let bound = match &value {
+                Ok(obj) => obj.clone(),
+                Err(err) => Object::Error {
+                    message: err.to_string(),
+                    data: Box::new(Object::Null),
+                },
+            };
+            self.interpreter.set(name.as_str(), bound);
+            if let Some(cell) = self.cells.get_mut(&name) {
+                cell.value = value;
+            }
+            evaluated.push(name);
+        }
+        evaluated
+    }
+
+    /// Pushes the dependents of `name` in post-order, then `name`.
+    fn visit(&self, name: &str, visited: &mut HashSet<String>, order: &mut Vec<String>) {
+        if !visited.insert(name.to_owned()) {
+            return;
+        }
+        for dependent in self.dependents(name) {
+            self.visit(&dependent, visited, order);
+        }
+        order.push(name.to_owned());
+    }
+}
+
+fn check_name(name: &str) -> Result<()> {
+    let tokens = Lexer::init(name).get_all_tokens();
+    if !matches!(tokens.as_slice(), [Token::Ident(ident), Token::Eof] if ident == name) {
+        fail!(
+            ErrorCode::InvalidInput,
+            "`{name}` is not a valid identifier"
+        );
+    }
+    if is_builtin(name) {
+        fail!(ErrorCode::InvalidInput, "`{name}` is the name of a builtin");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recomputation() {
+        let mut sheet = Sheet::default();
+        sheet.set("price", Object::Int(12)).unwrap();
+        sheet.set("qty", Object::Int(3)).unwrap();
+        sheet.set("rate", Object::Int(10)).unwrap();
+        assert_eq!(sheet.define("total", "price * qty").unwrap(), vec!["total"]);
+        assert_eq!(
+            sheet.define("tax", "total * rate / 100").unwrap(),
+            vec!["tax"]
+        );
+        assert_eq!(
+            sheet.define("label", "\"x\" + rest(\"_\")").unwrap(),
+            vec!["label"]
+        );
+        assert_eq!(sheet.dependencies("tax"), ["rate", "total"]);
+        assert!(sheet.dependencies("label").is_empty());
+        assert_eq!(sheet.get("tax").unwrap().unwrap(), Object::Int(3));
+
+        assert_eq!(
+            sheet.set("qty", Object::Int(10)).unwrap(),
+            vec!["total", "tax"]
+        );
+        assert_eq!(sheet.get("total").unwrap().unwrap(), Object::Int(120));
+        assert_eq!(sheet.get("tax").unwrap().unwrap(), Object::Int(12));
+        assert_eq!(sheet.set("rate", Object::Int(20)).unwrap(), vec!["tax"]);
+
+        // A formula can refer to cells defined later, and fails until then.
+        sheet.define("net", "total - discount").unwrap();
+        let err = sheet.get("net").unwrap().unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnknownIdentifier);
+        assert_eq!(
+            sheet.define("discount", "20").unwrap(),
+            vec!["discount", "net"]
+        );
+        assert_eq!(sheet.get("net").unwrap().unwrap(), Object::Int(100));
+
+        // Errors propagate to the dependents, which recover with their dependencies.
+        sheet.define("total", "price * len(qty)").unwrap();
+        assert!(sheet.get("net").unwrap().is_err());
+        sheet.define("total", "price * qty").unwrap();
+        assert_eq!(sheet.get("net").unwrap().unwrap(), Object::Int(100));
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        let mut sheet = Sheet::default();
+        sheet.define("a", "b + 1").unwrap();
+        sheet.define("b", "c + 1").unwrap();
+        let err = sheet.define("c", "a * 2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot define `c`, it would depend on itself: c -> a -> b -> c"
+        );
+        assert!(sheet.define("d", "d").is_err());
+        assert!(sheet.define("e", "let x = 1").is_err());
+        assert_eq!(sheet.names(), vec!["a", "b"]);
+        for name in ["len", "1x", "if", "a b"] {
+            let err = sheet.set(name, Object::Int(1)).unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidInput, "{name}");
+        }
+        // Replacing a formula by an input removes its dependencies.
+        sheet.set("b", Object::Int(1)).unwrap();
+        assert!(sheet.dependencies("b").is_empty());
+        assert_eq!(sheet.get("a").unwrap().unwrap(), Object::Int(2));
+        sheet.define("c", "a * 2").unwrap();
+        assert_eq!(sheet.get("c").unwrap().unwrap(), Object::Int(4));
+    }
+}
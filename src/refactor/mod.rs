@@ -0,0 +1,163 @@
+use crate::analysis::SymbolTable;
+use crate::cst::{self, SyntaxNode};
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::is_builtin;
+use crate::{Lexer, Token};
+use std::ops::Range;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub new_text: String,
+}
+
+/// Applies non-overlapping edits to `source`.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|e| e.range.start);
+
+    let mut output = String::new();
+    let mut last = 0;
+    for edit in edits {
+        output.push_str(&source[last..edit.range.start]);
+        output.push_str(&edit.new_text);
+        last = edit.range.end;
+    }
+    output.push_str(&source[last..]);
+    output
+}
+
+/// Renames the binding the identifier at `span_of_ident` refers to, along with all its uses,
+/// keyword arguments naming it included. Fails if `new_name` is not a valid identifier,
+/// if renaming would change what any identifier in the program refers to, or if keyword
+/// arguments whose parameter isn't known statically have the name of the binding.
+pub fn rename(
+    program: &SyntaxNode,
+    span_of_ident: Range<usize>,
+    new_name: &str,
+) -> Result<Vec<TextEdit>> {
+    let tokens = Lexer::init(new_name).get_all_tokens();
+    if !matches!(tokens.as_slice(), [Token::Ident(name), Token::Eof] if name == new_name) {
+        fail!(
+            ErrorCode::InvalidRename,
+            "`{new_name}` is not a valid identifier"
+        );
+    }
+    if is_builtin(new_name) {
+        fail!(
+            ErrorCode::InvalidRename,
+            "`{new_name}` is the name of a builtin"
+        );
+    }
+
+    let table = SymbolTable::build(program);
+    let Some(symbol) = table.symbol_at(span_of_ident.start) else {
+        fail!(
+            ErrorCode::InvalidRename,
+            "No binding found at {span_of_ident:?}"
+        );
+    };
+
+    if let Some((_, span)) = table
+        .keyword_args
+        .iter()
+        .find(|(name, _)| *name == symbol.name)
+    {
+        fail!(
+            ErrorCode::InvalidRename,
+            "Cannot rename `{}`: the keyword argument at {span:?} may refer to it",
+            symbol.name
+        );
+    }
+
+    let edits: Vec<TextEdit> = symbol
+        .occurrences()
+        .into_iter()
+        .map(|range| TextEdit {
+            range,
+            new_text: new_name.to_owned(),
+        })
+        .collect();
+
+    // Renaming must not capture or be captured by other bindings: re-resolve the edited
+    // source and check the renamed symbol still has exactly the same occurrences.
+    let renamed = cst::parse(&apply_edits(&program.to_string(), &edits));
+    let new_table = SymbolTable::build(&renamed);
+    let delta = new_name.len() as isize - symbol.name.len() as isize;
+    let expected: Vec<Range<usize>> = edits
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let start = (e.range.start as isize + delta * i as isize) as usize;
+            start..start + new_name.len()
+        })
+        .collect();
+    let unchanged = new_table
+        .symbol_at(expected[0].start)
+        .is_some_and(|s| s.occurrences() == expected)
+        && new_table.symbols.len() == table.symbols.len()
+        && new_table.unresolved.len() == table.unresolved.len();
+    if !unchanged {
+        fail!(
+            ErrorCode::InvalidRename,
+            "Renaming `{}` to `{new_name}` would clash with another binding",
+            symbol.name
+        );
+    }
+
+    Ok(edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_at(input: &str, at: usize, new_name: &str) -> Result<String> {
+        let edits = rename(&cst::parse(input), at..at + 1, new_name)?;
+        Ok(apply_edits(input, &edits))
+    }
+
+    #[test]
+    fn renames_binding_and_uses() {
+        let input = "let x = 1; let f = fn(x) { x * 2 }; f(x) + x";
+        assert_eq!(
+            rename_at(input, 4, "count").unwrap(),
+            "let count = 1; let f = fn(x) { x * 2 }; f(count) + count"
+        );
+        assert_eq!(
+            rename_at(input, 22, "n").unwrap(),
+            "let x = 1; let f = fn(n) { n * 2 }; f(x) + x"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_renames() {
+        let input = "let x = 1; let y = fn(a) { a + x }; y(2)";
+        assert!(rename_at(input, 4, "let").is_err());
+        assert!(rename_at(input, 4, "two words").is_err());
+        assert!(rename_at(input, 4, "len").is_err());
+        assert!(rename_at(input, 4, "y").is_err());
+        // `x` would be captured by the parameter `a` inside the function.
+        assert!(rename_at(input, 4, "a").is_err());
+        assert!(rename_at(input, 8, "z").is_err());
+    }
+
+    #[test]
+    fn renames_keyword_arguments() {
+        let input = "let f = fn(a, b) { a - b }; f(b: 1, a: 2) + f(a: 3, b: 4)";
+        assert_eq!(
+            rename_at(input, 11, "x").unwrap(),
+            "let f = fn(x, b) { x - b }; f(b: 1, x: 2) + f(x: 3, b: 4)"
+        );
+        let input = "let f = fn(a) { a }; let g = fn(h) { h(a: 1) }; let a = 2; g(f) + a";
+        let err = rename_at(input, 11, "x").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidRename);
+        assert!(rename_at(input, 52, "x").is_err());
+        // Keyword arguments of other functions don't get in the way.
+        let input = "let f = fn(a) { a }; let g = fn(a) { a }; f(a: 1) + g(a: 2)";
+        assert_eq!(
+            rename_at(input, 11, "x").unwrap(),
+            "let f = fn(x) { x }; let g = fn(a) { a }; f(x: 1) + g(a: 2)"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Reading the input of the REPL, with line editing: the arrow keys move through the
+//! line and the history, which is kept across sessions in `$WAIIR_HISTORY`, by default
+//! `~/.waiir_history`.
+
+use std::path::PathBuf;
+
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+
+use crate::lexer::is_incomplete;
+
+pub struct LineEditor {
+    editor: DefaultEditor,
+    history: Option<PathBuf>,
+}
+
+impl LineEditor {
+    /// An editor with the history of the previous sessions, if any.
+    pub fn new() -> Result<Self, ReadlineError> {
+        let mut editor = DefaultEditor::new()?;
+        let history = history_path();
+        if let Some(path) = &history
+            && path.exists()
+        {
+            editor.load_history(path)?;
+        }
+        Ok(LineEditor { editor, history })
+    }
+
+    /// The next input, each line ending with a newline, reading more lines while
+    /// brackets, strings or comments are open. Ctrl-C drops the input and starts over,
+    /// `None` on Ctrl-D or at the end of the input.
+    pub fn read(&mut self) -> Result<Option<String>, ReadlineError> {
+        let mut input = String::new();
+        loop {
+            let prompt = if input.is_empty() { ">> " } else { "... " };
+            match self.editor.readline(prompt) {
+                Ok(line) => {
+                    input.push_str(&line);
+                    input.push('\n');
+                    if !is_incomplete(&input) {
+                        break;
+                    }
+                }
+                Err(ReadlineError::Interrupted) => input.clear(),
+                // What was read so far is still evaluated, like at the end of a script.
+                Err(ReadlineError::Eof) if !input.is_empty() => break,
+                Err(ReadlineError::Eof) => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+        if !input.trim().is_empty() {
+            self.editor.add_history_entry(input.trim_end())?;
+            if let Some(path) = &self.history {
+                self.editor.save_history(path)?;
+            }
+        }
+        Ok(Some(input))
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("WAIIR_HISTORY") {
+        return Some(path.into());
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".waiir_history"))
+}
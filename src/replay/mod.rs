@@ -0,0 +1,216 @@
+//! Recording and replaying REPL sessions.
+//! Sessions are evaluated with the builtins reading the outside world (stdin, files,
+//! arguments, the host, workers, ...) denied, leaving the interpreter without sources of
+//! nondeterminism, so the inputs alone reproduce a session; outputs are stored to detect
+//! divergences.
+
+use crate::ast::printer::statement_to_source;
+use crate::error::{Error, ErrorCode, Result, fail};
+use crate::eval::{Capability, Environment, Object, Policy, PrettyOptions, eval_with_env};
+use crate::{Expression, Statement};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Entry {
+    pub input: String,
+    pub output: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Session {
+    pub entries: Vec<Entry>,
+}
+
+impl Session {
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|err| Error::new(ErrorCode::InvalidInput, format!("Invalid session: {err}")))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// Evaluates inputs in a persistent environment, keeping track of the session.
+pub struct Recorder {
+    env: Rc<Environment>,
+    session: Session,
+    /// Whether inputs are compiled and run on the VM rather than evaluated.
+    vm: bool,
+}
+
+impl Default for Recorder {
+    /// Only evaluating code is allowed: the builtins needing any other capability fail
+    /// with [`ErrorCode::SandboxViolation`], both when recording and when replaying.
+    fn default() -> Self {
+        Self::with_policy(Policy::locked_down().allow(Capability::Eval))
+    }
+}
+
+impl Recorder {
+    /// Evaluates under `policy` instead, e.g. for a REPL that doesn't record: a session
+    /// using other capabilities than evaluating code may not replay.
+    pub fn with_policy(policy: Policy) -> Self {
+        Self {
+            env: Rc::new(Environment::default().with_policy(policy)),
+            session: Session::default(),
+            vm: false,
+        }
+    }
+
+    /// Runs the inputs on the VM. The sessions recorded replay the same on both engines.
+    pub fn with_vm(self) -> Self {
+        Self { vm: true, ..self }
+    }
+
+    /// Evaluates `input` and returns what the REPL prints for it.
+    pub fn eval(&mut self, input: &str) -> String {
+        let result = if self.vm {
+            crate::vm::run(input, Rc::clone(&self.env))
+        } else {
+            eval_with_env(input, Rc::clone(&self.env))
+        };
+        let output = match result {
+            Ok(obj) => obj.pretty(&PrettyOptions::default()),
+            Err(err) => err.to_string(),
+        };
+        self.session.entries.push(Entry {
+            input: input.to_owned(),
+            output: output.clone(),
+        });
+        output
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Pretty-printed `let` statement rebinding the function bound to `name` when
+    /// evaluated, for the REPL's `:edit`. Type annotations aren't kept by functions,
+    /// and the rebound function closes over the top-level bindings.
+    pub fn function_source(&self, name: &str) -> Result<String> {
+        let Some(Object::Function {
+            parameters, body, ..
+        }) = self.env.get(name)
+        else {
+            fail!(ErrorCode::InvalidInput, "`{name}` is not a function");
+        };
+        let statement = Statement::Let {
+            name: name.to_owned(),
+            mutable: false,
+            annotation: None,
+            value: Expression::Func {
+                args: parameters,
+                arg_types: vec![],
+                return_type: None,
+                body,
+            },
+        };
+        Ok(statement_to_source(&statement) + "\n")
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Divergence {
+    pub index: usize,
+    pub input: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Entry {} ({:?}) printed {:?}, recorded {:?}",
+            self.index,
+            self.input.trim_end(),
+            self.actual,
+            self.expected
+        )
+    }
+}
+
+/// Re-runs the session, stopping at the first entry whose output differs from the recording.
+pub fn replay(session: &Session) -> Result<Session, Divergence> {
+    let mut recorder = Recorder::default();
+    for (index, entry) in session.entries.iter().enumerate() {
+        let actual = recorder.eval(&entry.input);
+        if actual != entry.output {
+            return Err(Divergence {
+                index,
+                input: entry.input.clone(),
+                expected: entry.output.clone(),
+                actual,
+            });
+        }
+    }
+    Ok(recorder.session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_replay() {
+        let mut recorder = Recorder::default();
+        assert_eq!(recorder.eval("let x = 2;\n"), "2");
+        assert_eq!(
+            recorder.eval("x * \"a\"\n"),
+            "Invalid operation (`*`) between 2 and a!"
+        );
+        assert_eq!(recorder.eval("x * 3\n"), "6");
+
+        let session = Session::from_json(&recorder.session().to_json()).unwrap();
+        assert_eq!(&session, recorder.session());
+        assert_eq!(replay(&session), Ok(session.clone()));
+
+        let mut recorder = Recorder::default().with_vm();
+        for entry in &session.entries {
+            assert_eq!(recorder.eval(&entry.input), entry.output);
+        }
+
+        let mut tampered = session.clone();
+        tampered.entries[2].output = "7".to_owned();
+        assert_eq!(
+            replay(&tampered).unwrap_err().to_string(),
+            "Entry 2 (\"x * 3\") printed \"6\", recorded \"7\""
+        );
+    }
+
+    #[test]
+    fn nondeterministic_builtins() {
+        let mut recorder = Recorder::default();
+        for input in [
+            "args()",
+            "csv_read(\"data.csv\")",
+            "collect(map([1], fn(x) { args() }))",
+        ] {
+            let output = recorder.eval(input);
+            assert!(output.contains("denied by the sandbox policy"), "{output}");
+        }
+        assert_eq!(recorder.eval("len(\"abc\")"), "3");
+        assert_eq!(replay(recorder.session()), Ok(recorder.session().clone()));
+    }
+
+    #[test]
+    fn function_sources() {
+        let mut recorder = Recorder::default();
+        recorder.eval("let add = fn(x: int, y) {\n x + y };\n");
+        let source = recorder.function_source("add").unwrap();
+        assert_eq!(source, "let add = fn(x, y) {\n    x + y;\n};\n");
+
+        recorder.eval(&source.replace('+', "*"));
+        assert_eq!(recorder.eval("let x = add(2, 3);"), "6");
+        for name in ["x", "len", "missing"] {
+            assert_eq!(
+                recorder.function_source(name).unwrap_err().code,
+                ErrorCode::InvalidInput
+            );
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::eval::{Environment, Object, eval_with_env};
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Exit status of a script that can't be read or fails.
+pub const FAILURE: i32 = 1;
+
+/// Exit status of a script stopped by Ctrl-C, following the shell convention of
+/// 128 plus the signal number.
+pub const INTERRUPTED: i32 = 130;
+
+/// Evaluates `source` in a fresh environment, returning its value and the exit status
+/// given by [`Object::exit_code`]. On failure the value is the error message.
+pub fn run_source(source: &str) -> (Object, i32) {
+    match eval_with_env(source, Rc::new(Environment::default())) {
+        Ok(obj) => {
+            let code = obj.exit_code();
+            (obj, code)
+        }
+        Err(err) => (Object::String(err.to_string()), FAILURE),
+    }
+}
+
+/// Like [`run_source`], reading the script from `path`.
+pub fn run_script(path: impl AsRef<Path>) -> (Object, i32) {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(source) => run_source(&source),
+        Err(err) => (
+            Object::String(format!("Cannot read {}: {err}", path.display())),
+            FAILURE,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let code = |source: &str| run_source(source).1;
+        assert_eq!(code("3"), 3);
+        assert_eq!(code("let f = fn() { return -2; }; f()"), -2);
+        assert_eq!(code("2000000000 * 2"), FAILURE);
+        assert_eq!(code("true"), 0);
+        assert_eq!(code("1 < 0"), 1);
+        assert_eq!(code("\"done\""), 0);
+        assert_eq!(code("let x = 1;"), 1);
+        assert_eq!(
+            run_source("1 + true"),
+            (
+                Object::String(String::from("Invalid operation (`+`) between 1 and true!")),
+                FAILURE
+            )
+        );
+    }
+
+    #[test]
+    fn scripts() {
+        let path = std::env::temp_dir().join(format!("waiir-script-{}.monkey", std::process::id()));
+        fs::write(&path, "let answer = 42; answer").unwrap();
+        assert_eq!(run_script(&path), (Object::Int(42), 42));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(run_script(&path).1, FAILURE);
+    }
+}
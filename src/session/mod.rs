@@ -0,0 +1,214 @@
+//! Named, isolated interpreters, e.g. one per user of a playground service.
+
+use crate::error::{ErrorCode, Result, fail};
+use crate::eval::{Budget, Environment, Interrupt, Object, eval_with_stats};
+use crate::prelude::HashMap;
+use crate::prelude::*;
+use alloc::rc::Rc;
+
+/// Resources a session may use; `None` means unlimited.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Statements run by a single evaluation, those of function bodies and loops
+    /// included, counted like [`ExecutionStats::steps`](crate::eval::ExecutionStats::steps).
+    pub max_statements: Option<usize>,
+    /// Length in bytes of the source of a single evaluation.
+    pub max_source_len: Option<usize>,
+    /// Top-level bindings of the session.
+    pub max_bindings: Option<usize>,
+}
+
+/// Top-level bindings of a session at some point.
+/// Functions and iterators are shared with the session, not copied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub bindings: Vec<(String, Object)>,
+}
+
+struct Session {
+    env: Rc<Environment>,
+    limits: Limits,
+}
+
+#[derive(Default)]
+pub struct SessionPool {
+    sessions: HashMap<String, Session>,
+    /// Limits of the sessions created without explicit ones.
+    pub default_limits: Limits,
+    /// Number of sessions the pool can hold at once.
+    pub max_sessions: Option<usize>,
+}
+
+impl SessionPool {
+    pub fn create(&mut self, name: &str) -> Result<()> {
+        self.create_with_limits(name, self.default_limits)
+    }
+
+    pub fn create_with_limits(&mut self, name: &str, limits: Limits) -> Result<()> {
+        if self.sessions.contains_key(name) {
+            fail!(ErrorCode::InvalidInput, "Session `{name}` already exists");
+        }
+        if self
+            .max_sessions
+            .is_some_and(|max| self.sessions.len() >= max)
+        {
+            fail!(
+                ErrorCode::LimitExceeded,
+                "Cannot create session `{name}`, the pool is full"
+            );
+        }
+        self.sessions.insert(
+            name.to_owned(),
+            Session {
+                env: Rc::new(Environment::default().with_budget(Budget {
+                    max_steps: limits.max_statements.map(|max| max as u64),
+                    ..Budget::default()
+                })),
+                limits,
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes the session, returning whether it existed.
+    pub fn destroy(&mut self, name: &str) -> bool {
+        self.sessions.remove(name).is_some()
+    }
+
+    /// Names of the sessions, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.sessions.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Evaluates `input` in the session. Bindings that would exceed the limits are
+    /// rolled back, but the other effects of the evaluation are kept.
+    pub fn eval(&self, name: &str, input: &str) -> Result<Object> {
+        let session = self.session(name)?;
+        let limits = session.limits;
+        if limits.max_source_len.is_some_and(|max| input.len() > max) {
+            fail!(
+                ErrorCode::LimitExceeded,
+                "The source is longer than the {} bytes allowed in session `{name}`",
+                limits.max_source_len.unwrap_or_default()
+            );
+        }
+
+        let before = session.env.bindings();
+        let (result, _) = eval_with_stats(input, Rc::clone(&session.env));
+        let obj = result?;
+
+        if let Some(max) = limits.max_bindings
+            && session.env.bindings().len() > max
+        {
+            restore(&session.env, &before);
+            fail!(
+                ErrorCode::LimitExceeded,
+                "Session `{name}` can't hold more than {max} bindings"
+            );
+        }
+        Ok(obj)
+    }
+
+    pub fn snapshot(&self, name: &str) -> Result<Snapshot> {
+        Ok(Snapshot {
+            bindings: self.session(name)?.env.bindings(),
+        })
+    }
+
+    /// Replaces the bindings of the session with the ones of `snapshot`.
+    pub fn restore(&self, name: &str, snapshot: &Snapshot) -> Result<()> {
+        restore(&self.session(name)?.env, &snapshot.bindings);
+        Ok(())
+    }
+
+    /// Stops the evaluation running in the session, e.g. from another thread.
+    pub fn interrupt(&self, name: &str) -> Result<Interrupt> {
+        Ok(self.session(name)?.env.interrupt().clone())
+    }
+
+    fn session(&self, name: &str) -> Result<&Session> {
+        match self.sessions.get(name) {
+            Some(session) => Ok(session),
+            None => fail!(ErrorCode::InvalidInput, "Unknown session `{name}`"),
+        }
+    }
+}
+
+fn restore(env: &Environment, bindings: &[(String, Object)]) {
+    env.clear();
+    for (name, obj) in bindings {
+        env.set(name.as_str(), obj.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isolation_and_snapshots() {
+        let mut pool = SessionPool::default();
+        pool.create("alice").unwrap();
+        pool.create("bob").unwrap();
+        assert_eq!(
+            pool.create("bob").unwrap_err().code,
+            ErrorCode::InvalidInput
+        );
+
+        pool.eval("alice", "let x = 1;").unwrap();
+        pool.eval("bob", "let x = 2;").unwrap();
+        assert_eq!(pool.eval("alice", "x").unwrap(), Object::Int(1));
+
+        let snapshot = pool.snapshot("alice").unwrap();
+        pool.eval("alice", "let x = 10; let y = 5;").unwrap();
+        pool.restore("alice", &snapshot).unwrap();
+        assert_eq!(pool.eval("alice", "x").unwrap(), Object::Int(1));
+        assert!(pool.eval("alice", "y").is_err());
+
+        assert!(pool.destroy("bob"));
+        assert!(!pool.destroy("bob"));
+        assert_eq!(pool.names(), vec!["alice"]);
+        assert!(pool.eval("bob", "x").is_err());
+    }
+
+    #[test]
+    fn limits() {
+        let mut pool = SessionPool {
+            max_sessions: Some(1),
+            default_limits: Limits {
+                max_statements: Some(20),
+                max_source_len: Some(100),
+                max_bindings: Some(2),
+            },
+            ..Default::default()
+        };
+        pool.create("a").unwrap();
+        let err = pool.create("b").unwrap_err();
+        assert_eq!(err.code, ErrorCode::LimitExceeded);
+
+        assert_eq!(
+            pool.eval("a", "for (x in range(5)) { x }; 1").unwrap(),
+            Object::Int(1)
+        );
+        let err = pool.eval("a", "for (x in range(100)) { x }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::LimitExceeded);
+
+        let input = "fn() { let mut i = 0; while (true) { i = i + 1; } }()";
+        let err = pool.eval("a", input).unwrap_err();
+        assert_eq!(err.code, ErrorCode::LimitExceeded);
+
+        let err = pool.eval("a", &"1 + ".repeat(30)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::LimitExceeded);
+
+        pool.eval("a", "let y = 2;").unwrap();
+        let err = pool.eval("a", "let z = 3;").unwrap_err();
+        assert_eq!(err.code, ErrorCode::LimitExceeded);
+        assert_eq!(pool.eval("a", "y").unwrap(), Object::Int(2));
+        assert_eq!(
+            pool.eval("a", "z").unwrap_err().code,
+            ErrorCode::UnknownIdentifier
+        );
+    }
+}
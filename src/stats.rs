@@ -0,0 +1,135 @@
+//! Counts of the work done by each stage of the pipeline, to spot regressions in the
+//! lexer, parser or evaluator without a profiler.
+
+use crate::error::Result;
+use crate::eval::{Environment, Eval, ExecutionStats, Object, measure};
+use crate::{Expression, Lexer, Parser, Program, Statement, Token};
+use alloc::rc::Rc;
+use core::mem::size_of;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// Tokens produced by the lexer, `Eof` excluded.
+    pub tokens: usize,
+    /// Statements and expressions of the parsed program.
+    pub ast_nodes: usize,
+    /// Rough estimate of the memory allocated for the AST and the objects created.
+    pub bytes_allocated: usize,
+    /// Statements evaluated, see [`ExecutionStats::steps`].
+    pub statements_evaluated: u64,
+    pub execution: ExecutionStats,
+}
+
+/// Lexes, parses and evaluates `input` like [`eval_with_env`](crate::eval::eval_with_env),
+/// counting the work of each stage. Stages after a failing one report zeros.
+pub fn eval_instrumented(input: &str, env: Rc<Environment>) -> (Result<Object>, PipelineStats) {
+    let mut lexer = Lexer::init(input);
+    let mut stats = PipelineStats {
+        tokens: core::iter::from_fn(|| Some(lexer.next_token()))
+            .take_while(|token| *token != Token::Eof)
+            .count(),
+        ..Default::default()
+    };
+
+    let program = match Parser::init(input).parse_program() {
+        Ok(program) => program,
+        Err(err) => return (Err(err), stats),
+    };
+    stats.ast_nodes = count_nodes(&program);
+
+    let (result, execution) = measure(&env, || program.eval(Rc::clone(&env)));
+    stats.statements_evaluated = execution.steps;
+    stats.bytes_allocated = stats.ast_nodes * size_of::<Expression>()
+        + execution.objects_allocated as usize * size_of::<Object>();
+    stats.execution = execution;
+    (result, stats)
+}
+
+fn count_nodes(program: &Program) -> usize {
+    program.statements.iter().map(statement_nodes).sum()
+}
+
+fn statement_nodes(statement: &Statement) -> usize {
+    1 + match statement {
+        Statement::Let { value, .. }
+        | Statement::Assign { value, .. }
+        | Statement::Destructure { value, .. }
+        | Statement::Return { value }
+        | Statement::Yield { value }
+        | Statement::Expr(value) => expression_nodes(value),
+        Statement::Block(stmts) => stmts.iter().map(statement_nodes).sum(),
+        Statement::Struct { .. } | Statement::Break | Statement::Continue => 0,
+    }
+}
+
+fn expression_nodes(expr: &Expression) -> usize {
+    let block = |stmts: &[Statement]| stmts.iter().map(statement_nodes).sum::<usize>();
+    1 + match expr {
+        Expression::Bool(_)
+        | Expression::Int(_)
+        | Expression::Float(_)
+        | Expression::Ident(_)
+        | Expression::String(_) => 0,
+        Expression::Prefix { right, .. } | Expression::Field { object: right, .. } => {
+            expression_nodes(right)
+        }
+        Expression::Infix { left, right, .. } => expression_nodes(left) + expression_nodes(right),
+        Expression::Func { body, .. } => block(body),
+        Expression::Call {
+            func, args, kwargs, ..
+        } => {
+            expression_nodes(func)
+                + args.iter().map(expression_nodes).sum::<usize>()
+                + kwargs
+                    .iter()
+                    .map(|(_, value)| expression_nodes(value))
+                    .sum::<usize>()
+        }
+        Expression::For { iterable, body, .. } => expression_nodes(iterable) + block(body),
+        Expression::While { cond, body } => expression_nodes(cond) + block(body),
+        Expression::Cond { cond, then_, else_ } => {
+            expression_nodes(cond) + block(then_) + else_.as_deref().map_or(0, block)
+        }
+        Expression::Match { subject, arms } => {
+            expression_nodes(subject)
+                + arms
+                    .iter()
+                    .map(|arm| {
+                        arm.guard.as_ref().map_or(0, expression_nodes)
+                            + expression_nodes(&arm.value)
+                    })
+                    .sum::<usize>()
+        }
+        Expression::Array(content) | Expression::Tuple(content) => {
+            content.iter().map(expression_nodes).sum()
+        }
+        Expression::Hash { pairs, .. } => pairs
+            .iter()
+            .map(|(k, v)| expression_nodes(k) + expression_nodes(v))
+            .sum(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+
+    #[test]
+    fn stages() {
+        let input = "let f = fn(x) { x * 2 }; f(3)";
+        let (result, stats) = eval_instrumented(input, Rc::new(Environment::default()));
+        assert_eq!(result.unwrap(), Object::Int(6));
+        assert_eq!(stats.tokens, 17);
+        assert_eq!(stats.ast_nodes, 10);
+        assert_eq!(stats.statements_evaluated, 4);
+        assert_eq!(
+            stats.bytes_allocated,
+            10 * size_of::<Expression>() + size_of::<Object>()
+        );
+
+        let (result, stats) = eval_instrumented("let = 1;", Rc::new(Environment::default()));
+        assert_eq!(result.unwrap_err().code, ErrorCode::UnexpectedToken);
+        assert_eq!((stats.tokens, stats.ast_nodes), (4, 0));
+    }
+}
@@ -0,0 +1,332 @@
+//! Templates interleaving text with Monkey expressions:
+//!
+//! ```text
+//! Hello {{ name }}!
+//! {% if len(items) > 0 %}
+//! {% for item in items %}- {{ item["title"] }}
+//! {% endfor %}
+//! {% else %}
+//! Nothing to do.
+//! {% endif %}
+//! ```
+//!
+//! `{{ expr }}` is replaced by the value of `expr`, nothing for `null`. Tags are
+//! `{% if expr %}`, `{% else %}`, `{% endif %}`, `{% for name in expr %}` and
+//! `{% endfor %}`; the line break right after a tag is dropped, so that tags on their
+//! own line don't leave blank lines.
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::eval::{Environment, Eval, Iter, Object};
+use crate::prelude::*;
+use crate::{Expression, Parser};
+use alloc::rc::Rc;
+use core::ops::Range;
+
+#[derive(PartialEq, Debug, Clone)]
+enum Node {
+    Text(String),
+    Expr(Expression),
+    If {
+        cond: Expression,
+        then_: Vec<Node>,
+        else_: Vec<Node>,
+    },
+    For {
+        name: String,
+        iterable: Expression,
+        body: Vec<Node>,
+    },
+}
+
+/// A part of the template, with its span for `{{ }}` expressions and `{% %}` tags, the
+/// latter being trimmed.
+enum Part<'a> {
+    Text(&'a str),
+    Expr(&'a str, Range<usize>),
+    Tag(&'a str, Range<usize>),
+}
+
+/// Returns early with an [`ErrorCode::UnexpectedToken`] error at `span` of the template.
+macro_rules! fail_at {
+    ($span:expr, $($arg:tt)*) => {
+        return Err(Error::new(ErrorCode::UnexpectedToken, format!($($arg)*)).with_span($span))
+    };
+}
+
+/// Renders `template`, its expressions being evaluated in a new environment nested in
+/// `env`, so that loop variables don't outlive the rendering.
+pub fn render_template(template: &str, env: Rc<Environment>) -> Result<String> {
+    let mut parts = split(template)?.into_iter();
+    let (nodes, end) = parse(&mut parts)?;
+    if let Some((tag, span)) = end {
+        fail_at!(span, "Unexpected `{{% {tag} %}}` in template");
+    }
+    let mut output = String::new();
+    render(
+        &nodes,
+        &Rc::new(Environment::init_with_outer(env)),
+        &mut output,
+    )?;
+    Ok(output)
+}
+
+fn split(template: &str) -> Result<Vec<Part<'_>>> {
+    let mut parts = vec![];
+    let mut rest = 0;
+    while let Some(found) = template[rest..]
+        .find("{{")
+        .into_iter()
+        .chain(template[rest..].find("{%"))
+        .min()
+    {
+        let open = rest + found;
+        let close = if template[open..].starts_with("{{") {
+            "}}"
+        } else {
+            "%}"
+        };
+        if open > rest {
+            parts.push(Part::Text(&template[rest..open]));
+        }
+        let Some(length) = template[open + 2..].find(close) else {
+            fail_at!(
+                open..template.len(),
+                "Unterminated `{}` in template",
+                &template[open..open + 2]
+            );
+        };
+        let contents = open + 2..open + 2 + length;
+        rest = contents.end + 2;
+        if close == "}}" {
+            parts.push(Part::Expr(&template[contents.clone()], contents));
+        } else {
+            let tag = template[contents.clone()].trim_start();
+            let start = contents.end - tag.len();
+            let tag = tag.trim_end();
+            parts.push(Part::Tag(tag, start..start + tag.len()));
+            if template[rest..].starts_with('\n') {
+                rest += 1;
+            } else if template[rest..].starts_with("\r\n") {
+                rest += 2;
+            }
+        }
+    }
+    if rest < template.len() {
+        parts.push(Part::Text(&template[rest..]));
+    }
+    Ok(parts)
+}
+
+/// Nodes of a block and the tag it ends with, along with its span.
+type Block<'a> = (Vec<Node>, Option<(&'a str, Range<usize>)>);
+
+/// Parses nodes up to the end of the template or to a tag closing a block.
+fn parse<'a>(parts: &mut impl Iterator<Item = Part<'a>>) -> Result<Block<'a>> {
+    let mut nodes = vec![];
+    while let Some(part) = parts.next() {
+        match part {
+            Part::Text(text) => nodes.push(Node::Text(text.to_owned())),
+            Part::Expr(source, span) => nodes.push(Node::Expr(expression(source, span)?)),
+            Part::Tag(tag, span) => {
+                let (keyword, rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+                // The expressions of tags end with them.
+                let suffix = |source: &str| span.end - source.len()..span.end;
+                match keyword {
+                    "if" => {
+                        let cond = expression(rest, suffix(rest))?;
+                        let (then_, end) = parse(parts)?;
+                        let else_ = match end {
+                            Some(("else", _)) => match parse(parts)? {
+                                (else_, Some(("endif", _))) => else_,
+                                (_, end) => unclosed(end, span, tag, "endif")?,
+                            },
+                            Some(("endif", _)) => vec![],
+                            end => unclosed(end, span, tag, "endif")?,
+                        };
+                        nodes.push(Node::If { cond, then_, else_ });
+                    }
+                    "for" => {
+                        let (name, iterable) = match rest.split_once(" in ") {
+                            Some((name, iterable)) if is_identifier(name.trim()) => (
+                                name.trim().to_owned(),
+                                expression(iterable, suffix(iterable))?,
+                            ),
+                            _ => fail_at!(
+                                span,
+                                "Invalid tag `{{% {tag} %}}`, expected `{{% for name in expr %}}`"
+                            ),
+                        };
+                        let body = match parse(parts)? {
+                            (body, Some(("endfor", _))) => body,
+                            (_, end) => unclosed(end, span, tag, "endfor")?,
+                        };
+                        nodes.push(Node::For {
+                            name,
+                            iterable,
+                            body,
+                        });
+                    }
+                    "else" | "endif" | "endfor" if rest.is_empty() => {
+                        return Ok((nodes, Some((keyword, span))));
+                    }
+                    _ => fail_at!(span, "Unknown tag `{{% {tag} %}}` in template"),
+                }
+            }
+        }
+    }
+    Ok((nodes, None))
+}
+
+fn unclosed<T>(
+    end: Option<(&str, Range<usize>)>,
+    open: Range<usize>,
+    opening: &str,
+    expected: &str,
+) -> Result<T> {
+    match end {
+        Some((tag, span)) => fail_at!(
+            span,
+            "Unexpected `{{% {tag} %}}` in template, expected `{{% {expected} %}}`"
+        ),
+        None => fail_at!(open, "Unclosed `{{% {opening} %}}` in template"),
+    }
+}
+
+/// Parses the expression `source`, found at `span` of the template.
+fn expression(source: &str, span: Range<usize>) -> Result<Expression> {
+    Parser::init(source).parse_expression_only().map_err(|err| {
+        let start = span.start;
+        Error {
+            span: Some(match &err.span {
+                Some(s) => s.start + start..s.end + start,
+                None => span.clone(),
+            }),
+            ..err
+        }
+    })
+}
+
+fn is_identifier(name: &str) -> bool {
+    matches!(crate::lex(name).as_deref(), Ok([crate::Token::Ident(ident)]) if ident == name)
+}
+
+fn render(nodes: &[Node], env: &Rc<Environment>, output: &mut String) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Expr(expr) => match value(expr, env)? {
+                Object::Null => {}
+                obj => output.push_str(&obj.to_string()),
+            },
+            Node::If { cond, then_, else_ } => {
+                let branch = if value(cond, env)?.to_bool() {
+                    then_
+                } else {
+                    else_
+                };
+                render(branch, env, output)?;
+            }
+            Node::For {
+                name,
+                iterable,
+                body,
+            } => {
+                let iter = Iter::from_object(value(iterable, env)?)?;
+                while let Some(item) = iter.next()? {
+                    env.interrupt().check()?;
+                    env.set(name.as_str(), item);
+                    render(body, env, output)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn value(expr: &Expression, env: &Rc<Environment>) -> Result<Object> {
+    Ok(match expr.clone().eval(Rc::clone(env))? {
+        Object::Return(value) => *value,
+        obj => obj,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_with(template: &str, bindings: &str) -> Result<String> {
+        let env = Rc::new(Environment::default());
+        crate::eval::eval_with_env(bindings, Rc::clone(&env))?;
+        render_template(template, env)
+    }
+
+    #[test]
+    fn rendering() {
+        let template = "Hello {{ name }}{{ null }}!\n\
+                        {% if len(items) > 0 %}\n\
+                        {% for item in items %}- {{ item[\"title\"] }} ({{ item[\"n\"] * 2 }})\n\
+                        {% endfor %}\
+                        {% else %}\n\
+                        Nothing to do.\n\
+                        {% endif %}\
+                        {{ {\"a\": [1, true]} }}";
+        let bindings = "let name = \"Ada\"; let items = [{\"title\": \"x\", \"n\": 1}, {\"title\": \"y\", \"n\": 2}];";
+        assert_eq!(
+            render_with(template, bindings).unwrap(),
+            "Hello Ada!\n- x (2)\n- y (4)\n{ a: [1, true] }"
+        );
+        assert_eq!(
+            render_with(template, "let name = \"Bob\"; let items = [];").unwrap(),
+            "Hello Bob!\nNothing to do.\n{ a: [1, true] }"
+        );
+
+        let env = Rc::new(Environment::default());
+        let output = render_template(
+            "{% for i in range(3) %}{{ i }}{% endfor %}",
+            Rc::clone(&env),
+        );
+        assert_eq!(output.unwrap(), "012");
+        assert_eq!(env.get("i"), None);
+    }
+
+    #[test]
+    fn errors() {
+        for (template, message, span) in [
+            ("a {{ 1 +", "Unterminated `{{` in template", 2..8),
+            (
+                "{% if true %}a",
+                "Unclosed `{% if true %}` in template",
+                3..10,
+            ),
+            (
+                "{% if true %}{% endfor %}",
+                "Unexpected `{% endfor %}` in template, expected `{% endif %}`",
+                16..22,
+            ),
+            ("{% endif %}", "Unexpected `{% endif %}` in template", 3..8),
+            (
+                "{% while x %}",
+                "Unknown tag `{% while x %}` in template",
+                3..10,
+            ),
+            (
+                "{% for 1 in x %}{% endfor %}",
+                "Invalid tag `{% for 1 in x %}`, expected `{% for name in expr %}`",
+                3..13,
+            ),
+        ] {
+            let err = render_template(template, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(
+                (err.to_string().as_str(), err.code, err.span),
+                (message, ErrorCode::UnexpectedToken, Some(span))
+            );
+        }
+        let err = render_template("ab {{ let x }}", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.span, Some(6..9));
+        let err = render_template("{% if 1 + %}{% endif %}", Rc::new(Environment::default()))
+            .unwrap_err();
+        assert_eq!(err.span, Some(9..9));
+        let err = render_template("{{ 1 + true }}", Rc::new(Environment::default())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidOperation);
+    }
+}
@@ -0,0 +1,124 @@
+//! Assertions for tests evaluating Monkey code, which compare values with Monkey
+//! literals instead of `Object` trees built by hand:
+//!
+//! ```
+//! waiir::assert_monkey_eq!("let x = 2; {\"a\": [x, x * 2]}", "{\"a\": [2, 4]}");
+//! ```
+
+use crate::eval::{HashMapKey, Object, PrettyOptions};
+use crate::prelude::*;
+
+/// Asserts that `program` evaluates to the value of `expected`, usually a literal, both
+/// being evaluated in new environments. The message of the panic locates the first
+/// difference, e.g. `at [1]["a"]`, and shows both values in full.
+#[macro_export]
+macro_rules! assert_monkey_eq {
+    ($program:expr, $expected:expr $(,)?) => {
+        $crate::testing::assert_monkey_eq($program, $expected)
+    };
+}
+
+/// See [`assert_monkey_eq!`](crate::assert_monkey_eq).
+#[track_caller]
+pub fn assert_monkey_eq(program: &str, expected: &str) {
+    let actual = match crate::eval(program) {
+        Ok(obj) => obj,
+        Err(err) => panic!("`{program}` failed: {err}"),
+    };
+    let expected = match crate::eval(expected) {
+        Ok(obj) => obj,
+        Err(err) => panic!("Expected value `{expected}` failed: {err}"),
+    };
+    if let Some((path, left, right)) = difference(&actual, &expected) {
+        let options = PrettyOptions::default();
+        panic!(
+            "`{program}` evaluated to an unexpected value, at {}: {left} != {right}\n  \
+             actual: {}\nexpected: {}",
+            if path.is_empty() { "the top" } else { &path },
+            actual.pretty(&options),
+            expected.pretty(&options)
+        );
+    }
+}
+
+/// Path to the first difference between `left` and `right`, descending into arrays and
+/// hashes, with the values found there, `<missing>` standing for an absent hash entry.
+pub fn difference(left: &Object, right: &Object) -> Option<(String, String, String)> {
+    match (left, right) {
+        (Object::Array(l), Object::Array(r)) => {
+            for (i, (l, r)) in l.iter().zip(r.iter()).enumerate() {
+                if let Some((path, l, r)) = difference(l, r) {
+                    return Some((format!("[{i}]{path}"), l, r));
+                }
+            }
+            (l.len() != r.len()).then(|| {
+                let len = |content: &[Object]| match content.len() {
+                    1 => "1 item".to_owned(),
+                    n => format!("{n} items"),
+                };
+                (String::new(), len(l), len(r))
+            })
+        }
+        (Object::Hash(l), Object::Hash(r)) => {
+            let mut keys: Vec<&HashMapKey> = l.keys().chain(r.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter().find_map(|key| {
+                let at = match key {
+                    HashMapKey::String(key) => format!("[\"{key}\"]"),
+                    key => format!("[{key}]"),
+                };
+                let (path, l, r) = match (l.get(key), r.get(key)) {
+                    (Some(l), Some(r)) => difference(l, r)?,
+                    (l, r) => {
+                        let show = |obj: Option<&Object>| {
+                            obj.map_or_else(|| "<missing>".to_owned(), Object::to_string)
+                        };
+                        (String::new(), show(l), show(r))
+                    }
+                };
+                Some((at + &path, l, r))
+            })
+        }
+        _ => (left != right).then(|| (String::new(), left.to_string(), right.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_difference(left: &str, right: &str) -> Option<(String, String, String)> {
+        difference(&crate::eval(left).unwrap(), &crate::eval(right).unwrap())
+    }
+
+    #[test]
+    fn differences() {
+        assert_eq!(
+            first_difference("[1, {\"a\": [2]}]", "[1, {\"a\": [2]}]"),
+            None
+        );
+        let found = |path: &str, left: &str, right: &str| {
+            Some((path.to_owned(), left.to_owned(), right.to_owned()))
+        };
+        assert_eq!(
+            first_difference("[1, {\"a\": [2], 3: 0}]", "[1, {\"a\": [4], 3: 0}]"),
+            found("[1][\"a\"][0]", "2", "4")
+        );
+        assert_eq!(
+            first_difference("{true: 1}", "{true: 1, 2: 2}"),
+            found("[2]", "<missing>", "2")
+        );
+        assert_eq!(
+            first_difference("[1, 2]", "[1]"),
+            found("", "2 items", "1 item")
+        );
+        assert_eq!(first_difference("1", "1.0"), found("", "1", "1.0"));
+    }
+
+    #[test]
+    #[should_panic(expected = "at [1]: 3 != 2")]
+    fn failed_assertion() {
+        crate::assert_monkey_eq!("[1, 1 + 2]", "[1, 2]");
+    }
+}
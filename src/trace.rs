@@ -0,0 +1,21 @@
+//! Instrumentation hooks forwarding to `tracing` when the `tracing` feature is enabled
+//! and compiling to nothing otherwise.
+
+/// Emits a `TRACE` event.
+macro_rules! event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!($($arg)*);
+    };
+}
+
+/// Enters a `TRACE` span until the end of the enclosing block.
+macro_rules! span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::trace_span!($($arg)*).entered();
+    };
+}
+
+pub(crate) use event;
+pub(crate) use span;
@@ -0,0 +1,867 @@
+use crate::ast::printer::expression_to_source;
+use crate::ast::{MatchArm, Pattern};
+use crate::eval::{BUILTINS, is_builtin};
+use crate::{Expression, InfixOperator, PrefixOperator, Program, Statement};
+use std::mem;
+
+const PRELUDE: &str = include_str!("prelude.js");
+
+const JS_RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "finally",
+    "for",
+    "function",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "new",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Translates a program to a standalone JavaScript script.
+/// The script defines `$main()`, returning the value of the program, and stores its
+/// result in `$result`; `$rt.inspect($result)` formats it like the interpreter does.
+pub fn to_js(program: &Program) -> String {
+    format!("{}const $result = $main();\n", script(program))
+}
+
+fn script(program: &Program) -> String {
+    let mut transpiler = Transpiler {
+        buffers: vec![vec![]],
+        depth: 1,
+        temps: 0,
+        suspendable: false,
+    };
+    transpiler.block(&program.statements, &Completion::Return);
+    let body = transpiler.buffers.pop().unwrap_or_default();
+    let docs: Vec<String> = BUILTINS
+        .iter()
+        .map(|builtin| {
+            let help = format!("{}\n{}", builtin.usage, builtin.help);
+            format!("  {}: {},\n", builtin.name, string_literal(&help))
+        })
+        .collect();
+    format!(
+        "{PRELUDE}\nObject.assign($rt.docs, {{\n{}}});\n\nfunction $main() {{\n{}\n}}\n",
+        docs.concat(),
+        body.join("\n")
+    )
+}
+
+/// What to do with the value of the last statement of a block.
+enum Completion {
+    Return,
+    Assign(String),
+    Discard,
+}
+
+struct Transpiler {
+    /// One buffer of lines per function being translated.
+    buffers: Vec<Vec<String>>,
+    depth: usize,
+    temps: usize,
+    /// Whether a `yield` statement can suspend the function being translated: it must be
+    /// a generator, and the statement must not be part of an expression.
+    suspendable: bool,
+}
+
+impl Transpiler {
+    fn emit(&mut self, line: String) {
+        let indent = "  ".repeat(self.depth);
+        if let Some(buffer) = self.buffers.last_mut() {
+            buffer.push(format!("{indent}{line}"));
+        }
+    }
+
+    fn block(&mut self, stmts: &[Statement], completion: &Completion) {
+        for (i, statement) in stmts.iter().enumerate() {
+            if i + 1 == stmts.len() {
+                self.statement(statement, completion);
+            } else {
+                self.statement(statement, &Completion::Discard);
+            }
+        }
+        if stmts.is_empty() {
+            self.complete("null".to_owned(), completion);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, completion: &Completion) {
+        match statement {
+            Statement::Let { name, value, .. } => {
+                let value = self.expression(value);
+                let name = mangle(name);
+                self.emit(format!("var {name} = {value};"));
+                self.complete(name, completion);
+            }
+            Statement::Assign { name, value } => {
+                let value = self.expression(value);
+                let message = string_literal(&format!(
+                    "Cannot assign to `{name}`, it isn't defined: use `let` to define it"
+                ));
+                let name = mangle(name);
+                self.emit(format!(
+                    "if (typeof {name} === \"undefined\") $rt.fail({message});"
+                ));
+                self.emit(format!("{name} = {value};"));
+                self.complete(name, completion);
+            }
+            Statement::Struct { name, fields } => {
+                let fields: Vec<String> = fields.iter().map(|f| string_literal(f)).collect();
+                let ty = format!(
+                    "$rt.struct({}, [{}])",
+                    string_literal(name),
+                    fields.join(", ")
+                );
+                let name = mangle(name);
+                self.emit(format!("var {name} = {ty};"));
+                self.complete(name, completion);
+            }
+            Statement::Destructure { pattern, value } => {
+                let value = self.expression(value);
+                let temp = self.temp();
+                let bound = self.temp();
+                let spec = self.pattern(pattern);
+                self.emit(format!("var {temp} = {value};"));
+                self.emit(format!("var {bound} = $rt.match({spec}, {temp});"));
+                let message = string_literal(&format!(" doesn't match the pattern {pattern}"));
+                self.emit(format!(
+                    "if ({bound} === null) $rt.fail($rt.inspect({temp}) + {message});"
+                ));
+                for (i, name) in pattern.names().into_iter().enumerate() {
+                    self.emit(format!("var {} = {bound}[{i}];", mangle(name)));
+                }
+                self.complete(temp, completion);
+            }
+            Statement::Return { value } => {
+                let value = self.expression(value);
+                self.emit(format!("return {value};"));
+            }
+            Statement::Yield { value } if self.suspendable => {
+                let value = self.expression(value);
+                self.emit(format!("yield {value};"));
+                self.complete("null".to_owned(), completion);
+            }
+            Statement::Break => self.emit("break;".to_owned()),
+            Statement::Continue => self.emit("continue;".to_owned()),
+            Statement::Yield { .. } => self.emit(format!(
+                "$rt.fail({});",
+                string_literal("`yield` can only be used as a statement of a generator")
+            )),
+            Statement::Expr(Expression::Cond { cond, then_, else_ }) => self.cond(
+                cond,
+                then_,
+                else_.as_deref().unwrap_or_default(),
+                completion,
+            ),
+            Statement::Expr(Expression::For {
+                name,
+                iterable,
+                body,
+            }) => {
+                self.for_loop(name, iterable, body);
+                self.complete("null".to_owned(), completion);
+            }
+            Statement::Expr(Expression::While { cond, body }) => {
+                self.while_loop(cond, body);
+                self.complete("null".to_owned(), completion);
+            }
+            Statement::Expr(expr) => {
+                let value = self.expression(expr);
+                match completion {
+                    Completion::Discard => self.emit(format!("{value};")),
+                    _ => self.complete(value, completion),
+                }
+            }
+            Statement::Block(stmts) => self.block(stmts, completion),
+        }
+    }
+
+    fn complete(&mut self, value: String, completion: &Completion) {
+        match completion {
+            Completion::Return => self.emit(format!("return {value};")),
+            Completion::Assign(target) => self.emit(format!("{target} = {value};")),
+            Completion::Discard => {}
+        }
+    }
+
+    /// `if` is translated to a statement so that `return` inside its blocks still
+    /// returns from the enclosing function.
+    fn cond(
+        &mut self,
+        cond: &Expression,
+        then_: &[Statement],
+        else_: &[Statement],
+        completion: &Completion,
+    ) {
+        let cond = self.expression(cond);
+        self.emit(format!("if ($rt.truthy({cond})) {{"));
+        self.depth += 1;
+        self.block(then_, completion);
+        self.depth -= 1;
+        self.emit("} else {".to_owned());
+        self.depth += 1;
+        self.block(else_, completion);
+        self.depth -= 1;
+        self.emit("}".to_owned());
+    }
+
+    /// The loop variable is a `var`: like in the interpreter, it outlives the loop.
+    fn for_loop(&mut self, name: &str, iterable: &Expression, body: &[Statement]) {
+        let iterable = self.expression(iterable);
+        let name = mangle(name);
+        self.emit(format!("var {name};"));
+        self.emit(format!("for ({name} of $rt.iterate({iterable})) {{"));
+        self.depth += 1;
+        self.block(body, &Completion::Discard);
+        self.depth -= 1;
+        self.emit("}".to_owned());
+    }
+
+    /// The condition is evaluated in the loop, as it may need statements of its own.
+    fn while_loop(&mut self, cond: &Expression, body: &[Statement]) {
+        self.emit("while (true) {".to_owned());
+        self.depth += 1;
+        let cond = self.expression(cond);
+        self.emit(format!("if (!$rt.truthy({cond})) break;"));
+        self.block(body, &Completion::Discard);
+        self.depth -= 1;
+        self.emit("}".to_owned());
+    }
+
+    /// The arms are tried in a labelled block, left as soon as one matches.
+    fn match_arms(&mut self, subject: &Expression, arms: &[MatchArm]) -> String {
+        let result = self.temp();
+        self.emit(format!("var {result} = null;"));
+        let subject = self.expression(subject);
+        let value = self.temp();
+        self.emit(format!("var {value} = {subject};"));
+        let label = self.temp();
+        self.emit(format!("{label}: {{"));
+        self.depth += 1;
+        for arm in arms {
+            let bound = self.temp();
+            let names: Vec<String> = arm.pattern.names().into_iter().map(mangle).collect();
+            let spec = self.pattern(&arm.pattern);
+            self.emit(format!("var {bound} = $rt.match({spec}, {value});"));
+            self.emit(format!("if ({bound} !== null) {{"));
+            self.depth += 1;
+            // The guard sees the bindings through `let`s, so they only leak if it holds.
+            let mut closing = 0;
+            if let Some(guard) = &arm.guard {
+                let holds = self.temp();
+                self.emit(format!("var {holds} = false;"));
+                self.emit("{".to_owned());
+                self.depth += 1;
+                if !names.is_empty() {
+                    self.emit(format!("let [{}] = {bound};", names.join(", ")));
+                }
+                let guard = self.expression(guard);
+                self.emit(format!("{holds} = $rt.truthy({guard});"));
+                self.depth -= 1;
+                self.emit("}".to_owned());
+                self.emit(format!("if ({holds}) {{"));
+                self.depth += 1;
+                closing += 1;
+            }
+            for (i, name) in names.iter().enumerate() {
+                self.emit(format!("var {name} = {bound}[{i}];"));
+            }
+            let output = self.expression(&arm.value);
+            self.emit(format!("{result} = {output};"));
+            self.emit(format!("break {label};"));
+            for _ in 0..=closing {
+                self.depth -= 1;
+                self.emit("}".to_owned());
+            }
+        }
+        self.depth -= 1;
+        self.emit("}".to_owned());
+        result
+    }
+
+    /// The pattern as the spec `$rt.match` expects.
+    fn pattern(&mut self, pattern: &Pattern) -> String {
+        match pattern {
+            Pattern::Wildcard => "\"_\"".to_owned(),
+            Pattern::Ident(_) => "\"$\"".to_owned(),
+            Pattern::Literal(literal) => format!("[\"lit\", {}]", self.expression(literal)),
+            Pattern::Array { items, rest } => {
+                let items: Vec<String> = items.iter().map(|item| self.pattern(item)).collect();
+                let rest = match rest {
+                    Some(rest) => self.pattern(rest),
+                    None => "null".to_owned(),
+                };
+                format!("[\"array\", [{}], {rest}]", items.join(", "))
+            }
+            Pattern::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(|item| self.pattern(item)).collect();
+                format!("[\"tuple\", [{}]]", items.join(", "))
+            }
+            Pattern::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(k, p)| format!("[{}, {}]", self.expression(k), self.pattern(p)))
+                    .collect();
+                format!("[\"hash\", [{}]]", pairs.join(", "))
+            }
+            Pattern::Struct { name, fields } => {
+                let fields: Vec<String> = fields.iter().map(|f| self.pattern(f)).collect();
+                format!(
+                    "[\"struct\", {}, [{}]]",
+                    string_literal(name),
+                    fields.join(", ")
+                )
+            }
+        }
+    }
+
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("$t{}", self.temps)
+    }
+
+    fn expression(&mut self, expr: &Expression) -> String {
+        match expr {
+            Expression::Bool(value) => value.to_string(),
+            Expression::Int(value) => format!("{value}n"),
+            Expression::Float(value) if value.is_infinite() => "Infinity".to_owned(),
+            Expression::Float(value) => format!("{value:?}"),
+            Expression::String(string) => string_literal(string),
+            Expression::Ident(ident) if ident == "null" => "null".to_owned(),
+            // Bindings shadow the builtins of the same name.
+            Expression::Ident(ident) if is_builtin(ident) => {
+                let name = mangle(ident);
+                format!("(typeof {name} === \"undefined\" ? $rt.builtins.{ident} : {name})")
+            }
+            // Names bound by desugared syntax are always defined.
+            Expression::Ident(ident) if ident.starts_with('$') => mangle(ident),
+            Expression::Ident(ident) => {
+                let message = string_literal(&format!("identifier not found: {ident}"));
+                let name = mangle(ident);
+                format!("(typeof {name} === \"undefined\" ? $rt.fail({message}) : {name})")
+            }
+            Expression::Field { object, name } => {
+                let object = self.expression(object);
+                format!("$rt.field({object}, {})", string_literal(name))
+            }
+            Expression::Prefix { operator, right } => {
+                let right = self.expression(right);
+                match operator {
+                    PrefixOperator::Not => format!("$rt.not({right})"),
+                    PrefixOperator::Neg => format!("$rt.neg({right})"),
+                    PrefixOperator::Plus => format!("$rt.plus({right})"),
+                }
+            }
+            Expression::Infix {
+                operator: operator @ (InfixOperator::And | InfixOperator::Or),
+                left,
+                right,
+            } => {
+                // The right operand may need statements, run only when the left one
+                // doesn't decide.
+                let left = self.expression(left);
+                self.temps += 1;
+                let temp = format!("$t{}", self.temps);
+                self.emit(format!("var {temp} = $rt.truthy({left});"));
+                let negation = if *operator == InfixOperator::Or {
+                    "!"
+                } else {
+                    ""
+                };
+                self.emit(format!("if ({negation}{temp}) {{"));
+                self.depth += 1;
+                let suspendable = mem::replace(&mut self.suspendable, false);
+                let right = self.expression(right);
+                self.suspendable = suspendable;
+                self.emit(format!("{temp} = $rt.truthy({right});"));
+                self.depth -= 1;
+                self.emit("}".to_owned());
+                temp
+            }
+            Expression::Infix {
+                operator,
+                left,
+                right,
+            } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                format!(
+                    "$rt.infix({}, {left}, {right})",
+                    string_literal(&operator.to_string())
+                )
+            }
+            Expression::Func { args, body, .. } => {
+                // What `Object::pretty` shows, the annotations being dropped at runtime.
+                let source = string_literal(&expression_to_source(&Expression::Func {
+                    args: args.clone(),
+                    arg_types: vec![],
+                    return_type: None,
+                    body: body.clone(),
+                }));
+                let doc = Statement::docstring(body).map_or("null".to_owned(), string_literal);
+                let params: Vec<String> = args.iter().map(|a| string_literal(a)).collect();
+                let args: Vec<String> = args.iter().map(|a| mangle(a)).collect();
+                let generator = body.iter().any(Statement::yields);
+
+                let suspendable = mem::replace(&mut self.suspendable, generator);
+                self.buffers.push(vec![]);
+                self.depth += 1;
+                self.block(body, &Completion::Return);
+                self.depth -= 1;
+                let lines = self.buffers.pop().unwrap_or_default();
+                self.suspendable = suspendable;
+
+                let (wrapper, function) = if generator {
+                    ("generator", "function* ")
+                } else {
+                    ("func", "")
+                };
+                let arrow = if generator { "" } else { " =>" };
+                format!(
+                    "$rt.{wrapper}([{}], {function}({}){arrow} {{\n{}\n{}}}, {source}, {doc})",
+                    params.join(", "),
+                    args.join(", "),
+                    lines.join("\n"),
+                    "  ".repeat(self.depth)
+                )
+            }
+            Expression::Call {
+                func, args, kwargs, ..
+            } => {
+                let mut callee = string_literal(&expression_to_source(func));
+                if let Expression::Ident(name) = func.as_ref() {
+                    callee = format!("{callee}, {}", string_literal(name));
+                }
+                let func = self.expression(func);
+                let args: Vec<String> = args.iter().map(|a| self.expression(a)).collect();
+                if kwargs.is_empty() {
+                    return format!("$rt.call({func}, [{}], {callee})", args.join(", "));
+                }
+                let kwargs: Vec<String> = kwargs
+                    .iter()
+                    .map(|(name, value)| {
+                        format!("[{}, {}]", string_literal(name), self.expression(value))
+                    })
+                    .collect();
+                format!(
+                    "$rt.callKeywords({func}, [{}], [{}], {callee})",
+                    args.join(", "),
+                    kwargs.join(", ")
+                )
+            }
+            Expression::Cond { cond, then_, else_ } => {
+                self.temps += 1;
+                let temp = format!("$t{}", self.temps);
+                self.emit(format!("var {temp} = null;"));
+                let suspendable = mem::replace(&mut self.suspendable, false);
+                self.cond(
+                    cond,
+                    then_,
+                    else_.as_deref().unwrap_or_default(),
+                    &Completion::Assign(temp.clone()),
+                );
+                self.suspendable = suspendable;
+                temp
+            }
+            Expression::For {
+                name,
+                iterable,
+                body,
+            } => {
+                let suspendable = mem::replace(&mut self.suspendable, false);
+                self.for_loop(name, iterable, body);
+                self.suspendable = suspendable;
+                "null".to_owned()
+            }
+            Expression::While { cond, body } => {
+                let suspendable = mem::replace(&mut self.suspendable, false);
+                self.while_loop(cond, body);
+                self.suspendable = suspendable;
+                "null".to_owned()
+            }
+            Expression::Match { subject, arms } => {
+                let suspendable = mem::replace(&mut self.suspendable, false);
+                let result = self.match_arms(subject, arms);
+                self.suspendable = suspendable;
+                result
+            }
+            Expression::Array(content) => {
+                let content: Vec<String> = content.iter().map(|e| self.expression(e)).collect();
+                format!("[{}]", content.join(", "))
+            }
+            Expression::Tuple(content) => {
+                let content: Vec<String> = content.iter().map(|e| self.expression(e)).collect();
+                format!("$rt.tuple({})", content.join(", "))
+            }
+            Expression::Hash { pairs, .. } => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(k, v)| format!("[{}, {}]", self.expression(k), self.expression(v)))
+                    .collect();
+                format!("$rt.hash([{}])", pairs.join(", "))
+            }
+        }
+    }
+}
+
+/// Monkey identifiers can't contain `$`, so prefixing reserved words with it never clashes.
+fn mangle(ident: &str) -> String {
+    if JS_RESERVED.contains(&ident) {
+        format!("${ident}")
+    } else {
+        ident.to_owned()
+    }
+}
+
+fn string_literal(string: &str) -> String {
+    let mut output = String::from("\"");
+    for ch in string.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\u{2028}' => output.push_str("\\u2028"),
+            '\u{2029}' => output.push_str("\\u2029"),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+    use crate::eval::{Environment, eval_with_env, reset_output, set_output};
+    use std::cell::RefCell;
+    use std::process::Command;
+    use std::rc::Rc;
+
+    /// Runs the translated program under node and compares its output with the
+    /// interpreter's; skipped when node isn't installed.
+    fn assert_same_output(input: &str) {
+        let printed = Rc::new(RefCell::new(String::new()));
+        let sink = Rc::clone(&printed);
+        set_output(move |text| sink.borrow_mut().push_str(text));
+        let result = eval_with_env(input, Rc::new(Environment::default()));
+        reset_output();
+        let expected = match result {
+            Ok(obj) => format!("{}{obj}", printed.borrow()),
+            Err(err) => format!("{}{err}", printed.borrow()),
+        };
+
+        let program = Parser::init(input).parse_program().unwrap();
+        let js = format!(
+            "{}try {{ console.log($rt.inspect($main())); }} catch (e) {{ console.log(e.message); }}\n",
+            script(&program)
+        );
+        let Ok(output) = Command::new("node").args(["-e", &js]).output() else {
+            return;
+        };
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), expected);
+    }
+
+    #[test]
+    fn golden() {
+        assert_same_output("(5 + 10 * 2 + 15 / 3) * 2 + -10");
+        assert_same_output(
+            "[0.1 + 0.2, 7 / 2.0, -1.5 * 2, 1e16 + 1, 1e-5, 2.5e-3, 1.0 / 0, 2 == 2.0, 1 < 1.5, !0.0]",
+        );
+        assert_same_output("[1.5][0.0]");
+        assert_same_output("[+5 - +1.5, -+2]");
+        assert_same_output("[1 <= 1, 2 >= 3, 1.5 >= 1, 2 <= 1.5, true <= false]");
+        assert_same_output("[\"abc\" <= \"abd\", \"ab\" >= \"abc\", \"a\" != \"a\", \"a\" < 1]");
+        assert_same_output("[17 % 5, -7 % 2, 7.5 % -2, 2 ** 3 ** 2, 3 ** 41, 2 ** 64]");
+        assert_same_output("1 % 0");
+        assert_same_output("9223372036854775807 * 2");
+        assert_same_output("[(-9223372036854775807 - 1) % -1, -(-9223372036854775807 - 1)]");
+        assert_same_output("2 ** -1");
+        assert_same_output(
+            "let mut n = 0; let f = fn(x) { n = n + 1; x }; \
+             [f(0) && f(1), f(\"a\") || f(0), f(null) || if (f(1)) { [] } else { 2 }, n]",
+        );
+        assert_same_output("+[1]");
+        assert_same_output("let a = 5; let b = a; let c = a + b + 5; c;");
+        assert_same_output("if (1 > 2) { 10 } else { 20 }");
+        assert_same_output("if (10 > 1) { if (10 > 1) { return 10; } return 1; }");
+        assert_same_output(
+            "let f = fn(x) { let y = if (x > 1) { return 100; } else { x }; y * 2 }; [f(1), f(5)]",
+        );
+        assert_same_output(
+            "let newAdder = fn(x) { fn(y) { x + y } }; let addTwo = newAdder(2); addTwo(2)",
+        );
+        assert_same_output("\"Hello\" + \" \" + \"World!\"");
+        assert_same_output(
+            "[len(\"four\"), first([1, 2]), last(\"abc\"), rest([1, 2, 3]), push([1], 2)]",
+        );
+        assert_same_output("[rest(\"😀ab\"), rest(\"é\"), first(\"😀\")]");
+        assert_same_output("{\"foo\": 5}[\"foo\"] + {true: 1}[true]");
+        assert_same_output("[[1, 2, 3][3], {}[\"foo\"], !null, !!5]");
+        assert_same_output(
+            "let each = fn(arr, f) { let step = fn(a, acc) { if (len(a) == 0) { acc } else { step(rest(a), push(acc, f(first(a)))) } }; step(arr, []) }; each([1, 2, 3], fn(x) { x * 2 })",
+        );
+        assert_same_output("let new = fn(this) { this }; new(3)");
+        assert_same_output("fn(x) { x }");
+        assert_same_output("len");
+        assert_same_output("let sub = fn(a, b) { a - b }; [sub(b: 1, a: 5), sub(5, b: 1)]");
+        assert_same_output("let sub = fn(a, b) { a - b }; sub(5, a: 1)");
+        assert_same_output("let sub = fn(a, b) { a - b }; sub(b: 1)");
+        assert_same_output("fn(a) { a }(b: 1)");
+        assert_same_output("len(x: [1])");
+        assert_same_output("puts(\"a\", [1, \"b\"]); puts(); puts(null)");
+        assert_same_output(
+            "let point = fn(x) { {\"x\": x, \"plus\": fn(self, dx) { point(self[\"x\"] + dx) }} }; [point(1).plus(2)[\"x\"], point(1).minus(2)]",
+        );
+    }
+
+    #[test]
+    fn golden_structs() {
+        assert_same_output(
+            "struct Point { x, y } struct Pair { x, y } let p = Point(1, y: [2]); \
+             [Point, p, p.x, p[\"y\"], p[\"z\"], p == Point(1, [2]), p == Pair(1, [2]), {\"k\": 1}.k]",
+        );
+        assert_same_output(
+            "struct Point { x, y } let f = fn(p) { match (p) { Point(0, y) => y, Point(x, _) => x, _ => null } }; \
+             [f(Point(0, 5)), f(Point(3, 4)), f([0, 1])]",
+        );
+        assert_same_output("struct Point { x, y } Point(1)");
+        assert_same_output("struct Point { x, y } Point(1, 2).z");
+        assert_same_output("struct Point { x, y } Point(1, z: 2)");
+    }
+
+    #[test]
+    fn golden_overloaded_operators() {
+        assert_same_output(
+            "let v = fn(x) { {\"x\": x, \"__add\": fn(a, b) { v(a.x + b.x) }, \"__eq\": fn(a, b) { a.x == b.x }, \
+             \"__index\": fn(a, key) { key }} }; [(v(1) + v(2)).x, v(1) == v(1), v(1) != v(2), v(1)[\"k\"]]",
+        );
+        assert_same_output(
+            "struct P { x, __lt } let lt = fn(a, b) { a.x < b.x }; [P(1, lt) < P(2, lt), P(1, lt)[\"y\"], P(1, 2) < 1]",
+        );
+    }
+
+    #[test]
+    fn golden_patterns() {
+        assert_same_output(
+            "let sum = fn(xs) { match (xs) { [] => 0, [head, ...tail] => head + sum(tail) } }; sum([1, 2, 3])",
+        );
+        assert_same_output(
+            "let f = fn(s) { match (s) { {\"type\": \"circle\", \"r\": r} if r > 1 => r * r, {\"type\": t} => t, -1 => true, null => 0 } }; [f({\"type\": \"circle\", \"r\": 2}), f({\"type\": \"circle\", \"r\": 1}), f(-1), f(null), f(5)]",
+        );
+        assert_same_output(
+            "let f = fn(x) { match (x) { 0.5 => 1, -1.5 => 2, _ => 0 } }; [f(0.5), f(-1.5), f(1)]",
+        );
+        assert_same_output(
+            "let x = 0; let y = match ([1]) { [x] if x > 1 => x, [z] => z + x }; let [a, [b, _], ...c] = [1, [2, 3], 4]; [x, y, a, b, c]",
+        );
+        assert_same_output("let [a, b] = [1]; a");
+        assert_same_output(
+            "let f = fn((a, b), [c]) { (b, a + c) }; let (x, y) = f((1, \"s\"), [2]); \
+             [x, y, f((0, 0), [1]), (1,), (), len((1, 2)), (1, 2) == (1, 2), (x, y)[5], match ((1, 2)) { (1, z) => z }]",
+        );
+        assert_same_output("let (a, b) = (1,); a");
+        assert_same_output(
+            "let lines = 5; let map = fn(a, b, c) { a }; let f = fn(len) { len + lines }; \
+             [f(1), map(1, 2, 3), first([2])]",
+        );
+        assert_same_output(
+            "let it = iter([1, 2, 3]); let c = copy(it); next(it); let r = range(3); \
+             let d = deepcopy([r, r, {\"k\": (r, [1])}]); next(d[0]); \
+             [next(it), next(c), next(c), next(it), next(d[1]), next(d[2][\"k\"][0]), next(r), d[2]]",
+        );
+        assert_same_output("let {\"k\": v} = {\"k\": [1]}");
+    }
+
+    #[test]
+    fn golden_propagation() {
+        assert_same_output(
+            "let f = fn(x) { let y = len(x?)?; [y, -len(x)? * 2, is_error(x), is_error(y?)] }; f(\"ab\")",
+        );
+        assert_same_output(
+            "let g = fn(x) { [1, if (x) { return 2; } else { 3 }, {\"k\": if (x) { return 4; } else { 5 }}] }; [g(true), g(false)]",
+        );
+        assert_same_output(
+            "let parse = fn(s) { if (len(s) == 0) { return error(\"empty\", {\"code\": 400}); }; len(s) }; \
+             let total = fn(a, b) { parse(a)? + parse(b)? }; let e = total(\"ab\", \"\"); \
+             [total(\"ab\", \"c\"), e, !e, e[\"message\"], e[\"data\"], e[\"other\"], error(\"x\")[\"data\"], is_error(e)]",
+        );
+        assert_same_output("error(1)");
+        assert_same_output("error(\"a\", 1, 2)");
+    }
+
+    #[test]
+    fn golden_iterators() {
+        assert_same_output(
+            "let total = 0; for (x in range(10, 0, -3)) { let total = total + x; }; [total, x]",
+        );
+        assert_same_output(
+            "collect(map(filter(range(10), fn(x) { x / 2 * 2 == x }), fn(x) { x * x }))",
+        );
+        assert_same_output("collect({\"b\": 1, 2: 2, true: 3, \"a\": 4})");
+        assert_same_output("let it = iter(\"héllo\"); next(it); [next(it), collect(it), next(it)]");
+        assert_same_output(
+            "let f = fn() { for (x in [1, 2, 3]) { if (x == 2) { return x; } } }; f()",
+        );
+        assert_same_output("[range(1), next([]), for (x in []) {}]");
+        assert_same_output(
+            "let i = 0; let xs = []; while (len(xs) < 3) { let xs = push(xs, i * i); let i = i + 1; }; [xs, i, while (false) {}]",
+        );
+        assert_same_output(
+            "let xs = []; for (x in range(6)) { if (x == 4) { break; }; let y = if (x == 1) { continue; } else { x * 10 }; let xs = push(xs, y); }; xs",
+        );
+        assert_same_output(
+            "let f = fn(n) { while (true) { if (n > 3) { return n; }; let n = n * 2; } }; let g = fn(n) { while (n > 0) { yield n; let n = n - 2; } }; [f(1), collect(g(5))]",
+        );
+        assert_same_output(
+            "let g = fn(a) { yield a; if (a > 1) { yield a * 10; return 0; } else { yield -1; }; yield 99; }; [collect(g(2)), collect(g(1))]",
+        );
+        assert_same_output(
+            "let pairs = fn(xs) { for (x in xs) { for (y in xs) { yield [x, y]; } } }; let a = pairs([1, 2]); next(a); [next(a), collect(a)]",
+        );
+        assert_same_output("let g = fn() { let x = if (true) { yield 1; }; }; next(g())");
+        assert_same_output("yield 1;");
+        assert_same_output(
+            "let counter = fn() { let mut n = 0; fn() { n = n + 1 } }; let c = counter(); c(); let mut i = 0; while (i < 3) { i = i + 1; }; [c(), i, counter()()]",
+        );
+        assert_same_output("let f = fn() { let x = 1; }; f(); x = 2");
+    }
+
+    #[test]
+    fn golden_text() {
+        assert_same_output(
+            "let s = \"a b\r\n\n  c\td \n\"; [lines(s), words(s), fields(\"x,,y\", \",\"), lines(\"\")]",
+        );
+        assert_same_output("fields(\"x\", 1)");
+        assert_same_output("dump([1, {\"b\": [2, 3, 4], 1: {}, true: [[5]]}], 2, 2)");
+        assert_same_output("dump(1, -1, 2)");
+        assert_same_output(
+            "dump([fn() {}, fn(a: int) -> int { if (a) { yield a; } }, partial(fn(x, y) { x }, 1), compose(len, len)])",
+        );
+        assert_same_output("dump({1: fn(x) { \"long\" }}, 1, 1)");
+        assert_same_output("words(1)");
+        assert_same_output("dump(csv_parse(\"id,name\r\n1,a b\n007,\n\"))");
+        assert_same_output("csv_parse(\"a,b\n1\")");
+        assert_same_output(
+            "let rows = csv_parse(\"name,team,age\nada,x,36\nbob,y,29\neve,x,\njoe,y,29\n\"); \
+             [select(order_by(where(rows, fn(r) { r[\"age\"] }), \"age\", true), [\"name\"]), \
+             select(group_by(rows, \"team\")[\"y\"], fn(r) { r[\"name\"] })]",
+        );
+        assert_same_output("order_by([{\"a\": 1}, {\"a\": \"b\"}], \"a\")");
+        assert_same_output(
+            "select(order_by([{\"a\": 2}, {\"a\": 0.5}, {}], \"a\", true), [\"a\"])",
+        );
+        assert_same_output("select([1], [\"a\"])");
+        assert_same_output(
+            "table(select(csv_parse(\"name,age\nada,36\nzoé,\nbob,7\n\"), [\"name\", \"age\", \"x\"])); table([{1: [\"a\"], true: 0.5}]); table([])",
+        );
+        assert_same_output("table([{}, 1])");
+        assert_same_output("where([], 1)");
+        assert_same_output("order_by([{}], \"a\", 1, 2)");
+        assert_same_output(
+            "let b = bytes(\"hé\t\") + bytes([0, 255]); [b, len(b), b[2], b[9], slice(b, -1, 3), \
+             decode(slice(b, 0, 3)), decode(bytes(\"é\", \"latin1\"), \"latin1\"), collect(bytes(\"ab\")), dump(b)]",
+        );
+        assert_same_output("decode(bytes([255]))");
+        assert_same_output("bytes([1, 256])");
+        assert_same_output(
+            "let h = insert({\"a\": 1}, \"b\", 2); [dump(h), dump(merge(h, {\"a\": 3, true: 4})), dump(insert(h, \"a\", null))]",
+        );
+        assert_same_output(
+            "let a = [1]; for (x in range(3)) { let a = push(a, x, x * 2); }; [a, push(\"a\", \"b\", \"c\")]",
+        );
+        assert_same_output("push(\"a\", \"b\", [1])");
+        assert_same_output("insert({}, [1], 2)");
+        assert_same_output("merge({}, [])");
+        assert_same_output(
+            "[hex(\"hé\"), from_hex(\"00fF\"), base64(bytes([251, 255])), from_base64(\"+/8\"), from_base64(\"Zg==\")]",
+        );
+        assert_same_output("from_base64(\"Zg=\")");
+        assert_same_output("hex(1)");
+        #[cfg(feature = "crypto")]
+        assert_same_output(
+            "[hex(hash_sha256(\"abc\")), base64(hmac_sha256(\"key\", bytes(\"message\"))), hmac_sha256(1, 2)]",
+        );
+    }
+
+    #[test]
+    fn golden_errors() {
+        assert_same_output("1 + true");
+        assert_same_output("-true");
+        assert_same_output("let f = fn(x) { x }; f(1, 2)");
+        assert_same_output("[fn(x, y) { x }][0](1)");
+        assert_same_output("collect(map([1], fn() { 1 }))");
+        assert_same_output("let f = partial(fn(x, y) { x - y }, 5); [f, f(2), partial(f, 1)()]");
+        assert_same_output("partial(fn(x) { x }, 1, 2)");
+        assert_same_output("partial(len)");
+        assert_same_output("let f = compose(fn(x) { x + 1 }, len); [f, f([1, 2])]");
+        assert_same_output("compose(len, 1)");
+        assert_same_output("[help(\"range\"), help(\"lines\")]");
+        assert_same_output("help(\"nope\")");
+        assert_same_output(
+            "let f = fn(x, y) { \"Adds.\"; x + y }; [help(f), help(partial(f, 1)), help(fn() { \"a\" }), help(len)]",
+        );
+        assert_same_output("help(1)");
+        assert_same_output(
+            "let spec = {\"verbose\": false, \"count\": 1, \"out\": null}; \
+             [args(), dump(parse_args(spec)), dump(parse_args(spec, [\"a\", \"--count=-3\", \"--verbose\", \"--out\", \"x\", \"--\", \"--b\"]))]",
+        );
+        assert_same_output("parse_args({\"count\": 1}, [\"--count\", \"x\"])");
+        assert_same_output("parse_args({\"count\": 1}, [\"--count\"])");
+        assert_same_output("parse_args({\"v\": true}, [\"--v=1\"])");
+        assert_same_output("parse_args({\"v\": true}, [\"--w\"])");
+        assert_same_output("parse_args({\"v\": [1]})");
+        assert_same_output("parse_args({}, [1])");
+        assert_same_output("parse_args([])");
+        assert_same_output("compose(len, fn(a, b) { a })(1)");
+        assert_same_output("5(1)");
+        assert_same_output("len(1, 2)");
+        assert_same_output("{[1]: 2}");
+        assert_same_output("range(1, 2, 0)");
+        assert_same_output("range(1, \"a\")");
+        assert_same_output("collect(map([1], 2))");
+        assert_same_output("for (x in 5) { x }");
+        assert_same_output("let f = fn() { later }; let later = 3; [f(), missing]");
+    }
+}
@@ -0,0 +1,1305 @@
+"use strict";
+// Runtime support mirroring the semantics of the tree-walking evaluator.
+const $rt = (() => {
+  class MonkeyError extends Error {}
+  const fail = (message) => {
+    throw new MonkeyError(message);
+  };
+
+  // A lazy sequence: `next` returns `undefined` once exhausted.
+  class Iter {
+    constructor(next) {
+      this.next = next;
+    }
+  }
+
+  // Mirrors `Object::Error`.
+  class ErrorValue {
+    constructor(message, data) {
+      this.message = message;
+      this.data = data;
+    }
+  }
+
+  // Mirrors `Object::Struct`, `ty` being the constructor made by `struct`.
+  class StructValue {
+    constructor(ty, values) {
+      this.ty = ty;
+      this.values = values;
+    }
+  }
+
+  // Mirrors `Object::Tuple`, told apart from arrays.
+  class TupleValue {
+    constructor(values) {
+      this.values = values;
+    }
+  }
+
+  const type = (v) => {
+    if (v === null) return "null";
+    if (typeof v === "bigint") return "int";
+    if (typeof v === "number") return "float";
+    if (typeof v === "boolean") return "bool";
+    if (typeof v === "string") return "str";
+    if (typeof v === "function") return "fn";
+    if (Array.isArray(v)) return "array";
+    if (v instanceof Uint8Array) return "bytes";
+    if (v instanceof Map) return "hash";
+    if (v instanceof Iter) return "iterator";
+    if (v instanceof ErrorValue) return "error";
+    if (v instanceof StructValue) return "struct";
+    if (v instanceof TupleValue) return "tuple";
+    return "unknown";
+  };
+
+  // Mirrors `<[u8]>::escape_ascii`.
+  const escapeAscii = (bytes) =>
+    [...bytes]
+      .map((b) => {
+        const escaped = { 9: "\\t", 10: "\\n", 13: "\\r", 34: '\\"', 39: "\\'", 92: "\\\\" }[b];
+        if (escaped !== undefined) return escaped;
+        return b >= 0x20 && b < 0x7f ? String.fromCharCode(b) : "\\x" + b.toString(16).padStart(2, "0");
+      })
+      .join("");
+
+  // Mirrors the `Debug` formatting of `f64`, which always tells floats from ints.
+  const float = (v) => {
+    if (Number.isNaN(v)) return "NaN";
+    if (!Number.isFinite(v)) return v > 0 ? "inf" : "-inf";
+    const a = Math.abs(v);
+    if (a !== 0 && (a < 1e-4 || a >= 1e16)) return v.toExponential().replace("e+", "e");
+    const s = Object.is(v, -0) ? "-0" : String(v);
+    return s.includes(".") ? s : s + ".0";
+  };
+
+  const inspect = (v) => {
+    switch (type(v)) {
+      case "null":
+        return "null";
+      case "float":
+        return float(v);
+      case "array":
+        return "[" + v.map(inspect).join(", ") + "]";
+      case "hash":
+        return "{ " + [...v].map(([k, x]) => inspect(k) + ": " + inspect(x)).join(", ") + " }";
+      case "iterator":
+        return "<iterator>";
+      case "bytes":
+        return 'b"' + escapeAscii(v) + '"';
+      case "error":
+        return "Error: " + v.message;
+      case "tuple":
+        return "(" + v.values.map(inspect).join(", ") + (v.values.length === 1 ? ",)" : ")");
+      case "struct":
+        return `${v.ty.$struct}(${v.ty.$params.map((f, i) => f + ": " + inspect(v.values[i])).join(", ")})`;
+      case "fn":
+        if (v.$builtin) return `Builtin function '${v.$builtin}'`;
+        if (v.$struct) return `struct ${v.$struct} { ${v.$params.join(", ")} }`;
+        if (v.$source === undefined) return `fn(${v.$params.join(", ")}) {...}`;
+        return functionSource(v.$source, null);
+      default:
+        return String(v);
+    }
+  };
+
+  // Mirrors `function_source`: indented by `depth` levels, or on one line when `depth` is null.
+  const functionSource = (source, depth) => {
+    const chars = [...source];
+    const shown = chars.slice(0, 400).join("");
+    const laidOut =
+      depth === null
+        ? shown
+            .split("\n")
+            .map((line) => line.trim())
+            .join(" ")
+        : shown.replaceAll("\n", "\n" + "    ".repeat(depth));
+    return chars.length > 400 ? laidOut + "..." : laidOut;
+  };
+
+  const nested = (v) =>
+    (type(v) === "array" && v.length > 0) ||
+    (type(v) === "hash" && v.size > 0) ||
+    (type(v) === "fn" && v.$source?.includes("\n"));
+
+  // Mirrors `Object::pretty`.
+  const pretty = (v, maxDepth, maxItems, depth = 0) => {
+    const t = type(v);
+    if (t === "fn" && v.$source !== undefined) return functionSource(v.$source, depth);
+    if (t !== "array" && t !== "hash") return inspect(v);
+    const [open, close] = t === "array" ? ["[", "]"] : ["{", "}"];
+    const items = t === "array" ? v.map((x) => [undefined, x]) : [...v].sort(([a], [b]) => compare(a, b));
+    if (items.length === 0) return open + close;
+    if (depth >= maxDepth) return open + "..." + close;
+    const parts = items
+      .slice(0, maxItems)
+      .map(([k, x]) => (k === undefined ? "" : inspect(k) + ": ") + pretty(x, maxDepth, maxItems, depth + 1));
+    if (items.length > maxItems) parts.push("...");
+    if (items.some(([, x]) => nested(x))) {
+      const pad = "    ".repeat(depth);
+      return open + "\n" + parts.map((p) => pad + "    " + p + ",\n").join("") + pad + close;
+    }
+    return t === "hash" ? "{ " + parts.join(", ") + " }" : "[" + parts.join(", ") + "]";
+  };
+
+  const truthy = (v) => {
+    switch (type(v)) {
+      case "bool":
+        return v;
+      case "int":
+        return v !== 0n;
+      case "float":
+        return v !== 0;
+      case "str":
+      case "array":
+      case "bytes":
+        return v.length > 0;
+      case "hash":
+        return v.size > 0;
+      case "tuple":
+        return v.values.length > 0;
+      case "null":
+      case "error":
+        return false;
+      default:
+        return true;
+    }
+  };
+
+  const int = (v) => BigInt.asIntN(64, v);
+
+  const checked = (v, message) => {
+    if (int(v) !== v) fail(`Integer overflow in ${message}`);
+    return v;
+  };
+
+  // Squaring keeps the intermediate values within 64 bits, wrapping like the interpreter.
+  const pow = (base, exponent) => {
+    if (exponent < 0n || exponent > 4294967295n) {
+      fail(`Invalid exponent ${exponent}, must be between 0 and 4294967295`);
+    }
+    let result = 1n;
+    while (exponent > 0n) {
+      if (exponent & 1n) result = int(result * base);
+      base = int(base * base);
+      exponent >>= 1n;
+    }
+    return result;
+  };
+
+  const key = (k) => {
+    if (["int", "str", "bool"].includes(type(k))) return k;
+    fail("Invalid object type for an hash key, must be int, str or bool!");
+  };
+
+  const hash = (pairs) => new Map(pairs.map(([k, v]) => [key(k), v]));
+
+  const func = (params, f, source, doc = null) => {
+    const g = (...args) => {
+      checkArity("fn", params, args);
+      return f(...args);
+    };
+    g.$params = params;
+    g.$source = source;
+    g.$doc = doc;
+    return g;
+  };
+
+  const generator = (params, f, source, doc) =>
+    func(
+      params,
+      (...args) => {
+        const g = f(...args);
+        return new Iter(() => {
+          const { value, done } = g.next();
+          return done ? undefined : value;
+        });
+      },
+      source,
+      doc,
+    );
+
+  // Mirrors `Object::StructType`: a constructor checking its arity like functions do.
+  const struct = (name, fields) => {
+    const ty = (...args) => {
+      if (args.length !== fields.length) {
+        const s = fields.length === 1 ? "" : "s";
+        fail(
+          `Struct \`${name}(${fields.join(", ")})\` expects ${fields.length} argument${s}, got ${args.length}.`,
+        );
+      }
+      return new StructValue(ty, args);
+    };
+    ty.$params = fields;
+    ty.$struct = name;
+    return ty;
+  };
+
+  const tuple = (...values) => new TupleValue(values);
+
+  // Mirrors the equality of `Object`, for the values of structs and tuples.
+  const same = (a, b) => {
+    if (type(a) !== type(b)) return false;
+    switch (type(a)) {
+      case "array":
+        return a.length === b.length && a.every((x, i) => same(x, b[i]));
+      case "hash":
+        return a.size === b.size && [...a].every(([k, x]) => b.has(k) && same(x, b.get(k)));
+      case "struct":
+        return a.ty === b.ty && a.values.every((x, i) => same(x, b.values[i]));
+      case "tuple":
+        return a.values.length === b.values.length && a.values.every((x, i) => same(x, b.values[i]));
+      default:
+        return a === b;
+    }
+  };
+
+  const field = (v, name) => {
+    if (type(v) !== "struct") return infix("`[...]`", v, name);
+    const i = v.ty.$params.indexOf(name);
+    if (i < 0) fail(`Struct \`${v.ty.$struct}\` has no field \`${name}\``);
+    return v.values[i];
+  };
+
+  const checkArity = (name, params, args) => {
+    if (args.length === params.length) return;
+    const s = params.length === 1 ? "" : "s";
+    fail(
+      `Function \`${name}(${params.join(", ")})\` expects ${params.length} argument${s}, got ${args.length}.`,
+    );
+  };
+
+  const call = (f, args, callee, name) => {
+    if (type(f) !== "fn") fail(`\`${callee}\` is not a function, found ${inspect(f)}`);
+    if (name !== undefined && f.$params && !f.$struct) checkArity(name, f.$params, args);
+    return f(...args);
+  };
+
+  const callKeywords = (f, args, kwargs, callee, name) => {
+    if (type(f) !== "fn") fail(`\`${callee}\` is not a function, found ${inspect(f)}`);
+    if (!f.$params) fail(`${inspect(f)} doesn't take keyword arguments`);
+    const kind = f.$struct ? "Struct" : "Function";
+    const signature = `${kind} \`${name ?? f.$struct ?? "fn"}(${f.$params.join(", ")})\``;
+    const slots = [...args];
+    for (const [keyword, value] of kwargs) {
+      const i = f.$params.indexOf(keyword);
+      if (i < 0) fail(`${signature} has no parameter \`${keyword}\``);
+      if (slots[i] !== undefined) fail(`${signature} got the argument \`${keyword}\` twice`);
+      slots[i] = value;
+    }
+    f.$params.forEach((param, i) => {
+      if (slots[i] === undefined) fail(`${signature} is missing the argument \`${param}\``);
+    });
+    return call(f, slots, callee, name);
+  };
+
+  const not = (v) => !truthy(v);
+
+  const neg = (v) => {
+    if (type(v) === "int") return checked(-v, `the negation of ${v}`);
+    if (type(v) === "float") return -v;
+    fail(`${inspect(v)} cannot be negated!`);
+  };
+
+  const plus = (v) => {
+    if (type(v) === "int" || type(v) === "float") return v;
+    fail(`Unary \`+\` expects a number, found ${inspect(v)}!`);
+  };
+
+  const infix = (op, l, r) => {
+    const tl = type(l);
+    const tr = type(r);
+    if (tl === "null" && tr === "null") return null;
+    if ((tl === "float" || tr === "float") && ["int", "float"].includes(tl) && ["int", "float"].includes(tr)) {
+      const [a, b] = [Number(l), Number(r)];
+      switch (op) {
+        case "`+`":
+          return a + b;
+        case "`-`":
+          return a - b;
+        case "`*`":
+          return a * b;
+        case "`/`":
+          return a / b;
+        case "`%`":
+          return a % b;
+        case "`==`":
+          return a === b;
+        case "`!=`":
+          return a !== b;
+        case "`>`":
+          return a > b;
+        case "`<`":
+          return a < b;
+        case "`>=`":
+          return a >= b;
+        case "`<=`":
+          return a <= b;
+      }
+    }
+    if (tl === "bool" && tr === "bool") {
+      if (op === "`==`") return l === r;
+      if (op === "`!=`") return l !== r;
+    }
+    if (tl === "int" && tr === "int") {
+      switch (op) {
+        case "`+`":
+          return checked(l + r, `${op} of ${l} and ${r}`);
+        case "`-`":
+          return checked(l - r, `${op} of ${l} and ${r}`);
+        case "`*`":
+          return checked(l * r, `${op} of ${l} and ${r}`);
+        case "`/`":
+          if (r === 0n) fail("Division by zero");
+          return checked(l / r, `${op} of ${l} and ${r}`);
+        case "`%`":
+          if (r === 0n) fail("Division by zero");
+          return int(l % r);
+        case "`**`":
+          return pow(l, r);
+        case "`==`":
+          return l === r;
+        case "`!=`":
+          return l !== r;
+        case "`>`":
+          return l > r;
+        case "`<`":
+          return l < r;
+        case "`>=`":
+          return l >= r;
+        case "`<=`":
+          return l <= r;
+      }
+    }
+    if (tl === "str" && tr === "str") {
+      switch (op) {
+        case "`+`":
+          return l + r;
+        case "`==`":
+          return l === r;
+        case "`!=`":
+          return l !== r;
+        case "`>`":
+          return l > r;
+        case "`<`":
+          return l < r;
+        case "`>=`":
+          return l >= r;
+        case "`<=`":
+          return l <= r;
+      }
+    }
+    if (tl === "bytes" && tr === "bytes" && op === "`+`") return new Uint8Array([...l, ...r]);
+    if (tl === "bytes" && tr === "int" && op === "`[...]`") {
+      return r < 0n || r >= BigInt(l.length) ? null : BigInt(l[Number(r)]);
+    }
+    if (tl === "array" && tr === "int" && op === "`[...]`") {
+      return r < 0n || r >= BigInt(l.length) ? null : l[Number(r)];
+    }
+    if (tl === "tuple" && tr === "int" && op === "`[...]`") {
+      return r < 0n || r >= BigInt(l.values.length) ? null : l.values[Number(r)];
+    }
+    if (tl === "tuple" && tr === "tuple" && (op === "`==`" || op === "`!=`")) {
+      return same(l, r) === (op === "`==`");
+    }
+    if (tl === "error" && tr === "str" && op === "`[...]`") {
+      return r === "message" ? l.message : r === "data" ? l.data : null;
+    }
+    if (tl === "struct" && tr === "struct" && (op === "`==`" || op === "`!=`")) {
+      return same(l, r) === (op === "`==`");
+    }
+    if (tl === "struct" && tr === "str" && op === "`[...]`") {
+      const i = l.ty.$params.indexOf(r);
+      return i < 0 ? (overloaded(op, l, r) ?? null) : l.values[i];
+    }
+    if (tl === "hash" && op === "`[...]`") {
+      if (["int", "str", "bool"].includes(tr)) return l.has(r) ? l.get(r) : (overloaded(op, l, r) ?? null);
+    }
+    const output = tl === "hash" && op === "`[...]`" ? undefined : overloaded(op, l, r);
+    if (output !== undefined) return output;
+    fail(`Invalid operation (${op}) between ${inspect(l)} and ${inspect(r)}!`);
+  };
+
+  // Mirrors `Expression::overloaded`: `undefined` when `l` has no hook for `op`.
+  const hooks = {
+    "`+`": "__add",
+    "`-`": "__sub",
+    "`*`": "__mul",
+    "`/`": "__div",
+    "`%`": "__mod",
+    "`**`": "__pow",
+    "`==`": "__eq",
+    "`!=`": "__eq",
+    "`>`": "__gt",
+    "`<`": "__lt",
+    "`>=`": "__ge",
+    "`<=`": "__le",
+    "`[...]`": "__index",
+  };
+  const overloaded = (op, l, r) => {
+    const name = hooks[op];
+    let hook;
+    if (type(l) === "hash") hook = l.get(name);
+    if (type(l) === "struct") hook = l.values[l.ty.$params.indexOf(name)];
+    if (type(hook) !== "fn") return undefined;
+    const output = call(hook, [l, r], name, name);
+    return op === "`!=`" ? !truthy(output) : output;
+  };
+
+  // Mirrors `eval::pattern::matches`: `spec` is "_", "$" for a binding, ["lit", value],
+  // ["array", items, rest or null], ["tuple", items], ["hash", [[key, spec], ...]] or
+  // ["struct", name, [spec, ...]]. Returns the bound values in order, or null when `v`
+  // doesn't match.
+  const match = (spec, v, bound = []) => {
+    if (spec === "_") return bound;
+    if (spec === "$") {
+      bound.push(v);
+      return bound;
+    }
+    if (spec[0] === "lit") return v === spec[1] ? bound : null;
+    if (spec[0] === "array") {
+      const [, items, rest] = spec;
+      if (type(v) !== "array") return null;
+      if (rest === null ? v.length !== items.length : v.length < items.length) return null;
+      for (let i = 0; i < items.length; i++) {
+        if (match(items[i], v[i], bound) === null) return null;
+      }
+      return rest === null ? bound : match(rest, v.slice(items.length), bound);
+    }
+    if (spec[0] === "tuple") {
+      const [, items] = spec;
+      if (type(v) !== "tuple" || v.values.length !== items.length) return null;
+      for (let i = 0; i < items.length; i++) {
+        if (match(items[i], v.values[i], bound) === null) return null;
+      }
+      return bound;
+    }
+    if (spec[0] === "struct") {
+      const [, name, fields] = spec;
+      if (type(v) !== "struct" || v.ty.$struct !== name || v.values.length !== fields.length) return null;
+      for (let i = 0; i < fields.length; i++) {
+        if (match(fields[i], v.values[i], bound) === null) return null;
+      }
+      return bound;
+    }
+    if (type(v) !== "hash") return null;
+    for (const [k, s] of spec[1]) {
+      if (!v.has(k) || match(s, v.get(k), bound) === null) return null;
+    }
+    return bound;
+  };
+
+  const rank = { bool: 0, int: 1, str: 2 };
+  const compare = (a, b) => rank[type(a)] - rank[type(b)] || (a < b ? -1 : a > b ? 1 : 0);
+
+  const iter = (v) => {
+    switch (type(v)) {
+      case "iterator":
+        return v;
+      case "array":
+      case "str":
+      case "bytes":
+      case "hash": {
+        const items =
+          type(v) === "hash"
+            ? [...v].sort(([a], [b]) => compare(a, b))
+            : type(v) === "bytes"
+              ? [...v].map(BigInt)
+              : [...v];
+        let index = 0;
+        return new Iter(() => items[index++]);
+      }
+    }
+    fail(`Cannot iterate over ${inspect(v)}`);
+  };
+
+  const iterate = function* (v) {
+    const it = iter(v);
+    for (let item = it.next(); item !== undefined; item = it.next()) yield item;
+  };
+
+  // Mirrors `Iter::tee`: `it` reads the items of `source` from `link` on, each link being
+  // filled by the first iterator to reach it.
+  const branch = (it, source, link) => {
+    it.$source = source;
+    it.$link = link;
+    it.next = () => {
+      const link = it.$link;
+      if (link.next === undefined) {
+        link.item = source();
+        link.next = {};
+      }
+      it.$link = link.next;
+      return link.item;
+    };
+    return it;
+  };
+
+  const tee = (it) => {
+    if (it.$link === undefined) branch(it, it.next, {});
+    return branch(new Iter(null), it.$source, it.$link);
+  };
+
+  // Mirrors `Object::shallow_copy`, `item` being applied to the items of containers.
+  const copy = (v, item) => {
+    if (Array.isArray(v)) return v.map(item);
+    if (v instanceof Map) return new Map([...v].map(([k, x]) => [k, item(x)]));
+    if (v instanceof TupleValue) return new TupleValue(v.values.map(item));
+    if (v instanceof StructValue) return new StructValue(v.ty, v.values.map(item));
+    if (v instanceof ErrorValue) return new ErrorValue(v.message, item(v.data));
+    if (v instanceof Iter) return tee(v);
+    return v;
+  };
+
+  const builtin = (name, f) => {
+    f.$builtin = name;
+    return f;
+  };
+
+  const arity = (name, args, n) => {
+    if (args.length !== n) {
+      fail(`Builtin function \`${name}\` expects ${n} arg, found ${args.length}.`);
+    }
+  };
+
+  const text = (name, args) => {
+    arity(name, args, 1);
+    if (type(args[0]) !== "str") {
+      fail(
+        `Invalid argument for builtin function \`${name}\`, expected string, found ${inspect(args[0])}`,
+      );
+    }
+    return args[0];
+  };
+
+  // Mirrors `eval::query`: the rows, then what to compute for each of them.
+  const query = (name, args, max) => {
+    if (args.length < 2 || args.length > max) {
+      const n = max === 2 ? "2" : `2 to ${max}`;
+      fail(`Builtin function \`${name}\` expects ${n} args, found ${args.length}.`);
+    }
+    if (type(args[0]) !== "array") {
+      fail(`Invalid first argument for builtin function \`${name}\`, expected array, found ${inspect(args[0])}`);
+    }
+    return args[0];
+  };
+
+  const by = (name, f) => {
+    if (type(f) === "fn") return (row) => f(row);
+    if (!["int", "str", "bool"].includes(type(f))) {
+      fail(`Invalid second argument for builtin function \`${name}\`, expected key or function, found ${inspect(f)}`);
+    }
+    return (row) => {
+      if (type(row) !== "hash") fail(`Invalid row ${inspect(row)} for builtin function \`${name}\`, expected hash`);
+      return row.has(f) ? row.get(f) : null;
+    };
+  };
+
+  // Mirrors `eval::csv`.
+  const csvRecords = (input) => {
+    const records = [];
+    let record = [];
+    let field = "";
+    for (let i = 0; i < input.length; i++) {
+      const c = input[i];
+      if (c === '"' && field === "") {
+        for (i++; ; i++) {
+          if (i >= input.length) fail("Invalid CSV, unterminated quote");
+          if (input[i] === '"' && input[i + 1] === '"') {
+            field += '"';
+            i++;
+          } else if (input[i] === '"') {
+            break;
+          } else {
+            field += input[i];
+          }
+        }
+      } else if (c === ",") {
+        record.push(field);
+        field = "";
+      } else if (c === "\r" && input[i + 1] === "\n") {
+        continue;
+      } else if (c === "\n") {
+        record.push(field);
+        records.push(record);
+        [record, field] = [[], ""];
+      } else {
+        field += c;
+      }
+    }
+    if (field !== "" || record.length > 0) records.push([...record, field]);
+    return records;
+  };
+
+  const csvParse = (input) => {
+    const [header, ...records] = csvRecords(input);
+    return records.map((record, i) => {
+      if (record.length !== header.length) {
+        fail(
+          `Invalid CSV, record ${i + 2} has ${record.length} fields but the header has ${header.length}`,
+        );
+      }
+      return new Map(
+        header.map((column, j) => {
+          const field = record[j];
+          return [column, /^-?\d+$/.test(field) && String(BigInt(field)) === field ? BigInt(field) : field];
+        }),
+      );
+    });
+  };
+
+  const csvFormat = (rows, columns) => {
+    const bad = columns?.find((c) => !["str", "int", "bool"].includes(type(c)));
+    if (bad !== undefined) {
+      fail(`Invalid column ${inspect(bad)} for builtin function \`csv_write\`, expected string, int or bool`);
+    }
+    let header = columns;
+    if (header === undefined && rows.some((row) => type(row) === "hash")) {
+      const keys = rows.flatMap((row) => (type(row) === "hash" ? [...row.keys()] : []));
+      header = [...new Set(keys)].sort(compare);
+    }
+    const record = (fields) =>
+      fields
+        .map((field) => {
+          if (!["null", "int", "float", "bool", "str"].includes(type(field))) {
+            fail(
+              `Invalid field ${inspect(field)} for builtin function \`csv_write\`, expected null, int, float, bool or string`,
+            );
+          }
+          const s = field === null ? "" : inspect(field);
+          return /[,"\n\r]/.test(s) ? '"' + s.replaceAll('"', '""') + '"' : s;
+        })
+        .join(",") + "\n";
+    let output = header === undefined ? "" : record(header);
+    for (const row of rows) {
+      if (type(row) === "array") {
+        output += record(row);
+      } else if (type(row) === "hash") {
+        output += record(header.map((key) => (row.has(key) ? row.get(key) : null)));
+      } else {
+        fail(`Invalid row ${inspect(row)} for builtin function \`csv_write\`, expected hash or array`);
+      }
+    }
+    return output;
+  };
+
+  // Bytes of a bytes or string argument, strings being encoded in UTF-8.
+  const data = (name, position, v) => {
+    if (type(v) === "bytes") return v;
+    if (type(v) === "str") return new TextEncoder().encode(v);
+    fail(
+      `Invalid ${position}argument for builtin function \`${name}\`, expected bytes or string, found ${inspect(v)}`,
+    );
+  };
+
+  const adapter = (name, make) =>
+    builtin(name, (...args) => {
+      if (args.length !== 2) {
+        fail(`Builtin function \`${name}\` expects 2 args, found ${args.length}.`);
+      }
+      const [v, f] = args;
+      if (type(f) !== "fn") {
+        fail(
+          `Invalid second argument for builtin function \`${name}\`, expected function, found ${inspect(f)}`,
+        );
+      }
+      return make(iter(v), f);
+    });
+
+  // Filled with the `help` of each builtin by the translated script.
+  const docs = {};
+
+  const builtins = {
+    len: builtin("len", (...args) => {
+      arity("len", args, 1);
+      const [v] = args;
+      switch (type(v)) {
+        case "str":
+          return BigInt(new TextEncoder().encode(v).length);
+        case "array":
+        case "bytes":
+          return BigInt(v.length);
+        case "hash":
+          return BigInt(v.size);
+        case "tuple":
+          return BigInt(v.values.length);
+      }
+      fail(
+        `Invalid argument for builtin function \`len\`, expected string or array, found ${inspect(v)}`,
+      );
+    }),
+    first: builtin("first", (...args) => {
+      arity("first", args, 1);
+      const [v] = args;
+      if (type(v) === "str") return v.length === 0 ? null : [...v][0];
+      if (type(v) === "array") return v.length === 0 ? null : v[0];
+      fail(
+        `Invalid argument for builtin function \`first\`, expected string or array, found ${inspect(v)}`,
+      );
+    }),
+    last: builtin("last", (...args) => {
+      arity("last", args, 1);
+      const [v] = args;
+      if (type(v) === "str") return v.length === 0 ? null : [...v].at(-1);
+      if (type(v) === "array") return v.length === 0 ? null : v.at(-1);
+      fail(
+        `Invalid argument for builtin function \`last\`, expected string or array, found ${inspect(v)}`,
+      );
+    }),
+    rest: builtin("rest", (...args) => {
+      arity("rest", args, 1);
+      const [v] = args;
+      if (type(v) === "str") return v.length === 0 ? null : [...v].slice(1).join("");
+      if (type(v) === "array") return v.length === 0 ? null : v.slice(1);
+      fail(
+        `Invalid argument for builtin function \`rest\`, expected string or array, found ${inspect(v)}`,
+      );
+    }),
+    push: builtin("push", (...args) => {
+      if (args.length < 2) {
+        fail(`Builtin function \`push\` expects at least 2 args, found ${args.length}.`);
+      }
+      const [a, b, ...rest] = args;
+      switch (type(a)) {
+        case "str":
+          for (const item of [b, ...rest]) {
+            if (type(item) !== "str") {
+              fail(
+                `Invalid item ${inspect(item)} for builtin function \`push\` on a string, expected string`,
+              );
+            }
+          }
+          return a + [b, ...rest].join("");
+        case "array":
+          return [...a, b, ...rest];
+        case "hash": {
+          if (rest.length > 0) {
+            fail(`Builtin function \`push\` expects 2 args with a hash, found ${args.length}.`);
+          }
+          const output = new Map(a);
+          if (type(b) === "array" && b.length === 2) {
+            output.set(key(b[0]), b[1]);
+          } else if (type(b) === "array") {
+            fail(
+              "Invalid second argument for builtin function `push`, expected array with 2 elements",
+            );
+          } else if (type(b) === "hash") {
+            for (const [k, v] of b) output.set(k, v);
+          } else {
+            fail(
+              "Invalid second argument for builtin function `push`, expected array with 2 elements or another hashmap",
+            );
+          }
+          return output;
+        }
+      }
+      fail(
+        `Invalid first argument for builtin function \`push\`, expected string or array, found ${inspect(a)}`,
+      );
+    }),
+    insert: builtin("insert", (...args) => {
+      if (args.length !== 3) {
+        fail(`Builtin function \`insert\` expects 3 args, found ${args.length}.`);
+      }
+      const [h, k, v] = args;
+      if (type(h) !== "hash") {
+        fail(`Invalid first argument for builtin function \`insert\`, expected hash, found ${inspect(h)}`);
+      }
+      return new Map(h).set(key(k), v);
+    }),
+    merge: builtin("merge", (...args) => {
+      if (args.length !== 2) {
+        fail(`Builtin function \`merge\` expects 2 args, found ${args.length}.`);
+      }
+      const [h, other] = args;
+      if (type(h) !== "hash") {
+        fail(`Invalid first argument for builtin function \`merge\`, expected hash, found ${inspect(h)}`);
+      }
+      if (type(other) !== "hash") {
+        fail(`Invalid second argument for builtin function \`merge\`, expected hash, found ${inspect(other)}`);
+      }
+      return new Map([...h, ...other]);
+    }),
+    slice: builtin("slice", (...args) => {
+      const [v, start, end] = args;
+      if (args.length < 2 || args.length > 3) {
+        fail(`Builtin function \`slice\` expects 2 or 3 args, found ${args.length}.`);
+      }
+      if (type(start) !== "int") {
+        fail(`Invalid second argument for builtin function \`slice\`, expected int, found ${inspect(start)}`);
+      }
+      if (args.length === 3 && type(end) !== "int") {
+        fail(`Invalid third argument for builtin function \`slice\`, expected int, found ${inspect(end)}`);
+      }
+      if (type(v) !== "array" && type(v) !== "bytes") {
+        fail(
+          `Invalid first argument for builtin function \`slice\`, expected array or bytes, found ${inspect(v)}`,
+        );
+      }
+      const clamp = (i) => Number(i < 0n ? 0n : i > BigInt(v.length) ? BigInt(v.length) : i);
+      const from = clamp(start);
+      return v.slice(from, Math.max(from, args.length === 3 ? clamp(end) : v.length));
+    }),
+    range: builtin("range", (...args) => {
+      for (const v of args) {
+        if (type(v) !== "int") {
+          fail(`Invalid argument for builtin function \`range\`, expected int, found ${inspect(v)}`);
+        }
+      }
+      if (args.length < 1 || args.length > 3) {
+        fail(`Builtin function \`range\` expects 1 to 3 args, found ${args.length}.`);
+      }
+      const [start, end, step] = args.length === 1 ? [0n, args[0], 1n] : [...args, 1n];
+      if (step === 0n) fail("The step of a range can't be 0");
+      let next = start;
+      return new Iter(() => {
+        if ((step > 0n && next >= end) || (step < 0n && next <= end)) return undefined;
+        const value = next;
+        next = int(next + step) === next + step ? next + step : end;
+        return value;
+      });
+    }),
+    iter: builtin("iter", (...args) => {
+      arity("iter", args, 1);
+      return iter(args[0]);
+    }),
+    next: builtin("next", (...args) => {
+      arity("next", args, 1);
+      return iter(args[0]).next() ?? null;
+    }),
+    collect: builtin("collect", (...args) => {
+      arity("collect", args, 1);
+      return [...iterate(args[0])];
+    }),
+    lines: builtin("lines", (...args) => {
+      const s = text("lines", args);
+      const lines = s.split("\n");
+      if (lines.at(-1) === "") lines.pop();
+      return lines.map((l) => (l.endsWith("\r") ? l.slice(0, -1) : l));
+    }),
+    words: builtin("words", (...args) => text("words", args).split(/\s+/).filter((w) => w)),
+    fields: builtin("fields", (...args) => {
+      const [s, sep] = args;
+      if (args.length === 2 && type(s) === "str" && type(sep) === "str" && sep !== "") {
+        return s.split(sep);
+      }
+      if (args.length === 2 && type(s) === "str") {
+        fail(
+          `Invalid second argument for builtin function \`fields\`, expected non-empty string, found ${inspect(sep)}`,
+        );
+      }
+      if (args.length === 2) {
+        fail(
+          `Invalid first argument for builtin function \`fields\`, expected string, found ${inspect(s)}`,
+        );
+      }
+      fail(`Builtin function \`fields\` expects 2 args, found ${args.length}.`);
+    }),
+    bytes: builtin("bytes", (...args) => {
+      const [v, encoding = "utf8"] = args;
+      if (args.length < 1 || args.length > 2) {
+        fail(`Builtin function \`bytes\` expects 1 or 2 args, found ${args.length}.`);
+      }
+      if (type(encoding) !== "str") {
+        fail(`Invalid second argument for builtin function \`bytes\`, expected string, found ${inspect(encoding)}`);
+      }
+      switch (type(v)) {
+        case "str":
+          if (encoding === "utf8") return new TextEncoder().encode(v);
+          if (encoding !== "latin1") fail(`Unknown encoding \`${encoding}\`, expected utf8 or latin1`);
+          return Uint8Array.from([...v], (c) => {
+            if (c.codePointAt(0) > 255) fail(`Cannot encode \`${c}\` as latin1`);
+            return c.codePointAt(0);
+          });
+        case "bytes":
+          return v;
+        case "array":
+          return Uint8Array.from(v, (b) => {
+            if (type(b) !== "int" || b < 0n || b > 255n) {
+              fail(
+                `Invalid byte ${inspect(b)} for builtin function \`bytes\`, expected int from 0 to 255`,
+              );
+            }
+            return Number(b);
+          });
+      }
+      fail(
+        `Invalid first argument for builtin function \`bytes\`, expected string, bytes or array, found ${inspect(v)}`,
+      );
+    }),
+    decode: builtin("decode", (...args) => {
+      const [v, encoding = "utf8"] = args;
+      if (args.length < 1 || args.length > 2) {
+        fail(`Builtin function \`decode\` expects 1 or 2 args, found ${args.length}.`);
+      }
+      if (type(v) !== "bytes") {
+        fail(`Invalid first argument for builtin function \`decode\`, expected bytes, found ${inspect(v)}`);
+      }
+      if (type(encoding) !== "str") {
+        fail(`Invalid second argument for builtin function \`decode\`, expected string, found ${inspect(encoding)}`);
+      }
+      if (encoding === "latin1") return [...v].map((b) => String.fromCharCode(b)).join("");
+      if (encoding !== "utf8") fail(`Unknown encoding \`${encoding}\`, expected utf8 or latin1`);
+      try {
+        return new TextDecoder("utf-8", { fatal: true }).decode(v);
+      } catch {
+        fail(`Cannot decode ${inspect(v)} as utf8`);
+      }
+    }),
+    ...Object.fromEntries(
+      [
+        ["hex", (b) => Buffer.from(b).toString("hex")],
+        ["base64", (b) => Buffer.from(b).toString("base64")],
+        ["hash_sha256", (b) => new Uint8Array(require("crypto").createHash("sha256").update(b).digest())],
+      ].map(([name, encode]) => [
+        name,
+        builtin(name, (...args) => {
+          arity(name, args, 1);
+          return encode(data(name, "", args[0]));
+        }),
+      ]),
+    ),
+    from_hex: builtin("from_hex", (...args) => {
+      const s = text("from_hex", args);
+      if (!/^([0-9a-fA-F]{2})*$/.test(s)) fail(`Invalid hex string \`${s}\``);
+      return new Uint8Array(Buffer.from(s, "hex"));
+    }),
+    from_base64: builtin("from_base64", (...args) => {
+      const s = text("from_base64", args);
+      const digits = s.replace(/={1,2}$/, "");
+      if (!/^[A-Za-z0-9+/]*$/.test(digits) || digits.length % 4 === 1 || (digits !== s && s.length % 4 !== 0)) {
+        fail(`Invalid base64 string \`${s}\``);
+      }
+      return new Uint8Array(Buffer.from(digits, "base64"));
+    }),
+    hmac_sha256: builtin("hmac_sha256", (...args) => {
+      if (args.length !== 2) {
+        fail(`Builtin function \`hmac_sha256\` expects 2 args, found ${args.length}.`);
+      }
+      const [key, message] = [data("hmac_sha256", "first ", args[0]), data("hmac_sha256", "second ", args[1])];
+      return new Uint8Array(require("crypto").createHmac("sha256", key).update(message).digest());
+    }),
+    csv_parse: builtin("csv_parse", (...args) => csvParse(text("csv_parse", args))),
+    csv_read: builtin("csv_read", (...args) => {
+      const path = text("csv_read", args);
+      let input;
+      try {
+        input = require("fs").readFileSync(path, "utf8");
+      } catch (e) {
+        fail(`Cannot read ${path}: ${e.message}`);
+      }
+      return csvParse(input);
+    }),
+    csv_write: builtin("csv_write", (...args) => {
+      const [path, rows, columns] = args;
+      if (args.length < 2 || args.length > 3) {
+        fail(`Builtin function \`csv_write\` expects 2 or 3 args, found ${args.length}.`);
+      }
+      if (type(path) !== "str") {
+        fail(`Invalid first argument for builtin function \`csv_write\`, expected string, found ${inspect(path)}`);
+      }
+      if (type(rows) !== "array") {
+        fail(`Invalid second argument for builtin function \`csv_write\`, expected array, found ${inspect(rows)}`);
+      }
+      if (args.length === 3 && type(columns) !== "array") {
+        fail(`Invalid third argument for builtin function \`csv_write\`, expected array, found ${inspect(columns)}`);
+      }
+      const output = csvFormat(rows, columns);
+      try {
+        require("fs").writeFileSync(path, output);
+      } catch (e) {
+        fail(`Cannot write ${path}: ${e.message}`);
+      }
+      return BigInt(rows.length);
+    }),
+    where: builtin("where", (...args) => {
+      const rows = query("where", args, 2);
+      if (type(args[1]) !== "fn") {
+        fail(`Invalid second argument for builtin function \`where\`, expected function, found ${inspect(args[1])}`);
+      }
+      return rows.filter((row) => truthy(args[1](row)));
+    }),
+    select: builtin("select", (...args) => {
+      const rows = query("select", args, 2);
+      const columns = args[1];
+      if (type(columns) === "fn") return rows.map((row) => columns(row));
+      if (type(columns) !== "array") {
+        fail(
+          `Invalid second argument for builtin function \`select\`, expected array or function, found ${inspect(columns)}`,
+        );
+      }
+      const bad = columns.find((column) => !["int", "str", "bool"].includes(type(column)));
+      if (bad !== undefined) {
+        fail(`Invalid column ${inspect(bad)} for builtin function \`select\`, expected string, int or bool`);
+      }
+      const getters = columns.map((column) => [column, by("select", column)]);
+      return rows.map((row) => new Map(getters.map(([column, get]) => [column, get(row)])));
+    }),
+    group_by: builtin("group_by", (...args) => {
+      const rows = query("group_by", args, 2);
+      const get = by("group_by", args[1]);
+      const groups = new Map();
+      for (const row of rows) {
+        const k = key(get(row));
+        if (!groups.has(k)) groups.set(k, []);
+        groups.get(k).push(row);
+      }
+      return groups;
+    }),
+    order_by: builtin("order_by", (...args) => {
+      const rows = query("order_by", args, 3);
+      const get = by("order_by", args[1]);
+      const descending = args.length === 3 ? args[2] : false;
+      if (type(descending) !== "bool") {
+        fail(`Invalid third argument for builtin function \`order_by\`, expected bool, found ${inspect(descending)}`);
+      }
+      const keyed = rows.map((row) => [get(row), row]);
+      const kinds = { int: "number", float: "number", str: "str", bool: "bool" };
+      let kind = null;
+      for (const [k] of keyed) {
+        if (k === null) continue;
+        if (kinds[type(k)] === undefined || (kind !== null && kinds[type(k)] !== kind)) {
+          fail(
+            `Invalid sort key ${inspect(k)} for builtin function \`order_by\`, expected number, string or bool like the others`,
+          );
+        }
+        kind = kinds[type(k)];
+      }
+      const order = ([a], [b]) => (a === null ? (b === null ? 0 : -1) : b === null ? 1 : a < b ? -1 : a > b ? 1 : 0);
+      return keyed.sort((a, b) => (descending ? order(b, a) : order(a, b))).map(([, row]) => row);
+    }),
+    puts: builtin("puts", (...args) => {
+      for (const arg of args) process.stdout.write(inspect(arg) + "\n");
+      return null;
+    }),
+    table: builtin("table", (...args) => {
+      const [rows] = args;
+      if (args.length !== 1) fail(`Builtin function \`table\` expects 1 arg, found ${args.length}.`);
+      if (type(rows) !== "array") {
+        fail(`Invalid argument for builtin function \`table\`, expected array, found ${inspect(rows)}`);
+      }
+      const bad = rows.find((row) => type(row) !== "hash");
+      if (bad !== undefined) fail(`Invalid row ${inspect(bad)} for builtin function \`table\`, expected hash`);
+      const columns = [...new Set(rows.flatMap((row) => [...row.keys()]))].sort(compare);
+      if (columns.length === 0) return null;
+      const cell = (row, column) => (row.has(column) && row.get(column) !== null ? inspect(row.get(column)) : "");
+      const cells = rows.map((row) => columns.map((column) => cell(row, column)));
+      const numeric = columns.map((column) =>
+        rows.every((row) => !row.has(column) || ["null", "int", "float"].includes(type(row.get(column)))),
+      );
+      const header = columns.map((column) => inspect(column));
+      const length = (s) => [...s].length;
+      const widths = columns.map((_, i) => Math.max(...[header, ...cells].map((line) => length(line[i]))));
+      const border = widths.map((width) => "+" + "-".repeat(width + 2)).join("") + "+\n";
+      const line = (fields, right) =>
+        fields
+          .map((field, i) => {
+            const padding = " ".repeat(widths[i] - length(field));
+            return right[i] ? `| ${padding}${field} ` : `| ${field}${padding} `;
+          })
+          .join("") + "|\n";
+      let output = border + line(header, []) + border;
+      for (const row of cells) output += line(row, numeric);
+      process.stdout.write(output + border);
+      return null;
+    }),
+    dump: builtin("dump", (...args) => {
+      if (args.length === 1) return pretty(args[0], 8, 100);
+      if (args.length !== 3) {
+        fail(`Builtin function \`dump\` expects 1 or 3 args, found ${args.length}.`);
+      }
+      const bad = args.slice(1).find((n) => type(n) !== "int" || n < 0n);
+      if (bad !== undefined) {
+        fail(
+          `Invalid argument for builtin function \`dump\`, expected non-negative int, found ${inspect(bad)}`,
+        );
+      }
+      return pretty(args[0], Number(args[1]), Number(args[2]));
+    }),
+    copy: builtin("copy", (...args) => {
+      arity("copy", args, 1);
+      return copy(args[0], (v) => v);
+    }),
+    deepcopy: builtin("deepcopy", (...args) => {
+      arity("deepcopy", args, 1);
+      const copies = new Map();
+      const deep = (v) => {
+        if (copies.has(v)) return copies.get(v);
+        const c = copy(v, deep);
+        if (Array.isArray(v) || v instanceof Iter) copies.set(v, c);
+        return c;
+      };
+      return deep(args[0]);
+    }),
+    partial: builtin("partial", (f, ...bound) => {
+      if (f === undefined) fail("Builtin function `partial` expects at least 1 arg, found 0.");
+      if (type(f) !== "fn" || f.$builtin) {
+        fail(
+          `Invalid first argument for builtin function \`partial\`, expected function, found ${inspect(f)}`,
+        );
+      }
+      if (bound.length > f.$params.length) checkArity("fn", f.$params, bound);
+      const rest = f.$params.slice(bound.length);
+      return func(rest, (...r) => f(...bound, ...r), undefined, f.$doc);
+    }),
+    help: builtin("help", (...args) => {
+      arity("help", args, 1);
+      const [v] = args;
+      if (type(v) === "fn" && !v.$builtin) {
+        const signature = `fn(${v.$params.join(", ")})`;
+        return v.$doc === null ? signature : signature + "\n" + v.$doc;
+      }
+      const name = type(v) === "fn" ? v.$builtin : v;
+      if (type(name) !== "str") {
+        fail(
+          `Invalid argument for builtin function \`help\`, expected string or function, found ${inspect(name)}`,
+        );
+      }
+      if (!Object.hasOwn(docs, name)) {
+        fail(`Invalid argument for builtin function \`help\`, \`${name}\` is not a builtin`);
+      }
+      return docs[name];
+    }),
+    error: builtin("error", (...args) => {
+      const [message, data = null] = args;
+      if (args.length < 1 || args.length > 2) {
+        fail(`Builtin function \`error\` expects 1 or 2 args, found ${args.length}.`);
+      }
+      if (type(message) !== "str") {
+        fail(
+          `Invalid first argument for builtin function \`error\`, expected string, found ${inspect(message)}`,
+        );
+      }
+      return new ErrorValue(message, data);
+    }),
+    is_error: builtin("is_error", (...args) => {
+      arity("is_error", args, 1);
+      return type(args[0]) === "error";
+    }),
+    compose: builtin("compose", (...args) => {
+      if (args.length !== 2) {
+        fail(`Builtin function \`compose\` expects 2 args, found ${args.length}.`);
+      }
+      const bad = args.find((f) => type(f) !== "fn");
+      if (bad !== undefined) {
+        fail(
+          `Invalid argument for builtin function \`compose\`, expected function, found ${inspect(bad)}`,
+        );
+      }
+      const [f, g] = args;
+      return func(["x"], (x) => f(g(x)));
+    }),
+    args: builtin("args", (...args) => {
+      if (args.length !== 0) fail(`Builtin function \`args\` expects 0 args, found ${args.length}.`);
+      return process.argv.slice(2);
+    }),
+    parse_args: builtin("parse_args", (...args) => {
+      const [spec, argv = process.argv.slice(2)] = args;
+      if (args.length < 1 || args.length > 2) {
+        fail(`Builtin function \`parse_args\` expects 1 or 2 args, found ${args.length}.`);
+      }
+      if (type(spec) !== "hash") {
+        fail(`Invalid first argument for builtin function \`parse_args\`, expected hash, found ${inspect(spec)}`);
+      }
+      const badArgv = type(argv) === "array" ? argv.find((a) => type(a) !== "str") : argv;
+      if (badArgv !== undefined) {
+        fail(
+          `Invalid second argument for builtin function \`parse_args\`, expected array of strings, found ${inspect(badArgv)}`,
+        );
+      }
+      const options = new Map();
+      for (const [k, d] of spec) {
+        if (type(k) !== "str" || !["bool", "int", "str", "null"].includes(type(d))) {
+          fail(
+            `Invalid option \`${inspect(k)}: ${inspect(d)}\` for builtin function \`parse_args\`, expected a string name and a bool, int, string or null default`,
+          );
+        }
+        options.set(k, d);
+      }
+      const positional = [];
+      for (let i = 0; i < argv.length; i++) {
+        const arg = argv[i];
+        if (arg === "--") {
+          positional.push(...argv.slice(i + 1));
+          break;
+        }
+        if (!arg.startsWith("--")) {
+          positional.push(arg);
+          continue;
+        }
+        const eq = arg.indexOf("=");
+        const name = eq === -1 ? arg.slice(2) : arg.slice(2, eq);
+        let value = eq === -1 ? undefined : arg.slice(eq + 1);
+        if (!spec.has(name)) fail(`Unknown option \`--${name}\``);
+        const d = spec.get(name);
+        if (type(d) === "bool") {
+          if (value !== undefined) fail(`Option \`--${name}\` doesn't take a value`);
+          options.set(name, true);
+          continue;
+        }
+        if (value === undefined) {
+          if (i + 1 === argv.length) fail(`Option \`--${name}\` expects a value`);
+          value = argv[++i];
+        }
+        if (type(d) === "int") {
+          if (!/^[+-]?[0-9]+$/.test(value) || int(BigInt(value)) !== BigInt(value)) {
+            fail(`Option \`--${name}\` expects an int, found \`${value}\``);
+          }
+          value = BigInt(value);
+        }
+        options.set(name, value);
+      }
+      options.set("_", positional);
+      return options;
+    }),
+    ...Object.fromEntries(
+      ["send_host", "recv_host", "spawn", "send", "recv", "sql_open", "sql_query", "sql_exec"].map((name) => [
+        name,
+        builtin(name, () => fail(`Builtin function \`${name}\` isn't supported in JavaScript`)),
+      ]),
+    ),
+    read_stdin: builtin("read_stdin", (...args) => {
+      if (args.length !== 0) {
+        fail(`Builtin function \`read_stdin\` expects 0 args, found ${args.length}.`);
+      }
+      return require("fs").readFileSync(0, "utf8");
+    }),
+    map: adapter("map", (it, f) =>
+      new Iter(() => {
+        const item = it.next();
+        return item === undefined ? undefined : call(f, [item]);
+      }),
+    ),
+    filter: adapter("filter", (it, f) =>
+      new Iter(() => {
+        for (let item = it.next(); item !== undefined; item = it.next()) {
+          if (truthy(call(f, [item]))) return item;
+        }
+        return undefined;
+      }),
+    ),
+  };
+
+  return {
+    MonkeyError,
+    fail,
+    inspect,
+    truthy,
+    hash,
+    func,
+    generator,
+    call,
+    callKeywords,
+    struct,
+    tuple,
+    field,
+    not,
+    neg,
+    plus,
+    infix,
+    match,
+    iterate,
+    builtins,
+    docs,
+  };
+})();
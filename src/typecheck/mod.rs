@@ -0,0 +1,500 @@
+use crate::analysis::Type;
+use crate::ast::Pattern;
+use crate::error::ErrorCode;
+use crate::eval::is_builtin;
+use crate::{Expression, PrefixOperator, Program, Statement, TypeAnnotation};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TypeError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Checks the program against its type annotations.
+/// Typing is gradual: unannotated bindings are only checked when their type can be inferred.
+pub fn typecheck(program: &Program) -> Vec<TypeError> {
+    let mut checker = Checker {
+        scopes: vec![HashMap::new()],
+        return_types: vec![],
+        errors: vec![],
+    };
+    for statement in &program.statements {
+        checker.check_statement(statement);
+    }
+    checker.errors
+}
+
+#[derive(Debug, Clone)]
+struct Signature {
+    params: Vec<Type>,
+    result: Type,
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    ty: Type,
+    annotated: bool,
+    signature: Option<Signature>,
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Binding>>,
+    return_types: Vec<Type>,
+    errors: Vec<TypeError>,
+}
+
+impl Checker {
+    fn check_statement(&mut self, statement: &Statement) -> Type {
+        match statement {
+            Statement::Expr(expr) => self.infer(expr),
+            Statement::Yield { value } => {
+                self.infer(value);
+                Type::Unknown
+            }
+            Statement::Block(stmts) => self.check_block(stmts),
+            Statement::Break | Statement::Continue => Type::Unknown,
+            Statement::Let {
+                name,
+                annotation,
+                value,
+                ..
+            } => {
+                let actual = self.infer(value);
+                let previous = self.scopes.last().and_then(|s| s.get(name)).cloned();
+                let (ty, annotated) = match (annotation, previous) {
+                    (Some(annotation), _) => (Type::from(*annotation), true),
+                    (None, Some(previous)) if previous.annotated => (previous.ty, true),
+                    (None, _) => (actual, false),
+                };
+                if annotated && !ty.accepts(actual) {
+                    self.error(
+                        ErrorCode::AnnotationMismatch,
+                        format!("`{name}` has type {ty} but is bound to a {actual}"),
+                    );
+                }
+                let signature = match value {
+                    Expression::Func {
+                        arg_types,
+                        return_type,
+                        ..
+                    } => Some(signature_of(arg_types, return_type)),
+                    _ => None,
+                };
+                self.bind(
+                    name,
+                    Binding {
+                        ty,
+                        annotated,
+                        signature,
+                    },
+                );
+                Type::Unknown
+            }
+            Statement::Assign { name, value } => {
+                let actual = self.infer(value);
+                if let Some(binding) = self.scopes.iter_mut().rev().find_map(|s| s.get_mut(name)) {
+                    if !binding.annotated {
+                        binding.ty = binding.ty.join(actual);
+                        binding.signature = None;
+                    } else if !binding.ty.accepts(actual) {
+                        let ty = binding.ty;
+                        self.error(
+                            ErrorCode::AnnotationMismatch,
+                            format!("`{name}` has type {ty} but is assigned a {actual}"),
+                        );
+                    }
+                }
+                Type::Unknown
+            }
+            // The constructor: its arity isn't checked as keyword arguments may follow.
+            Statement::Struct { name, .. } => {
+                self.bind(
+                    name,
+                    Binding {
+                        ty: Type::Function,
+                        annotated: false,
+                        signature: None,
+                    },
+                );
+                Type::Unknown
+            }
+            Statement::Destructure { pattern, value } => {
+                self.infer(value);
+                self.bind_pattern(pattern);
+                Type::Unknown
+            }
+            Statement::Return { value } => {
+                let actual = self.infer(value);
+                if let Some(&expected) = self.return_types.last()
+                    && !expected.accepts(actual)
+                {
+                    self.error(
+                        ErrorCode::ReturnTypeMismatch,
+                        format!("Function should return {expected}, found {actual}"),
+                    );
+                }
+                Type::Unknown
+            }
+        }
+    }
+
+    fn check_block(&mut self, stmts: &[Statement]) -> Type {
+        let mut output = Type::Null;
+        for statement in stmts {
+            output = self.check_statement(statement);
+        }
+        output
+    }
+
+    fn infer(&mut self, expr: &Expression) -> Type {
+        match expr {
+            Expression::Bool(_) => Type::Bool,
+            Expression::Int(_) => Type::Int,
+            Expression::Float(_) => Type::Float,
+            Expression::String(_) => Type::String,
+            Expression::Ident(ident) if ident == "null" => Type::Null,
+            Expression::Ident(ident) => match self.lookup(ident) {
+                Some(binding) => binding.ty,
+                None if is_builtin(ident) => Type::Function,
+                None => Type::Unknown,
+            },
+            Expression::Array(content) => {
+                for e in content {
+                    self.infer(e);
+                }
+                Type::Array
+            }
+            Expression::Tuple(content) => {
+                for e in content {
+                    self.infer(e);
+                }
+                Type::Tuple
+            }
+            Expression::Hash { pairs, .. } => {
+                for (k, v) in pairs {
+                    self.infer(k);
+                    self.infer(v);
+                }
+                Type::Hash
+            }
+            Expression::Field { object, .. } => {
+                self.infer(object);
+                Type::Unknown
+            }
+            Expression::Prefix { operator, right } => {
+                let right = self.infer(right);
+                match operator {
+                    PrefixOperator::Not => Type::Bool,
+                    PrefixOperator::Neg | PrefixOperator::Plus if Type::Int.accepts(right) => {
+                        Type::Int
+                    }
+                    PrefixOperator::Neg | PrefixOperator::Plus if right == Type::Float => {
+                        Type::Float
+                    }
+                    PrefixOperator::Neg => {
+                        self.error(
+                            ErrorCode::TypeMismatch,
+                            format!("A {right} cannot be negated"),
+                        );
+                        Type::Unknown
+                    }
+                    PrefixOperator::Plus => {
+                        self.error(
+                            ErrorCode::TypeMismatch,
+                            format!("Unary `+` expects a number, found a {right}"),
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            Expression::Infix {
+                operator,
+                left,
+                right,
+            } => {
+                let left = self.infer(left);
+                let right = self.infer(right);
+                match Type::infix(operator, left, right) {
+                    Some(ty) => ty,
+                    None => {
+                        self.error(
+                            ErrorCode::TypeMismatch,
+                            format!("Invalid operation ({operator}) between {left} and {right}"),
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            Expression::Cond { cond, then_, else_ } => {
+                self.infer(cond);
+                let then_ty = self.check_block(then_);
+                match else_ {
+                    Some(stmts) => then_ty.join(self.check_block(stmts)),
+                    None => then_ty.join(Type::Null),
+                }
+            }
+            Expression::For {
+                name,
+                iterable,
+                body,
+            } => {
+                self.infer(iterable);
+                // Elements are untyped, an annotated variable keeps its annotation.
+                if !self.lookup(name).is_some_and(|b| b.annotated) {
+                    self.bind(
+                        name,
+                        Binding {
+                            ty: Type::Unknown,
+                            annotated: false,
+                            signature: None,
+                        },
+                    );
+                }
+                self.check_block(body);
+                Type::Null
+            }
+            Expression::While { cond, body } => {
+                self.infer(cond);
+                self.check_block(body);
+                Type::Null
+            }
+            Expression::Match { subject, arms } => {
+                self.infer(subject);
+                // Without an arm matching everything, the match can evaluate to null.
+                let exhaustive = arms
+                    .iter()
+                    .any(|arm| arm.guard.is_none() && arm.pattern.is_irrefutable());
+                let mut output = if exhaustive { None } else { Some(Type::Null) };
+                for arm in arms {
+                    self.bind_pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.infer(guard);
+                    }
+                    let ty = self.infer(&arm.value);
+                    output = Some(output.map_or(ty, |output| output.join(ty)));
+                }
+                output.unwrap_or(Type::Null)
+            }
+            Expression::Func {
+                args,
+                arg_types,
+                return_type,
+                body,
+            } => {
+                let signature = signature_of(arg_types, return_type);
+                self.scopes.push(HashMap::new());
+                for (name, &ty) in args.iter().zip(&signature.params) {
+                    self.bind(
+                        name,
+                        Binding {
+                            ty,
+                            annotated: ty != Type::Unknown,
+                            signature: None,
+                        },
+                    );
+                }
+                self.return_types.push(signature.result);
+                // A generator returns an iterator, which has no type of its own.
+                let actual = match body.last() {
+                    Some(Statement::Expr(_)) if !body.iter().any(Statement::yields) => {
+                        self.check_block(body)
+                    }
+                    _ => {
+                        self.check_block(body);
+                        Type::Unknown
+                    }
+                };
+                if !signature.result.accepts(actual) {
+                    self.error(
+                        ErrorCode::ReturnTypeMismatch,
+                        format!(
+                            "Function should return {}, found {actual}",
+                            signature.result
+                        ),
+                    );
+                }
+                self.return_types.pop();
+                self.scopes.pop();
+                Type::Function
+            }
+            Expression::Call {
+                func, args, kwargs, ..
+            } => {
+                let callee = self.infer(func);
+                let actual: Vec<Type> = args.iter().map(|a| self.infer(a)).collect();
+                for (_, value) in kwargs {
+                    self.infer(value);
+                }
+                if !Type::Function.accepts(callee) {
+                    self.error(
+                        ErrorCode::NotCallable,
+                        format!("A {callee} is not a function"),
+                    );
+                    return Type::Unknown;
+                }
+                let signature = match func.as_ref() {
+                    Expression::Ident(ident) => self.lookup(ident).and_then(|b| b.signature),
+                    Expression::Func {
+                        arg_types,
+                        return_type,
+                        ..
+                    } => Some(signature_of(arg_types, return_type)),
+                    _ => None,
+                };
+                let Some(signature) = signature else {
+                    return match func.as_ref() {
+                        Expression::Ident(ident) if ident == "len" => Type::Int,
+                        _ => Type::Unknown,
+                    };
+                };
+                // Signatures don't name their parameters, so keyword arguments aren't checked.
+                if kwargs.is_empty() && signature.params.len() != actual.len() {
+                    self.error(
+                        ErrorCode::ArityMismatch,
+                        format!(
+                            "Function expects {} args, found {}",
+                            signature.params.len(),
+                            actual.len()
+                        ),
+                    );
+                }
+                for (i, (expected, actual)) in signature.params.iter().zip(actual).enumerate() {
+                    if !expected.accepts(actual) {
+                        self.error(
+                            ErrorCode::TypeMismatch,
+                            format!("Argument {} should be a {expected}, found {actual}", i + 1),
+                        );
+                    }
+                }
+                signature.result
+            }
+        }
+    }
+
+    /// Binds the names of the pattern, whose values are untyped, as a `for` loop does.
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        for name in pattern.names() {
+            if !self.lookup(name).is_some_and(|b| b.annotated) {
+                self.bind(
+                    name,
+                    Binding {
+                        ty: Type::Unknown,
+                        annotated: false,
+                        signature: None,
+                    },
+                );
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.scopes.iter().rev().find_map(|s| s.get(name)).cloned()
+    }
+
+    fn bind(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), binding);
+        }
+    }
+
+    fn error(&mut self, code: ErrorCode, message: String) {
+        self.errors.push(TypeError { code, message });
+    }
+}
+
+fn signature_of(
+    arg_types: &[Option<TypeAnnotation>],
+    return_type: &Option<TypeAnnotation>,
+) -> Signature {
+    Signature {
+        params: arg_types
+            .iter()
+            .map(|t| t.map_or(Type::Unknown, Type::from))
+            .collect(),
+        result: return_type.map_or(Type::Unknown, Type::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn errors(input: &str) -> Vec<String> {
+        let program = Parser::init(input).parse_program().unwrap();
+        typecheck(&program).into_iter().map(|e| e.message).collect()
+    }
+
+    #[test]
+    fn well_typed() {
+        assert!(errors("let x: int = 5; let y = x * 2; y").is_empty());
+        assert!(errors("let f = fn(a: int, b: int) -> int { a + b }; f(1, 2) + 3").is_empty());
+        assert!(errors("let f = fn(a) { a }; let s: str = f(1);").is_empty());
+        assert!(errors("let g: fn = fn(a: any) -> bool { return a == a; };").is_empty());
+        assert!(errors("let r: float = 2 * 1.5 - -0.5; let b: bool = r > 1;").is_empty());
+        assert!(errors("let i: int = +1; let f: float = +0.5;").is_empty());
+        assert!(errors("let mut s = 1; s = \"a\"; let t: str = s;").is_empty());
+    }
+
+    #[test]
+    fn codes() {
+        let program = Parser::init("let x: int = true; x(1)")
+            .parse_program()
+            .unwrap();
+        let codes: Vec<ErrorCode> = typecheck(&program).into_iter().map(|e| e.code).collect();
+        assert_eq!(
+            codes,
+            vec![ErrorCode::AnnotationMismatch, ErrorCode::NotCallable]
+        );
+    }
+
+    #[test]
+    fn ill_typed() {
+        assert_eq!(
+            errors("let x: int = \"five\";"),
+            vec!["`x` has type int but is bound to a str"]
+        );
+        assert_eq!(
+            errors("let x: int = 1 / 2.0;"),
+            vec!["`x` has type int but is bound to a float"]
+        );
+        assert_eq!(
+            errors("+true"),
+            vec!["Unary `+` expects a number, found a bool"]
+        );
+        assert_eq!(
+            errors("let x: int = 5; let x = true;"),
+            vec!["`x` has type int but is bound to a bool"]
+        );
+        assert_eq!(
+            errors("let mut x: int = 5; let f = fn() { x = \"six\"; }; x = 7;"),
+            vec!["`x` has type int but is assigned a str"]
+        );
+        assert_eq!(
+            errors("let f = fn(a: int) -> str { a }; f(true)"),
+            vec![
+                "Function should return str, found int",
+                "Argument 1 should be a int, found bool"
+            ]
+        );
+        assert_eq!(
+            errors("let f = fn(a) -> int { if (a) { return \"no\"; } 1 };"),
+            vec!["Function should return int, found str"]
+        );
+        assert_eq!(
+            errors("let a = \"a\" - 1; 5(1)"),
+            vec![
+                "Invalid operation (`-`) between str and int",
+                "A int is not a function"
+            ]
+        );
+    }
+}
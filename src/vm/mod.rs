@@ -0,0 +1,842 @@
+//! Runs the bytecode of the [`compiler`](crate::compiler) on a stack, much faster than
+//! evaluating the tree for programs making many calls, like recursive ones. It runs in
+//! an [`Environment`] holding the globals, with the builtins, policy, interrupt and
+//! budget of the evaluator, but:
+//!
+//! - generators can `yield` in any block of their body, even in an `if` used as a value;
+//! - a variable of a function is `null` until its `let`, rather than the outer one;
+//! - the bindings of a `match` arm are made before its guard runs, even if it fails;
+//! - steps count loop iterations and calls rather than statements.
+
+use crate::compiler::{Bytecode, Capture, CompiledFunction, Opcode, compile, read_u16};
+use crate::error::{Error, ErrorCode, Result, catch_unwind, fail};
+use crate::eval::pattern::matches;
+use crate::eval::{
+    Environment, ExecutionStats, HashLiteral, HashMapKey, Iter, Object, apply, bind_keywords,
+    field, measure, not_a_function,
+};
+use crate::prelude::*;
+use crate::{Expression, InfixOperator, Parser, PrefixOperator};
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+
+/// A variable of a function, shared with the closures capturing it.
+type Cell = Rc<RefCell<Object>>;
+
+/// A compiled function literal with the variables it captured.
+pub struct Closure {
+    bytecode: Rc<Bytecode>,
+    function: usize,
+    free: Vec<Cell>,
+    env: Rc<Environment>,
+}
+
+impl Closure {
+    pub fn function(&self) -> &CompiledFunction {
+        &self.bytecode.functions[self.function]
+    }
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Closure")
+            .field("function", &self.function)
+            .field("parameters", &self.function().parameters)
+            .finish()
+    }
+}
+
+/// Parses, compiles and runs `input` in `env`, like
+/// [`eval_with_env`](crate::eval::eval_with_env) evaluates it.
+pub fn run(input: &str, env: Rc<Environment>) -> Result<Object> {
+    catch_unwind(|| {
+        let program = Parser::init(input).parse_program()?;
+        #[cfg(feature = "std")]
+        crate::analysis::check_assignments(&crate::cst::parse(input))?;
+        let closure = Closure {
+            bytecode: Rc::new(compile(&program)?),
+            function: 0,
+            free: vec![],
+            env,
+        };
+        let mut vm = Vm::default();
+        vm.frames.push(Frame::new(Rc::new(closure), vec![]));
+        vm.execute()
+    })
+}
+
+/// Like [`run`], also reporting the resources the program used.
+pub fn run_with_stats(input: &str, env: Rc<Environment>) -> (Result<Object>, ExecutionStats) {
+    measure(&env, || run(input, Rc::clone(&env)))
+}
+
+/// Calls `closure` with already evaluated arguments, e.g. from a builtin.
+pub(crate) fn call(closure: Rc<Closure>, args: Vec<Object>) -> Result<Object> {
+    if closure.function().generator {
+        return Ok(Object::Iterator(Iter::compiled(Generator::start(
+            closure, args,
+        )?)));
+    }
+    let mut vm = Vm::default();
+    vm.enter(closure, args)?;
+    vm.execute()
+}
+
+/// A call of a generator function, suspended at its last `yield` until the iterator
+/// over what it yields is advanced. It runs on its own stack.
+pub(crate) struct Generator {
+    vm: Vm,
+    env: Rc<Environment>,
+}
+
+impl Generator {
+    fn start(closure: Rc<Closure>, args: Vec<Object>) -> Result<Self> {
+        let env = Rc::clone(&closure.env);
+        let mut vm = Vm::default();
+        vm.enter(closure, args)?;
+        // The call only counts as nested while the generator runs.
+        env.meter().leave_call();
+        Ok(Self { vm, env })
+    }
+
+    pub(crate) fn env(&self) -> &Rc<Environment> {
+        &self.env
+    }
+
+    /// Runs the generator until it yields a value or finishes.
+    pub(crate) fn resume(&mut self) -> Result<Option<Object>> {
+        if self.vm.frames.is_empty() {
+            return Ok(None);
+        }
+        self.env.meter().enter_call()?;
+        match self.vm.resume()? {
+            Exit::Yield(obj) => {
+                self.env.meter().leave_call();
+                Ok(Some(obj))
+            }
+            Exit::Return(_) => Ok(None),
+        }
+    }
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Generator")
+            .field("frames", &self.vm.frames.len())
+            .finish()
+    }
+}
+
+/// Why the VM stopped running.
+enum Exit {
+    /// The first frame returned.
+    Return(Object),
+    /// A generator yielded, its frame being left to resume.
+    Yield(Object),
+}
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    /// Height of the stack when the function was called, its arguments popped.
+    base: usize,
+    locals: Vec<Cell>,
+    /// Heights of the stack when the loops running in the function started.
+    loops: Vec<usize>,
+}
+
+impl Frame {
+    fn new(closure: Rc<Closure>, args: Vec<Object>) -> Self {
+        let mut locals: Vec<Cell> = args
+            .into_iter()
+            .map(|arg| Rc::new(RefCell::new(arg)))
+            .collect();
+        locals.resize_with(closure.function().num_locals, || {
+            Rc::new(RefCell::new(Object::Null))
+        });
+        Self {
+            closure,
+            ip: 0,
+            base: 0,
+            locals,
+            loops: vec![],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Vm {
+    stack: Vec<Object>,
+    frames: Vec<Frame>,
+}
+
+impl Vm {
+    /// Pushes the frame of a call, once the arguments and the function are popped.
+    fn enter(&mut self, closure: Rc<Closure>, args: Vec<Object>) -> Result<()> {
+        step(&closure.env)?;
+        closure.env.meter().enter_call()?;
+        let function = closure.function();
+        let (n_params, n_args) = (function.parameters.len(), args.len());
+        if n_params != n_args {
+            closure.env.meter().leave_call();
+            fail!(
+                ErrorCode::ArgumentCount,
+                "Function `{}({})` expects {n_params} argument{}, got {n_args}.",
+                function.name.as_deref().unwrap_or("fn"),
+                function.parameters.join(", "),
+                if n_params == 1 { "" } else { "s" },
+            );
+        }
+        let mut frame = Frame::new(closure, args);
+        frame.base = self.stack.len();
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    /// Runs the frames pushed until the first one returns, its value being the result.
+    fn execute(&mut self) -> Result<Object> {
+        match self.resume()? {
+            Exit::Return(obj) => Ok(obj),
+            Exit::Yield(_) => fail!(ErrorCode::Internal, "Yielded outside of a generator"),
+        }
+    }
+
+    /// Runs the frames pushed until the first one returns or a generator yields.
+    /// Errors leave all the frames, which they record.
+    fn resume(&mut self) -> Result<Exit> {
+        self.run().map_err(|mut err| {
+            while let Some(frame) = self.frames.pop() {
+                if frame.closure.function != 0 {
+                    frame.closure.env.meter().leave_call();
+                    let parameters = frame.closure.function().parameters.join(", ");
+                    err = err.in_frame(format!("fn({parameters})"));
+                }
+                if let Some(caller) = self.frames.last() {
+                    let site = caller.closure.function().call_site(caller.ip);
+                    err = err.or_span(site.and_then(|site| site.span.call()));
+                }
+            }
+            err
+        })
+    }
+
+    fn run(&mut self) -> Result<Exit> {
+        'frames: loop {
+            let depth = self.frames.len() - 1;
+            let closure = Rc::clone(&self.frames[depth].closure);
+            let (bytecode, env) = (&closure.bytecode, &closure.env);
+            let instructions = &closure.function().instructions.0;
+            let mut ip = self.frames[depth].ip;
+            loop {
+                let start = ip;
+                let Some(op) = Opcode::lookup(instructions[ip]) else {
+                    fail!(
+                        ErrorCode::Internal,
+                        "Unknown opcode {} at {ip}",
+                        instructions[ip]
+                    );
+                };
+                ip += 1;
+                let operand = match op.definition().operand_widths {
+                    [2] => read_u16(&instructions[ip..]),
+                    [1, ..] => usize::from(instructions[ip]),
+                    _ => 0,
+                };
+                ip += op.definition().operand_widths.iter().sum::<usize>();
+
+                match op {
+                    Opcode::Constant => self.push_new(bytecode.constants[operand].clone(), env)?,
+                    Opcode::Pop => {
+                        self.pop();
+                    }
+                    Opcode::Null => self.stack.push(Object::Null),
+                    Opcode::True => self.stack.push(Object::TRUE),
+                    Opcode::False => self.stack.push(Object::FALSE),
+                    Opcode::Add
+                    | Opcode::Sub
+                    | Opcode::Mul
+                    | Opcode::Div
+                    | Opcode::Mod
+                    | Opcode::Pow
+                    | Opcode::Equal
+                    | Opcode::NotEqual
+                    | Opcode::GreaterThan
+                    | Opcode::LessThan
+                    | Opcode::GreaterEqual
+                    | Opcode::LessEqual
+                    | Opcode::Index => {
+                        let right = self.pop();
+                        let left = self.pop();
+                        let obj = Expression::eval_infix(infix_operator(op), left, right)?;
+                        self.push_new(obj, env)?;
+                    }
+                    Opcode::Minus | Opcode::Bang | Opcode::Plus => {
+                        let operator = match op {
+                            Opcode::Minus => PrefixOperator::Neg,
+                            Opcode::Bang => PrefixOperator::Not,
+                            _ => PrefixOperator::Plus,
+                        };
+                        let right = self.pop();
+                        self.stack.push(Expression::eval_prefix(operator, right)?);
+                    }
+                    Opcode::Jump => {
+                        if operand < start {
+                            step(env)?;
+                        }
+                        ip = operand;
+                    }
+                    Opcode::JumpNotTruthy => {
+                        if !self.pop().to_bool() {
+                            ip = operand;
+                        }
+                    }
+                    Opcode::GetGlobal => self.stack.push(env.lookup(name(bytecode, operand))?),
+                    Opcode::SetGlobal => {
+                        env.set(name(bytecode, operand), self.top().clone());
+                    }
+                    Opcode::SetMutableGlobal => {
+                        env.set_mut(name(bytecode, operand), self.top().clone());
+                    }
+                    Opcode::AssignGlobal => {
+                        env.assign(name(bytecode, operand), self.top().clone())?;
+                    }
+                    Opcode::GetLocal => {
+                        let obj = self.frames[depth].locals[operand].borrow().clone();
+                        self.stack.push(obj);
+                    }
+                    Opcode::SetLocal => {
+                        let obj = self.top().clone();
+                        *self.frames[depth].locals[operand].borrow_mut() = obj;
+                    }
+                    Opcode::GetFree => self.stack.push(closure.free[operand].borrow().clone()),
+                    Opcode::SetFree => *closure.free[operand].borrow_mut() = self.top().clone(),
+                    Opcode::Field => {
+                        let Object::String(name) = &bytecode.constants[operand] else {
+                            fail!(ErrorCode::Internal, "Field name at {start} isn't a string");
+                        };
+                        let obj = field(self.pop(), name)?;
+                        self.push_new(obj, env)?;
+                    }
+                    Opcode::GetBuiltin => {
+                        let builtin = bytecode.constants[operand].clone();
+                        // Globals shadow the builtins of the same name.
+                        let global = match &builtin {
+                            Object::Builtin(builtin_fn) => env.get_defined(&builtin_fn.to_string()),
+                            _ => None,
+                        };
+                        match global {
+                            Some(obj) => self.stack.push(obj),
+                            None => {
+                                if let Object::Builtin(builtin_fn) = &builtin {
+                                    env.policy().check_builtin(builtin_fn)?;
+                                }
+                                self.stack.push(builtin);
+                            }
+                        }
+                    }
+                    Opcode::Array => {
+                        let items = self.stack.split_off(self.stack.len() - operand);
+                        self.push_new(Object::Array(Rc::new(items)), env)?;
+                    }
+                    Opcode::Tuple => {
+                        let values = self.stack.split_off(self.stack.len() - operand);
+                        self.push_new(Object::Tuple(values), env)?;
+                    }
+                    Opcode::Hash => {
+                        let pairs = self.stack.split_off(self.stack.len() - 2 * operand);
+                        let mut literal = HashLiteral::default();
+                        let span = closure.function().hash_site(ip).map(|site| &site.span);
+                        let mut pairs = pairs.into_iter();
+                        let mut index = 0;
+                        while let (Some(key), Some(value)) = (pairs.next(), pairs.next()) {
+                            let key_span = span.and_then(|span| span.key(index));
+                            literal.insert(hash_key(key)?, key_span, value, env)?;
+                            index += 1;
+                        }
+                        self.push_new(Object::Hash(literal.finish()), env)?;
+                    }
+                    Opcode::Call | Opcode::CallKeywords => {
+                        let mut keywords = Vec::new();
+                        if op == Opcode::CallKeywords {
+                            let Object::Array(names) =
+                                &bytecode.constants[read_u16(&instructions[start + 2..])]
+                            else {
+                                fail!(
+                                    ErrorCode::Internal,
+                                    "Keyword names at {start} aren't an array"
+                                );
+                            };
+                            let values = self.stack.split_off(self.stack.len() - names.len());
+                            keywords = names
+                                .iter()
+                                .filter_map(|name| match name {
+                                    Object::String(name) => Some(name.clone()),
+                                    _ => None,
+                                })
+                                .zip(values)
+                                .collect();
+                        }
+                        let args = self.stack.split_off(self.stack.len() - operand);
+                        let func = self.pop();
+                        let site = closure.function().call_site(ip);
+                        if !matches!(
+                            func,
+                            Object::Closure(_)
+                                | Object::Builtin(_)
+                                | Object::Function { .. }
+                                | Object::StructType(_)
+                        ) {
+                            let callee = site.map_or("", |site| &site.callee);
+                            return Err(not_a_function(callee, &func)
+                                .or_span(site.and_then(|site| site.span.callee())));
+                        }
+                        let at_call =
+                            |err: Error| err.or_span(site.and_then(|site| site.span.call()));
+                        let args = bind_keywords(&func, args, keywords, None).map_err(at_call)?;
+                        match func {
+                            Object::Closure(callee) if callee.function().generator => {
+                                let generator = Generator::start(callee, args).map_err(at_call)?;
+                                self.push_new(Object::Iterator(Iter::compiled(generator)), env)?;
+                            }
+                            Object::Closure(callee) => {
+                                self.frames[depth].ip = ip;
+                                self.enter(callee, args).map_err(at_call)?;
+                                continue 'frames;
+                            }
+                            func => {
+                                if let Object::Builtin(builtin) = &func
+                                    && let Some(message) = builtin.deprecation(&args)
+                                {
+                                    env.warn(message);
+                                }
+                                let obj =
+                                    env.calling(|| apply(func, args, None)).map_err(at_call)?;
+                                self.push_new(obj, env)?;
+                            }
+                        }
+                    }
+                    Opcode::ReturnValue => {
+                        let obj = self.pop();
+                        let frame = self.frames.pop();
+                        if let Some(frame) = frame {
+                            self.stack.truncate(frame.base);
+                            if frame.closure.function != 0 {
+                                env.meter().leave_call();
+                            }
+                        }
+                        if self.frames.is_empty() {
+                            return Ok(Exit::Return(obj));
+                        }
+                        self.stack.push(obj);
+                        continue 'frames;
+                    }
+                    Opcode::Closure => {
+                        let free = bytecode.functions[operand]
+                            .captures
+                            .iter()
+                            .map(|capture| match capture {
+                                Capture::Local(index) => {
+                                    Rc::clone(&self.frames[depth].locals[*index])
+                                }
+                                Capture::Free(index) => Rc::clone(&closure.free[*index]),
+                            })
+                            .collect();
+                        let obj = Object::Closure(Rc::new(Closure {
+                            bytecode: Rc::clone(bytecode),
+                            function: operand,
+                            free,
+                            env: Rc::clone(env),
+                        }));
+                        self.push_new(obj, env)?;
+                    }
+                    Opcode::Loop => {
+                        let height = self.stack.len();
+                        self.frames[depth].loops.push(height);
+                    }
+                    Opcode::EndLoop => {
+                        self.frames[depth].loops.pop();
+                    }
+                    Opcode::Unwind => {
+                        if let Some(height) = self.frames[depth].loops.last() {
+                            self.stack.truncate(*height);
+                        }
+                        if operand < start {
+                            step(env)?;
+                        }
+                        ip = operand;
+                    }
+                    Opcode::Iter => {
+                        let iter = Iter::from_object(self.pop())?;
+                        self.stack.push(Object::Iterator(iter));
+                    }
+                    Opcode::IterNext => {
+                        let next = match self.top() {
+                            Object::Iterator(iter) => iter.clone().next()?,
+                            _ => None,
+                        };
+                        match next {
+                            Some(item) => self.stack.push(item),
+                            None => ip = operand,
+                        }
+                    }
+                    Opcode::Match | Opcode::Destructure => {
+                        let pattern = &bytecode.patterns[operand];
+                        let mut bindings = vec![];
+                        let matched = matches(pattern, self.top(), &mut bindings);
+                        if !matched && op == Opcode::Destructure {
+                            fail!(
+                                ErrorCode::PatternMismatch,
+                                "{} doesn't match the pattern {pattern}",
+                                self.top()
+                            );
+                        }
+                        if matched {
+                            self.stack.extend(bindings.into_iter().map(|(_, obj)| obj));
+                        }
+                        if op == Opcode::Match {
+                            self.stack.push(Object::from(matched));
+                        }
+                    }
+                    Opcode::Yield => {
+                        let obj = self.pop();
+                        self.stack.push(Object::Null);
+                        self.frames[depth].ip = ip;
+                        return Ok(Exit::Yield(obj));
+                    }
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Object {
+        self.stack.pop().unwrap_or(Object::Null)
+    }
+
+    fn top(&self) -> &Object {
+        self.stack.last().unwrap_or(&Object::NULL)
+    }
+
+    /// Pushes an object created by an instruction, counting it if it's allocated.
+    fn push_new(&mut self, obj: Object, env: &Environment) -> Result<()> {
+        if obj.is_allocated() {
+            env.meter().allocated()?;
+        }
+        self.stack.push(obj);
+        Ok(())
+    }
+}
+
+/// Counts a call or an iteration of a loop, which can be interrupted.
+fn step(env: &Environment) -> Result<()> {
+    env.interrupt().check()?;
+    env.meter().step()
+}
+
+/// The name of a global variable, stored in the constants.
+fn name(bytecode: &Bytecode, index: usize) -> &str {
+    match &bytecode.constants[index] {
+        Object::String(name) => name,
+        _ => "",
+    }
+}
+
+fn hash_key(key: Object) -> Result<HashMapKey> {
+    Ok(match key {
+        Object::Int(key) => HashMapKey::Int(key),
+        Object::String(key) => HashMapKey::String(key),
+        Object::Bool(key) => HashMapKey::Bool(key),
+        _ => fail!(
+            ErrorCode::InvalidHashKey,
+            "Invalid object type for an hash key, must be int, str or bool!",
+        ),
+    })
+}
+
+fn infix_operator(op: Opcode) -> InfixOperator {
+    match op {
+        Opcode::Add => InfixOperator::Add,
+        Opcode::Sub => InfixOperator::Sub,
+        Opcode::Mul => InfixOperator::Mul,
+        Opcode::Div => InfixOperator::Div,
+        Opcode::Mod => InfixOperator::Mod,
+        Opcode::Pow => InfixOperator::Pow,
+        Opcode::Equal => InfixOperator::Eq,
+        Opcode::NotEqual => InfixOperator::NotEq,
+        Opcode::GreaterThan => InfixOperator::Gt,
+        Opcode::LessThan => InfixOperator::Lt,
+        Opcode::GreaterEqual => InfixOperator::GtEq,
+        Opcode::LessEqual => InfixOperator::LtEq,
+        _ => InfixOperator::Index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Budget, Policy, eval_with_env};
+
+    /// Asserts that the VM and the evaluator agree on the value of `input`.
+    fn assert_run(input: &str, expected: &str) {
+        let obj = run(input, Rc::new(Environment::default())).unwrap();
+        assert_eq!(obj.to_string(), expected);
+        let evaluated = eval_with_env(input, Rc::new(Environment::default())).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    #[test]
+    fn expressions() {
+        assert_run("(5 + 10 * 2 + 15 / 3) * 2 + -10", "50");
+        assert_run("!(1 < 2) == false", "true");
+        assert_run("[1 <= 1, 2 >= 3, 1.5 >= 1]", "[true, false, true]");
+        assert_run(
+            "[\"abc\" <= \"abd\", \"ab\" >= \"abc\", \"b\" > \"a\", \"a\" == \"a\"]",
+            "[true, false, true, true]",
+        );
+        assert_run(
+            "[17 % 5, -7 % 2, 2 ** 3 ** 2, 7.5 % 2]",
+            "[2, -1, 512, 1.5]",
+        );
+        assert_run(
+            "[true && 1, 0 && true, 0 || \"a\", null || 0, false && 1(2), true || 1(2)]",
+            "[true, false, true, false, false, true]",
+        );
+        assert_run("1.5 * 2", "3.0");
+        assert_run("+3 * -+2", "-6");
+        assert_run("[1, \"a\", [true]][2][0]", "true");
+        assert_run("{\"a\": 1, 2: [3]}[2]", "[3]");
+        assert_run("if (1 > 2) { 10 }", "null");
+        assert_run("let x = 5; let y = x * 2; y", "10");
+        assert_run("", "null");
+    }
+
+    #[test]
+    fn functions() {
+        assert_run(
+            "let point = fn(x) { {\"x\": x, \"plus\": fn(self, dx) { point(self[\"x\"] + dx) }} }; point(1).plus(2).plus(dx: 3)[\"x\"]",
+            "6",
+        );
+        assert_run(
+            "let sub = fn(a, b) { a - b }; [sub(b: 1, a: 5), sub(5, b: 1), fn(x) { x }(x: 3)]",
+            "[4, 4, 3]",
+        );
+        assert_run(
+            "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(20)",
+            "6765",
+        );
+        assert_run(
+            "let counter = fn() { let mut n = 0; fn() { n = n + 1 } }; \
+             let c = counter(); c(); c(); [c(), counter()()]",
+            "[3, 1]",
+        );
+        assert_run(
+            "let adder = fn(a) { fn(b) { fn(c) { a + b + c } } }; adder(1)(2)(3)",
+            "6",
+        );
+        assert_run(
+            "let lines = 5; let map = fn(a, b, c) { a }; \
+             let f = fn(len) { len + lines }; [f(1), map(1, 2, 3), first([2])]",
+            "[6, 1, 2]",
+        );
+        assert_run(
+            "let mut total = 1; let add = fn(x) { total = total + x }; add(2); total",
+            "3",
+        );
+        assert_run(
+            "[collect(map([1, 2], fn(x) { x * 10 })), collect(filter([1, 2, 3], fn(x) { x > 2 }))]",
+            "[[10, 20], [3]]",
+        );
+        assert_run("let f = fn() { 1 }; f", "fn() { 1; }");
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_run(
+            "let mut sum = 0; for (x in [1, 2, 3, 4, 5]) { if (x == 2) { continue; } \
+             if (x == 5) { break; } sum = sum + x }; sum",
+            "8",
+        );
+        assert_run(
+            "let f = fn() { let mut i = 0; while (true) { i = i + 1; for (x in [1, 2]) { \
+             if (i > 2) { return [i, x]; } } } }; f()",
+            "[3, 1]",
+        );
+        assert_run(
+            "let f = fn(x) { match (x) { [a, ...others] if a > 1 => others, {\"k\": v} => v, \
+             _ => 0 } }; [f([2, 3]), f([1]), f({\"k\": 4}), f(5)]",
+            "[[3], 0, 4, 0]",
+        );
+        assert_run("let [a, [b, _]] = [1, [2, 3]]; a + b", "3");
+        assert_run(
+            "let f = fn((a, b), c) { (a + c, (b,)) }; let (x, (y,)) = f((1, 2), 3); [x, y, (), f((0, 0), 0)[1]]",
+            "[4, 2, (), (0,)]",
+        );
+        assert_run(
+            "let v = fn(x) { {\"x\": x, \"__sub\": fn(a, b) { v(a.x - b.x) }} }; (v(5) - v(2)).x",
+            "3",
+        );
+        assert_run(
+            "let f = fn(a) { struct P { x, y } match (P(a, y: 2)) { P(1, y) => y, p => p.x } }; [f(1), f(3)]",
+            "[2, 3]",
+        );
+        assert_run(
+            "let f = fn(x) { match (x) { 0.5 => 1, -1.5 => 2, _ => 0 } }; [f(0.5), f(-1.5), f(1)]",
+            "[1, 2, 0]",
+        );
+    }
+
+    #[test]
+    fn failures() {
+        let failure = |input: &str| run(input, Rc::new(Environment::default())).unwrap_err();
+        let err = failure("let f = fn(x) { x + true }; f(1)");
+        assert_eq!(err.code, ErrorCode::InvalidOperation);
+        assert_eq!(err.stack, vec!["fn(x)"]);
+        let err = failure("let f = fn(a, b) { a }; f(1)");
+        assert_eq!(
+            err.message,
+            "Function `f(a, b)` expects 2 arguments, got 1."
+        );
+        assert_eq!(err.span, Some(24..28));
+        let input =
+            "let add = fn(x, y) {\n  x + y\n};\nlet twice = fn(x) { add(x, x) };\ntwice(true)";
+        assert_eq!(
+            failure(input).located_in(input).message,
+            "Invalid operation (`+`) between true and true! at line 4, column 21"
+        );
+        assert_eq!(
+            failure("let [a] = [1, 2];").code,
+            ErrorCode::PatternMismatch
+        );
+        assert_eq!(failure("y = 1;").code, ErrorCode::UnknownIdentifier);
+        assert_eq!(
+            failure("let f = fn() { let n = 0; n = 1 }; f()").code,
+            ErrorCode::ImmutableAssignment
+        );
+        let env = Rc::new(Environment::default());
+        run("let mut a = 1; let b = 2;", Rc::clone(&env)).unwrap();
+        assert_eq!(run("a = a + b", Rc::clone(&env)).unwrap(), Object::Int(3));
+        let err = run("b = 3", env).unwrap_err();
+        assert_eq!(err.code, ErrorCode::ImmutableAssignment);
+        assert_eq!(
+            failure("let f = fn() { y }; f()").message,
+            "identifier not found: y"
+        );
+        assert_eq!(
+            failure("let count = 1; let f = fn() { coutn }; f()").message,
+            "identifier not found: coutn, did you mean `count`?"
+        );
+        assert_eq!(
+            failure("lenn([1])").message,
+            "identifier not found: lenn, did you mean `len`?"
+        );
+        let err = failure("let h = {\"f\": 5};\nh[\"f\"](1)");
+        assert_eq!(
+            (err.code, err.message, err.span),
+            (
+                ErrorCode::NotAFunction,
+                "`h[\"f\"]` is not a function, found 5".to_owned(),
+                Some(18..24)
+            )
+        );
+        let env = Rc::new(Environment::default().with_policy(Policy::default().strict()));
+        let err = run("let k = 1; {1: 1, k: 2}", env).unwrap_err();
+        assert_eq!(
+            (err.code, err.message, err.span),
+            (
+                ErrorCode::DuplicateHashKey,
+                "Duplicate key 1 in hash literal".to_owned(),
+                Some(18..19)
+            )
+        );
+        assert_eq!(failure("yield 1;").code, ErrorCode::InvalidYield);
+
+        let env = Environment::default().with_budget(Budget {
+            max_depth: Some(50),
+            ..Budget::default()
+        });
+        let (result, stats) = run_with_stats("let f = fn(n) { f(n + 1) }; f(0)", Rc::new(env));
+        assert_eq!(result.unwrap_err().code, ErrorCode::LimitExceeded);
+        assert_eq!(stats.max_depth, 50);
+    }
+
+    #[test]
+    fn same_errors_as_the_evaluator() {
+        for input in [
+            "let add = fn(x, y) { x + y };\nadd(1, 2, 3)",
+            "let f = fn(x) { x + true };\nf(1)",
+            "let h = {\"f\": 5};\nh[\"f\"](1)",
+            "let f = fn(a) { len(a) };\n[1, f(2)]",
+            "let sub = fn(a, b) { a - b };\nsub(1, c: 2)",
+            "let f = fn() { g() };\nlet g = fn() { 1 / 0 };\nf()",
+            "collect(map([1, 2], fn(x) { x + y }))",
+            "fn(x) { coutn }(1)",
+            "let [a] = [1, 2];",
+            "1 / 0",
+        ] {
+            let err = run(input, Rc::new(Environment::default())).unwrap_err();
+            let evaluated = eval_with_env(input, Rc::new(Environment::default())).unwrap_err();
+            assert_eq!(
+                (err.code, err.message, err.span),
+                (evaluated.code, evaluated.message, evaluated.span),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn generators() {
+        assert_run(
+            "let countdown = fn(n) { let mut n = n; while (n > 0) { yield n; n = n - 1; } }; \
+             collect(countdown(3))",
+            "[3, 2, 1]",
+        );
+        assert_run(
+            "let g = fn(a) { yield a; if (a > 1) { yield a * 10; return 0; yield 5; } yield 99; }; \
+             [collect(g(2)), collect(g(1))]",
+            "[[2, 20], [1, 99]]",
+        );
+        assert_run(
+            "let pairs = fn(xs) { for (x in xs) { for (y in xs) { yield [x, y]; } } }; \
+             let a = pairs([1, 2]); let b = pairs([3]); next(a); \
+             [next(a), next(b), next(a), next(a), next(a)]",
+            "[[1, 2], [3, 3], [2, 1], [2, 2], null]",
+        );
+        assert_run(
+            "let evens = fn(xs) { for (x in xs) { if (x % 2 == 0) { yield x; } } }; \
+             collect(map(evens(range(7)), fn(x) { x * 10 }))",
+            "[0, 20, 40, 60]",
+        );
+        let input = "let g = fn() { let x = if (true) { yield 1; 2 }; yield x; }; collect(g())";
+        let obj = run(input, Rc::new(Environment::default())).unwrap();
+        assert_eq!(obj.to_string(), "[1, 2]");
+
+        let failure = |input: &str| run(input, Rc::new(Environment::default())).unwrap_err();
+        let err =
+            failure("let g = fn() { yield 1; yield 1 + true; }; let it = g(); next(it); next(it)");
+        assert_eq!(err.code, ErrorCode::InvalidOperation);
+        assert_eq!(
+            failure("let g = fn() { yield next(it); }; let it = g(); next(it)").code,
+            ErrorCode::InvalidYield
+        );
+    }
+
+    #[test]
+    fn closures_in_builtins() {
+        assert_run("let add = fn(a, b) { a + b }; partial(add, 1)(2)", "3");
+        assert_run(
+            "let f = fn(x) { \"Doubles x.\"; x * 2 }; help(f)",
+            "fn(x)\nDoubles x.",
+        );
+        assert_run("let f = fn(x) { x * 2 }; dump(f)", "fn(x) {\n    x * 2;\n}");
+    }
+
+    #[test]
+    fn closures_called_by_the_evaluator() {
+        let env = Rc::new(Environment::default());
+        run("let double = fn(x) { x * 2 };", Rc::clone(&env)).unwrap();
+        assert_eq!(
+            eval_with_env("double(21)", Rc::clone(&env)).unwrap(),
+            Object::Int(42)
+        );
+    }
+}